@@ -14,7 +14,7 @@ use crate::{types::*, *};
 
 pub use self::zkapp::{ZkAppOptionHashableWrapper, ZkAppUriOptionHashableWrapper};
 use mina_serialization_types_macros::AutoFrom;
-pub use permissions::{AuthRequired, Permissions, PermissionsLegacy};
+pub use permissions::{AuthRequired, ControlTag, Permissions, PermissionsLegacy};
 pub use timing::Timing;
 pub use token_permissions::TokenPermissions;
 pub use token_symbol::TokenSymbol;
@@ -27,6 +27,32 @@ pub enum AuthRequired {
     Impossible,
 }
 
+/// The kind of authorization that is provided when performing an action with an account
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ControlTag {
+    /// A proof is provided
+    Proof,
+    /// A signature is provided
+    Signature,
+    /// No authorization is provided
+    NoneGiven,
+}
+
+impl AuthRequired {
+    /// Checks if the given kind of authorization satisfies the requirement
+    /// <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/mina_base/permissions.ml#L158>
+    pub fn check(&self, tag: ControlTag) -> bool {
+        match (self, tag) {
+            (Self::None, _) => true,
+            (Self::Either, ControlTag::Proof | ControlTag::Signature)
+            | (Self::Proof, ControlTag::Proof)
+            | (Self::Signature, ControlTag::Signature) => true,
+            // Impossible can never be satisfied and Both is not supported by the protocol
+            _ => false,
+        }
+    }
+}
+
 impl ToChunkedROInput for AuthRequired {
     fn to_chunked_roinput(&self) -> ChunkedROInput {
         let mut roi = ChunkedROInput::new();
@@ -62,6 +88,21 @@ pub struct PermissionsLegacy {
     pub set_verification_key: AuthRequired,
 }
 
+impl PermissionsLegacy {
+    /// Default permissions of a newly created user account on mainnet
+    pub fn user_default() -> Self {
+        Self {
+            stake: true,
+            edit_state: AuthRequired::Signature,
+            send: AuthRequired::Signature,
+            receive: AuthRequired::None,
+            set_delegate: AuthRequired::Signature,
+            set_permissions: AuthRequired::Signature,
+            set_verification_key: AuthRequired::Signature,
+        }
+    }
+}
+
 /// Permissions associated with the account
 #[derive(Clone, Debug, AutoFrom)]
 #[auto_from(mina_serialization_types::account::Permissions)]
@@ -90,6 +131,25 @@ pub struct Permissions {
     pub set_voting_for: AuthRequired,
 }
 
+impl Permissions {
+    /// Default permissions of a newly created user account
+    pub fn user_default() -> Self {
+        Self {
+            edit_state: AuthRequired::Signature,
+            send: AuthRequired::Signature,
+            receive: AuthRequired::None,
+            set_delegate: AuthRequired::Signature,
+            set_permissions: AuthRequired::Signature,
+            set_verification_key: AuthRequired::Signature,
+            set_zkapp_uri: AuthRequired::Signature,
+            edit_sequence_state: AuthRequired::Signature,
+            set_token_symbol: AuthRequired::Signature,
+            increment_nonce: AuthRequired::Signature,
+            set_voting_for: AuthRequired::Signature,
+        }
+    }
+}
+
 impl ToChunkedROInput for Permissions {
     fn to_chunked_roinput(&self) -> ChunkedROInput {
         ChunkedROInput::new()
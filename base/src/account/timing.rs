@@ -10,7 +10,7 @@ use smart_default::SmartDefault;
 
 use crate::{
     from_graphql_json::FromGraphQLJson,
-    numbers::{Amount, BlockTime},
+    numbers::{Amount, BlockTime, GlobalSlotNumber},
 };
 
 /// Payload for the timing variant Timed
@@ -42,6 +42,34 @@ impl Default for TimedData {
     }
 }
 
+impl TimedData {
+    /// Calculates the minimum balance of the account at the given global slot,
+    /// the balance below which is still locked
+    /// <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/mina_base/account.ml#L800>
+    pub fn min_balance_at_slot(&self, global_slot: GlobalSlotNumber) -> Amount {
+        let global_slot = global_slot.0 as u64;
+        if global_slot < self.cliff_time.0 {
+            self.initial_minimum_balance
+        } else if self.vesting_period.0 == 0 {
+            // vesting period is checked to be positive, this guards against division by zero
+            Amount(0)
+        } else {
+            match self
+                .initial_minimum_balance
+                .0
+                .checked_sub(self.cliff_amount.0)
+            {
+                None => Amount(0),
+                Some(min_balance_past_cliff) => {
+                    let num_periods = (global_slot - self.cliff_time.0) / self.vesting_period.0;
+                    let vesting_decrement = num_periods.saturating_mul(self.vesting_increment.0);
+                    Amount(min_balance_past_cliff.saturating_sub(vesting_decrement))
+                }
+            }
+        }
+    }
+}
+
 impl ToChunkedROInput for TimedData {
     fn to_chunked_roinput(&self) -> ChunkedROInput {
         ChunkedROInput::new()
@@ -80,7 +108,7 @@ impl FromGraphQLJson for TimedData {
 
 /// Timing information for an account with regard to when its balance is accessable
 /// This is to allow vesting from an initial genesis allocation
-#[derive(Debug, Clone, SmartDefault, AutoFrom)]
+#[derive(Debug, Clone, Eq, PartialEq, SmartDefault, AutoFrom)]
 #[auto_from(mina_serialization_types::account::Timing)]
 #[auto_from(mina_serialization_types::account::TimingV0)]
 pub enum Timing {
@@ -103,6 +131,17 @@ impl FromGraphQLJson for Timing {
     }
 }
 
+impl Timing {
+    /// Calculates the minimum balance of the account at the given global slot,
+    /// untimed accounts do not have a minimum balance
+    pub fn min_balance_at_slot(&self, global_slot: GlobalSlotNumber) -> Amount {
+        match self {
+            Self::Untimed => Amount(0),
+            Self::Timed(timed) => timed.min_balance_at_slot(global_slot),
+        }
+    }
+}
+
 impl ToChunkedROInput for Timing {
     fn to_chunked_roinput(&self) -> ChunkedROInput {
         match &self {
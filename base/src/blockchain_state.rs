@@ -8,8 +8,13 @@ use std::convert::TryInto;
 use crate::{
     blockchain_state_registers::BlockchainStateRegisters,
     numbers::{BlockTime, TokenId},
+    staged_ledger_diff::StagedLedgerDiff,
     *,
 };
+use blake2::{
+    digest::{Update, VariableOutput},
+    Blake2bVar,
+};
 use mina_crypto::hash::*;
 use mina_serialization_types::{json::*, v1::*};
 use mina_serialization_types_macros::AutoFrom;
@@ -121,6 +126,21 @@ impl BodyReference {
         hex::decode_to_slice(data, &mut r as &mut [u8])?;
         Ok(Self(r))
     }
+
+    /// Calculates the blake2b digest of the bin-prot serialized [StagedLedgerDiff],
+    /// which the body reference of the block commits to
+    pub fn from_staged_ledger_diff(
+        diff: &StagedLedgerDiff,
+    ) -> Result<Self, bin_prot::error::Error> {
+        let bytes = diff.clone().try_into_binprot()?;
+        let mut hasher = Blake2bVar::new(32).expect("Invalid Blake2bVar output size");
+        hasher.update(&bytes);
+        let mut r = [0; 32];
+        hasher
+            .finalize_variable(&mut r)
+            .expect("Invalid Blake2bVar output size");
+        Ok(Self(r))
+    }
 }
 
 impl ToChunkedROInput for BodyReference {
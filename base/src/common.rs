@@ -9,6 +9,15 @@ use mina_serialization_types_macros::AutoFrom;
 use once_cell::sync::OnceCell;
 use proof_systems::{mina_signer::CompressedPubKey, ChunkedROInput, ToChunkedROInput};
 
+/// Version of the Poseidon hash that a network computes its hashes with
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PoseidonVersion {
+    /// Legacy Poseidon of mainnet
+    Legacy,
+    /// Kimchi Poseidon of berkeley
+    Kimchi,
+}
+
 /// Wrapper of Vec<u8>
 #[derive(Clone, Debug, Eq, PartialEq, AutoFrom)]
 #[auto_from(mina_serialization_types::common::ByteVec)]
@@ -6,9 +6,14 @@
 use mina_crypto::hash::*;
 use mina_serialization_types::json::*;
 use mina_serialization_types_macros::AutoFrom;
+use proof_systems::mina_hasher::{create_legacy, Fp, Hashable, Hasher, ROInput};
 use versioned::*;
 
 /// Proof that the block was produced within the allotted slot time
+///
+/// The proof consists of the state hash of an ancestor followed by the body hashes
+/// of the protocol states from that ancestor to the parent of the block, see
+/// <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/transition_chain_verifier/transition_chain_verifier.ml>
 #[derive(Clone, Debug, Default, Eq, PartialEq, derive_more::From, derive_more::Into, AutoFrom)]
 #[auto_from(mina_serialization_types::delta_transition_chain_proof::DeltaTransitionChainProof)]
 pub struct DeltaTransitionChainProof(pub StateHash, pub Vec<StateHash>);
@@ -18,3 +23,60 @@ impl_from_with_proxy!(
     mina_serialization_types::delta_transition_chain_proof::DeltaTransitionChainProof,
     DeltaTransitionChainProofJson
 );
+
+impl DeltaTransitionChainProof {
+    /// Recomputes the state hashes from the initial state hash and the body hashes,
+    /// returns all the state hashes of the chain starting from the initial one
+    pub fn state_hashes(&self) -> Option<Vec<StateHash>> {
+        let mut state_hashes = Vec::with_capacity(self.1.len() + 1);
+        state_hashes.push(self.0.clone());
+        for body_hash in self.1.iter() {
+            let previous_state_hash = state_hashes.last()?;
+            state_hashes.push(state_hash(previous_state_hash, body_hash.try_into().ok()?));
+        }
+        Some(state_hashes)
+    }
+
+    /// Verifies that the chain ends at the target state hash,
+    /// returns the verified state hashes from the initial one to the target
+    pub fn verify(&self, target_hash: &StateHash) -> Option<Vec<StateHash>> {
+        let state_hashes = self.state_hashes()?;
+        if state_hashes.last() == Some(target_hash) {
+            Some(state_hashes)
+        } else {
+            None
+        }
+    }
+}
+
+/// Calculates the state hash of a protocol state from its previous state hash and body hash
+pub fn state_hash(previous_state_hash: &StateHash, body_hash: Fp) -> StateHash {
+    let mut hasher = create_legacy(());
+    let hash = hasher.hash(&StateHashInput {
+        previous_state_hash: previous_state_hash.clone(),
+        body_hash,
+    });
+    (&hash).into()
+}
+
+/// Input of the state hash, which is the same as the one of
+/// [crate::protocol_state::ProtocolStateLegacy] with the body already hashed
+#[derive(Clone)]
+struct StateHashInput {
+    previous_state_hash: StateHash,
+    body_hash: Fp,
+}
+
+impl Hashable for StateHashInput {
+    type D = ();
+
+    fn to_roinput(&self) -> ROInput {
+        ROInput::new()
+            .append_hashable(&self.previous_state_hash)
+            .append_field(self.body_hash)
+    }
+
+    fn domain_string(_: Self::D) -> Option<String> {
+        Some("CodaProtoState".into())
+    }
+}
@@ -5,6 +5,7 @@
 
 use crate::types::*;
 use crate::verifiable::Verifiable;
+use mina_crypto::hash::StateHash;
 use mina_serialization_types::{json::*, v1::ExternalTransitionV1, *};
 use mina_serialization_types_macros::AutoFrom;
 use proof_systems::mina_signer::Signer;
@@ -37,6 +38,18 @@ impl_from_with_proxy!(
     ExternalTransitionJson
 );
 
+impl ExternalTransition {
+    /// Verifies that the delta transition chain proof ends at the parent of the block,
+    /// returns the state hashes from the initial one of the proof to the block itself
+    pub fn verify_delta_transition_chain(&self) -> Option<Vec<StateHash>> {
+        let mut state_hashes = self
+            .delta_transition_chain_proof
+            .verify(&self.protocol_state.previous_state_hash)?;
+        state_hashes.push(self.protocol_state.state_hash());
+        Some(state_hashes)
+    }
+}
+
 impl BinProtSerializationType<'_> for ExternalTransition {
     type T = ExternalTransitionV1;
 }
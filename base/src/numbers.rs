@@ -18,6 +18,9 @@ use time::Duration;
 /// Newtype for TokenIds
 pub struct TokenId(pub u64);
 
+/// Token id of MINA, which is the default token that fees are paid in
+pub const DEFAULT_TOKEN_ID: TokenId = TokenId(1);
+
 impl Hashable for TokenId {
     type D = ();
 
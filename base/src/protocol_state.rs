@@ -5,6 +5,7 @@
 
 use crate::{
     blockchain_state::*,
+    common::PoseidonVersion,
     consensus_state::ConsensusState,
     from_graphql_json::FromGraphQLJson,
     global_slot::GlobalSlot,
@@ -179,6 +180,8 @@ pub trait ProtocolStateHeader {
     fn constants(&self) -> &ProtocolConstants;
     /// State hash fp
     fn state_hash_fp(&self) -> Fp;
+    /// Version of the Poseidon hash that the state is hashed with
+    fn poseidon_version(&self) -> PoseidonVersion;
 }
 
 impl ProtocolStateHeader for ProtocolStateLegacy {
@@ -205,6 +208,10 @@ impl ProtocolStateHeader for ProtocolStateLegacy {
     fn state_hash_fp(&self) -> Fp {
         self.state_hash_fp()
     }
+
+    fn poseidon_version(&self) -> PoseidonVersion {
+        PoseidonVersion::Legacy
+    }
 }
 
 #[derive(Clone, Default, Debug, Eq, PartialEq)]
@@ -290,6 +297,10 @@ impl ProtocolStateHeader for ProtocolState {
     fn state_hash_fp(&self) -> Fp {
         self.state_hash_fp()
     }
+
+    fn poseidon_version(&self) -> PoseidonVersion {
+        PoseidonVersion::Kimchi
+    }
 }
 
 impl FromGraphQLJson for ProtocolState {
@@ -8,7 +8,14 @@ use crate::types::*;
 use mina_crypto::hash::*;
 use mina_serialization_types::json::*;
 use mina_serialization_types_macros::AutoFrom;
-use proof_systems::mina_signer::CompressedPubKey;
+use once_cell::sync::OnceCell;
+use proof_systems::{
+    mina_hasher::{create_legacy, Fp, Hashable, Hasher, ROInput},
+    mina_signer::CompressedPubKey,
+    o1_utils::FieldHelpers,
+};
+use std::collections::BTreeMap;
+use thiserror::Error;
 use versioned::*;
 
 #[derive(Clone, Debug, Eq, PartialEq, AutoFrom)]
@@ -19,6 +26,37 @@ pub struct TransactionSnarkWork {
     pub prover: CompressedPubKey,
 }
 
+impl TransactionSnarkWork {
+    /// Proofs in the work
+    pub fn snarks(&self) -> Vec<&TransactionSnark> {
+        match &self.proofs {
+            OneORTwo::One(snark) => vec![snark],
+            OneORTwo::Two(first, second) => vec![first, second],
+        }
+    }
+
+    /// Statements of the proofs in the work
+    pub fn statements(&self) -> Vec<&Statement> {
+        self.snarks()
+            .into_iter()
+            .map(|snark| &snark.statement)
+            .collect()
+    }
+
+    /// Checks that the statements of the work are well formed and,
+    /// for a pair of proofs, that the second one continues from the first one
+    pub fn check_statements(&self) -> Result<(), SnarkWorkError> {
+        let statements = self.statements();
+        for statement in statements.iter() {
+            statement.fee_excess.check()?;
+        }
+        if let [first, second] = statements[..] {
+            first.check_connected(second)?;
+        }
+        Ok(())
+    }
+}
+
 impl_from_with_proxy!(
     TransactionSnarkWork,
     mina_serialization_types::snark_work::TransactionSnarkWork,
@@ -53,6 +91,38 @@ pub struct Statement {
     pub sok_digest: ByteVec,
 }
 
+impl Statement {
+    /// Checks that the statement `next` starts where this statement ends, see `merge` in
+    /// <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/transaction_snark/transaction_snark.ml>
+    pub fn check_connected(&self, next: &Statement) -> Result<(), SnarkWorkError> {
+        if self.target != next.source {
+            return Err(SnarkWorkError::LedgerHashMismatch {
+                target: self.target.clone(),
+                source: next.source.clone(),
+            });
+        }
+        if self.next_available_token_after != next.next_available_token_before {
+            return Err(SnarkWorkError::NextAvailableTokenMismatch {
+                after: self.next_available_token_after.clone(),
+                before: next.next_available_token_before.clone(),
+            });
+        }
+        if !self
+            .pending_coinbase_stack_state
+            .target
+            .is_connected(&next.pending_coinbase_stack_state.source)
+        {
+            return Err(SnarkWorkError::PendingCoinbaseStackDisconnected);
+        }
+        self.supply_increase
+            .0
+            .checked_add(next.supply_increase.0)
+            .ok_or(SnarkWorkError::SupplyIncreaseOverflow)?;
+        self.fee_excess.combine(&next.fee_excess)?;
+        Ok(())
+    }
+}
+
 impl_from_with_proxy!(
     Statement,
     mina_serialization_types::snark_work::Statement,
@@ -73,6 +143,50 @@ pub struct PendingCoinbase {
     pub state_stack: StateStack,
 }
 
+impl PendingCoinbase {
+    /// Whether the stack `next` continues from this stack, its coinbases are either the same
+    /// or it's a new stack without coinbases, and its state stack is either the same or a new one,
+    /// see `connected` in
+    /// <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/mina_base/pending_coinbase.ml>
+    pub fn is_connected(&self, next: &PendingCoinbase) -> bool {
+        (self.data_stack == next.data_stack
+            || next.data_stack == StateHash::from(&empty_coinbase_stack()))
+            && (self.state_stack == next.state_stack
+                || next.state_stack.init == next.state_stack.curr)
+    }
+}
+
+/// Hash of a coinbase stack without coinbases, `Coinbase_stack.empty` in OCaml,
+/// which is the digest of the legacy sponge salted with `CoinbaseStack`
+pub fn empty_coinbase_stack() -> Fp {
+    static EMPTY: OnceCell<Fp> = OnceCell::new();
+    *EMPTY.get_or_init(|| {
+        let mut hasher = create_legacy(());
+        hasher.hash(&Salt("CoinbaseStack"))
+    })
+}
+
+// `Random_oracle.salt` absorbs the domain prefix into the initial sponge state
+// and `Random_oracle.digest` takes the first element of the state,
+// which is the same as hashing the prefix as a field without domain
+#[derive(Clone)]
+struct Salt(&'static str);
+
+impl Hashable for Salt {
+    type D = ();
+
+    fn to_roinput(&self) -> ROInput {
+        const MAX_DOMAIN_STRING_LEN: usize = 20;
+        let mut bytes = format!("{:*<MAX_DOMAIN_STRING_LEN$}", self.0).into_bytes();
+        bytes.resize(Fp::size_in_bytes(), 0);
+        ROInput::new().append_field(Fp::from_bytes(&bytes).expect("valid domain prefix"))
+    }
+
+    fn domain_string(_: Self::D) -> Option<String> {
+        None
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, AutoFrom)]
 #[auto_from(mina_serialization_types::snark_work::StateStack)]
 pub struct StateStack {
@@ -84,6 +198,41 @@ pub struct StateStack {
 #[auto_from(mina_serialization_types::snark_work::FeeExcessPair)]
 pub struct FeeExcessPair(pub FeeExcess, pub FeeExcess);
 
+impl FeeExcessPair {
+    /// Checks that both fee excesses are canonical and that
+    /// the pair doesn't hold two non-zero excesses of the same token
+    pub fn check(&self) -> Result<(), SnarkWorkError> {
+        self.0.check()?;
+        self.1.check()?;
+        if self.0.token == self.1.token && !self.0.is_zero() && !self.1.is_zero() {
+            return Err(SnarkWorkError::InvalidFeeExcess);
+        }
+        Ok(())
+    }
+
+    /// Sums the fee excesses of two consecutive statements per token,
+    /// the result must fit in a pair of fee excesses without overflowing
+    pub fn combine(&self, next: &FeeExcessPair) -> Result<Vec<(TokenId, i128)>, SnarkWorkError> {
+        let mut excesses: BTreeMap<u64, i128> = BTreeMap::new();
+        for excess in [&self.0, &self.1, &next.0, &next.1] {
+            *excesses.entry(excess.token.0).or_default() += excess.value();
+        }
+        let excesses: Vec<_> = excesses
+            .into_iter()
+            .filter(|(_, value)| *value != 0)
+            .map(|(token, value)| (TokenId(token), value))
+            .collect();
+        if excesses.len() > 2
+            || excesses
+                .iter()
+                .any(|(_, value)| value.unsigned_abs() > u64::MAX as u128)
+        {
+            return Err(SnarkWorkError::FeeExcessOverflow);
+        }
+        Ok(excesses)
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, AutoFrom)]
 #[auto_from(mina_serialization_types::snark_work::FeeExcess)]
 pub struct FeeExcess {
@@ -91,6 +240,30 @@ pub struct FeeExcess {
     pub amount: Signed,
 }
 
+impl FeeExcess {
+    /// Whether the fee excess has a zero magnitude
+    pub fn is_zero(&self) -> bool {
+        self.amount.magnitude.0 == 0
+    }
+
+    /// Signed value of the fee excess
+    pub fn value(&self) -> i128 {
+        let magnitude = self.amount.magnitude.0 as i128;
+        match self.amount.sgn {
+            SgnType::Pos => magnitude,
+            SgnType::Neg => -magnitude,
+        }
+    }
+
+    /// Checks that a zero fee excess is positive and in the default token
+    pub fn check(&self) -> Result<(), SnarkWorkError> {
+        if self.is_zero() && (self.amount.sgn == SgnType::Neg || self.token != DEFAULT_TOKEN_ID) {
+            return Err(SnarkWorkError::InvalidFeeExcess);
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, AutoFrom)]
 #[auto_from(mina_serialization_types::snark_work::Signed)]
 pub struct Signed {
@@ -104,3 +277,74 @@ pub enum SgnType {
     Pos,
     Neg,
 }
+
+/// Reasons for the completed works of a staged ledger diff to be malformed
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum SnarkWorkError {
+    /// The target ledger of a statement is not the source ledger of the next one
+    #[error("Ledger hash mismatch, target: {target:?}, next source: {source:?}")]
+    LedgerHashMismatch {
+        /// Target ledger hash of the first statement
+        target: StateHash,
+        /// Source ledger hash of the second statement
+        source: StateHash,
+    },
+
+    /// The next available token after a statement is not the one before the next statement
+    #[error("Next available token mismatch, after: {after:?}, next before: {before:?}")]
+    NextAvailableTokenMismatch {
+        /// Next available token after the first statement
+        after: TokenId,
+        /// Next available token before the second statement
+        before: TokenId,
+    },
+
+    /// The pending coinbase stack of a statement doesn't continue from the previous one
+    #[error("Pending coinbase stacks are not connected")]
+    PendingCoinbaseStackDisconnected,
+
+    /// A fee excess has a negative or non-default token zero, or the pair repeats a token
+    #[error("Fee excess is not in canonical form")]
+    InvalidFeeExcess,
+
+    /// The fee excesses of two statements cannot be combined
+    #[error("Fee excess overflow")]
+    FeeExcessOverflow,
+
+    /// The supply increases of two statements cannot be combined
+    #[error("Supply increase overflow")]
+    SupplyIncreaseOverflow,
+
+    /// A coinbase fee transfer does not pay for any completed work
+    #[error("Coinbase fee transfer of {fee} to {receiver_pk:?} does not match a completed work")]
+    UnmatchedCoinbaseFeeTransfer {
+        /// Receiver of the coinbase fee transfer
+        receiver_pk: CompressedPubKey,
+        /// Fee of the coinbase fee transfer
+        fee: Amount,
+    },
+
+    /// The fees of the user commands cannot pay for the completed works
+    #[error("Fees of user commands are insufficient to pay for the snark work")]
+    InsufficientFeeExcess,
+
+    /// The number of fee transfers in the diff is not the number of derived fee transfers
+    #[error("Expected {expected} fee transfers, got {actual}")]
+    FeeTransferCountMismatch {
+        /// Number of fee transfers that pay the provers and the block producer
+        expected: usize,
+        /// Number of fee transfers in the diff
+        actual: usize,
+    },
+
+    /// A fee transfer in the diff doesn't pay as many receivers as the derived fee transfer
+    #[error("Fee transfer {index} pays {actual} receivers, expected {expected}")]
+    FeeTransferReceiversMismatch {
+        /// Index of the fee transfer
+        index: usize,
+        /// Number of receivers of the derived fee transfer
+        expected: usize,
+        /// Number of receivers of the fee transfer in the diff
+        actual: usize,
+    },
+}
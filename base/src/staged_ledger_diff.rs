@@ -8,14 +8,16 @@
 #![allow(missing_docs)]
 
 use crate::numbers::Amount;
-use crate::snark_work::TransactionSnarkWork;
+use crate::snark_work::{SnarkWorkError, TransactionSnarkWork};
 use crate::types::TokenId;
 use crate::user_commands::{SignedCommandPayload, UserCommand};
 use crate::verifiable::Verifiable;
-use mina_serialization_types::{json::*, v1::*};
+use mina_serialization_types::{json::*, v1::*, BinProtSerializationType};
 use mina_serialization_types_macros::AutoFrom;
+use proof_systems::mina_hasher::Fp;
 use proof_systems::mina_signer::{CompressedPubKey, Signer};
 use smart_default::SmartDefault;
+use std::collections::BTreeMap;
 use versioned::*;
 
 #[derive(Clone, Eq, PartialEq, Debug, Default, AutoFrom)]
@@ -27,6 +29,27 @@ pub struct StagedLedgerDiff {
 
 impl_from_with_proxy!(StagedLedgerDiff, StagedLedgerDiffV1, StagedLedgerDiffJson);
 
+impl BinProtSerializationType<'_> for StagedLedgerDiff {
+    type T = StagedLedgerDiffV1;
+}
+
+impl StagedLedgerDiff {
+    /// Checks the completed works of all the pre diffs,
+    /// see [StagedLedgerPreDiff::check_completed_works]
+    pub fn check_completed_works(
+        &self,
+        coinbase_receiver: &CompressedPubKey,
+    ) -> Result<(), SnarkWorkError> {
+        self.diff
+            .diff_two()
+            .check_completed_works(coinbase_receiver)?;
+        if let Some(diff_one) = self.diff.diff_one() {
+            diff_one.check_completed_works(coinbase_receiver)?;
+        }
+        Ok(())
+    }
+}
+
 impl<CTX> Verifiable<CTX> for StagedLedgerDiff
 where
     CTX: Signer<SignedCommandPayload>,
@@ -50,6 +73,10 @@ pub struct StagedLedgerDiffTuple(
 );
 
 impl StagedLedgerDiffTuple {
+    pub fn new(diff_two: StagedLedgerPreDiff, diff_one: Option<StagedLedgerPreDiff>) -> Self {
+        Self(diff_two, diff_one)
+    }
+
     pub fn diff_two(&self) -> &StagedLedgerPreDiff {
         &self.0
     }
@@ -68,6 +95,145 @@ pub struct StagedLedgerPreDiff {
     pub internal_command_balances: Vec<InternalCommandBalanceData>,
 }
 
+impl StagedLedgerPreDiff {
+    /// Checks the statements of the completed works and that the provers are paid,
+    /// this doesn't verify the proofs
+    ///
+    /// Provers are paid either by a coinbase fee transfer of the fee of one of their works,
+    /// or by the fee transfers derived by [StagedLedgerPreDiff::fee_transfers].
+    /// The diff only records the balances after each fee transfer, so the derived fee transfers
+    /// are checked against the number of fee transfers and of receivers of each one,
+    /// their receivers and fees are checked by applying them to the ledger
+    pub fn check_completed_works(
+        &self,
+        coinbase_receiver: &CompressedPubKey,
+    ) -> Result<(), SnarkWorkError> {
+        for work in self.completed_works.iter() {
+            work.check_statements()?;
+        }
+
+        let mut unpaid: Vec<&TransactionSnarkWork> = self
+            .completed_works
+            .iter()
+            .filter(|work| work.fee.0 > 0)
+            .collect();
+        for fee_transfer in self.coinbase.fee_transfers() {
+            let index = unpaid
+                .iter()
+                .position(|work| {
+                    work.prover == fee_transfer.receiver_pk && work.fee == fee_transfer.fee
+                })
+                .ok_or_else(|| SnarkWorkError::UnmatchedCoinbaseFeeTransfer {
+                    receiver_pk: fee_transfer.receiver_pk.clone(),
+                    fee: fee_transfer.fee,
+                })?;
+            unpaid.remove(index);
+        }
+
+        let expected = self.fee_transfers(coinbase_receiver)?;
+        let actual: Vec<&FeeTransferBalanceData> = self
+            .internal_command_balances
+            .iter()
+            .filter_map(|balance| match balance {
+                InternalCommandBalanceData::FeeTransfer(balance) => Some(balance),
+                InternalCommandBalanceData::CoinBase(_) => None,
+            })
+            .collect();
+        if expected.len() != actual.len() {
+            return Err(SnarkWorkError::FeeTransferCountMismatch {
+                expected: expected.len(),
+                actual: actual.len(),
+            });
+        }
+        for (index, ((_, second), balance)) in expected.iter().zip(actual).enumerate() {
+            if second.is_some() != balance.receiver2_balance.is_some() {
+                return Err(SnarkWorkError::FeeTransferReceiversMismatch {
+                    index,
+                    expected: 1 + second.iter().count(),
+                    actual: 1 + balance.receiver2_balance.iter().count(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Fee transfers of the pre diff, which pay the provers of the completed works and
+    /// the fees of the user commands that are left to the coinbase receiver, see `fee_transfers` in
+    /// <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/staged_ledger_diff/pre_diff_info.ml>
+    ///
+    /// Fees to the same receiver are combined and the fees paid by the coinbase fee transfers
+    /// are deducted, the rest are ordered by receiver and paired into fee transfers of one or two
+    pub fn fee_transfers(
+        &self,
+        coinbase_receiver: &CompressedPubKey,
+    ) -> Result<Vec<(CoinBaseFeeTransfer, Option<CoinBaseFeeTransfer>)>, SnarkWorkError> {
+        let user_command_fees = self
+            .commands
+            .iter()
+            .map(|command| match &command.data {
+                UserCommand::SignedCommand(signed_command) => signed_command.payload.common.fee,
+            })
+            .try_fold(0_u64, |acc, fee| acc.checked_add(fee.0))
+            .ok_or(SnarkWorkError::FeeExcessOverflow)?;
+        let work_fees = self
+            .completed_works
+            .iter()
+            .try_fold(0_u64, |acc, work| acc.checked_add(work.fee.0))
+            .ok_or(SnarkWorkError::FeeExcessOverflow)?;
+        let coinbase_work_fees = self
+            .coinbase
+            .fee_transfers()
+            .iter()
+            .try_fold(0_u64, |acc, ft| acc.checked_add(ft.fee.0))
+            .ok_or(SnarkWorkError::FeeExcessOverflow)?;
+        let fee_excess = work_fees
+            .checked_sub(coinbase_work_fees)
+            .and_then(|work_fees| user_command_fees.checked_sub(work_fees))
+            .ok_or(SnarkWorkError::InsufficientFeeExcess)?;
+
+        // Receivers are ordered by public key, as the OCaml implementation collects them in a map
+        let mut singles: BTreeMap<(Fp, bool), (CompressedPubKey, u64)> = BTreeMap::new();
+        let mut add_single =
+            |receiver: &CompressedPubKey, fee: u64| -> Result<(), SnarkWorkError> {
+                let (_, total) = singles
+                    .entry((receiver.x, receiver.is_odd))
+                    .or_insert_with(|| (receiver.clone(), 0));
+                *total = total
+                    .checked_add(fee)
+                    .ok_or(SnarkWorkError::FeeExcessOverflow)?;
+                Ok(())
+            };
+        if fee_excess > 0 {
+            add_single(coinbase_receiver, fee_excess)?;
+        }
+        for work in self.completed_works.iter().filter(|w| w.fee.0 > 0) {
+            add_single(&work.prover, work.fee.0)?;
+        }
+        for ft in self.coinbase.fee_transfers() {
+            let key = (ft.receiver_pk.x, ft.receiver_pk.is_odd);
+            if let Some((_, fee)) = singles.get_mut(&key) {
+                if *fee <= ft.fee.0 {
+                    singles.remove(&key);
+                } else {
+                    *fee -= ft.fee.0;
+                }
+            }
+        }
+
+        let mut singles = singles
+            .into_values()
+            .map(|(receiver_pk, fee)| CoinBaseFeeTransfer {
+                receiver_pk,
+                fee: Amount(fee),
+            });
+        let mut fee_transfers = Vec::new();
+        while let Some(first) = singles.next() {
+            fee_transfers.push((first, singles.next()));
+        }
+        Ok(fee_transfers)
+    }
+}
+
 impl<CTX> Verifiable<CTX> for StagedLedgerPreDiff
 where
     CTX: Signer<SignedCommandPayload>,
@@ -161,6 +327,17 @@ pub enum CoinBase {
     Two(Option<CoinBaseFeeTransfer>, Option<CoinBaseFeeTransfer>),
 }
 
+impl CoinBase {
+    /// Fee transfers paid from the coinbase
+    pub fn fee_transfers(&self) -> Vec<&CoinBaseFeeTransfer> {
+        match self {
+            CoinBase::Zero => vec![],
+            CoinBase::One(fee_transfer) => fee_transfer.iter().collect(),
+            CoinBase::Two(first, second) => first.iter().chain(second.iter()).collect(),
+        }
+    }
+}
+
 impl_from_with_proxy!(
     CoinBase,
     mina_serialization_types::staged_ledger_diff::CoinBase,
@@ -0,0 +1,76 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use mina_crypto::hash::StateHash;
+    use mina_rs_base::types::*;
+    use mina_serialization_types::json::ExternalTransitionJson;
+    use proof_systems::mina_hasher::{create_legacy, Hasher};
+    use std::str::FromStr;
+    use test_fixtures::*;
+
+    fn read_block_json(block_path: &str) -> ExternalTransition {
+        let json_block = JSON_TEST_BLOCKS.get(block_path).unwrap();
+        serde_json::from_value::<ExternalTransitionJson>(json_block.clone())
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn verify_all_json_fixtures_delta_transition_chain() {
+        for (path, v) in JSON_TEST_BLOCKS.iter() {
+            let block: ExternalTransition =
+                serde_json::from_value::<ExternalTransitionJson>(v.clone())
+                    .unwrap()
+                    .into();
+            let state_hash = path.trim_end_matches(".json").split('-').last().unwrap();
+            assert_eq!(
+                block.verify_delta_transition_chain(),
+                Some(vec![
+                    block.protocol_state.previous_state_hash.clone(),
+                    StateHash::from_str(state_hash).unwrap(),
+                ])
+            );
+        }
+    }
+
+    #[test]
+    fn verify_delta_transition_chain_with_body_hashes() {
+        let parent = read_block_json(
+            "mainnet-77748-3NKaBJsN1SehD6iJwRwJSFmVzJg5DXSUQVgnMxtH4eer4aF5BrDK.json",
+        );
+        let mut block = read_block_json(
+            "mainnet-77749-3NK3P5bJHhqR7xkZBquGGfq3sERUeXNYNma5YXRMjgCNsTJRZpgL.json",
+        );
+
+        // The proof starts from the grandparent of the block
+        let mut hasher = create_legacy::<ProtocolStateBodyLegacy>(());
+        let parent_body_hash = hasher.hash(&parent.protocol_state.body);
+        let proof = DeltaTransitionChainProof(
+            parent.protocol_state.previous_state_hash.clone(),
+            vec![(&parent_body_hash).into()],
+        );
+        let ancestors = vec![
+            parent.protocol_state.previous_state_hash.clone(),
+            parent.protocol_state.state_hash(),
+        ];
+        assert_eq!(
+            proof.verify(&block.protocol_state.previous_state_hash),
+            Some(ancestors.clone())
+        );
+        assert_eq!(
+            proof.verify(&parent.protocol_state.previous_state_hash),
+            None
+        );
+
+        block.delta_transition_chain_proof = proof;
+        let mut expected = ancestors;
+        expected.push(block.protocol_state.state_hash());
+        assert_eq!(block.verify_delta_transition_chain(), Some(expected));
+
+        // Tampered body hashes lead elsewhere
+        block.delta_transition_chain_proof.1[0] = parent.protocol_state.state_hash();
+        assert_eq!(block.verify_delta_transition_chain(), None);
+    }
+}
@@ -0,0 +1,215 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use mina_rs_base::types::*;
+    use mina_serialization_types::json::ExternalTransitionJson;
+    use proof_systems::mina_signer::CompressedPubKey;
+    use test_fixtures::*;
+
+    fn read_pre_diff_json(block_path: &str) -> (StagedLedgerPreDiff, CompressedPubKey) {
+        let json_block = JSON_TEST_BLOCKS.get(block_path).unwrap();
+        let block: ExternalTransition =
+            serde_json::from_value::<ExternalTransitionJson>(json_block.clone())
+                .unwrap()
+                .into();
+        (
+            block.staged_ledger_diff.diff.diff_two().clone(),
+            block.protocol_state.body.consensus_state.coinbase_receiver,
+        )
+    }
+
+    fn first_pair(pre_diff: &mut StagedLedgerPreDiff) -> (&mut Statement, &mut Statement) {
+        let work = pre_diff
+            .completed_works
+            .iter_mut()
+            .find(|work| matches!(work.proofs, OneORTwo::Two(..)))
+            .unwrap();
+        match &mut work.proofs {
+            OneORTwo::Two(first, second) => (&mut first.statement, &mut second.statement),
+            OneORTwo::One(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn check_all_json_fixtures_completed_works() {
+        for (_, v) in JSON_TEST_BLOCKS.iter() {
+            let block: ExternalTransition =
+                serde_json::from_value::<ExternalTransitionJson>(v.clone())
+                    .unwrap()
+                    .into();
+            let coinbase_receiver = &block.protocol_state.body.consensus_state.coinbase_receiver;
+            assert_eq!(
+                block
+                    .staged_ledger_diff
+                    .check_completed_works(coinbase_receiver),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    fn check_invalid_statements() {
+        let (pre_diff, receiver) = read_pre_diff_json(
+            "mainnet-116121-3NK6myZRzc3GvS5iydv88on2XTEU2btYrjMVkgtbuoeXASRipSa6.json",
+        );
+
+        let mut invalid = pre_diff.clone();
+        let (first, second) = first_pair(&mut invalid);
+        second.source = first.source.clone();
+        let expected = SnarkWorkError::LedgerHashMismatch {
+            target: first.target.clone(),
+            source: first.source.clone(),
+        };
+        assert_eq!(invalid.check_completed_works(&receiver), Err(expected));
+
+        let mut invalid = pre_diff.clone();
+        let (first, second) = first_pair(&mut invalid);
+        second.next_available_token_before = TokenId(first.next_available_token_after.0 + 1);
+        assert!(matches!(
+            invalid.check_completed_works(&receiver),
+            Err(SnarkWorkError::NextAvailableTokenMismatch { .. })
+        ));
+
+        let mut invalid = pre_diff.clone();
+        let (first, second) = first_pair(&mut invalid);
+        second.pending_coinbase_stack_state.source.state_stack.init =
+            first.pending_coinbase_stack_state.target.data_stack.clone();
+        assert_eq!(
+            invalid.check_completed_works(&receiver),
+            Err(SnarkWorkError::PendingCoinbaseStackDisconnected)
+        );
+
+        let mut invalid = pre_diff.clone();
+        let (first, _) = first_pair(&mut invalid);
+        first.fee_excess.1.amount.sgn = SgnType::Neg;
+        assert_eq!(
+            invalid.check_completed_works(&receiver),
+            Err(SnarkWorkError::InvalidFeeExcess)
+        );
+
+        let mut invalid = pre_diff;
+        let (first, second) = first_pair(&mut invalid);
+        first.fee_excess.0.amount = Signed {
+            magnitude: Amount(u64::MAX),
+            sgn: SgnType::Pos,
+        };
+        second.fee_excess.0.amount = Signed {
+            magnitude: Amount(1),
+            sgn: SgnType::Pos,
+        };
+        assert_eq!(
+            invalid.check_completed_works(&receiver),
+            Err(SnarkWorkError::FeeExcessOverflow)
+        );
+    }
+
+    #[test]
+    fn check_prover_fee_payments() {
+        // The prover of the only work is paid by the coinbase fee transfer,
+        // the rest of the user command fees is paid to the block producer
+        let (pre_diff, receiver) = read_pre_diff_json(
+            "mainnet-149909-3NLCeY7UwgCryuvk3Wevm9ndMDvWAMjwGBfBJS12MqL1QoTQWEWt.json",
+        );
+        assert_eq!(
+            pre_diff.fee_transfers(&receiver),
+            Ok(vec![(
+                CoinBaseFeeTransfer {
+                    receiver_pk: receiver.clone(),
+                    fee: Amount(1_000_000),
+                },
+                None
+            )])
+        );
+
+        let mut invalid = pre_diff.clone();
+        let fee_transfer = CoinBaseFeeTransfer {
+            receiver_pk: pre_diff.completed_works[0].prover.clone(),
+            fee: Amount(1),
+        };
+        invalid.coinbase = CoinBase::One(Some(fee_transfer.clone()));
+        assert_eq!(
+            invalid.check_completed_works(&receiver),
+            Err(SnarkWorkError::UnmatchedCoinbaseFeeTransfer {
+                receiver_pk: fee_transfer.receiver_pk,
+                fee: fee_transfer.fee,
+            })
+        );
+
+        // Without the coinbase fee transfer the user command fees cannot pay the prover
+        let mut invalid = pre_diff.clone();
+        invalid.coinbase = CoinBase::One(None);
+        assert_eq!(
+            invalid.check_completed_works(&receiver),
+            Err(SnarkWorkError::InsufficientFeeExcess)
+        );
+
+        let mut invalid = pre_diff.clone();
+        invalid.internal_command_balances.pop();
+        assert_eq!(
+            invalid.check_completed_works(&receiver),
+            Err(SnarkWorkError::FeeTransferCountMismatch {
+                expected: 1,
+                actual: 0,
+            })
+        );
+
+        let mut invalid = pre_diff;
+        invalid.internal_command_balances[1] =
+            InternalCommandBalanceData::FeeTransfer(FeeTransferBalanceData {
+                receiver1_balance: Amount(1),
+                receiver2_balance: Some(Amount(1)),
+            });
+        assert_eq!(
+            invalid.check_completed_works(&receiver),
+            Err(SnarkWorkError::FeeTransferReceiversMismatch {
+                index: 0,
+                expected: 1,
+                actual: 2,
+            })
+        );
+
+        // Two provers with non-zero fees and the producer are paid by two fee transfers
+        let (mut pre_diff, receiver) = read_pre_diff_json(
+            "mainnet-116121-3NK6myZRzc3GvS5iydv88on2XTEU2btYrjMVkgtbuoeXASRipSa6.json",
+        );
+        let fee_transfers = pre_diff.fee_transfers(&receiver).unwrap();
+        assert_eq!(fee_transfers.len(), 2);
+        assert!(fee_transfers[0].1.is_some());
+        assert!(fee_transfers[1].1.is_none());
+        let fee_transfer = pre_diff
+            .internal_command_balances
+            .iter()
+            .find(|balance| matches!(balance, InternalCommandBalanceData::FeeTransfer(_)))
+            .cloned()
+            .unwrap();
+        pre_diff.internal_command_balances.push(fee_transfer);
+        assert_eq!(
+            pre_diff.check_completed_works(&receiver),
+            Err(SnarkWorkError::FeeTransferCountMismatch {
+                expected: 2,
+                actual: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn check_pending_coinbase_new_stack_is_connected() {
+        let (mut pre_diff, _) = read_pre_diff_json(
+            "mainnet-116121-3NK6myZRzc3GvS5iydv88on2XTEU2btYrjMVkgtbuoeXASRipSa6.json",
+        );
+        let (first, second) = first_pair(&mut pre_diff);
+        let target = first.pending_coinbase_stack_state.target.clone();
+        let mut next = second.pending_coinbase_stack_state.source.clone();
+        assert!(target.is_connected(&next));
+
+        // A new stack starts without coinbases and with a new state stack
+        next.data_stack = (&empty_coinbase_stack()).into();
+        next.state_stack.init = next.state_stack.curr.clone();
+        assert!(target.is_connected(&next));
+
+        next.data_stack = next.state_stack.curr.clone();
+        assert!(!target.is_connected(&next));
+    }
+}
@@ -9,7 +9,7 @@ use crate::error::ConsensusError;
 use mina_rs_base::consensus_state::ConsensusState;
 use mina_rs_base::global_slot::GlobalSlot;
 use mina_rs_base::protocol_state::ProtocolStateHeader;
-use mina_rs_base::types::{BlockTime, Length};
+use mina_rs_base::types::{BlockTime, BlockTimeSpan, Length};
 use proof_systems::mina_hasher::Fp;
 
 // TODO: derive from protocol constants
@@ -25,10 +25,14 @@ pub struct ConsensusConstants {
     pub delta: Length,
     /// Timestamp of genesis block in unixtime
     pub genesis_state_timestamp: BlockTime,
+    /// Duration of a slot in milliseconds
+    pub block_window_duration: BlockTimeSpan,
     /// Sub windows within a window
     pub sub_windows_per_window: Length,
     /// Number of slots before minimum density is used in chain selection
     pub grace_period_end: Length,
+    /// Number of slots in a checkpoint window, there are 12 checkpoint windows per year
+    pub checkpoint_window_size_in_slots: Length,
 }
 
 impl ConsensusConstants {
@@ -40,8 +44,10 @@ impl ConsensusConstants {
             slots_per_sub_window: Length(7),
             delta: Length(0),
             genesis_state_timestamp: BlockTime(1615939200000),
+            block_window_duration: BlockTimeSpan(180000),
             sub_windows_per_window: Length(11),
             grace_period_end: Length(1440),
+            checkpoint_window_size_in_slots: Length(14600),
         }
     }
 
@@ -104,8 +110,13 @@ where
     /// The function computes the epoch slot number of a block.
     /// The output is the epoch slot number in [0, slots_per_epoch].
     pub fn epoch_slot(&self) -> Option<u32> {
-        self.global_slot()
-            .map(|s| (s.slot_number.0 % s.slots_per_epoch.0))
+        self.global_slot().map(|s| {
+            ConsensusConstants {
+                slots_per_epoch: s.slots_per_epoch,
+                ..self.config()
+            }
+            .slot_in_epoch(s.slot_number)
+        })
     }
 
     /// The function the length of a chain. The output is the length of the chain in blocks.
@@ -131,6 +142,85 @@ where
     pub fn state_hash(&self) -> Option<Fp> {
         self.top().map(|s| s.state_hash_fp())
     }
+
+    /// Compares the blockchain lengths, then the blake2b digests of the last VRF outputs,
+    /// then the state hashes of the chains, returns whether to take the candidate chain
+    /// along with the rule that decides it
+    fn tie_break(&self, candidate: &Self) -> Result<(bool, SelectionReason), ConsensusError> {
+        let top_state = self
+            .consensus_state()
+            .ok_or(ConsensusError::ConsensusStateNotFound)?;
+        let candidate_state = candidate
+            .consensus_state()
+            .ok_or(ConsensusError::ConsensusStateNotFound)?;
+        if top_state.blockchain_length != candidate_state.blockchain_length {
+            return Ok((
+                top_state.blockchain_length < candidate_state.blockchain_length,
+                SelectionReason::BlockchainLength,
+            ));
+        }
+        // tiebreak logic
+        match candidate
+            .last_vrf_hash_digest()?
+            .cmp(&self.last_vrf_hash_digest()?)
+        {
+            std::cmp::Ordering::Greater => Ok((true, SelectionReason::LastVrfOutput)),
+            std::cmp::Ordering::Less => Ok((false, SelectionReason::LastVrfOutput)),
+            std::cmp::Ordering::Equal => match candidate.state_hash().cmp(&self.state_hash()) {
+                std::cmp::Ordering::Greater => Ok((true, SelectionReason::StateHash)),
+                std::cmp::Ordering::Less => Ok((false, SelectionReason::StateHash)),
+                std::cmp::Ordering::Equal => Ok((false, SelectionReason::SameTip)),
+            },
+        }
+    }
+}
+
+/// Whether to keep the current chain or to take the candidate chain
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SelectionChoice {
+    /// Keep the current chain
+    Keep,
+    /// Take the candidate chain
+    Take,
+}
+
+/// Range of a fork between the current chain and a candidate chain
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ForkRange {
+    /// The chains share the most recent finalized lock checkpoint
+    Short,
+    /// The chains do not share the most recent finalized lock checkpoint,
+    /// including forks whose epochs differ by more than one
+    Long,
+}
+
+/// The rule that decides the [SelectionChoice]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SelectionReason {
+    /// The candidate chain of a long range fork has malformed sub window densities
+    InvalidSubWindowDensities,
+    /// The chains of a long range fork have different relative minimum window densities
+    MinWindowDensity,
+    /// The chains have different blockchain lengths
+    BlockchainLength,
+    /// The chains have different blake2b digests of the last VRF outputs
+    LastVrfOutput,
+    /// The chains have different state hashes
+    StateHash,
+    /// The chains have the same tip
+    SameTip,
+}
+
+/// Decision of chain selection along with the reason, which follows `select` in
+/// <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/consensus/proof_of_stake.ml>
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ChainSelectionDecision {
+    /// Whether to keep the current chain or to take the candidate chain
+    pub choice: SelectionChoice,
+    /// Range of the fork
+    pub fork_range: ForkRange,
+    /// The rule that decides the choice
+    pub reason: SelectionReason,
 }
 
 /// A trait that defines operations for chain selection
@@ -140,6 +230,13 @@ pub trait ChainSelection {
     where
         Self: Sized;
 
+    /// Decides whether the candidate chain should replace the current chain.
+    ///
+    /// Short range forks are decided by the blockchain length, long range forks are decided by
+    /// the relative minimum window density first, ties are broken by the blake2b digest of
+    /// the last VRF output and then by the state hash
+    fn select(&self, candidate: &Self) -> Result<ChainSelectionDecision, ConsensusError>;
+
     /// Selects the longer chain when there's a short range fork.
     fn select_longer_chain(&mut self, candidate: Self) -> Result<(), ConsensusError>
     where
@@ -161,69 +258,64 @@ where
 {
     fn select_secure_chain(&mut self, candidates: Vec<Self>) -> Result<(), ConsensusError> {
         for candidate in candidates {
-            if self.is_short_range(&candidate)? {
-                // short-range fork, select longer chain
-                self.select_longer_chain(candidate)?;
+            if self.select(&candidate)?.choice == SelectionChoice::Take {
+                *self = candidate;
+            }
+        }
+        Ok(())
+    }
+
+    fn select(&self, candidate: &Self) -> Result<ChainSelectionDecision, ConsensusError> {
+        let fork_range = if self.is_short_range(candidate)? {
+            ForkRange::Short
+        } else {
+            ForkRange::Long
+        };
+        let decision = |take: bool, reason: SelectionReason| ChainSelectionDecision {
+            choice: if take {
+                SelectionChoice::Take
             } else {
-                // check against sub window density sizes > 11
-                let candidate_state = candidate
-                    .consensus_state()
-                    .ok_or(ConsensusError::ConsensusStateNotFound)?;
-
-                // sub window density must not be greater than initial genesis subwindow density value.
-                if candidate_state
-                    .sub_window_densities()
-                    .iter()
-                    .any(|s| *s > self.config().slots_per_sub_window.0)
-                {
-                    continue;
-                };
+                SelectionChoice::Keep
+            },
+            fork_range,
+            reason,
+        };
 
-                // sub window densities must not be greater than sub_windows_per_window
-                let sub_windows_per_window = self.config().sub_windows_per_window.0 as usize;
-                if candidate_state.sub_window_densities.len() != sub_windows_per_window {
-                    continue;
-                }
+        if fork_range == ForkRange::Long {
+            let candidate_state = candidate
+                .consensus_state()
+                .ok_or(ConsensusError::ConsensusStateNotFound)?;
+
+            // sub window density must not be greater than initial genesis subwindow density value,
+            // and the number of sub window densities must be sub_windows_per_window
+            let sub_windows_per_window = self.config().sub_windows_per_window.0 as usize;
+            if candidate_state
+                .sub_window_densities()
+                .iter()
+                .any(|s| *s > self.config().slots_per_sub_window.0)
+                || candidate_state.sub_window_densities.len() != sub_windows_per_window
+            {
+                return Ok(decision(false, SelectionReason::InvalidSubWindowDensities));
+            }
 
-                let tip_density = self.relative_min_window_density(&candidate)?;
-                let candidate_density = candidate.relative_min_window_density(self)?;
-                match candidate_density.cmp(&tip_density) {
-                    std::cmp::Ordering::Greater => *self = candidate,
-                    std::cmp::Ordering::Equal => self.select_longer_chain(candidate)?,
-                    _ => (), // no change
-                }
+            let tip_density = self.relative_min_window_density(candidate)?;
+            let candidate_density = candidate.relative_min_window_density(self)?;
+            if candidate_density != tip_density {
+                return Ok(decision(
+                    candidate_density > tip_density,
+                    SelectionReason::MinWindowDensity,
+                ));
             }
         }
-        Ok(())
+
+        let (take, reason) = self.tie_break(candidate)?;
+        Ok(decision(take, reason))
     }
 
     fn select_longer_chain(&mut self, candidate: Self) -> Result<(), ConsensusError> {
-        let top_state = self
-            .consensus_state()
-            .ok_or(ConsensusError::ConsensusStateNotFound)?;
-        let candidate_state = candidate
-            .consensus_state()
-            .ok_or(ConsensusError::ConsensusStateNotFound)?;
-        if top_state.blockchain_length < candidate_state.blockchain_length {
+        if self.tie_break(&candidate)?.0 {
             *self = candidate;
-        } else if top_state.blockchain_length == candidate_state.blockchain_length {
-            // tiebreak logic
-            match candidate
-                .last_vrf_hash_digest()?
-                .cmp(&self.last_vrf_hash_digest()?)
-            {
-                std::cmp::Ordering::Greater => {
-                    *self = candidate;
-                }
-                std::cmp::Ordering::Equal => {
-                    if candidate.state_hash() > self.state_hash() {
-                        *self = candidate;
-                    }
-                }
-                _ => {}
-            }
         }
-
         Ok(())
     }
 
@@ -237,9 +329,11 @@ where
         let a_prev_lock_checkpoint = &a.staking_epoch_data.lock_checkpoint;
         let b_prev_lock_checkpoint = &b.staking_epoch_data.lock_checkpoint;
 
-        let check = |s1: &ConsensusState, s2: &ConsensusState, s2_epoch_slot: Option<u32>| {
+        let check = |s1: &ConsensusState, s2: &ConsensusState| {
             if s1.epoch_count.0 == s2.epoch_count.0 + 1
-                && s2_epoch_slot >= Some(self.config().slots_per_epoch.0 * 2 / 3)
+                && !self
+                    .config()
+                    .in_seed_update_range(s2.curr_global_slot.slot_number)
             {
                 // S1 is one epoch ahead of S2 and S2 is not in the seed update range
                 s1.staking_epoch_data.lock_checkpoint == s2.next_epoch_data.lock_checkpoint
@@ -253,7 +347,7 @@ where
             Ok(a_prev_lock_checkpoint == b_prev_lock_checkpoint)
         } else {
             // Check for previous epoch case using both orientations
-            Ok(check(a, b, candidate.epoch_slot()) || check(b, a, self.epoch_slot()))
+            Ok(check(a, b) || check(b, a))
         }
     }
 
@@ -302,9 +396,9 @@ where
             let mut projected_window = tip_state.sub_window_densities.clone();
 
             // relative sub window
-            let mut rel_sub_window = tip_state.curr_global_slot.slot_number.0
-                / self.config().sub_windows_per_window.0
-                % self.config().sub_windows_per_window.0;
+            let mut rel_sub_window = self
+                .config()
+                .relative_sub_window(tip_state.curr_global_slot.slot_number);
 
             // ring shift
             while shift_count > 0 {
@@ -36,6 +36,24 @@ pub enum ConsensusError {
     #[error("Invalid sub window density length")]
     InvalidSubWindowDensityLen,
 
+    /// Global slot of the new block is not after the previous one
+    #[error("Global slot did not increase, prev: {prev}, next: {next}")]
+    SlotNotIncreasing {
+        /// Global slot of the previous block
+        prev: u32,
+        /// Global slot of the new block
+        next: u32,
+    },
+    /// Total currency overflows
+    #[error("Failed to add total currency")]
+    TotalCurrencyOverflow,
+    /// Epoch seed is not a valid field element
+    #[error("Invalid epoch seed")]
+    InvalidEpochSeed,
+    /// Consensus state of the block does not match the expected one
+    #[error("Consensus state mismatch in field {0}")]
+    ConsensusStateMismatch(&'static str),
+
     /// Blake2b digest generation failed
     #[error("Could not generate blake2b digest of last vrf output: {0}")]
     FailedVrfHashDigest(Utf8Error),
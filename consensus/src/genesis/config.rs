@@ -3,6 +3,7 @@
 
 use std::str::FromStr;
 
+use crate::common::ConsensusConstants;
 use mina_crypto::{hash::*, prelude::*};
 use mina_rs_base::{
     finite_ec_point, finite_ec_point_pair,
@@ -44,6 +45,21 @@ pub struct GenesisInitConfig {
 }
 
 impl GenesisInitConfig {
+    /// Consensus constants of the genesis protocol constants, the block window
+    /// duration, grace period and checkpoint window are not part of the genesis
+    /// config and are the mainnet ones
+    pub(crate) fn consensus_constants(&self) -> ConsensusConstants {
+        ConsensusConstants {
+            k: self.constants.k,
+            slots_per_epoch: self.constants.slots_per_epoch,
+            slots_per_sub_window: self.constants.slots_per_sub_window,
+            delta: self.constants.delta,
+            genesis_state_timestamp: self.constants.genesis_state_timestamp.clone(),
+            sub_windows_per_window: Length(self.sub_windows_per_window),
+            ..ConsensusConstants::mainnet()
+        }
+    }
+
     pub(crate) fn mainnet() -> Self {
         // https://github.com/MinaProtocol/mina/tree/feature/9665-spec-ouroboros-samasika-checkpointing/docs/specs/consensus#3-constants
         let constants = ProtocolConstants {
@@ -28,9 +28,7 @@ impl Genesis for ExternalTransition {
                     global_slot_since_genesis: 0_u32.into(),
                     has_ancestor_in_same_checkpoint_window: true,
                     last_vrf_output: config.last_vrf_output.clone(),
-                    min_window_density: (config.sub_windows_per_window
-                        * config.constants.slots_per_sub_window.0)
-                        .into(),
+                    min_window_density: config.consensus_constants().slots_per_window().into(),
                     next_epoch_data: config.next_epoch_data.clone(),
                     staking_epoch_data: config.staking_epoch_data.clone(),
                     sub_window_densities: config.sub_window_densities.clone(),
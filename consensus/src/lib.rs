@@ -11,3 +11,7 @@
 pub mod common;
 pub mod error;
 pub mod genesis;
+pub mod time;
+pub mod transition;
+pub mod validation;
+pub mod vrf;
@@ -0,0 +1,107 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//!
+//! Conversions between block time, global slots, epochs and windows,
+//! following `Block_time`, `Epoch` and `Slot` in
+//! <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/consensus/proof_of_stake.ml>
+//!
+//! Global slots are counted from the genesis state timestamp, every slot lasts
+//! `block_window_duration` and every epoch has `slots_per_epoch` slots
+//!
+
+use crate::common::ConsensusConstants;
+use mina_rs_base::types::{BlockTime, GlobalSlotNumber};
+
+impl ConsensusConstants {
+    /// Global slot that contains the given time, [None] if the time is before genesis
+    pub fn slot_of_time(&self, time: &BlockTime) -> Option<GlobalSlotNumber> {
+        let elapsed = time.0.checked_sub(self.genesis_state_timestamp.0)?;
+        Some(GlobalSlotNumber(
+            (elapsed / self.block_window_duration.0) as u32,
+        ))
+    }
+
+    /// Time at which the global slot starts
+    pub fn slot_start_time(&self, slot: GlobalSlotNumber) -> BlockTime {
+        BlockTime(self.genesis_state_timestamp.0 + slot.0 as u64 * self.block_window_duration.0)
+    }
+
+    /// Time at which the global slot ends, which is the start time of the next slot
+    pub fn slot_end_time(&self, slot: GlobalSlotNumber) -> BlockTime {
+        BlockTime(self.slot_start_time(slot).0 + self.block_window_duration.0)
+    }
+
+    /// Epoch of the global slot
+    pub fn epoch(&self, slot: GlobalSlotNumber) -> u32 {
+        slot.0 / self.slots_per_epoch.0
+    }
+
+    /// Index of the global slot within its epoch
+    pub fn slot_in_epoch(&self, slot: GlobalSlotNumber) -> u32 {
+        slot.0 % self.slots_per_epoch.0
+    }
+
+    /// Epoch of the global slot and its index within the epoch
+    pub fn epoch_and_slot(&self, slot: GlobalSlotNumber) -> (u32, u32) {
+        (self.epoch(slot), self.slot_in_epoch(slot))
+    }
+
+    /// Global slot of the given index within the epoch
+    pub fn global_slot(&self, epoch: u32, slot_in_epoch: u32) -> GlobalSlotNumber {
+        GlobalSlotNumber(epoch * self.slots_per_epoch.0 + slot_in_epoch)
+    }
+
+    /// First global slot of the epoch
+    pub fn epoch_start_slot(&self, epoch: u32) -> GlobalSlotNumber {
+        self.global_slot(epoch, 0)
+    }
+
+    /// Time at which the epoch starts
+    pub fn epoch_start_time(&self, epoch: u32) -> BlockTime {
+        self.slot_start_time(self.epoch_start_slot(epoch))
+    }
+
+    /// Epoch that contains the given time, [None] if the time is before genesis
+    pub fn epoch_of_time(&self, time: &BlockTime) -> Option<u32> {
+        self.slot_of_time(time).map(|slot| self.epoch(slot))
+    }
+
+    /// Whether the global slot is in the first 2/3 of its epoch,
+    /// where the seed and the lock checkpoint of the next epoch are updated
+    pub fn in_seed_update_range(&self, slot: GlobalSlotNumber) -> bool {
+        self.slot_in_epoch(slot) < self.slots_per_epoch.0 * 2 / 3
+    }
+
+    /// Whether the global slot is before the end of the grace period,
+    /// during which the minimum window density is not updated
+    pub fn in_grace_period(&self, slot: GlobalSlotNumber) -> bool {
+        slot.0 < self.grace_period_end.0
+    }
+
+    /// Index of the checkpoint window of the global slot
+    pub fn checkpoint_window(&self, slot: GlobalSlotNumber) -> u32 {
+        slot.0 / self.checkpoint_window_size_in_slots.0
+    }
+
+    /// Whether both global slots are in the same checkpoint window
+    pub fn in_same_checkpoint_window(&self, a: GlobalSlotNumber, b: GlobalSlotNumber) -> bool {
+        self.checkpoint_window(a) == self.checkpoint_window(b)
+    }
+
+    /// Number of slots in a window, which is the density of a full window
+    pub fn slots_per_window(&self) -> u32 {
+        self.sub_windows_per_window.0 * self.slots_per_sub_window.0
+    }
+
+    /// Index of the sub window of the global slot since genesis
+    pub fn sub_window(&self, slot: GlobalSlotNumber) -> u32 {
+        slot.0 / self.slots_per_sub_window.0
+    }
+
+    /// Index of the sub window of the global slot within the window,
+    /// which is the index of its density in the sub window densities
+    pub fn relative_sub_window(&self, slot: GlobalSlotNumber) -> u32 {
+        self.sub_window(slot) % self.sub_windows_per_window.0
+    }
+}
@@ -0,0 +1,331 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//!
+//! Consensus state transition, which derives the consensus state of a new block
+//! from the protocol state of its parent, following `Consensus_state.update` in
+//! <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/consensus/proof_of_stake.ml>
+//!
+
+use crate::{common::ConsensusConstants, error::ConsensusError, vrf::truncate_vrf_output};
+use mina_crypto::hash::{EpochSeed, LedgerHash, StateHash};
+use mina_rs_base::{
+    common::PoseidonVersion,
+    consensus_state::ConsensusState,
+    epoch_data::{EpochData, EpochLedger},
+    global_slot::GlobalSlot,
+    numbers::{Amount, GlobalSlotNumber, Length},
+    protocol_state::ProtocolStateHeader,
+};
+use proof_systems::{
+    mina_hasher::{create_kimchi, create_legacy, Fp, Hashable, Hasher, ROInput},
+    mina_signer::CompressedPubKey,
+    ChunkedROInput, ToChunkedROInput,
+};
+
+/// Inputs of the consensus state transition that come with the new block
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConsensusTransition {
+    /// Global slot of the new block
+    pub global_slot: u32,
+    /// Whether the coinbase of the new block is supercharged
+    pub supercharge_coinbase: bool,
+    /// VRF output of the block producer before truncation
+    pub vrf_output: Fp,
+    /// Amount of currency issued by the new block
+    pub supply_increase: Amount,
+    /// Snarked ledger hash of the parent block
+    pub snarked_ledger_hash: LedgerHash,
+    /// Compressed public key of winning account
+    pub block_stake_winner: CompressedPubKey,
+    /// Compressed public key of the block producer
+    pub block_creator: CompressedPubKey,
+    /// Compresed public key of account receiving the block reward
+    pub coinbase_receiver: CompressedPubKey,
+}
+
+/// Computes the expected consensus state of a new block on top of the previous protocol state
+pub fn next_consensus_state<T>(
+    prev: &T,
+    transition: &ConsensusTransition,
+    constants: &ConsensusConstants,
+) -> Result<ConsensusState, ConsensusError>
+where
+    T: ProtocolStateHeader,
+{
+    let prev_state = prev.consensus_state();
+    let prev_slot = prev_state.curr_global_slot.slot_number.0;
+    let next_slot = transition.global_slot;
+    if next_slot <= prev_slot {
+        return Err(ConsensusError::SlotNotIncreasing {
+            prev: prev_slot,
+            next: next_slot,
+        });
+    }
+    let total_currency = prev_state
+        .total_currency
+        .0
+        .checked_add(transition.supply_increase.0)
+        .map(Amount)
+        .ok_or(ConsensusError::TotalCurrencyOverflow)?;
+    let prev_state_hash: StateHash = (&prev.state_hash_fp()).into();
+
+    let (prev_global_slot, next_global_slot) =
+        (GlobalSlotNumber(prev_slot), GlobalSlotNumber(next_slot));
+    let (staking_epoch_data, mut next_epoch_data, epoch_count) =
+        if constants.epoch(next_global_slot) > constants.epoch(prev_global_slot) {
+            // The next epoch ledger becomes the staking ledger, and the snarked ledger
+            // of the parent block is frozen as the new next epoch ledger
+            let next = &prev_state.next_epoch_data;
+            (
+                next.clone(),
+                EpochData {
+                    ledger: EpochLedger {
+                        hash: transition.snarked_ledger_hash.clone(),
+                        total_currency,
+                    },
+                    seed: next.seed.clone(),
+                    start_checkpoint: prev_state_hash.clone(),
+                    lock_checkpoint: next.lock_checkpoint.clone(),
+                    epoch_length: Length(1),
+                },
+                Length(prev_state.epoch_count.0 + 1),
+            )
+        } else {
+            let mut next = prev_state.next_epoch_data.clone();
+            next.epoch_length = Length(next.epoch_length.0 + 1);
+            (
+                prev_state.staking_epoch_data.clone(),
+                next,
+                prev_state.epoch_count,
+            )
+        };
+    // The seed and the lock checkpoint are only updated in the first 2/3 of the epoch
+    if constants.in_seed_update_range(next_global_slot) {
+        next_epoch_data.seed = update_epoch_seed(
+            &next_epoch_data.seed,
+            transition.vrf_output,
+            prev.poseidon_version(),
+        )?;
+        next_epoch_data.lock_checkpoint = prev_state_hash;
+    }
+
+    let (min_window_density, sub_window_densities) =
+        update_min_window_density(prev_state, next_global_slot, constants)?;
+
+    Ok(ConsensusState {
+        blockchain_length: Length(prev_state.blockchain_length.0 + 1),
+        epoch_count,
+        min_window_density,
+        sub_window_densities,
+        last_vrf_output: truncate_vrf_output(transition.vrf_output),
+        total_currency,
+        curr_global_slot: GlobalSlot {
+            slot_number: next_global_slot,
+            slots_per_epoch: constants.slots_per_epoch,
+        },
+        global_slot_since_genesis: GlobalSlotNumber(
+            prev_state.global_slot_since_genesis.0 + (next_slot - prev_slot),
+        ),
+        staking_epoch_data,
+        next_epoch_data,
+        has_ancestor_in_same_checkpoint_window: constants
+            .in_same_checkpoint_window(prev_global_slot, next_global_slot),
+        block_stake_winner: transition.block_stake_winner.clone(),
+        block_creator: transition.block_creator.clone(),
+        coinbase_receiver: transition.coinbase_receiver.clone(),
+        supercharge_coinbase: transition.supercharge_coinbase,
+    })
+}
+
+/// Checks the consensus state of an incoming block against the expected one
+/// computed by [next_consensus_state], reports the first field that does not match
+pub fn verify_consensus_state(
+    expected: &ConsensusState,
+    actual: &ConsensusState,
+) -> Result<(), ConsensusError> {
+    let fields = [
+        (
+            "blockchain_length",
+            expected.blockchain_length == actual.blockchain_length,
+        ),
+        ("epoch_count", expected.epoch_count == actual.epoch_count),
+        (
+            "min_window_density",
+            expected.min_window_density == actual.min_window_density,
+        ),
+        (
+            "sub_window_densities",
+            expected.sub_window_densities == actual.sub_window_densities,
+        ),
+        (
+            "last_vrf_output",
+            expected.last_vrf_output == actual.last_vrf_output,
+        ),
+        (
+            "total_currency",
+            expected.total_currency == actual.total_currency,
+        ),
+        (
+            "curr_global_slot",
+            expected.curr_global_slot == actual.curr_global_slot,
+        ),
+        (
+            "global_slot_since_genesis",
+            expected.global_slot_since_genesis == actual.global_slot_since_genesis,
+        ),
+        (
+            "staking_epoch_data",
+            expected.staking_epoch_data == actual.staking_epoch_data,
+        ),
+        (
+            "next_epoch_data",
+            expected.next_epoch_data == actual.next_epoch_data,
+        ),
+        (
+            "has_ancestor_in_same_checkpoint_window",
+            expected.has_ancestor_in_same_checkpoint_window
+                == actual.has_ancestor_in_same_checkpoint_window,
+        ),
+        (
+            "block_stake_winner",
+            expected.block_stake_winner == actual.block_stake_winner,
+        ),
+        (
+            "block_creator",
+            expected.block_creator == actual.block_creator,
+        ),
+        (
+            "coinbase_receiver",
+            expected.coinbase_receiver == actual.coinbase_receiver,
+        ),
+        (
+            "supercharge_coinbase",
+            expected.supercharge_coinbase == actual.supercharge_coinbase,
+        ),
+    ];
+    match fields.iter().find(|(_, equal)| !equal) {
+        Some((field, _)) => Err(ConsensusError::ConsensusStateMismatch(*field)),
+        None => Ok(()),
+    }
+}
+
+/// Shifts the sub window densities to the sub window of the next slot, clearing the sub windows
+/// that have been skipped, and updates the minimum window density once the grace period is over
+fn update_min_window_density(
+    prev_state: &ConsensusState,
+    next_slot: GlobalSlotNumber,
+    constants: &ConsensusConstants,
+) -> Result<(Length, Vec<Length>), ConsensusError> {
+    let sub_windows_per_window = constants.sub_windows_per_window.0;
+    if prev_state.sub_window_densities.len() != sub_windows_per_window as usize {
+        return Err(ConsensusError::InvalidSubWindowDensityLen);
+    }
+    let prev_slot = prev_state.curr_global_slot.slot_number;
+    let prev_sub_window = constants.sub_window(prev_slot);
+    let next_sub_window = constants.sub_window(next_slot);
+    let prev_relative = constants.relative_sub_window(prev_slot);
+    let next_relative = constants.relative_sub_window(next_slot);
+    let is_same_sub_window = prev_sub_window == next_sub_window;
+    let overlapping_window = prev_sub_window + sub_windows_per_window >= next_sub_window;
+
+    let mut sub_window_densities: Vec<Length> = prev_state
+        .sub_window_densities
+        .iter()
+        .enumerate()
+        .map(|(i, density)| {
+            let i = i as u32;
+            // Whether the sub window is skipped between the previous and the next slot
+            let within_range = if prev_relative < next_relative {
+                i > prev_relative && i < next_relative
+            } else {
+                i > prev_relative || i < next_relative
+            };
+            if is_same_sub_window || (overlapping_window && !within_range) {
+                *density
+            } else {
+                Length(0)
+            }
+        })
+        .collect();
+    let current_window_density: u32 = sub_window_densities.iter().map(|d| d.0).sum();
+
+    let min_window_density = if is_same_sub_window || constants.in_grace_period(next_slot) {
+        prev_state.min_window_density
+    } else {
+        Length(current_window_density.min(prev_state.min_window_density.0))
+    };
+
+    let next_density = &mut sub_window_densities[next_relative as usize];
+    *next_density = if is_same_sub_window {
+        Length(next_density.0 + 1)
+    } else {
+        Length(1)
+    };
+    Ok((min_window_density, sub_window_densities))
+}
+
+/// Mixes the VRF output of the block producer into the seed of the next epoch,
+/// mainnet hashes with the legacy Poseidon and berkeley with the kimchi one
+pub fn update_epoch_seed(
+    seed: &EpochSeed,
+    vrf_output: Fp,
+    poseidon_version: PoseidonVersion,
+) -> Result<EpochSeed, ConsensusError> {
+    let seed: Fp = seed
+        .try_into()
+        .map_err(|_| ConsensusError::InvalidEpochSeed)?;
+    let input = EpochSeedUpdate { seed, vrf_output };
+    let hash = match poseidon_version {
+        PoseidonVersion::Legacy => {
+            create_legacy::<LegacyEpochSeedUpdate>(()).hash(&LegacyEpochSeedUpdate(input))
+        }
+        PoseidonVersion::Kimchi => create_kimchi::<EpochSeedUpdate>(()).hash(&input),
+    };
+    Ok((&hash).into())
+}
+
+/// Input of the epoch seed update
+#[derive(Clone)]
+struct EpochSeedUpdate {
+    seed: Fp,
+    vrf_output: Fp,
+}
+
+impl ToChunkedROInput for EpochSeedUpdate {
+    fn to_chunked_roinput(&self) -> ChunkedROInput {
+        ChunkedROInput::new()
+            .append_field(self.seed)
+            .append_field(self.vrf_output)
+    }
+}
+
+impl Hashable for EpochSeedUpdate {
+    type D = ();
+
+    fn to_roinput(&self) -> ROInput {
+        self.roinput()
+    }
+
+    fn domain_string(_: Self::D) -> Option<String> {
+        Some("MinaEpochSeed".into())
+    }
+}
+
+/// Input of the epoch seed update of mainnet
+#[derive(Clone)]
+struct LegacyEpochSeedUpdate(EpochSeedUpdate);
+
+impl Hashable for LegacyEpochSeedUpdate {
+    type D = ();
+
+    fn to_roinput(&self) -> ROInput {
+        ROInput::new()
+            .append_field(self.0.seed)
+            .append_field(self.0.vrf_output)
+    }
+
+    fn domain_string(_: Self::D) -> Option<String> {
+        Some("CodaEpochSeed".into())
+    }
+}
@@ -0,0 +1,303 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//!
+//! Structural validation of blocks received from the network, which follows
+//! the validation pipeline of external transitions in
+//! <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/mina_block/validation.ml>
+//!
+//! The checks run in order and the first failing one is reported as a [BlockRejection],
+//! the blockchain SNARK is not verified here. The values outside the block that it is checked
+//! against are given in [ValidationInputs], validation fails with [ValidationError::MissingInput]
+//! when any of them is missing
+//!
+
+use crate::{
+    common::ConsensusConstants,
+    error::ConsensusError,
+    transition::{next_consensus_state, verify_consensus_state, ConsensusTransition},
+};
+use mina_crypto::hash::StateHash;
+use mina_rs_base::{
+    types::{
+        Amount, BlockTime, BlockchainState, BodyReference, ExternalTransition, ProtocolStateLegacy,
+        ProtocolVersion, SignedCommandPayload, StagedLedgerDiff,
+    },
+    verifiable::Verifiable,
+};
+use proof_systems::{mina_hasher::Fp, mina_signer::Signer};
+use thiserror::Error;
+
+/// Reasons for rejecting a block
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum BlockRejection {
+    /// The current protocol version of the block is not compatible with the daemon
+    #[error("Mismatched protocol version, expected: {expected:?}, actual: {actual:?}")]
+    MismatchedProtocolVersion {
+        /// Protocol version of the daemon
+        expected: ProtocolVersion,
+        /// Current protocol version of the block
+        actual: ProtocolVersion,
+    },
+
+    /// The previous state hash of the block is not the state hash of the parent
+    #[error("Previous state hash mismatch, expected: {expected:?}, actual: {actual:?}")]
+    PreviousStateHashMismatch {
+        /// State hash of the parent
+        expected: StateHash,
+        /// Previous state hash of the block
+        actual: StateHash,
+    },
+
+    /// The delta transition chain proof does not lead to the parent
+    #[error("Invalid delta transition chain proof")]
+    InvalidDeltaTransitionChainProof,
+
+    /// The timestamp of the block is not the start time of its global slot
+    #[error("Timestamp mismatch, expected: {expected:?}, actual: {actual:?}")]
+    TimestampMismatch {
+        /// Start time of the global slot of the block
+        expected: BlockTime,
+        /// Timestamp of the block
+        actual: BlockTime,
+    },
+
+    /// The global slot of the block is ahead of the current slot
+    #[error("Block slot {slot} is ahead of the current slot {current_slot}")]
+    SlotInFuture {
+        /// Global slot of the block
+        slot: u32,
+        /// Global slot at the time the block is received
+        current_slot: u32,
+    },
+
+    /// The consensus state of the block is not the successor of the parent consensus state
+    #[error("Invalid consensus state: {0}")]
+    InvalidConsensusState(ConsensusError),
+
+    /// Some commands in the staged ledger diff have invalid signatures
+    #[error("Invalid signatures in the staged ledger diff")]
+    InvalidSignatures,
+
+    /// The staged ledger diff cannot be serialized to calculate its body reference
+    #[error("Failed to serialize the staged ledger diff: {0}")]
+    StagedLedgerDiffSerialization(String),
+
+    /// The staged ledger diff does not match the body reference of the block
+    #[error("Body reference mismatch, expected: {expected:?}, actual: {actual:?}")]
+    BodyReferenceMismatch {
+        /// Body reference committed in the blockchain state
+        expected: BodyReference,
+        /// Body reference calculated from the staged ledger diff
+        actual: BodyReference,
+    },
+}
+
+/// Inputs of the validation that do not come with the block
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ValidationInput {
+    /// See [ValidationInputs::vrf_output]
+    VrfOutput,
+    /// See [ValidationInputs::supply_increase]
+    SupplyIncrease,
+    /// See [ValidationInputs::body_reference]
+    BodyReference,
+}
+
+/// Errors that can be produced when validating a block
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum ValidationError {
+    /// The block is invalid
+    #[error("Block is rejected: {0}")]
+    Rejected(#[from] BlockRejection),
+
+    /// An input that the block is checked against is not given
+    #[error("Missing validation input: {0:?}")]
+    MissingInput(ValidationInput),
+
+    /// The time the block is received is before genesis, so no slot can be current
+    #[error("Time {0:?} is before genesis")]
+    TimeBeforeGenesis(BlockTime),
+}
+
+/// Context that blocks are validated against
+pub struct ValidationContext {
+    /// Protocol version of the daemon
+    pub protocol_version: ProtocolVersion,
+    /// Constants used for consensus
+    pub constants: ConsensusConstants,
+    /// Time that the block is received
+    pub now: BlockTime,
+}
+
+/// Inputs of the validation of a block that do not come with the block,
+/// all of them are required
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ValidationInputs {
+    /// Untruncated VRF output of the block producer, e.g. verified with
+    /// [crate::vrf::VrfEvaluation::verify] and evaluated with [crate::vrf::VrfMessage::output]
+    pub vrf_output: Option<Fp>,
+    /// Supply increase of the ledger proof emitted by the staged ledger diff of the block,
+    /// which is zero when no proof is emitted
+    pub supply_increase: Option<Amount>,
+    /// Body reference in the blockchain state of the block, legacy blockchain states
+    /// do not commit to one so it has to be taken from the berkeley blockchain state
+    pub body_reference: Option<BodyReference>,
+}
+
+/// Validates a block received from the network on top of its parent protocol state
+pub fn validate_external_transition<CTX>(
+    block: &ExternalTransition,
+    parent: &ProtocolStateLegacy,
+    context: &ValidationContext,
+    inputs: &ValidationInputs,
+    signer: &mut CTX,
+) -> Result<(), ValidationError>
+where
+    CTX: Signer<SignedCommandPayload>,
+{
+    let vrf_output = inputs
+        .vrf_output
+        .ok_or(ValidationError::MissingInput(ValidationInput::VrfOutput))?;
+    let supply_increase = inputs.supply_increase.ok_or(ValidationError::MissingInput(
+        ValidationInput::SupplyIncrease,
+    ))?;
+    let body_reference = inputs
+        .body_reference
+        .as_ref()
+        .ok_or(ValidationError::MissingInput(
+            ValidationInput::BodyReference,
+        ))?;
+
+    validate_protocol_version(block, context)?;
+    validate_previous_state_hash(block, parent)?;
+    validate_delta_transition_chain(block)?;
+    validate_time(block, context)?;
+    validate_consensus_state(block, parent, context, vrf_output, supply_increase)?;
+    if !block.staged_ledger_diff.verify(signer) {
+        return Err(BlockRejection::InvalidSignatures.into());
+    }
+    check_body_reference(&block.staged_ledger_diff, body_reference)?;
+    Ok(())
+}
+
+fn validate_protocol_version(
+    block: &ExternalTransition,
+    context: &ValidationContext,
+) -> Result<(), BlockRejection> {
+    let actual = &block.current_protocol_version;
+    let expected = &context.protocol_version;
+    if actual.major != expected.major || actual.minor != expected.minor {
+        return Err(BlockRejection::MismatchedProtocolVersion {
+            expected: expected.clone(),
+            actual: actual.clone(),
+        });
+    }
+    Ok(())
+}
+
+fn validate_previous_state_hash(
+    block: &ExternalTransition,
+    parent: &ProtocolStateLegacy,
+) -> Result<(), BlockRejection> {
+    let expected = parent.state_hash();
+    let actual = &block.protocol_state.previous_state_hash;
+    if &expected != actual {
+        return Err(BlockRejection::PreviousStateHashMismatch {
+            expected,
+            actual: actual.clone(),
+        });
+    }
+    Ok(())
+}
+
+fn validate_delta_transition_chain(block: &ExternalTransition) -> Result<(), BlockRejection> {
+    block
+        .verify_delta_transition_chain()
+        .map(|_| ())
+        .ok_or(BlockRejection::InvalidDeltaTransitionChainProof)
+}
+
+fn validate_time(
+    block: &ExternalTransition,
+    context: &ValidationContext,
+) -> Result<(), ValidationError> {
+    let constants = &context.constants;
+    let slot = block
+        .protocol_state
+        .body
+        .consensus_state
+        .curr_global_slot
+        .slot_number;
+    let expected = constants.slot_start_time(slot);
+    let actual = &block.protocol_state.body.blockchain_state.timestamp;
+    if &expected != actual {
+        return Err(BlockRejection::TimestampMismatch {
+            expected,
+            actual: actual.clone(),
+        }
+        .into());
+    }
+
+    let current_slot = constants
+        .slot_of_time(&context.now)
+        .ok_or_else(|| ValidationError::TimeBeforeGenesis(context.now.clone()))?
+        .0;
+    if slot.0 > current_slot + constants.delta.0 {
+        return Err(BlockRejection::SlotInFuture {
+            slot: slot.0,
+            current_slot,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Recomputes the consensus state from the parent and the inputs,
+/// and checks it against the one of the block
+fn validate_consensus_state(
+    block: &ExternalTransition,
+    parent: &ProtocolStateLegacy,
+    context: &ValidationContext,
+    vrf_output: Fp,
+    supply_increase: Amount,
+) -> Result<(), BlockRejection> {
+    let actual = &block.protocol_state.body.consensus_state;
+    let transition = ConsensusTransition {
+        global_slot: actual.curr_global_slot.slot_number.0,
+        supercharge_coinbase: actual.supercharge_coinbase,
+        vrf_output,
+        supply_increase,
+        snarked_ledger_hash: parent.body.blockchain_state.snarked_ledger_hash.clone(),
+        block_stake_winner: actual.block_stake_winner.clone(),
+        block_creator: actual.block_creator.clone(),
+        coinbase_receiver: actual.coinbase_receiver.clone(),
+    };
+    let expected = next_consensus_state(parent, &transition, &context.constants)
+        .map_err(BlockRejection::InvalidConsensusState)?;
+    verify_consensus_state(&expected, actual).map_err(BlockRejection::InvalidConsensusState)
+}
+
+/// Checks the staged ledger diff of a block against the body reference
+/// committed in its blockchain state
+pub fn validate_body_reference(
+    staged_ledger_diff: &StagedLedgerDiff,
+    blockchain_state: &BlockchainState,
+) -> Result<(), BlockRejection> {
+    check_body_reference(staged_ledger_diff, &blockchain_state.body_reference)
+}
+
+fn check_body_reference(
+    staged_ledger_diff: &StagedLedgerDiff,
+    expected: &BodyReference,
+) -> Result<(), BlockRejection> {
+    let actual = BodyReference::from_staged_ledger_diff(staged_ledger_diff)
+        .map_err(|e| BlockRejection::StagedLedgerDiffSerialization(e.to_string()))?;
+    if expected != &actual {
+        return Err(BlockRejection::BodyReferenceMismatch {
+            expected: expected.clone(),
+            actual,
+        });
+    }
+    Ok(())
+}
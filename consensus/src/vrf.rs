@@ -0,0 +1,381 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//!
+//! Verifiable random function used for block producer election
+//!
+//! A block producer wins a slot when the truncated VRF output of the slot,
+//! evaluated with the private key of the producer, satisfies the threshold
+//! determined by the stake delegated to the producer in the staking ledger, see
+//! <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/consensus/vrf/consensus_vrf.ml>
+//! and <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/vrf_lib/standalone.ml>
+//!
+//! Mainnet hashes with the legacy Poseidon and the `Coda` prefixes,
+//! berkeley with the kimchi Poseidon and the `Mina` prefixes
+//!
+
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{PrimeField, Zero};
+use blake2::{Blake2b512, Digest};
+use mina_crypto::hash::EpochSeed;
+use mina_rs_base::{common::PoseidonVersion, consensus_state::VrfOutputTruncated, numbers::Amount};
+use proof_systems::{
+    groupmap::{BWParameters, GroupMap},
+    mina_curves::pasta::PallasParameters,
+    mina_hasher::{create_kimchi, create_legacy, Fp, Hashable, Hasher, ROInput},
+    mina_signer::{CurvePoint, Keypair, PubKey, ScalarField},
+    o1_utils::FieldHelpers,
+    ChunkedROInput, ToChunkedROInput,
+};
+
+/// Depth of the ledger that delegator indices refer to on mainnet
+pub const LEDGER_DEPTH: u32 = 20;
+
+/// Number of bits of the VRF output that are kept in [VrfOutputTruncated]
+pub const VRF_OUTPUT_TRUNCATED_BITS: u32 = 253;
+
+/// Active slot coefficient `f`, the probability that a slot has at least one winner
+pub const ACTIVE_SLOT_COEFFICIENT: f64 = 0.75;
+
+lazy_static::lazy_static! {
+    /// Parameters for hashing VRF messages to the pallas curve
+    static ref GROUP_MAP: BWParameters<PallasParameters> = BWParameters::setup();
+}
+
+/// Message that the VRF is evaluated on
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VrfMessage {
+    /// Global slot number of the slot to be won
+    pub global_slot: u32,
+    /// Seed of the staking epoch
+    pub epoch_seed: EpochSeed,
+    /// Index of the delegator account in the staking ledger
+    pub delegator_index: u64,
+    /// Depth of the staking ledger
+    pub ledger_depth: u32,
+    /// Version of the Poseidon hash of the network
+    pub poseidon_version: PoseidonVersion,
+}
+
+impl VrfMessage {
+    /// Creates a berkeley message for a delegator in a ledger of [LEDGER_DEPTH]
+    pub fn new(global_slot: u32, epoch_seed: EpochSeed, delegator_index: u64) -> Self {
+        Self {
+            global_slot,
+            epoch_seed,
+            delegator_index,
+            ledger_depth: LEDGER_DEPTH,
+            poseidon_version: PoseidonVersion::Kimchi,
+        }
+    }
+
+    /// Creates a mainnet message for a delegator in a ledger of [LEDGER_DEPTH]
+    pub fn legacy(global_slot: u32, epoch_seed: EpochSeed, delegator_index: u64) -> Self {
+        Self {
+            poseidon_version: PoseidonVersion::Legacy,
+            ..Self::new(global_slot, epoch_seed, delegator_index)
+        }
+    }
+
+    /// Hashes the message to a point on the pallas curve
+    pub fn hash_to_group(&self) -> CurvePoint {
+        let hash = poseidon_hash(self.poseidon_version, self.clone());
+        let (x, y) = GROUP_MAP.to_group(hash);
+        CurvePoint::new(x, y, false)
+    }
+
+    /// Evaluates the VRF with the private key of the block producer
+    pub fn evaluate(&self, keypair: &Keypair) -> VrfOutputTruncated {
+        truncate_vrf_output(self.output(keypair))
+    }
+
+    /// Evaluates the VRF with the private key of the block producer without truncation,
+    /// which is the input of the epoch seed update
+    pub fn output(&self, keypair: &Keypair) -> Fp {
+        let scaled_message_hash =
+            AffineCurve::mul(&self.hash_to_group(), *keypair.secret.scalar()).into_affine();
+        output_hash(self, &scaled_message_hash)
+    }
+}
+
+impl ToChunkedROInput for VrfMessage {
+    fn to_chunked_roinput(&self) -> ChunkedROInput {
+        let mut roi = ChunkedROInput::new()
+            .append_chunked(&self.epoch_seed)
+            .append_u32(self.global_slot);
+        // Bits of the account index are appended from the least significant one
+        for i in 0..self.ledger_depth {
+            roi = roi.append_bool((self.delegator_index >> i) & 1 == 1);
+        }
+        roi
+    }
+}
+
+impl VrfMessage {
+    /// Random oracle input of the legacy Poseidon, where the bits of the global slot
+    /// and the account index are packed after the epoch seed
+    fn legacy_roinput(&self) -> ROInput {
+        let mut roi = ROInput::new()
+            .append_hashable(&self.epoch_seed)
+            .append_u32(self.global_slot);
+        for i in 0..self.ledger_depth {
+            roi = roi.append_bool((self.delegator_index >> i) & 1 == 1);
+        }
+        roi
+    }
+}
+
+impl Hashable for VrfMessage {
+    type D = ();
+
+    fn to_roinput(&self) -> ROInput {
+        self.roinput()
+    }
+
+    fn domain_string(_: Self::D) -> Option<String> {
+        Some("MinaVrfMessage".into())
+    }
+}
+
+/// A VRF evaluation together with the proof that it is evaluated with
+/// the private key of a public key, which can be verified without the private key
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VrfEvaluation {
+    /// Message that the VRF is evaluated on
+    pub message: VrfMessage,
+    /// Hash of the message scaled by the private key
+    pub scaled_message_hash: CurvePoint,
+    /// Challenge of the discrete log equality proof
+    pub c: ScalarField,
+    /// Response of the discrete log equality proof
+    pub s: ScalarField,
+}
+
+impl VrfEvaluation {
+    /// Evaluates the VRF with the private key of the block producer,
+    /// the nonce of the proof is derived from the private key and the message
+    pub fn create(keypair: &Keypair, message: VrfMessage) -> Self {
+        let k = *keypair.secret.scalar();
+        let message_hash = message.hash_to_group();
+        let scaled_message_hash = AffineCurve::mul(&message_hash, k).into_affine();
+
+        let mut hasher = Blake2b512::new();
+        hasher.update(k.to_bytes());
+        hasher.update(message_hash.x.to_bytes());
+        hasher.update(message_hash.y.to_bytes());
+        let r = ScalarField::from_le_bytes_mod_order(&hasher.finalize());
+
+        let c = proof_challenge(
+            &message,
+            keypair.public.point(),
+            &AffineCurve::mul(&CurvePoint::prime_subgroup_generator(), r).into_affine(),
+            &AffineCurve::mul(&message_hash, r).into_affine(),
+        );
+        Self {
+            message,
+            scaled_message_hash,
+            c,
+            s: r + k * c,
+        }
+    }
+
+    /// Verifies the evaluation against the public key of the block producer,
+    /// returns the truncated VRF output when the proof is valid
+    pub fn verify(&self, public_key: &PubKey) -> Option<VrfOutputTruncated> {
+        let h = &self.scaled_message_hash;
+        if h.is_zero() || !h.is_on_curve() || !h.is_in_correct_subgroup_assuming_on_curve() {
+            return None;
+        }
+        let public_key = public_key.point();
+        let message_hash = self.message.hash_to_group();
+        let g_r = AffineCurve::mul(&CurvePoint::prime_subgroup_generator(), self.s)
+            - AffineCurve::mul(public_key, self.c);
+        let h_r = AffineCurve::mul(&message_hash, self.s) - AffineCurve::mul(h, self.c);
+        let c = proof_challenge(
+            &self.message,
+            public_key,
+            &g_r.into_affine(),
+            &h_r.into_affine(),
+        );
+        if c == self.c {
+            Some(truncate_vrf_output(output_hash(&self.message, h)))
+        } else {
+            None
+        }
+    }
+}
+
+/// Checks whether the truncated VRF output wins the slot with the given share of the stake,
+/// that is `output / 2^253 <= 1 - (1 - f)^(my_stake / total_stake)`
+///
+/// The right hand side is calculated with [f64] while the blockchain SNARK uses
+/// a Taylor series approximation, so outputs that are extremely close to the threshold
+/// may be judged differently
+pub fn is_threshold_satisfied(
+    output: &VrfOutputTruncated,
+    my_stake: Amount,
+    total_stake: Amount,
+    f: f64,
+) -> bool {
+    if my_stake.0 == 0 || total_stake.0 == 0 {
+        return false;
+    }
+    let stake_share = my_stake.0 as f64 / total_stake.0 as f64;
+    let threshold = -((1. - f).ln() * stake_share).exp_m1();
+    vrf_output_fraction(output) <= threshold
+}
+
+/// The truncated VRF output as a fraction in `[0, 1)`
+fn vrf_output_fraction(output: &VrfOutputTruncated) -> f64 {
+    let mut fraction = 0.;
+    for (i, &byte) in output.0.iter().enumerate().rev() {
+        let bits = (VRF_OUTPUT_TRUNCATED_BITS as i32 - 8 * i as i32).clamp(0, 8);
+        let byte = byte & ((1_u16 << bits) - 1) as u8;
+        fraction += byte as f64 * 2_f64.powi(8 * i as i32 - VRF_OUTPUT_TRUNCATED_BITS as i32);
+    }
+    fraction
+}
+
+/// Keeps the lowest [VRF_OUTPUT_TRUNCATED_BITS] bits of the VRF output
+pub fn truncate_vrf_output(output: Fp) -> VrfOutputTruncated {
+    let mut bytes = output.to_bytes();
+    bytes.truncate((VRF_OUTPUT_TRUNCATED_BITS as usize + 7) / 8);
+    if let Some(last) = bytes.last_mut() {
+        *last &= (1_u16 << (VRF_OUTPUT_TRUNCATED_BITS % 8)) as u8 - 1;
+    }
+    VrfOutputTruncated(bytes)
+}
+
+fn output_hash(message: &VrfMessage, scaled_message_hash: &CurvePoint) -> Fp {
+    poseidon_hash(
+        message.poseidon_version,
+        VrfOutputInput {
+            message: message.clone(),
+            points: vec![*scaled_message_hash],
+        },
+    )
+}
+
+fn proof_challenge(
+    message: &VrfMessage,
+    public_key: &CurvePoint,
+    g: &CurvePoint,
+    h: &CurvePoint,
+) -> ScalarField {
+    let hash = poseidon_hash(
+        message.poseidon_version,
+        VrfEvaluationInput(VrfOutputInput {
+            message: message.clone(),
+            points: vec![*public_key, *g, *h],
+        }),
+    );
+    ScalarField::from_le_bytes_mod_order(&hash.to_bytes())
+}
+
+/// Hashes the input with the given version of the Poseidon hash
+fn poseidon_hash<T>(poseidon_version: PoseidonVersion, input: T) -> Fp
+where
+    T: Hashable<D = ()>,
+    Legacy<T>: Hashable<D = ()>,
+{
+    match poseidon_version {
+        PoseidonVersion::Legacy => create_legacy::<Legacy<T>>(()).hash(&Legacy(input)),
+        PoseidonVersion::Kimchi => create_kimchi::<T>(()).hash(&input),
+    }
+}
+
+/// A message followed by the coordinates of some points
+#[derive(Clone)]
+struct VrfOutputInput {
+    message: VrfMessage,
+    points: Vec<CurvePoint>,
+}
+
+impl VrfOutputInput {
+    fn legacy_roinput(&self) -> ROInput {
+        let mut roi = self.message.legacy_roinput();
+        for point in self.points.iter() {
+            roi = roi.append_field(point.x).append_field(point.y);
+        }
+        roi
+    }
+}
+
+impl ToChunkedROInput for VrfOutputInput {
+    fn to_chunked_roinput(&self) -> ChunkedROInput {
+        let mut roi = self.message.to_chunked_roinput();
+        for point in self.points.iter() {
+            roi = roi.append_field(point.x).append_field(point.y);
+        }
+        roi
+    }
+}
+
+impl Hashable for VrfOutputInput {
+    type D = ();
+
+    fn to_roinput(&self) -> ROInput {
+        self.roinput()
+    }
+
+    fn domain_string(_: Self::D) -> Option<String> {
+        Some("MinaVrfOutput".into())
+    }
+}
+
+/// Input of the challenge of the discrete log equality proof
+#[derive(Clone)]
+struct VrfEvaluationInput(VrfOutputInput);
+
+impl Hashable for VrfEvaluationInput {
+    type D = ();
+
+    fn to_roinput(&self) -> ROInput {
+        self.0.roinput()
+    }
+
+    fn domain_string(_: Self::D) -> Option<String> {
+        Some("MinaVrfEvaluation".into())
+    }
+}
+
+/// Input of the legacy Poseidon hash, which differs in the packing of
+/// the random oracle input and in the prefixes
+#[derive(Clone)]
+struct Legacy<T>(T);
+
+impl Hashable for Legacy<VrfMessage> {
+    type D = ();
+
+    fn to_roinput(&self) -> ROInput {
+        self.0.legacy_roinput()
+    }
+
+    fn domain_string(_: Self::D) -> Option<String> {
+        Some("CodaVrfMessage".into())
+    }
+}
+
+impl Hashable for Legacy<VrfOutputInput> {
+    type D = ();
+
+    fn to_roinput(&self) -> ROInput {
+        self.0.legacy_roinput()
+    }
+
+    fn domain_string(_: Self::D) -> Option<String> {
+        Some("CodaVrfOutput".into())
+    }
+}
+
+impl Hashable for Legacy<VrfEvaluationInput> {
+    type D = ();
+
+    fn to_roinput(&self) -> ROInput {
+        self.0 .0.legacy_roinput()
+    }
+
+    fn domain_string(_: Self::D) -> Option<String> {
+        Some("MinaVrfEvaluation".into())
+    }
+}
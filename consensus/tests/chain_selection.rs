@@ -0,0 +1,200 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//! The following module tests the decisions of chain selection
+//! with the forks recorded on mainnet:
+//! * Blocks at height 113267 with different last vrf outputs
+//! * Blocks at height 117896 with different last vrf outputs
+//! * Blocks at height 117896 with the same last vrf output
+//! * Blocks that are long range forks of each other
+//!
+
+#[cfg(test)]
+mod tests {
+    use mina_consensus::common::*;
+    use mina_rs_base::{types::*, JsonSerializationType};
+    use wasm_bindgen_test::*;
+
+    fn chain(block_path: &str) -> ProtocolStateChain<ProtocolStateLegacy> {
+        let mut chain = ProtocolStateChain::default();
+        chain
+            .push(read_block_json(block_path).protocol_state)
+            .unwrap();
+        chain
+    }
+
+    fn decision(
+        choice: SelectionChoice,
+        fork_range: ForkRange,
+        reason: SelectionReason,
+    ) -> ChainSelectionDecision {
+        ChainSelectionDecision {
+            choice,
+            fork_range,
+            reason,
+        }
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn select_short_range_fork_by_last_vrf_output() {
+        // last vrf hash hex: "dd55ef09c0474817a64efffa7fe5a3aedd2db04a5f66e52e9630b59711f56613"
+        let a = chain("mainnet-113267-3NKtqqstB6h8SVNQCtspFisjUwCTqoQ6cC1KGvb6kx6n2dqKkiZS.json");
+        // last vrf hash hex: "e907e63d043c78b3dfa724b2ddc1152114fc91b983b40581b1036a8d19eb136d"
+        let b = chain("mainnet-113267-3NLenrog9wkiJMoA774T9VraqSUGhCuhbDLj3JKbEzomNdjr78G8.json");
+        assert_eq!(
+            a.select(&b).unwrap(),
+            decision(
+                SelectionChoice::Take,
+                ForkRange::Short,
+                SelectionReason::LastVrfOutput
+            )
+        );
+        assert_eq!(
+            b.select(&a).unwrap(),
+            decision(
+                SelectionChoice::Keep,
+                ForkRange::Short,
+                SelectionReason::LastVrfOutput
+            )
+        );
+
+        // last vrf hash hex: "024554e8668bb45c17cf471130896fa1fa2d076d07b47bb857c29f17cd390fa8"
+        let a = chain("mainnet-117896-3NKjZ5fjms6BMaH4aq7DopPGyMY7PbG6vhRsX5XnYRxih8i9G7dj.json");
+        // last vrf hash hex: "e1cfc87f7794349ed2a439951ed217d005bf3512a290d5693cf86469c0e74ea1"
+        let b = chain("mainnet-117896-3NKrv92FYZFHRNUJxiP7VGeRx3MeDY2iffFjUWXTPoXJorsS63ba.json");
+        assert_eq!(
+            a.select(&b).unwrap(),
+            decision(
+                SelectionChoice::Take,
+                ForkRange::Short,
+                SelectionReason::LastVrfOutput
+            )
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn select_short_range_fork_by_state_hash() {
+        // Both blocks have the same last vrf output
+        let a = chain("mainnet-117896-3NLPBDTckSdjcUFcQiE9raJsyzB84KayMPKi4PmwNybnA6J75GoL.json");
+        let b = chain("mainnet-117896-3NKrv92FYZFHRNUJxiP7VGeRx3MeDY2iffFjUWXTPoXJorsS63ba.json");
+        assert_eq!(
+            a.select(&b).unwrap(),
+            decision(
+                SelectionChoice::Take,
+                ForkRange::Short,
+                SelectionReason::StateHash
+            )
+        );
+        assert_eq!(
+            b.select(&a).unwrap(),
+            decision(
+                SelectionChoice::Keep,
+                ForkRange::Short,
+                SelectionReason::StateHash
+            )
+        );
+        assert_eq!(
+            b.select(&b.clone()).unwrap(),
+            decision(
+                SelectionChoice::Keep,
+                ForkRange::Short,
+                SelectionReason::SameTip
+            )
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn select_long_range_fork_by_min_window_density() {
+        // Epoch 15 and epoch 23 are more than one epoch apart
+        let a = chain("mainnet-77748-3NKaBJsN1SehD6iJwRwJSFmVzJg5DXSUQVgnMxtH4eer4aF5BrDK.json");
+        let b = chain("mainnet-113267-3NLenrog9wkiJMoA774T9VraqSUGhCuhbDLj3JKbEzomNdjr78G8.json");
+        assert_eq!(
+            a.select(&b).unwrap(),
+            decision(
+                SelectionChoice::Take,
+                ForkRange::Long,
+                SelectionReason::MinWindowDensity
+            )
+        );
+        assert_eq!(
+            b.select(&a).unwrap(),
+            decision(
+                SelectionChoice::Keep,
+                ForkRange::Long,
+                SelectionReason::MinWindowDensity
+            )
+        );
+
+        // Epoch 23 and epoch 24 are one epoch apart, but the block in epoch 23
+        // is in the seed update range, so its next epoch lock checkpoint is not finalized
+        let c = chain("mainnet-117896-3NKrv92FYZFHRNUJxiP7VGeRx3MeDY2iffFjUWXTPoXJorsS63ba.json");
+        assert_eq!(b.select(&c).unwrap().fork_range, ForkRange::Long);
+        assert_eq!(c.select(&b).unwrap().fork_range, ForkRange::Long);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn select_long_range_fork_rejects_invalid_sub_window_densities() {
+        let a = chain("mainnet-113267-3NLenrog9wkiJMoA774T9VraqSUGhCuhbDLj3JKbEzomNdjr78G8.json");
+        let mut b =
+            chain("mainnet-77748-3NKaBJsN1SehD6iJwRwJSFmVzJg5DXSUQVgnMxtH4eer4aF5BrDK.json");
+        b.0[0].body.consensus_state.sub_window_densities[5] = Length(999);
+        assert_eq!(
+            a.select(&b).unwrap(),
+            decision(
+                SelectionChoice::Keep,
+                ForkRange::Long,
+                SelectionReason::InvalidSubWindowDensities
+            )
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn select_long_range_fork_within_grace_period_by_length() {
+        // Chains that are more than one epoch apart are long range forks,
+        // even when their lock checkpoints are equal
+        let mut a = ProtocolStateChain::default();
+        let mut prot_state = ProtocolStateLegacy::default();
+        prot_state.body.consensus_state.epoch_count = Length(5);
+        prot_state.body.consensus_state.blockchain_length = Length(10);
+        prot_state.body.consensus_state.sub_window_densities = vec![Length(7); 11];
+        a.push(prot_state.clone()).unwrap();
+
+        let mut b = ProtocolStateChain::default();
+        prot_state.body.consensus_state.epoch_count = Length(7);
+        prot_state.body.consensus_state.blockchain_length = Length(11);
+        b.push(prot_state).unwrap();
+
+        // Both chains are within the grace period, their min window densities are equal
+        assert!(!a.is_short_range(&b).unwrap());
+        assert_eq!(
+            a.select(&b).unwrap(),
+            decision(
+                SelectionChoice::Take,
+                ForkRange::Long,
+                SelectionReason::BlockchainLength
+            )
+        );
+        assert_eq!(
+            b.select(&a).unwrap(),
+            decision(
+                SelectionChoice::Keep,
+                ForkRange::Long,
+                SelectionReason::BlockchainLength
+            )
+        );
+    }
+
+    // block path: mainnet-$BlockHeight-$StateHash.json eg: "mainnet-113267-3NLenrog9wkiJMoA774T9VraqSUGhCuhbDLj3JKbEzomNdjr78G8.json"
+    // Note: block path must exist in test_fixtures::JSON_TEST_BLOCKS
+    fn read_block_json(block_path: &str) -> ExternalTransition {
+        let json_block = test_fixtures::JSON_TEST_BLOCKS.get(block_path).unwrap();
+        let json_value: <ExternalTransition as JsonSerializationType>::T =
+            serde_json::from_value(json_block.clone()).unwrap();
+        json_value.into()
+    }
+}
@@ -0,0 +1,93 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use mina_consensus::common::ConsensusConstants;
+    use mina_rs_base::types::*;
+    use mina_serialization_types::json::ExternalTransitionJson;
+    use test_fixtures::*;
+    use wasm_bindgen_test::*;
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_slot_and_time_conversions() {
+        let constants = ConsensusConstants::mainnet();
+        let genesis = constants.genesis_state_timestamp.clone();
+
+        assert_eq!(constants.slot_of_time(&BlockTime(genesis.0 - 1)), None);
+        assert_eq!(constants.slot_of_time(&genesis), Some(GlobalSlotNumber(0)));
+        assert_eq!(
+            constants.slot_of_time(&BlockTime(genesis.0 + 180_000 * 3 + 179_999)),
+            Some(GlobalSlotNumber(3))
+        );
+        assert_eq!(
+            constants.slot_start_time(GlobalSlotNumber(3)),
+            BlockTime(genesis.0 + 180_000 * 3)
+        );
+        assert_eq!(
+            constants.slot_end_time(GlobalSlotNumber(3)),
+            constants.slot_start_time(GlobalSlotNumber(4))
+        );
+
+        let slot = GlobalSlotNumber(7140 * 2 + 5);
+        assert_eq!(constants.epoch_and_slot(slot), (2, 5));
+        assert_eq!(constants.global_slot(2, 5), slot);
+        assert_eq!(constants.epoch_start_slot(2), GlobalSlotNumber(7140 * 2));
+        assert_eq!(
+            constants.epoch_of_time(&constants.epoch_start_time(2)),
+            Some(2)
+        );
+        assert_eq!(
+            constants.epoch_of_time(&BlockTime(constants.epoch_start_time(2).0 - 1)),
+            Some(1)
+        );
+
+        assert!(constants.in_seed_update_range(GlobalSlotNumber(4759)));
+        assert!(!constants.in_seed_update_range(GlobalSlotNumber(4760)));
+        assert!(constants.in_grace_period(GlobalSlotNumber(1439)));
+        assert!(!constants.in_grace_period(GlobalSlotNumber(1440)));
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_windows() {
+        let constants = ConsensusConstants::mainnet();
+
+        assert_eq!(constants.checkpoint_window(GlobalSlotNumber(14599)), 0);
+        assert_eq!(constants.checkpoint_window(GlobalSlotNumber(14600)), 1);
+        assert!(constants.in_same_checkpoint_window(GlobalSlotNumber(0), GlobalSlotNumber(14599)));
+        assert!(
+            !constants.in_same_checkpoint_window(GlobalSlotNumber(14599), GlobalSlotNumber(14600))
+        );
+
+        assert_eq!(constants.sub_window(GlobalSlotNumber(76)), 10);
+        assert_eq!(constants.sub_window(GlobalSlotNumber(77)), 11);
+        assert_eq!(constants.relative_sub_window(GlobalSlotNumber(76)), 10);
+        assert_eq!(constants.relative_sub_window(GlobalSlotNumber(77)), 0);
+        assert_eq!(constants.slots_per_window(), 77);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_block_timestamps() {
+        let constants = ConsensusConstants::mainnet();
+        for (_, v) in JSON_TEST_BLOCKS.iter() {
+            let block: ExternalTransition =
+                serde_json::from_value::<ExternalTransitionJson>(v.clone())
+                    .unwrap()
+                    .into();
+            let body = &block.protocol_state.body;
+            let slot = body.consensus_state.curr_global_slot.slot_number;
+            assert_eq!(
+                constants.slot_start_time(slot),
+                body.blockchain_state.timestamp
+            );
+            assert_eq!(
+                constants.slot_of_time(&body.blockchain_state.timestamp),
+                Some(slot)
+            );
+            assert_eq!(constants.epoch(slot), body.consensus_state.epoch_count.0);
+        }
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use mina_consensus::{
+        common::ConsensusConstants, error::ConsensusError, transition::*, vrf::VrfMessage,
+    };
+    use mina_crypto::hash::{EpochSeed, LedgerHash};
+    use mina_rs_base::{
+        common::PoseidonVersion,
+        consensus_state::ConsensusState,
+        numbers::{Amount, GlobalSlotNumber, Length},
+        types::{ExternalTransition, ProtocolStateLegacy},
+    };
+    use mina_serialization_types::json::ExternalTransitionJson;
+    use proof_systems::mina_hasher::Fp;
+    use proof_systems::mina_signer::{CompressedPubKey, Keypair};
+    use proof_systems::o1_utils::FieldHelpers;
+    use std::str::FromStr;
+    use wasm_bindgen_test::*;
+
+    const PRODUCER: &str = "B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg";
+
+    fn prev_state(slot: u32) -> ProtocolStateLegacy {
+        let mut prot_state = ProtocolStateLegacy::default();
+        let consensus_state = &mut prot_state.body.consensus_state;
+        consensus_state.blockchain_length = Length(100);
+        consensus_state.epoch_count = Length(slot / 7140);
+        consensus_state.min_window_density = Length(55);
+        consensus_state.sub_window_densities = vec![Length(5); 11];
+        consensus_state.total_currency = Amount(1_000);
+        consensus_state.curr_global_slot.slot_number = GlobalSlotNumber(slot);
+        consensus_state.curr_global_slot.slots_per_epoch = Length(7140);
+        consensus_state.global_slot_since_genesis = GlobalSlotNumber(slot);
+        consensus_state.next_epoch_data.epoch_length = Length(10);
+        prot_state
+    }
+
+    fn transition(global_slot: u32) -> ConsensusTransition {
+        let producer = CompressedPubKey::from_address(PRODUCER).unwrap();
+        ConsensusTransition {
+            global_slot,
+            supercharge_coinbase: true,
+            vrf_output: Fp::from(42_u64),
+            supply_increase: Amount(720),
+            snarked_ledger_hash: LedgerHash::from(&Fp::from(7_u64)),
+            block_stake_winner: producer.clone(),
+            block_creator: producer.clone(),
+            coinbase_receiver: producer,
+        }
+    }
+
+    // Note: block path must exist in test_fixtures::JSON_TEST_BLOCKS
+    fn read_block_json(block_path: &str) -> ExternalTransition {
+        let json_block = test_fixtures::JSON_TEST_BLOCKS.get(block_path).unwrap();
+        serde_json::from_value::<ExternalTransitionJson>(json_block.clone())
+            .unwrap()
+            .into()
+    }
+
+    fn densities(values: &[u32]) -> Vec<Length> {
+        values.iter().map(|v| Length(*v)).collect()
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_transition_within_sub_window() {
+        let constants = ConsensusConstants::mainnet();
+        let prev = prev_state(1500);
+        let prev_consensus_state = &prev.body.consensus_state;
+        let next = next_consensus_state(&prev, &transition(1501), &constants).unwrap();
+
+        assert_eq!(next.blockchain_length, Length(101));
+        assert_eq!(next.epoch_count, Length(0));
+        assert_eq!(next.total_currency, Amount(1_720));
+        assert_eq!(next.curr_global_slot.slot_number, GlobalSlotNumber(1501));
+        assert_eq!(next.global_slot_since_genesis, GlobalSlotNumber(1501));
+        assert!(next.has_ancestor_in_same_checkpoint_window);
+        assert!(next.supercharge_coinbase);
+
+        // Slot 1501 is in sub window 214, whose relative index is 5
+        assert_eq!(next.min_window_density, Length(55));
+        assert_eq!(
+            next.sub_window_densities,
+            densities(&[5, 5, 5, 5, 5, 6, 5, 5, 5, 5, 5])
+        );
+
+        // Slot 1501 is in the seed update range of epoch 0
+        assert_eq!(
+            next.staking_epoch_data,
+            prev_consensus_state.staking_epoch_data
+        );
+        assert_eq!(next.next_epoch_data.epoch_length, Length(11));
+        assert_eq!(next.next_epoch_data.lock_checkpoint, prev.state_hash());
+        assert_ne!(
+            next.next_epoch_data.seed,
+            prev_consensus_state.next_epoch_data.seed
+        );
+
+        // Slot 5001 is after the seed update range, the seed and the lock checkpoint are kept
+        let prev = prev_state(5000);
+        let next = next_consensus_state(&prev, &transition(5001), &constants).unwrap();
+        assert_eq!(
+            next.next_epoch_data.seed,
+            prev.body.consensus_state.next_epoch_data.seed
+        );
+        assert_eq!(
+            next.next_epoch_data.lock_checkpoint,
+            prev.body.consensus_state.next_epoch_data.lock_checkpoint
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_transition_skipping_sub_windows() {
+        let constants = ConsensusConstants::mainnet();
+
+        // From sub window 214 to 216, sub window 215 is skipped
+        let next = next_consensus_state(&prev_state(1500), &transition(1514), &constants).unwrap();
+        assert_eq!(next.min_window_density, Length(50));
+        assert_eq!(
+            next.sub_window_densities,
+            densities(&[5, 5, 5, 5, 5, 5, 0, 1, 5, 5, 5])
+        );
+
+        // From sub window 214 to 234, the whole window is skipped
+        let next = next_consensus_state(&prev_state(1500), &transition(1640), &constants).unwrap();
+        assert_eq!(next.min_window_density, Length(0));
+        assert_eq!(
+            next.sub_window_densities,
+            densities(&[0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0])
+        );
+
+        // The minimum window density is kept within the grace period
+        let next = next_consensus_state(&prev_state(10), &transition(30), &constants).unwrap();
+        assert_eq!(next.min_window_density, Length(55));
+        assert_eq!(
+            next.sub_window_densities,
+            densities(&[5, 5, 0, 0, 1, 5, 5, 5, 5, 5, 5])
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_transition_across_epochs() {
+        let constants = ConsensusConstants::mainnet();
+        let prev = prev_state(7139);
+        let prev_consensus_state = &prev.body.consensus_state;
+        let t = transition(7141);
+        let next = next_consensus_state(&prev, &t, &constants).unwrap();
+
+        assert_eq!(next.epoch_count, Length(1));
+        assert_eq!(
+            next.staking_epoch_data,
+            prev_consensus_state.next_epoch_data
+        );
+        assert_eq!(next.next_epoch_data.ledger.hash, t.snarked_ledger_hash);
+        assert_eq!(next.next_epoch_data.ledger.total_currency, Amount(1_720));
+        assert_eq!(next.next_epoch_data.start_checkpoint, prev.state_hash());
+        assert_eq!(next.next_epoch_data.lock_checkpoint, prev.state_hash());
+        assert_eq!(next.next_epoch_data.epoch_length, Length(1));
+        assert_ne!(
+            next.next_epoch_data.seed,
+            prev_consensus_state.next_epoch_data.seed
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_transition_mainnet_77748_to_77749() {
+        let constants = ConsensusConstants::mainnet();
+        let prev = read_block_json(
+            "mainnet-77748-3NKaBJsN1SehD6iJwRwJSFmVzJg5DXSUQVgnMxtH4eer4aF5BrDK.json",
+        );
+        let block = read_block_json(
+            "mainnet-77749-3NK3P5bJHhqR7xkZBquGGfq3sERUeXNYNma5YXRMjgCNsTJRZpgL.json",
+        );
+        let prev_state = &prev.protocol_state.body;
+        let actual = &block.protocol_state.body.consensus_state;
+        let t = ConsensusTransition {
+            global_slot: actual.curr_global_slot.slot_number.0,
+            supercharge_coinbase: actual.supercharge_coinbase,
+            // The top bits of the VRF output are truncated, they only affect the seed update
+            vrf_output: Fp::from_bytes(&actual.last_vrf_output.0).unwrap(),
+            supply_increase: Amount(
+                actual.total_currency.0 - prev_state.consensus_state.total_currency.0,
+            ),
+            snarked_ledger_hash: prev_state.blockchain_state.snarked_ledger_hash.clone(),
+            block_stake_winner: actual.block_stake_winner.clone(),
+            block_creator: actual.block_creator.clone(),
+            coinbase_receiver: actual.coinbase_receiver.clone(),
+        };
+        let expected = next_consensus_state(&prev.protocol_state, &t, &constants).unwrap();
+        assert_eq!(verify_consensus_state(&expected, actual), Ok(()));
+        // Slot 111966 is the slot 4866 of epoch 15, which is after the seed update range
+        assert!(!constants.in_seed_update_range(GlobalSlotNumber(111966)));
+        assert_eq!(
+            expected.next_epoch_data.seed,
+            prev_state.consensus_state.next_epoch_data.seed
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_update_epoch_seed_mainnet_genesis() {
+        // The genesis block is produced by the genesis winner at slot 0 in the seed update range,
+        // which updates the initial seed with the legacy Poseidon
+        let keypair =
+            Keypair::from_hex("3d082fcfdd540532351b84ba15dbef5bd2a60fe95e850f1e28f8eb53f71284d6")
+                .unwrap();
+        let initial_seed =
+            EpochSeed::from_str("2va9BGv9JrLTtrzZttiEMDYw1Zj6a6EHzXjmP9evHDTG3oEquURA").unwrap();
+        let vrf_output = VrfMessage::legacy(0, initial_seed.clone(), 0).output(&keypair);
+        let seed = update_epoch_seed(&initial_seed, vrf_output, PoseidonVersion::Legacy).unwrap();
+        assert_eq!(
+            seed.to_string(),
+            "2vaRh7FQ5wSzmpFReF9gcRKjv48CcJvHs25aqb3SSZiPgHQBy5Dt"
+        );
+        assert_ne!(
+            update_epoch_seed(&initial_seed, vrf_output, PoseidonVersion::Kimchi).unwrap(),
+            seed
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_transition_errors() {
+        let constants = ConsensusConstants::mainnet();
+        let prev = prev_state(1500);
+        assert_eq!(
+            next_consensus_state(&prev, &transition(1500), &constants),
+            Err(ConsensusError::SlotNotIncreasing {
+                prev: 1500,
+                next: 1500
+            })
+        );
+
+        let mut prev_overflow = prev.clone();
+        prev_overflow.body.consensus_state.total_currency = Amount(u64::MAX);
+        assert_eq!(
+            next_consensus_state(&prev_overflow, &transition(1501), &constants),
+            Err(ConsensusError::TotalCurrencyOverflow)
+        );
+
+        let expected = next_consensus_state(&prev, &transition(1501), &constants).unwrap();
+        assert_eq!(verify_consensus_state(&expected, &expected), Ok(()));
+        let actual = ConsensusState {
+            min_window_density: Length(77),
+            ..expected.clone()
+        };
+        assert_eq!(
+            verify_consensus_state(&expected, &actual),
+            Err(ConsensusError::ConsensusStateMismatch("min_window_density"))
+        );
+    }
+}
@@ -0,0 +1,259 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//! The following module tests the block validation pipeline with the blocks
+//! at height 77748 and 77749 on mainnet, where the former is the parent of the latter
+
+#[cfg(test)]
+mod tests {
+    use mina_consensus::{common::ConsensusConstants, error::ConsensusError, validation::*};
+    use mina_rs_base::{types::*, JsonSerializationType};
+    use proof_systems::{
+        mina_hasher::Fp,
+        mina_signer::{self, NetworkId},
+        o1_utils::FieldHelpers,
+    };
+    use wasm_bindgen_test::*;
+
+    const PARENT: &str = "mainnet-77748-3NKaBJsN1SehD6iJwRwJSFmVzJg5DXSUQVgnMxtH4eer4aF5BrDK.json";
+    const BLOCK: &str = "mainnet-77749-3NK3P5bJHhqR7xkZBquGGfq3sERUeXNYNma5YXRMjgCNsTJRZpgL.json";
+    // Scheduled time of the block
+    const RECEIVED_AT: u64 = 1636093252249;
+
+    fn context() -> ValidationContext {
+        ValidationContext {
+            protocol_version: ProtocolVersion::default(),
+            constants: ConsensusConstants::mainnet(),
+            now: BlockTime(RECEIVED_AT),
+        }
+    }
+
+    // Inputs that are taken from the blocks themselves, the VRF output is truncated in the block,
+    // the truncated bits do not matter as the block is after the seed update range
+    fn inputs(block: &ExternalTransition, parent: &ProtocolStateLegacy) -> ValidationInputs {
+        let consensus_state = &block.protocol_state.body.consensus_state;
+        ValidationInputs {
+            vrf_output: Some(Fp::from_bytes(&consensus_state.last_vrf_output.0).unwrap()),
+            supply_increase: Some(Amount(
+                consensus_state.total_currency.0 - parent.body.consensus_state.total_currency.0,
+            )),
+            body_reference: Some(
+                BodyReference::from_staged_ledger_diff(&block.staged_ledger_diff).unwrap(),
+            ),
+        }
+    }
+
+    fn validate_with(
+        block: &ExternalTransition,
+        parent: &ProtocolStateLegacy,
+        context: &ValidationContext,
+        inputs: &ValidationInputs,
+    ) -> Result<(), ValidationError> {
+        let mut signer = mina_signer::create_legacy::<SignedCommandPayload>(NetworkId::MAINNET);
+        validate_external_transition(block, parent, context, inputs, &mut signer)
+    }
+
+    fn validate(
+        block: &ExternalTransition,
+        parent: &ProtocolStateLegacy,
+        context: &ValidationContext,
+    ) -> Result<(), ValidationError> {
+        validate_with(block, parent, context, &inputs(block, parent))
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_validate_mainnet_block() {
+        let parent = read_block_json(PARENT).protocol_state;
+        let block = read_block_json(BLOCK);
+        assert_eq!(validate(&block, &parent, &context()), Ok(()));
+
+        let inputs = inputs(&block, &parent);
+        let mut missing = inputs.clone();
+        missing.vrf_output = None;
+        assert_eq!(
+            validate_with(&block, &parent, &context(), &missing),
+            Err(ValidationError::MissingInput(ValidationInput::VrfOutput))
+        );
+        let mut missing = inputs.clone();
+        missing.supply_increase = None;
+        assert_eq!(
+            validate_with(&block, &parent, &context(), &missing),
+            Err(ValidationError::MissingInput(
+                ValidationInput::SupplyIncrease
+            ))
+        );
+        let mut missing = inputs.clone();
+        missing.body_reference = None;
+        assert_eq!(
+            validate_with(&block, &parent, &context(), &missing),
+            Err(ValidationError::MissingInput(
+                ValidationInput::BodyReference
+            ))
+        );
+
+        let mut invalid = inputs.clone();
+        invalid.vrf_output = Some(Fp::from(42_u64));
+        assert_eq!(
+            validate_with(&block, &parent, &context(), &invalid),
+            Err(
+                BlockRejection::InvalidConsensusState(ConsensusError::ConsensusStateMismatch(
+                    "last_vrf_output"
+                ))
+                .into()
+            )
+        );
+        let mut invalid = inputs.clone();
+        invalid.supply_increase = Some(Amount(0));
+        assert_eq!(
+            validate_with(&block, &parent, &context(), &invalid),
+            Err(
+                BlockRejection::InvalidConsensusState(ConsensusError::ConsensusStateMismatch(
+                    "total_currency"
+                ))
+                .into()
+            )
+        );
+        let mut invalid = inputs.clone();
+        invalid.body_reference = Some(BodyReference([0; 32]));
+        assert_eq!(
+            validate_with(&block, &parent, &context(), &invalid),
+            Err(BlockRejection::BodyReferenceMismatch {
+                expected: BodyReference([0; 32]),
+                actual: inputs.body_reference.unwrap(),
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_validate_body_reference() {
+        let block = read_block_json(BLOCK);
+        let body_reference =
+            BodyReference::from_staged_ledger_diff(&block.staged_ledger_diff).unwrap();
+        let mut blockchain_state = BlockchainState {
+            body_reference: body_reference.clone(),
+            ..Default::default()
+        };
+        assert_eq!(
+            validate_body_reference(&block.staged_ledger_diff, &blockchain_state),
+            Ok(())
+        );
+
+        blockchain_state.body_reference = BodyReference([0; 32]);
+        assert_eq!(
+            validate_body_reference(&block.staged_ledger_diff, &blockchain_state),
+            Err(BlockRejection::BodyReferenceMismatch {
+                expected: BodyReference([0; 32]),
+                actual: body_reference,
+            })
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_validate_rejections() {
+        let parent = read_block_json(PARENT).protocol_state;
+        let block = read_block_json(BLOCK);
+
+        let mut context = context();
+        context.protocol_version.major = 1;
+        assert_eq!(
+            validate(&block, &parent, &context),
+            Err(BlockRejection::MismatchedProtocolVersion {
+                expected: context.protocol_version.clone(),
+                actual: ProtocolVersion::default(),
+            }
+            .into())
+        );
+
+        let other_parent = read_block_json(BLOCK).protocol_state;
+        assert_eq!(
+            validate(&block, &other_parent, &self::context()),
+            Err(BlockRejection::PreviousStateHashMismatch {
+                expected: other_parent.state_hash(),
+                actual: parent.state_hash(),
+            }
+            .into())
+        );
+
+        let mut invalid = block.clone();
+        invalid.delta_transition_chain_proof.0 = other_parent.state_hash();
+        assert_eq!(
+            validate(&invalid, &parent, &self::context()),
+            Err(BlockRejection::InvalidDeltaTransitionChainProof.into())
+        );
+
+        let mut invalid = block.clone();
+        invalid.protocol_state.body.blockchain_state.timestamp = BlockTime(RECEIVED_AT);
+        assert_eq!(
+            validate(&invalid, &parent, &self::context()),
+            Err(BlockRejection::TimestampMismatch {
+                expected: BlockTime(1636093080000),
+                actual: BlockTime(RECEIVED_AT),
+            }
+            .into())
+        );
+
+        // Received before the slot of the block starts
+        let mut context = self::context();
+        context.now = BlockTime(1636092900000);
+        assert_eq!(
+            validate(&block, &parent, &context),
+            Err(BlockRejection::SlotInFuture {
+                slot: 111966,
+                current_slot: 111965,
+            }
+            .into())
+        );
+
+        // Received before the genesis of mainnet
+        context.now = BlockTime(0);
+        assert_eq!(
+            validate(&block, &parent, &context),
+            Err(ValidationError::TimeBeforeGenesis(BlockTime(0)))
+        );
+
+        let mut invalid = block.clone();
+        invalid
+            .protocol_state
+            .body
+            .consensus_state
+            .sub_window_densities[1] = Length(1);
+        assert_eq!(
+            validate(&invalid, &parent, &self::context()),
+            Err(
+                BlockRejection::InvalidConsensusState(ConsensusError::ConsensusStateMismatch(
+                    "sub_window_densities"
+                ))
+                .into()
+            )
+        );
+
+        let mut invalid = block;
+        invalid
+            .protocol_state
+            .body
+            .consensus_state
+            .blockchain_length = Length(77750);
+        assert_eq!(
+            validate(&invalid, &parent, &self::context()),
+            Err(
+                BlockRejection::InvalidConsensusState(ConsensusError::ConsensusStateMismatch(
+                    "blockchain_length"
+                ))
+                .into()
+            )
+        );
+    }
+
+    // block path: mainnet-$BlockHeight-$StateHash.json eg: "mainnet-113267-3NLenrog9wkiJMoA774T9VraqSUGhCuhbDLj3JKbEzomNdjr78G8.json"
+    // Note: block path must exist in test_fixtures::JSON_TEST_BLOCKS
+    fn read_block_json(block_path: &str) -> ExternalTransition {
+        let json_block = test_fixtures::JSON_TEST_BLOCKS.get(block_path).unwrap();
+        let json_value: <ExternalTransition as JsonSerializationType>::T =
+            serde_json::from_value(json_block.clone()).unwrap();
+        json_value.into()
+    }
+}
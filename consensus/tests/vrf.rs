@@ -0,0 +1,157 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use mina_consensus::vrf::*;
+    use mina_crypto::hash::EpochSeed;
+    use mina_rs_base::{consensus_state::VrfOutputTruncated, numbers::Amount, types::*};
+    use mina_serialization_types::json::ExternalTransitionJson;
+    use proof_systems::mina_signer::Keypair;
+    use std::str::FromStr;
+    use wasm_bindgen_test::*;
+
+    const SECRET_KEY: &str = "164244176fddb5d769b7de2027469d027ad428fadcc0c02396e6280142efb718";
+    const OTHER_SECRET_KEY: &str =
+        "3414fc16e86e6ac272fda03cf8dcb4d7d47af91b4b726494dab43bf773ce1779";
+    const EPOCH_SEED: &str = "2va9BGv9JrLTtrzZttiEMDYw1Zj6a6EHzXjmP9evHDTG3oEquURA";
+    // Private key of the genesis winner, EKFKgDtU3rcuFTVSEpmpXSkukjmX4cKefYREi6Sdsk7E7wsT7KRw
+    // in base58, whose public key is B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg
+    const GENESIS_WINNER_SECRET_KEY: &str =
+        "3d082fcfdd540532351b84ba15dbef5bd2a60fe95e850f1e28f8eb53f71284d6";
+
+    fn message(global_slot: u32, delegator_index: u64) -> VrfMessage {
+        VrfMessage::new(
+            global_slot,
+            EpochSeed::from_str(EPOCH_SEED).unwrap(),
+            delegator_index,
+        )
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_vrf_evaluate_and_verify() {
+        let keypair = Keypair::from_hex(SECRET_KEY).unwrap();
+        let other = Keypair::from_hex(OTHER_SECRET_KEY).unwrap();
+
+        let evaluation = VrfEvaluation::create(&keypair, message(100, 3));
+        let output = evaluation.verify(&keypair.public).unwrap();
+        assert_eq!(output, message(100, 3).evaluate(&keypair));
+        assert_eq!(output.0.len(), 32);
+        assert!(output.0[31] < 1 << 5);
+
+        // Outputs differ between messages and private keys
+        assert_ne!(output, message(101, 3).evaluate(&keypair));
+        assert_ne!(output, message(100, 4).evaluate(&keypair));
+        assert_ne!(output, message(100, 3).evaluate(&other));
+
+        // The proof does not verify against other public keys or messages
+        assert_eq!(evaluation.verify(&other.public), None);
+        let mut tampered = evaluation.clone();
+        tampered.message = message(101, 3);
+        assert_eq!(tampered.verify(&keypair.public), None);
+        let mut tampered = evaluation.clone();
+        tampered.scaled_message_hash =
+            VrfEvaluation::create(&other, message(100, 3)).scaled_message_hash;
+        assert_eq!(tampered.verify(&keypair.public), None);
+        let mut tampered = evaluation;
+        tampered.s += tampered.c;
+        assert_eq!(tampered.verify(&keypair.public), None);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_vrf_mainnet_genesis_output() {
+        // The genesis block is produced by the genesis winner with the
+        // initial epoch seed at slot 0 for the account at index 0
+        let keypair = Keypair::from_hex(GENESIS_WINNER_SECRET_KEY).unwrap();
+        let message = VrfMessage::legacy(0, EpochSeed::from_str(EPOCH_SEED).unwrap(), 0);
+        let output = message.evaluate(&keypair);
+        assert_eq!(
+            output.to_string(),
+            "NfThG1r1GxQuhaGLSJWGxcpv24SudtXG4etB0TnGqwg="
+        );
+        let evaluation = VrfEvaluation::create(&keypair, message);
+        assert_eq!(evaluation.verify(&keypair.public), Some(output));
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_vrf_mainnet_outputs_satisfy_threshold() {
+        // The stake of the producers is not known without the staking ledgers,
+        // but winning outputs satisfy the threshold of a producer with all the stake,
+        // which is the active slot coefficient
+        for (path, json) in test_fixtures::JSON_TEST_BLOCKS.iter() {
+            let block: ExternalTransition =
+                serde_json::from_value::<ExternalTransitionJson>(json.clone())
+                    .unwrap()
+                    .into();
+            let consensus_state = &block.protocol_state.body.consensus_state;
+            let total = consensus_state.staking_epoch_data.ledger.total_currency;
+            assert!(
+                is_threshold_satisfied(
+                    &consensus_state.last_vrf_output,
+                    total,
+                    total,
+                    ACTIVE_SLOT_COEFFICIENT
+                ),
+                "{path}"
+            );
+        }
+        // The output of 77749 is about 0.001147, which takes at least 0.0828% of the stake
+        let block: ExternalTransition = serde_json::from_value::<ExternalTransitionJson>(
+            test_fixtures::JSON_TEST_BLOCKS
+                ["mainnet-77749-3NK3P5bJHhqR7xkZBquGGfq3sERUeXNYNma5YXRMjgCNsTJRZpgL.json"]
+                .clone(),
+        )
+        .unwrap()
+        .into();
+        let output = &block.protocol_state.body.consensus_state.last_vrf_output;
+        let total = Amount(1_000_000);
+        let f = ACTIVE_SLOT_COEFFICIENT;
+        assert!(is_threshold_satisfied(output, Amount(829), total, f));
+        assert!(!is_threshold_satisfied(output, Amount(827), total, f));
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_vrf_threshold() {
+        let f = ACTIVE_SLOT_COEFFICIENT;
+        let total = Amount(1_000_000);
+        let zero = VrfOutputTruncated(vec![0; 32]);
+        let mut max = VrfOutputTruncated(vec![0xff; 32]);
+        max.0[31] = 0x1f;
+        // 0.5, which is below 0.75 but above 1 - 0.25^0.25
+        let mut half = VrfOutputTruncated(vec![0; 32]);
+        half.0[31] = 0x10;
+
+        assert!(!is_threshold_satisfied(&zero, Amount(0), total, f));
+        assert!(is_threshold_satisfied(&zero, Amount(1), total, f));
+        assert!(is_threshold_satisfied(&half, total, total, f));
+        assert!(!is_threshold_satisfied(&half, Amount(250_000), total, f));
+        assert!(!is_threshold_satisfied(&max, total, total, f));
+
+        // Bits beyond the truncated length are ignored
+        let mut padded = half.clone();
+        padded.0[31] |= 0xe0;
+        assert_eq!(
+            is_threshold_satisfied(&padded, Amount(250_000), total, f),
+            is_threshold_satisfied(&half, Amount(250_000), total, f)
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_vrf_winning_slots() {
+        // A producer with all the stake wins about f of the slots
+        let keypair = Keypair::from_hex(SECRET_KEY).unwrap();
+        let total = Amount(1_000_000);
+        let won = (0..200)
+            .filter(|&slot| {
+                let output = message(slot, 0).evaluate(&keypair);
+                is_threshold_satisfied(&output, total, total, ACTIVE_SLOT_COEFFICIENT)
+            })
+            .count();
+        assert!((110..190).contains(&won), "won {won} slots");
+    }
+}
@@ -63,7 +63,7 @@ impl TryFrom<&Field> for Fp {
     }
 }
 
-#[derive(Clone, Debug, Default, Eq, PartialEq, derive_more::From, derive_more::Into)]
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash, derive_more::From, derive_more::Into)]
 pub(crate) struct BaseHash(pub(crate) [u8; 32]);
 
 impl Hashable for BaseHash {
@@ -219,7 +219,7 @@ impl ToChunkedROInput for StateHash {
 
 //////////////////////////////////////////////////////////////////////////
 
-#[derive(Clone, Debug, Default, Eq, PartialEq, derive_more::From, derive_more::Into)]
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash, derive_more::From, derive_more::Into)]
 pub struct LedgerHash(BaseHash);
 
 impl_from_for_hash!(LedgerHash, HashV1);
@@ -307,6 +307,13 @@ impl From<ChainHash> for [u8; 32] {
     }
 }
 
+impl From<&Fp> for ChainHash {
+    fn from(i: &Fp) -> Self {
+        let base: BaseHash = i.into();
+        base.into()
+    }
+}
+
 //////////////////////////////////////////////////////////////////////////
 
 #[derive(Clone, Default, Debug, Eq, PartialEq, derive_more::From, derive_more::Into)]
@@ -318,6 +325,21 @@ impl_from_for_generic_with_proxy!(CoinBaseHash, HashV1, CoinBaseHashV1Json);
 impl_strconv_via_json!(CoinBaseHash, CoinBaseHashV1Json);
 impl_from_json_value_for_hash!(CoinBaseHash);
 
+impl From<&Fp> for CoinBaseHash {
+    fn from(i: &Fp) -> Self {
+        let base: BaseHash = i.into();
+        base.into()
+    }
+}
+
+impl TryFrom<&CoinBaseHash> for Fp {
+    type Error = FieldHelpersError;
+
+    fn try_from(i: &CoinBaseHash) -> Result<Self, Self::Error> {
+        (&i.0).try_into()
+    }
+}
+
 impl ToChunkedROInput for CoinBaseHash {
     fn to_chunked_roinput(&self) -> ChunkedROInput {
         self.0.to_chunked_roinput()
@@ -345,6 +367,21 @@ impl_from_for_hash!(EpochSeed, HashV1);
 impl_from_for_generic_with_proxy!(EpochSeed, HashV1, EpochSeedHashV1Json);
 impl_strconv_via_json!(EpochSeed, EpochSeedHashV1Json);
 
+impl From<&Fp> for EpochSeed {
+    fn from(i: &Fp) -> Self {
+        let base: BaseHash = i.into();
+        base.into()
+    }
+}
+
+impl TryFrom<&EpochSeed> for Fp {
+    type Error = FieldHelpersError;
+
+    fn try_from(i: &EpochSeed) -> Result<Self, Self::Error> {
+        (&i.0).try_into()
+    }
+}
+
 impl ToChunkedROInput for EpochSeed {
     fn to_chunked_roinput(&self) -> ChunkedROInput {
         self.0.to_chunked_roinput()
@@ -0,0 +1,66 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//! Replays a contiguous run of blocks on top of a genesis ledger stored in RocksDB,
+//! checking the staged and snarked ledger hashes of every block
+//!
+//! USAGE:
+//!     cargo run -p mina-ledger --example replay -- <GENESIS_LEDGER_DB> <BLOCK_JSON>...
+//!
+//! The blocks are json files in the format of the mainnet block dumps, given in the order
+//! they are applied, starting from the first block after genesis.
+//! The genesis ledger is a mainnet ledger of legacy accounts, which are hashed with the
+//! legacy Poseidon and replayed with the mainnet constraint constants
+
+use anyhow::{bail, Context};
+use mina_consensus::genesis::*;
+use mina_ledger::*;
+use mina_rs_base::{account::AccountLegacy, types::*};
+use mina_serialization_types::json::ExternalTransitionJson;
+use rocksdb::{Options, DB};
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let db_path = match args.next() {
+        Some(path) => path,
+        None => bail!("USAGE: replay <GENESIS_LEDGER_DB> <BLOCK_JSON>..."),
+    };
+    let db = DB::open_for_read_only(&Options::default(), &db_path, true)
+        .with_context(|| format!("Failed to open genesis ledger {db_path}"))?;
+    let genesis_ledger: RocksDbGenesisLedger<20, AccountLegacy> = RocksDbGenesisLedger::new(&db);
+    let ledger = InMemoryLedger::from_genesis_ledger(&genesis_ledger)?;
+    let mut replayer = BlockReplayer::new(ledger, ConstraintConstants::mainnet())?;
+    println!("Genesis ledger hash: {}", replayer.snarked_ledger_hash());
+    let genesis_block = ExternalTransition::from_genesis_config(&MAINNET_CONFIG);
+    let expected = &genesis_block
+        .protocol_state
+        .body
+        .blockchain_state
+        .genesis_ledger_hash;
+    if replayer.snarked_ledger_hash() != expected {
+        bail!("Genesis ledger hash does not match the one of mainnet: {expected}");
+    }
+
+    let mut previous_state_hash = None;
+    for path in args {
+        let json =
+            std::fs::read_to_string(&path).with_context(|| format!("Failed to read {path}"))?;
+        let block: ExternalTransition =
+            serde_json::from_str::<ExternalTransitionJson>(&json)?.into();
+        let state_hash = block.protocol_state.state_hash();
+        if let Some(previous_state_hash) = previous_state_hash {
+            if block.protocol_state.previous_state_hash != previous_state_hash {
+                bail!("Block {path} does not follow the previous block");
+            }
+        }
+        let ledger_hash = replayer
+            .apply_block(&block)
+            .with_context(|| format!("Failed to replay block {path}"))?;
+        println!(
+            "{state_hash}: staged ledger {ledger_hash}, snarked ledger {}",
+            replayer.snarked_ledger_hash()
+        );
+        previous_state_hash = Some(state_hash);
+    }
+    Ok(())
+}
@@ -0,0 +1,196 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//! Snapshots of the ledgers referred by the epoch data of the consensus state
+//!
+//! The staking ledger is the stake distribution used for VRF evaluation in the current epoch,
+//! the next epoch ledger is the one that becomes the staking ledger in the next epoch.
+//! At an epoch boundary the next epoch ledger becomes the staking ledger, and the snarked ledger
+//! of the last block of the previous epoch is frozen as the new next epoch ledger, see
+//! <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/consensus/proof_of_stake.ml>
+
+use crate::{genesis_ledger::MerkleLedgerAccount, in_memory_ledger::*, ledger::*};
+use mina_crypto::hash::LedgerHash;
+use mina_rs_base::{consensus_state::ConsensusState, epoch_data::EpochData};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Errors that can be produced when managing epoch ledger snapshots
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum EpochLedgerError {
+    /// The root of the frozen ledger does not match the ledger hash in the epoch data
+    #[error("Ledger hash mismatch, expected: {expected:?}, actual: {actual:?}")]
+    LedgerHashMismatch {
+        /// Ledger hash in the epoch data
+        expected: LedgerHash,
+        /// Merkle root of the frozen ledger
+        actual: LedgerHash,
+    },
+
+    /// Ledgers can only be rotated forward
+    #[error("Epoch {new} is not after the current epoch {current}")]
+    StaleEpoch {
+        /// Epoch of the current staking ledger
+        current: u32,
+        /// Epoch being rotated to
+        new: u32,
+    },
+
+    /// The merkle root of the ledger cannot be calculated
+    #[error("Merkle root of the ledger is not available")]
+    MissingMerkleRoot,
+
+    /// Errors from the ledger
+    #[error("Ledger error: {0}")]
+    Ledger(#[from] LedgerError),
+}
+
+/// Staking and next epoch ledger snapshots indexed by their [LedgerHash]
+pub struct EpochLedgers<Account>
+where
+    Account: LedgerAccount + MerkleLedgerAccount,
+{
+    epoch: u32,
+    staking: LedgerHash,
+    next: LedgerHash,
+    snapshots: HashMap<LedgerHash, InMemoryLedger<Account>>,
+}
+
+impl<Account> EpochLedgers<Account>
+where
+    Account: LedgerAccount + MerkleLedgerAccount,
+{
+    /// Creates the snapshots of epoch 0,
+    /// where both the staking ledger and the next epoch ledger are the genesis ledger
+    pub fn new<L>(genesis_ledger: &L) -> Result<Self, EpochLedgerError>
+    where
+        L: Ledger<Account = Account>,
+    {
+        let (hash, ledger) = freeze(genesis_ledger)?;
+        Ok(Self {
+            epoch: 0,
+            staking: hash.clone(),
+            next: hash.clone(),
+            snapshots: HashMap::from([(hash, ledger)]),
+        })
+    }
+
+    /// Current epoch number
+    pub fn epoch(&self) -> u32 {
+        self.epoch
+    }
+
+    /// Hash of the staking ledger
+    pub fn staking_ledger_hash(&self) -> &LedgerHash {
+        &self.staking
+    }
+
+    /// Hash of the next epoch ledger
+    pub fn next_epoch_ledger_hash(&self) -> &LedgerHash {
+        &self.next
+    }
+
+    /// Ledger used for VRF evaluation and delegation queries in the current epoch
+    pub fn staking_ledger(&self) -> &InMemoryLedger<Account> {
+        &self.snapshots[&self.staking]
+    }
+
+    /// Ledger that becomes the staking ledger in the next epoch
+    pub fn next_epoch_ledger(&self) -> &InMemoryLedger<Account> {
+        &self.snapshots[&self.next]
+    }
+
+    /// Looks up a snapshot by its [LedgerHash]
+    pub fn get(&self, hash: &LedgerHash) -> Option<&InMemoryLedger<Account>> {
+        self.snapshots.get(hash)
+    }
+
+    /// Rotates the snapshots at an epoch boundary, the next epoch ledger becomes
+    /// the staking ledger and the given snarked ledger is frozen as the new next epoch ledger,
+    /// whose root must match the ledger hash of the next epoch data of the new epoch
+    pub fn rotate<L>(
+        &mut self,
+        epoch: u32,
+        snarked_ledger: &L,
+        next_epoch_data: &EpochData,
+    ) -> Result<(), EpochLedgerError>
+    where
+        L: Ledger<Account = Account>,
+    {
+        if epoch <= self.epoch {
+            return Err(EpochLedgerError::StaleEpoch {
+                current: self.epoch,
+                new: epoch,
+            });
+        }
+        let (hash, ledger) = freeze(snarked_ledger)?;
+        if hash != next_epoch_data.ledger.hash {
+            return Err(EpochLedgerError::LedgerHashMismatch {
+                expected: next_epoch_data.ledger.hash.clone(),
+                actual: hash,
+            });
+        }
+        self.epoch = epoch;
+        self.staking = std::mem::replace(&mut self.next, hash.clone());
+        self.snapshots.insert(hash, ledger);
+        let (staking, next) = (&self.staking, &self.next);
+        self.snapshots.retain(|h, _| h == staking || h == next);
+        Ok(())
+    }
+
+    /// Checks that the snapshots match the ledger hashes in the epoch data of the consensus state
+    pub fn check(&self, consensus_state: &ConsensusState) -> Result<(), EpochLedgerError> {
+        for (hash, epoch_data) in [
+            (&self.staking, &consensus_state.staking_epoch_data),
+            (&self.next, &consensus_state.next_epoch_data),
+        ] {
+            if hash != &epoch_data.ledger.hash {
+                return Err(EpochLedgerError::LedgerHashMismatch {
+                    expected: epoch_data.ledger.hash.clone(),
+                    actual: hash.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Rotates the snapshots when the epoch of the consensus state is after the current one,
+    /// the snarked ledger is the one of the parent block, then checks the snapshots against
+    /// the epoch data of the consensus state
+    pub fn update<L>(
+        &mut self,
+        consensus_state: &ConsensusState,
+        snarked_ledger: &L,
+    ) -> Result<(), EpochLedgerError>
+    where
+        L: Ledger<Account = Account>,
+    {
+        let epoch = consensus_state.epoch_count.0;
+        if epoch > self.epoch {
+            self.rotate(epoch, snarked_ledger, &consensus_state.next_epoch_data)?;
+        }
+        self.check(consensus_state)
+    }
+}
+
+/// Copies the accounts of the ledger into an [InMemoryLedger] and calculates its hash
+fn freeze<L>(ledger: &L) -> Result<(LedgerHash, InMemoryLedger<L::Account>), EpochLedgerError>
+where
+    L: Ledger,
+    L::Account: MerkleLedgerAccount,
+{
+    // Accounts are allocated contiguously, an empty location would shift the rest of them
+    let accounts = (0..ledger.num_accounts())
+        .map(|i| {
+            let location = Location(i);
+            ledger
+                .get(location)?
+                .ok_or(LedgerError::InvalidLocation(location))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut snapshot = InMemoryLedger::from_accounts(ledger.depth(), accounts)?;
+    let root = snapshot
+        .merkle_root()
+        .ok_or(EpochLedgerError::MissingMerkleRoot)?;
+    Ok(((&root).into(), snapshot))
+}
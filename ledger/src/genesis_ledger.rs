@@ -8,7 +8,8 @@
 //!
 
 use mina_merkle::*;
-use proof_systems::mina_hasher::Hashable;
+use mina_rs_base::account::{Account, AccountLegacy};
+use proof_systems::mina_hasher::{Fp, Hashable};
 
 /// Type alias for mina merkle ledger hasher
 pub type MinaLedgerMerkleHasherLegacy<Account> = MinaPoseidonMerkleHasherLegacy<Account>;
@@ -34,6 +35,36 @@ pub type MinaLedgerMerkleTree<Account> = MinaMerkleTree<
     FixedHeightMode,
 >;
 
+/// Accounts whose ledger merkle tree is hashed by the Poseidon of their network,
+/// i.e. legacy accounts of mainnet are hashed with the legacy Poseidon
+/// and accounts of berkeley with the kimchi Poseidon
+pub trait MerkleLedgerAccount: Hashable + Clone {
+    /// Hasher of the accounts
+    type Hasher: MerkleHasher<Item = Self, Hash = Fp>;
+    /// Merger of the nodes of the ledger merkle tree
+    type Merger: MerkleMerger<Hash = Fp>;
+}
+
+impl MerkleLedgerAccount for AccountLegacy {
+    type Hasher = MinaLedgerMerkleHasherLegacy<Self>;
+    type Merger = MinaPoseidonMerkleMergerLegacy;
+}
+
+impl MerkleLedgerAccount for Account {
+    type Hasher = MinaLedgerMerkleHasher<Self>;
+    type Merger = MinaPoseidonMerkleMerger;
+}
+
+/// Type alias for the mina merkle ledger of the given account type,
+/// which uses the hasher of its network
+pub type MinaLedgerMerkleTreeOf<Account> = MinaMerkleTree<
+    Account,
+    Fp,
+    <Account as MerkleLedgerAccount>::Hasher,
+    <Account as MerkleLedgerAccount>::Merger,
+    FixedHeightMode,
+>;
+
 /// A genesis ledger provides access to its accounts by implementing IntoIterator
 /// This implementation must be provided to meet the trait requirements
 ///
@@ -0,0 +1,146 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//! An in-memory mutable ledger backed by a merkle tree of its account type
+//!
+//! Accounts are stored as leaves of the merkle tree in the order of allocation,
+//! an index from account id to location is maintained along with the tree.
+//! Updating an account only invalidates the hashes on the path from its leaf to the root,
+//! so the root hash is recalculated incrementally
+
+use crate::{genesis_ledger::*, ledger::*};
+use mina_merkle::*;
+use proof_systems::mina_hasher::{Fp, Hashable};
+use std::collections::HashMap;
+
+/// An in-memory mutable ledger backed by a [MinaLedgerMerkleTreeOf],
+/// ledgers of legacy accounts are hashed with the legacy Poseidon
+pub struct InMemoryLedger<Account>
+where
+    Account: LedgerAccount + MerkleLedgerAccount,
+{
+    depth: u32,
+    tree: MinaLedgerMerkleTreeOf<Account>,
+    locations: HashMap<AccountId, Location>,
+}
+
+impl<Account> InMemoryLedger<Account>
+where
+    Account: LedgerAccount + MerkleLedgerAccount,
+{
+    /// Creates an empty ledger with the given depth
+    pub fn new(depth: u32) -> Self {
+        Self {
+            depth,
+            tree: MinaLedgerMerkleTreeOf::new(depth),
+            locations: HashMap::new(),
+        }
+    }
+
+    /// Creates a ledger with the given depth from a collection of accounts,
+    /// accounts are allocated in the given order
+    pub fn from_accounts(
+        depth: u32,
+        accounts: impl IntoIterator<Item = Account>,
+    ) -> Result<Self, LedgerError> {
+        let mut ledger = Self::new(depth);
+        let accounts: Vec<_> = accounts.into_iter().collect();
+        if accounts.len() > ledger.capacity() {
+            return Err(LedgerError::OutOfLeaves(ledger.capacity()));
+        }
+        for (i, account) in accounts.iter().enumerate() {
+            ledger.locations.insert(account.account_id(), Location(i));
+        }
+        ledger.tree.add_batch(accounts);
+        Ok(ledger)
+    }
+
+    /// Creates a ledger from a [GenesisLedger]
+    pub fn from_genesis_ledger<'a, const DEPTH: usize, L>(
+        genesis_ledger: &'a L,
+    ) -> Result<Self, LedgerError>
+    where
+        L: GenesisLedger<'a, DEPTH, Account> + 'a,
+        &'a L: IntoIterator<Item = Result<Account, L::Error>>,
+        <Account as Hashable>::D: Default,
+    {
+        Self::from_accounts(DEPTH as u32, genesis_ledger.accounts().flatten())
+    }
+
+    /// Iterates over the accounts in the order of their locations
+    pub fn accounts(&self) -> impl Iterator<Item = &Account> {
+        (0..self.tree.count()).filter_map(|i| self.tree.get(i))
+    }
+
+    /// Gets the merkle proof of the account at the given location
+    pub fn merkle_proof(
+        &mut self,
+        location: Location,
+    ) -> Option<DefaultMerkleProof<Account, Fp, Account::Hasher, Account::Merger>> {
+        if location.0 < self.tree.count() {
+            self.tree.get_proof(location.0)
+        } else {
+            None
+        }
+    }
+}
+
+impl<Account> Ledger for InMemoryLedger<Account>
+where
+    Account: LedgerAccount + MerkleLedgerAccount,
+{
+    type Account = Account;
+    type Hash = Fp;
+
+    fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    fn num_accounts(&self) -> usize {
+        self.tree.count()
+    }
+
+    fn location_of_account(&self, account_id: &AccountId) -> Result<Option<Location>, LedgerError> {
+        Ok(self.locations.get(account_id).copied())
+    }
+
+    fn get(&self, location: Location) -> Result<Option<Self::Account>, LedgerError> {
+        Ok(self.tree.get(location.0).cloned())
+    }
+
+    fn set(&mut self, location: Location, account: Self::Account) -> Result<(), LedgerError> {
+        let existing = self
+            .tree
+            .get(location.0)
+            .ok_or(LedgerError::InvalidLocation(location))?;
+        if existing.account_id() != account.account_id() {
+            return Err(LedgerError::AccountIdMismatch(location));
+        }
+        self.tree.set(location.0, account);
+        Ok(())
+    }
+
+    fn get_or_create_account(
+        &mut self,
+        account_id: &AccountId,
+        account: Self::Account,
+    ) -> Result<(GetOrCreated, Location), LedgerError> {
+        if let Some(location) = self.location_of_account(account_id)? {
+            return Ok((GetOrCreated::Existed, location));
+        }
+        if &account.account_id() != account_id {
+            return Err(LedgerError::AccountIdMismatch(Location(self.tree.count())));
+        }
+        if self.tree.count() >= self.capacity() {
+            return Err(LedgerError::OutOfLeaves(self.capacity()));
+        }
+        let location = Location(self.tree.count());
+        self.tree.add(account);
+        self.locations.insert(account_id.clone(), location);
+        Ok((GetOrCreated::Added, location))
+    }
+
+    fn merkle_root(&mut self) -> Option<Self::Hash> {
+        self.tree.root()
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//! A ledger is a mapping from account ids to accounts that are stored
+//! as leaves of a merkle tree, whose root hash is the ledger hash
+//!
+//! This module defines the interface that every mutable ledger implements,
+//! it is modeled after `Base_ledger_intf` in the OCaml implementation
+//! <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/merkle_ledger/base_ledger_intf.ml>
+
+use mina_rs_base::account::{Account, AccountLegacy};
+use mina_rs_base::types::TokenId;
+use proof_systems::mina_signer::CompressedPubKey;
+use std::hash::{Hash, Hasher};
+use thiserror::Error;
+
+/// An account is uniquely identified by its public key and token id
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AccountId {
+    /// Public key of the account
+    pub public_key: CompressedPubKey,
+    /// Token id of the account
+    pub token_id: TokenId,
+}
+
+impl AccountId {
+    /// Creates a new [AccountId]
+    pub fn new(public_key: CompressedPubKey, token_id: TokenId) -> Self {
+        Self {
+            public_key,
+            token_id,
+        }
+    }
+}
+
+impl Hash for AccountId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.public_key.x.hash(state);
+        self.public_key.is_odd.hash(state);
+        self.token_id.0.hash(state);
+    }
+}
+
+/// Accounts that can be stored in a [Ledger]
+pub trait LedgerAccount: Clone {
+    /// Gets the id of the account
+    fn account_id(&self) -> AccountId;
+}
+
+impl LedgerAccount for Account {
+    fn account_id(&self) -> AccountId {
+        AccountId::new(self.public_key.clone(), self.token_id.clone())
+    }
+}
+
+impl LedgerAccount for AccountLegacy {
+    fn account_id(&self) -> AccountId {
+        AccountId::new(self.public_key.clone(), self.token_id.clone())
+    }
+}
+
+/// Location of an account in the ledger, which is the 0-based index of its leaf
+/// in the ledger merkle tree
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct Location(pub usize);
+
+/// Indicates whether an account is newly created by [Ledger::get_or_create_account]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GetOrCreated {
+    /// The account has been added to the ledger
+    Added,
+    /// The account already exists in the ledger
+    Existed,
+}
+
+/// Errors that can be produced when mutating a ledger
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum LedgerError {
+    /// All leaves of the ledger merkle tree have been allocated
+    #[error("Ledger is full, no location is available for new accounts, capacity: {0}")]
+    OutOfLeaves(usize),
+
+    /// There is no account at the given location
+    #[error("No account is found at location: {0:?}")]
+    InvalidLocation(Location),
+
+    /// The account id does not match the one stored at the given location
+    #[error("Account id does not match the one stored at location: {0:?}")]
+    AccountIdMismatch(Location),
+
+    /// Errors from the underlying storage
+    #[error("Storage error: {0}")]
+    Storage(String),
+}
+
+/// Trait for a mutable ledger
+pub trait Ledger {
+    /// Type of the accounts
+    type Account: LedgerAccount;
+    /// Type of the ledger hash
+    type Hash;
+
+    /// Depth of the ledger merkle tree, leaf nodes are not counted
+    fn depth(&self) -> u32;
+
+    /// Number of accounts in the ledger
+    fn num_accounts(&self) -> usize;
+
+    /// Looks up the location of an account by its id
+    fn location_of_account(&self, account_id: &AccountId) -> Result<Option<Location>, LedgerError>;
+
+    /// Gets the account at the given location
+    fn get(&self, location: Location) -> Result<Option<Self::Account>, LedgerError>;
+
+    /// Replaces the account at the given location,
+    /// the account id must match the one stored at the location
+    fn set(&mut self, location: Location, account: Self::Account) -> Result<(), LedgerError>;
+
+    /// Returns the location of the account with the given id,
+    /// allocates a new location for it when the account does not exist
+    fn get_or_create_account(
+        &mut self,
+        account_id: &AccountId,
+        account: Self::Account,
+    ) -> Result<(GetOrCreated, Location), LedgerError>;
+
+    /// Root hash of the ledger merkle tree
+    fn merkle_root(&mut self) -> Option<Self::Hash>;
+
+    /// Gets the account by its id
+    fn get_account(&self, account_id: &AccountId) -> Result<Option<Self::Account>, LedgerError> {
+        match self.location_of_account(account_id)? {
+            Some(location) => self.get(location),
+            None => Ok(None),
+        }
+    }
+
+    /// Capacity of the ledger
+    fn capacity(&self) -> usize {
+        2_usize.pow(self.depth())
+    }
+}
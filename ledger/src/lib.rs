@@ -8,8 +8,28 @@
 
 mod genesis_ledger;
 pub use genesis_ledger::*;
+mod ledger;
+pub use ledger::*;
+mod in_memory_ledger;
+pub use in_memory_ledger::*;
+mod transaction_logic;
+pub use transaction_logic::*;
+mod replayer;
+pub use replayer::*;
+mod epoch_ledgers;
+pub use epoch_ledgers::*;
+mod pending_coinbase;
+pub use pending_coinbase::*;
+mod scan_state;
+pub use scan_state::*;
+mod supply;
+pub use supply::*;
 
 #[cfg(not(target_arch = "wasm32"))]
 mod rocksdb_genesis_ledger;
 #[cfg(not(target_arch = "wasm32"))]
 pub use rocksdb_genesis_ledger::RocksDbGenesisLedger;
+#[cfg(not(target_arch = "wasm32"))]
+mod rocksdb_ledger;
+#[cfg(not(target_arch = "wasm32"))]
+pub use rocksdb_ledger::*;
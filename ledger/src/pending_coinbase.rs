@@ -0,0 +1,322 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//! Pending coinbase is a merkle tree of stacks, each stack collects the coinbases and the
+//! protocol state body hashes of the transactions in a tree of the scan state, until the
+//! ledger proof of that tree is emitted and the oldest stack is popped
+//!
+//! This module is modeled after `Pending_coinbase` in the OCaml implementation
+//! <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/mina_base/pending_coinbase.ml>
+
+use mina_crypto::hash::CoinBaseHash;
+use mina_merkle::*;
+use mina_rs_base::{snark_work::empty_coinbase_stack, types::Amount};
+use proof_systems::{
+    mina_hasher::{create_legacy, Fp, Hashable, Hasher, ROInput},
+    mina_signer::CompressedPubKey,
+};
+use std::collections::VecDeque;
+use thiserror::Error;
+
+/// Errors that can be produced when updating a pending coinbase
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum PendingCoinbaseError {
+    /// All the stacks of the pending coinbase are in use
+    #[error("Pending coinbase is full, capacity: {0}")]
+    Full(usize),
+
+    /// There is no stack in the pending coinbase
+    #[error("Pending coinbase has no stack")]
+    NoStack,
+}
+
+/// Hash of the coinbases pushed to a stack
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CoinbaseStack(pub Fp);
+
+impl CoinbaseStack {
+    /// Hash of a stack without coinbases, which is the digest of the sponge
+    /// salted with `CoinbaseStack` in OCaml
+    pub fn empty() -> Self {
+        Self(empty_coinbase_stack())
+    }
+
+    /// Pushes a coinbase of `amount` paid to `receiver` to the stack
+    pub fn push(&self, receiver: &CompressedPubKey, amount: Amount) -> Self {
+        let mut hasher = create_legacy(());
+        Self(hasher.hash(&CoinbaseData {
+            receiver: receiver.clone(),
+            amount,
+            stack: self.0,
+        }))
+    }
+}
+
+/// Hashes of the protocol state bodies pushed to a stack, starting from `init`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StateStack {
+    /// Hash that the stack starts from
+    pub init: Fp,
+    /// Hash after pushing all the protocol state body hashes
+    pub curr: Fp,
+}
+
+impl StateStack {
+    /// State stack of an empty pending coinbase, whose hashes are
+    /// the outside hash image `Stack_hash.dummy` in OCaml
+    pub fn empty() -> Self {
+        Self::create(Fp::from(0u64))
+    }
+
+    /// Creates a state stack that starts from `init`
+    pub fn create(init: Fp) -> Self {
+        Self { init, curr: init }
+    }
+
+    /// Pushes a protocol state body hash to the stack
+    pub fn push(&self, state_body_hash: Fp) -> Self {
+        let mut hasher = create_legacy(());
+        Self {
+            init: self.init,
+            curr: hasher.hash(&StateStackPush {
+                curr: self.curr,
+                state_body_hash,
+            }),
+        }
+    }
+}
+
+/// A stack of the pending coinbase, which is a leaf of its merkle tree
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Stack {
+    /// The coinbases of the stack
+    pub data: CoinbaseStack,
+    /// The protocol state body hashes of the stack
+    pub state: StateStack,
+}
+
+impl Stack {
+    /// The stack that fills the unused leaves of the merkle tree
+    pub fn empty() -> Self {
+        Self {
+            data: CoinbaseStack::empty(),
+            state: StateStack::empty(),
+        }
+    }
+
+    /// Creates a new stack without coinbases whose state stack
+    /// continues from the state stack of this stack
+    pub fn create_with(&self) -> Self {
+        Self {
+            data: CoinbaseStack::empty(),
+            state: StateStack::create(self.state.curr),
+        }
+    }
+
+    /// Pushes a coinbase to the stack
+    pub fn push_coinbase(&self, receiver: &CompressedPubKey, amount: Amount) -> Self {
+        Self {
+            data: self.data.push(receiver, amount),
+            state: self.state,
+        }
+    }
+
+    /// Pushes a protocol state body hash to the stack
+    pub fn push_state(&self, state_body_hash: Fp) -> Self {
+        Self {
+            data: self.data,
+            state: self.state.push(state_body_hash),
+        }
+    }
+}
+
+impl Hashable for Stack {
+    type D = ();
+
+    fn to_roinput(&self) -> ROInput {
+        ROInput::new()
+            .append_field(self.data.0)
+            .append_field(self.state.init)
+            .append_field(self.state.curr)
+    }
+
+    fn domain_string(_: Self::D) -> Option<String> {
+        Some("CoinbaseStack".into())
+    }
+}
+
+/// Merkle tree of the stacks
+pub type PendingCoinbaseMerkleTree = MinaMerkleTree<
+    Stack,
+    Fp,
+    MinaPoseidonMerkleHasherLegacy<Stack>,
+    MinaPoseidonCoinbaseMerkleMergerLegacy,
+    FixedHeightMode,
+>;
+
+/// Pending coinbase of a staged ledger
+pub struct PendingCoinbase {
+    tree: PendingCoinbaseMerkleTree,
+    // Leaf indices of the stacks in use, from the oldest to the newest one
+    positions: VecDeque<usize>,
+    // Leaf index of the next new stack
+    new_pos: usize,
+}
+
+impl PendingCoinbase {
+    /// Creates a pending coinbase of `2^depth` empty stacks
+    pub fn new(depth: u32) -> Self {
+        let mut tree = PendingCoinbaseMerkleTree::new(depth);
+        tree.add_batch(std::iter::repeat(Stack::empty()).take(1 << depth));
+        Self {
+            tree,
+            positions: VecDeque::new(),
+            new_pos: 0,
+        }
+    }
+
+    /// Depth of the pending coinbase that has enough stacks for all the trees of the scan state,
+    /// which is `ceil_log2((transaction_capacity_log_2 + 1) * (work_delay + 1) + 1)`,
+    /// on mainnet `transaction_capacity_log_2` is 7 and `work_delay` is 2
+    pub fn depth(transaction_capacity_log_2: u32, work_delay: u32) -> u32 {
+        let stacks = (transaction_capacity_log_2 + 1) * (work_delay + 1) + 1;
+        u32::BITS - (stacks - 1).leading_zeros()
+    }
+
+    /// Number of stacks of the pending coinbase
+    pub fn capacity(&self) -> usize {
+        self.tree.count()
+    }
+
+    /// Number of stacks in use
+    pub fn num_stacks(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Root hash of the merkle tree, which is the `pending_coinbase_hash` of the staged ledger hash
+    pub fn merkle_root(&mut self) -> CoinBaseHash {
+        let root = self
+            .tree
+            .root()
+            .expect("all leaves of the tree are present");
+        (&root).into()
+    }
+
+    /// The newest stack in use
+    pub fn latest_stack(&self) -> Option<Stack> {
+        self.positions
+            .back()
+            .and_then(|&i| self.tree.get(i))
+            .copied()
+    }
+
+    /// The oldest stack in use
+    pub fn oldest_stack(&self) -> Option<Stack> {
+        self.positions
+            .front()
+            .and_then(|&i| self.tree.get(i))
+            .copied()
+    }
+
+    /// Starts a new stack, whose state stack continues from the latest stack
+    pub fn new_stack(&mut self) -> Result<(), PendingCoinbaseError> {
+        if self.positions.len() == self.capacity() {
+            return Err(PendingCoinbaseError::Full(self.capacity()));
+        }
+        let stack = self
+            .latest_stack()
+            .unwrap_or_else(Stack::empty)
+            .create_with();
+        self.tree.set(self.new_pos, stack);
+        self.positions.push_back(self.new_pos);
+        self.new_pos = (self.new_pos + 1) % self.capacity();
+        Ok(())
+    }
+
+    /// Pushes a coinbase to the latest stack
+    pub fn add_coinbase(
+        &mut self,
+        receiver: &CompressedPubKey,
+        amount: Amount,
+    ) -> Result<(), PendingCoinbaseError> {
+        self.update_latest_stack(|stack| stack.push_coinbase(receiver, amount))
+    }
+
+    /// Pushes a protocol state body hash to the latest stack
+    pub fn add_state(&mut self, state_body_hash: Fp) -> Result<(), PendingCoinbaseError> {
+        self.update_latest_stack(|stack| stack.push_state(state_body_hash))
+    }
+
+    /// Removes the oldest stack, which is done when the ledger proof
+    /// that includes its coinbases is emitted from the scan state
+    pub fn pop_oldest(&mut self) -> Result<Stack, PendingCoinbaseError> {
+        let index = self
+            .positions
+            .pop_front()
+            .ok_or(PendingCoinbaseError::NoStack)?;
+        let stack = self
+            .tree
+            .get(index)
+            .copied()
+            .ok_or(PendingCoinbaseError::NoStack)?;
+        self.tree.set(index, Stack::empty());
+        Ok(stack)
+    }
+
+    fn update_latest_stack(
+        &mut self,
+        f: impl FnOnce(&Stack) -> Stack,
+    ) -> Result<(), PendingCoinbaseError> {
+        let index = *self.positions.back().ok_or(PendingCoinbaseError::NoStack)?;
+        let stack = self
+            .tree
+            .get(index)
+            .map(f)
+            .ok_or(PendingCoinbaseError::NoStack)?;
+        self.tree.set(index, stack);
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct CoinbaseData {
+    receiver: CompressedPubKey,
+    amount: Amount,
+    stack: Fp,
+}
+
+impl Hashable for CoinbaseData {
+    type D = ();
+
+    fn to_roinput(&self) -> ROInput {
+        ROInput::new()
+            .append_field(self.receiver.x)
+            .append_bool(self.receiver.is_odd)
+            .append_u64(self.amount.0)
+            .append_field(self.stack)
+    }
+
+    fn domain_string(_: Self::D) -> Option<String> {
+        Some("CoinbaseStackData".into())
+    }
+}
+
+#[derive(Clone)]
+struct StateStackPush {
+    curr: Fp,
+    state_body_hash: Fp,
+}
+
+impl Hashable for StateStackPush {
+    type D = ();
+
+    fn to_roinput(&self) -> ROInput {
+        ROInput::new()
+            .append_field(self.curr)
+            .append_field(self.state_body_hash)
+    }
+
+    fn domain_string(_: Self::D) -> Option<String> {
+        Some("CoinbaseStackStaHash".into())
+    }
+}
@@ -0,0 +1,383 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//! Replays blocks on top of a [Ledger]
+//!
+//! Transactions of every [StagedLedgerDiff] are applied in the same order as the OCaml
+//! implementation does, i.e. for each pre-diff the user commands first, then the coinbase
+//! parts and finally the fee transfers
+//! <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/staged_ledger_diff/pre_diff_info.ml>
+//!
+//! After a block is applied, the root of the ledger is checked against the staged ledger hash
+//! in the blockchain state of the block.
+//!
+//! The snarked ledger hash lags behind the staged one by the transactions that have not been
+//! proven yet. The replayer keeps a [ParallelScan] of the ledger transitions of the applied
+//! transactions, which the proofs of the completed works of each block are checked against.
+//! Once the scan state emits the proof of a tree, the target of the proof becomes the snarked
+//! ledger, which is checked against the snarked ledger hash in the blockchain state of the block.
+//! The proofs themselves are not verified, only the ledger hashes of their statements are
+
+use crate::{ledger::*, scan_state::*, transaction_logic::*};
+use mina_crypto::hash::LedgerHash;
+use mina_rs_base::types::*;
+use mina_serialization_types::v1::HashV1;
+use proof_systems::mina_hasher::Fp;
+use proof_systems::mina_signer::CompressedPubKey;
+use thiserror::Error;
+
+/// Errors that can be produced when replaying a block
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum ReplayError {
+    /// A transaction of the block cannot be applied
+    #[error("Failed to apply transaction {index}: {source}")]
+    Transaction {
+        /// Index of the transaction in its pre-diff
+        index: usize,
+        /// Reason of the failure
+        source: TransactionLogicError,
+    },
+
+    /// The status of a user command does not match the one recorded in the block
+    #[error(
+        "Status of user command {index} does not match, expected: {expected:?}, actual: {actual:?}"
+    )]
+    UserCommandStatusMismatch {
+        /// Index of the user command in its pre-diff
+        index: usize,
+        /// Status recorded in the block
+        expected: TransactionStatus,
+        /// Status computed by applying the user command
+        actual: TransactionStatus,
+    },
+
+    /// The balances after an internal command do not match the ones recorded in the block
+    #[error("Balances of internal command {index} do not match, expected: {expected:?}, actual: {actual:?}")]
+    InternalCommandBalanceMismatch {
+        /// Index of the internal command in its pre-diff
+        index: usize,
+        /// Balances recorded in the block
+        expected: Option<InternalCommandBalanceData>,
+        /// Balances computed by applying the internal command
+        actual: InternalCommandBalanceData,
+    },
+
+    /// The coinbase amount overflows or cannot be split into the parts described by the diff
+    #[error("Invalid coinbase amount or coinbase fee transfer")]
+    InvalidCoinbase,
+
+    /// The fee transfers of a pre-diff cannot be derived from its fees
+    #[error("Invalid fee transfers: {0}")]
+    FeeTransfers(#[from] SnarkWorkError),
+
+    /// The ledger has no root hash
+    #[error("Ledger is empty")]
+    EmptyLedger,
+
+    /// The root of the ledger does not match the staged ledger hash of the block
+    #[error("Staged ledger hash mismatch, expected: {expected:?}, actual: {actual:?}")]
+    StagedLedgerHashMismatch {
+        /// Staged ledger hash of the block
+        expected: LedgerHash,
+        /// Root hash of the ledger after the block is applied
+        actual: LedgerHash,
+    },
+
+    /// The statement of a completed proof does not match the job it completes
+    #[error("Ledger hashes of completed proof {index} do not match, expected: {expected:?}, actual: {actual:?}")]
+    ProofMismatch {
+        /// Index of the proof in the completed works of the block
+        index: usize,
+        /// Ledger transition of the job
+        expected: LedgerTransition,
+        /// Ledger transition of the statement of the proof
+        actual: LedgerTransition,
+    },
+
+    /// The snarked ledger does not match the snarked ledger hash of the block
+    #[error("Snarked ledger hash mismatch, expected: {expected:?}, actual: {actual:?}")]
+    SnarkedLedgerHashMismatch {
+        /// Snarked ledger hash of the block
+        expected: LedgerHash,
+        /// Target of the latest proof emitted by the scan state
+        actual: LedgerHash,
+    },
+
+    /// The scan state cannot be updated with the block
+    #[error("Scan state error: {0}")]
+    ScanState(#[from] ScanStateError),
+}
+
+/// Ledger hashes before and after a transaction, or a sequence of them
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LedgerTransition {
+    /// Hash of the ledger before the transactions
+    pub source: LedgerHash,
+    /// Hash of the ledger after the transactions
+    pub target: LedgerHash,
+}
+
+impl From<&Statement> for LedgerTransition {
+    fn from(statement: &Statement) -> Self {
+        // The ledger hashes of statements are typed as state hashes
+        Self {
+            source: HashV1::from(statement.source.clone()).into(),
+            target: HashV1::from(statement.target.clone()).into(),
+        }
+    }
+}
+
+/// Replays blocks on top of a [Ledger] of either legacy or berkeley accounts,
+/// the accounts have to match the [ConstraintConstants] of the network
+pub struct BlockReplayer<L> {
+    ledger: L,
+    constants: ConstraintConstants,
+    scan_state: ParallelScan<LedgerTransition, LedgerTransition>,
+    snarked_ledger_hash: LedgerHash,
+}
+
+impl<L> BlockReplayer<L>
+where
+    L: Ledger<Hash = Fp>,
+    L::Account: TransactionAccount,
+{
+    /// Creates a replayer that starts from the given ledger, which is also
+    /// the snarked ledger since no transactions are waiting to be proven
+    pub fn new(mut ledger: L, constants: ConstraintConstants) -> Result<Self, ReplayError> {
+        let snarked_ledger_hash = ledger
+            .merkle_root()
+            .map(|root| (&root).into())
+            .ok_or(ReplayError::EmptyLedger)?;
+        let scan_state = ParallelScan::empty(
+            1 << constants.transaction_capacity_log_2,
+            constants.work_delay.into(),
+        )?;
+        Ok(Self {
+            ledger,
+            constants,
+            scan_state,
+            snarked_ledger_hash,
+        })
+    }
+
+    /// Gets the current ledger
+    pub fn ledger(&self) -> &L {
+        &self.ledger
+    }
+
+    /// Consumes the replayer and returns the current ledger
+    pub fn into_ledger(self) -> L {
+        self.ledger
+    }
+
+    /// Hash of the ledger proven by the latest proof emitted by the scan state
+    pub fn snarked_ledger_hash(&self) -> &LedgerHash {
+        &self.snarked_ledger_hash
+    }
+
+    /// Hash of the current ledger
+    pub fn ledger_hash(&mut self) -> Result<LedgerHash, ReplayError> {
+        self.ledger
+            .merkle_root()
+            .map(|root| (&root).into())
+            .ok_or(ReplayError::EmptyLedger)
+    }
+
+    /// Applies the staged ledger diff of the block to the ledger and checks the resulting
+    /// ledger hash against the staged ledger hash of the block, then fills the scan state
+    /// with the completed works of the block and checks the snarked ledger hash of the block,
+    /// returns the new ledger hash.
+    /// The replayer is left in an intermediate state when an error is returned
+    pub fn apply_block(&mut self, block: &ExternalTransition) -> Result<LedgerHash, ReplayError> {
+        let consensus_state = &block.protocol_state.body.consensus_state;
+        let coinbase_amount = self
+            .constants
+            .coinbase_amount(consensus_state.supercharge_coinbase)
+            .ok_or(ReplayError::InvalidCoinbase)?;
+        let diff = &block.staged_ledger_diff.diff;
+        let pre_diffs: Vec<_> = std::iter::once(diff.diff_two())
+            .chain(diff.diff_one())
+            .collect();
+        let mut transitions = Vec::new();
+        for pre_diff in pre_diffs.iter() {
+            self.apply_pre_diff(
+                pre_diff,
+                consensus_state.global_slot_since_genesis,
+                &consensus_state.coinbase_receiver,
+                coinbase_amount,
+                &mut transitions,
+            )?;
+        }
+
+        let blockchain_state = &block.protocol_state.body.blockchain_state;
+        let actual = self.ledger_hash()?;
+        let expected = &blockchain_state.staged_ledger_hash.non_snark.ledger_hash;
+        if &actual != expected {
+            return Err(ReplayError::StagedLedgerHashMismatch {
+                expected: expected.clone(),
+                actual,
+            });
+        }
+
+        let proofs: Vec<LedgerTransition> = pre_diffs
+            .iter()
+            .flat_map(|pre_diff| pre_diff.completed_works.iter())
+            .flat_map(|work| work.statements())
+            .map(Into::into)
+            .collect();
+        self.update_scan_state(transitions, proofs)?;
+        if blockchain_state.snarked_ledger_hash != self.snarked_ledger_hash {
+            return Err(ReplayError::SnarkedLedgerHashMismatch {
+                expected: blockchain_state.snarked_ledger_hash.clone(),
+                actual: self.snarked_ledger_hash.clone(),
+            });
+        }
+        Ok(actual)
+    }
+
+    /// Completes the jobs of the scan state with the proofs, whose ledger hashes have to match
+    /// the ones of the jobs, then enqueues the transitions of the applied transactions
+    fn update_scan_state(
+        &mut self,
+        transitions: Vec<LedgerTransition>,
+        proofs: Vec<LedgerTransition>,
+    ) -> Result<(), ReplayError> {
+        let emitted =
+            self.scan_state
+                .update_with_check(transitions, proofs, |index, job, proof| {
+                    let expected = match job {
+                        AvailableJob::Base(transition) => transition.clone(),
+                        AvailableJob::Merge(left, right) => LedgerTransition {
+                            source: left.source.clone(),
+                            target: right.target.clone(),
+                        },
+                    };
+                    if &expected != proof {
+                        return Err(ReplayError::ProofMismatch {
+                            index,
+                            expected,
+                            actual: proof.clone(),
+                        });
+                    }
+                    Ok(())
+                })?;
+        if let Some((proof, _)) = emitted {
+            self.snarked_ledger_hash = proof.target;
+        }
+        Ok(())
+    }
+
+    fn apply_pre_diff(
+        &mut self,
+        pre_diff: &StagedLedgerPreDiff,
+        txn_global_slot: GlobalSlotNumber,
+        coinbase_receiver: &CompressedPubKey,
+        coinbase_amount: Amount,
+        transitions: &mut Vec<LedgerTransition>,
+    ) -> Result<(), ReplayError> {
+        let mut source = self.ledger_hash()?;
+        let mut record_transition = |replayer: &mut Self| -> Result<(), ReplayError> {
+            let target = replayer.ledger_hash()?;
+            transitions.push(LedgerTransition {
+                source: std::mem::replace(&mut source, target.clone()),
+                target,
+            });
+            Ok(())
+        };
+
+        for (index, command) in pre_diff.commands.iter().enumerate() {
+            apply_user_command(&mut self.ledger, &self.constants, txn_global_slot, command)
+                .map_err(|source| match source {
+                    TransactionLogicError::StatusMismatch { recorded, computed } => {
+                        ReplayError::UserCommandStatusMismatch {
+                            index,
+                            expected: recorded,
+                            actual: computed,
+                        }
+                    }
+                    source => ReplayError::Transaction { index, source },
+                })?;
+            record_transition(self)?;
+        }
+
+        let coinbases =
+            self.coinbase_parts(&pre_diff.coinbase, coinbase_receiver, coinbase_amount)?;
+        let fee_transfers: Vec<(SingleFeeTransfer, Option<SingleFeeTransfer>)> = pre_diff
+            .fee_transfers(coinbase_receiver)?
+            .iter()
+            .map(|(first, second)| (first.into(), second.as_ref().map(Into::into)))
+            .collect();
+
+        let mut expected_balances = pre_diff.internal_command_balances.iter();
+        let mut index = pre_diff.commands.len();
+        let mut check_balances = |actual: InternalCommandBalanceData| {
+            let expected = expected_balances.next().cloned();
+            let result = if expected.as_ref() == Some(&actual) {
+                Ok(())
+            } else {
+                Err(ReplayError::InternalCommandBalanceMismatch {
+                    index,
+                    expected,
+                    actual,
+                })
+            };
+            index += 1;
+            result
+        };
+        for coinbase in coinbases.iter() {
+            let balances =
+                apply_coinbase(&mut self.ledger, &self.constants, txn_global_slot, coinbase)
+                    .map_err(|source| ReplayError::Transaction {
+                        index: pre_diff.commands.len(),
+                        source,
+                    })?;
+            record_transition(self)?;
+            check_balances(InternalCommandBalanceData::CoinBase(balances))?;
+        }
+        for (first, second) in fee_transfers.iter() {
+            let balances = apply_fee_transfer(
+                &mut self.ledger,
+                &self.constants,
+                txn_global_slot,
+                first,
+                second.as_ref(),
+            )
+            .map_err(|source| ReplayError::Transaction {
+                index: pre_diff.commands.len() + coinbases.len(),
+                source,
+            })?;
+            record_transition(self)?;
+            check_balances(InternalCommandBalanceData::FeeTransfer(balances))?;
+        }
+        Ok(())
+    }
+
+    /// Splits the coinbase of a pre-diff into the coinbase transactions to apply
+    fn coinbase_parts(
+        &self,
+        coinbase: &CoinBase,
+        receiver: &CompressedPubKey,
+        amount: Amount,
+    ) -> Result<Vec<Coinbase>, ReplayError> {
+        let part = |amount: Amount, fee_transfer: &Option<CoinBaseFeeTransfer>| Coinbase {
+            receiver: receiver.clone(),
+            amount,
+            fee_transfer: fee_transfer.as_ref().map(Into::into),
+        };
+        Ok(match coinbase {
+            CoinBase::Zero => vec![],
+            CoinBase::One(fee_transfer) => vec![part(amount, fee_transfer)],
+            CoinBase::Two(first, second) => {
+                let first_amount = first
+                    .as_ref()
+                    .map(|ft| ft.fee)
+                    .unwrap_or(self.constants.account_creation_fee);
+                let rest = amount
+                    .0
+                    .checked_sub(first_amount.0)
+                    .ok_or(ReplayError::InvalidCoinbase)?;
+                vec![part(first_amount, first), part(Amount(rest), second)]
+            }
+        })
+    }
+}
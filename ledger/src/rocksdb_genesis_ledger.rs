@@ -14,7 +14,7 @@ use thiserror::Error;
 
 /// The first byte of keys in the RocksDB stored Ledger
 /// that indicates the value is an Account (leaf node)
-const ACCOUNT_PREFIX: u8 = 0xfe;
+pub(crate) const ACCOUNT_PREFIX: u8 = 0xfe;
 
 type RocksDBResult = Result<(Box<[u8]>, Box<[u8]>), rocksdb::Error>;
 
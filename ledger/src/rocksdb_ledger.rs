@@ -0,0 +1,327 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//! A persistent mutable ledger backed by a Rocksdb instance
+//!
+//! The database uses the same layout as the OCaml merkle ledger database,
+//! keys are built from mina merkle_ledger locations
+//! <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/merkle_ledger/location.ml>
+//!
+//! - Accounts are stored under `0xfe` followed by the path of their leaves
+//! - Hashes of the tree nodes are stored under the node height followed by the path of the nodes,
+//! including leaf nodes (of height 0) and the root node (of the ledger depth)
+//! - Generic records are stored under `0xff`, among which are the location of every account id
+//! and the location of the last allocated account
+//!
+//! A path is the node index among the nodes of the same height written in bits from the root,
+//! padded with zeros to the ledger depth and then to whole bytes. Paths of leaves are in
+//! ascending order of their locations, so the accounts can be iterated in order.
+//!
+//! Hashes of the nodes on the path from an updated leaf to the root are recalculated and written
+//! along with the account, so the ledger hash is available without loading the whole tree.
+//! Token owner records of the OCaml database are not maintained.
+
+use crate::{genesis_ledger::*, ledger::*, rocksdb_genesis_ledger::ACCOUNT_PREFIX};
+use mina_merkle::*;
+use mina_rs_base::*;
+use proof_systems::{mina_hasher::Fp, o1_utils::FieldHelpers};
+use rocksdb::{Options, WriteBatch, DB};
+use std::{marker::PhantomData, path::Path};
+use thiserror::Error;
+
+/// The first byte of keys in the RocksDB stored Ledger
+/// that indicates the value is a generic record
+const GENERIC_PREFIX: u8 = 0xff;
+
+/// Key of the generic record that stores the location of the last allocated account
+const LAST_ACCOUNT_LOCATION_KEY: &[u8] = b"last_account_location";
+
+/// Errors that can be produced when accessing a Rocksdb backed ledger
+#[derive(Error, Debug)]
+pub enum RocksDbLedgerError {
+    /// Error from the database
+    #[error("RocksDBError: {0}")]
+    RocksDB(#[from] rocksdb::Error),
+
+    /// Error in serializing or deserializing an account
+    #[error("Could not serialize or deserialize account: {0}")]
+    BinProt(#[from] bin_prot::error::Error),
+
+    /// The stored location is malformed
+    #[error("Invalid location: {0:?}")]
+    InvalidLocation(Vec<u8>),
+
+    /// The stored hash is malformed
+    #[error("Invalid hash: {0:?}")]
+    InvalidHash(Vec<u8>),
+}
+
+impl From<RocksDbLedgerError> for LedgerError {
+    fn from(e: RocksDbLedgerError) -> Self {
+        Self::Storage(e.to_string())
+    }
+}
+
+/// A persistent mutable ledger backed by a RocksDB instance
+pub struct RocksDbLedger<Account>
+where
+    Account: LedgerAccount + MerkleLedgerAccount + for<'a> BinProtSerializationType<'a>,
+{
+    db: DB,
+    depth: u32,
+    num_accounts: usize,
+    _pd: PhantomData<Account>,
+}
+
+impl<Account> RocksDbLedger<Account>
+where
+    Account: LedgerAccount + MerkleLedgerAccount + for<'a> BinProtSerializationType<'a>,
+{
+    /// Opens the ledger with the given depth at the given path,
+    /// the database is created when it does not exist
+    pub fn open(path: impl AsRef<Path>, depth: u32) -> Result<Self, RocksDbLedgerError> {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        let db = DB::open(&options, path)?;
+        let num_accounts = match db.get(generic_key(LAST_ACCOUNT_LOCATION_KEY))? {
+            Some(value) => parse_location(depth, &value)?.0 + 1,
+            None => 0,
+        };
+        Ok(Self {
+            db,
+            depth,
+            num_accounts,
+            _pd: Default::default(),
+        })
+    }
+
+    /// Gets the merkle proof of the account at the given location
+    pub fn merkle_proof(
+        &self,
+        location: Location,
+    ) -> Result<
+        Option<DefaultMerkleProof<Account, Fp, Account::Hasher, Account::Merger>>,
+        RocksDbLedgerError,
+    > {
+        let account = match self.read_account(location)? {
+            Some(account) => account,
+            None => return Ok(None),
+        };
+        let mut peer_indices = Vec::with_capacity(self.depth as usize);
+        let mut peer_hashes = Vec::with_capacity(self.depth as usize);
+        let mut index = location.0;
+        for height in 0..self.depth {
+            peer_indices.push(node_index(self.depth, height, index ^ 1));
+            peer_hashes.push(self.read_hash(height, index ^ 1)?);
+            index /= 2;
+        }
+        Ok(Some(DefaultMerkleProof::new(
+            node_index(self.depth, 0, location.0),
+            account,
+            peer_indices,
+            peer_hashes,
+        )))
+    }
+
+    fn read_account(&self, location: Location) -> Result<Option<Account>, RocksDbLedgerError> {
+        if location.0 >= self.num_accounts {
+            return Ok(None);
+        }
+        match self.db.get(account_key(self.depth, location))? {
+            Some(value) => Ok(Some(Account::try_from_binprot(value.as_slice())?)),
+            None => Ok(None),
+        }
+    }
+
+    fn read_location(
+        &self,
+        account_id: &AccountId,
+    ) -> Result<Option<Location>, RocksDbLedgerError> {
+        match self.db.get(account_id_key(account_id))? {
+            Some(value) => Ok(Some(parse_location(self.depth, &value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads the hash of a node, [None] indicates an empty sub-tree
+    fn read_hash(&self, height: u32, index: usize) -> Result<Option<Fp>, RocksDbLedgerError> {
+        match self.db.get(hash_key(self.depth, height, index))? {
+            Some(value) => Ok(Some(
+                Fp::from_bytes(&value).map_err(|_| RocksDbLedgerError::InvalidHash(value))?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Writes the account and the hashes of the nodes on its path
+    /// along with the records that are already in the batch
+    fn write_account(
+        &self,
+        mut batch: WriteBatch,
+        location: Location,
+        account: Account,
+    ) -> Result<(), RocksDbLedgerError> {
+        let mut hash = Some(Account::Hasher::hash(
+            &account,
+            MerkleTreeNodeMetadata::new(0, 1),
+        ));
+        batch.put(
+            account_key(self.depth, location),
+            account.try_into_binprot()?,
+        );
+        let mut index = location.0;
+        for height in 0..self.depth {
+            if let Some(hash) = &hash {
+                batch.put(hash_key(self.depth, height, index), hash.to_bytes());
+            }
+            let sibling = self.read_hash(height, index ^ 1)?;
+            let hashes = if index % 2 == 0 {
+                [hash, sibling]
+            } else {
+                [sibling, hash]
+            };
+            index /= 2;
+            hash = Account::Merger::merge(hashes, MerkleTreeNodeMetadata::new(0, height + 1));
+        }
+        if let Some(hash) = &hash {
+            batch.put(hash_key(self.depth, self.depth, 0), hash.to_bytes());
+        }
+        self.db.write(batch)?;
+        Ok(())
+    }
+}
+
+impl<Account> Ledger for RocksDbLedger<Account>
+where
+    Account: LedgerAccount + MerkleLedgerAccount + for<'a> BinProtSerializationType<'a>,
+{
+    type Account = Account;
+    type Hash = Fp;
+
+    fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    fn num_accounts(&self) -> usize {
+        self.num_accounts
+    }
+
+    fn location_of_account(&self, account_id: &AccountId) -> Result<Option<Location>, LedgerError> {
+        Ok(self.read_location(account_id)?)
+    }
+
+    fn get(&self, location: Location) -> Result<Option<Self::Account>, LedgerError> {
+        Ok(self.read_account(location)?)
+    }
+
+    fn set(&mut self, location: Location, account: Self::Account) -> Result<(), LedgerError> {
+        let existing = self
+            .read_account(location)?
+            .ok_or(LedgerError::InvalidLocation(location))?;
+        if existing.account_id() != account.account_id() {
+            return Err(LedgerError::AccountIdMismatch(location));
+        }
+        self.write_account(WriteBatch::default(), location, account)?;
+        Ok(())
+    }
+
+    fn get_or_create_account(
+        &mut self,
+        account_id: &AccountId,
+        account: Self::Account,
+    ) -> Result<(GetOrCreated, Location), LedgerError> {
+        if let Some(location) = self.read_location(account_id)? {
+            return Ok((GetOrCreated::Existed, location));
+        }
+        if &account.account_id() != account_id {
+            return Err(LedgerError::AccountIdMismatch(Location(self.num_accounts)));
+        }
+        if self.num_accounts >= self.capacity() {
+            return Err(LedgerError::OutOfLeaves(self.capacity()));
+        }
+        let location = Location(self.num_accounts);
+        let location_value = account_key(self.depth, location);
+        let mut batch = WriteBatch::default();
+        batch.put(account_id_key(account_id), &location_value);
+        batch.put(generic_key(LAST_ACCOUNT_LOCATION_KEY), &location_value);
+        self.write_account(batch, location, account)?;
+        self.num_accounts += 1;
+        Ok((GetOrCreated::Added, location))
+    }
+
+    /// Panics when the root hash in the database is unreadable,
+    /// which means the database is corrupted
+    fn merkle_root(&mut self) -> Option<Self::Hash> {
+        let root = self
+            .read_hash(self.depth, 0)
+            .unwrap_or_else(|e| panic!("Failed to read the ledger root hash: {e}"));
+        match root {
+            Some(hash) => Some(hash),
+            None => {
+                // Root hash of an empty ledger
+                let mut hash = None;
+                for height in 1..=self.depth {
+                    hash = Account::Merger::merge(
+                        [hash, hash],
+                        MerkleTreeNodeMetadata::new(0, height),
+                    );
+                }
+                hash
+            }
+        }
+    }
+}
+
+/// Index of the node counted from the root, as used in [DefaultMerkleProof]
+fn node_index(depth: u32, height: u32, index: usize) -> usize {
+    2_usize.pow(depth - height) - 1 + index
+}
+
+/// Path of the node with the given height and index among the nodes of the same height
+fn path(depth: u32, height: u32, index: usize) -> Vec<u8> {
+    let num_bytes = (depth as usize + 7) / 8;
+    let padding = num_bytes * 8 - depth as usize + height as usize;
+    let bits = (index as u128) << padding;
+    bits.to_be_bytes()[16 - num_bytes..].to_vec()
+}
+
+fn account_key(depth: u32, location: Location) -> Vec<u8> {
+    let mut key = vec![ACCOUNT_PREFIX];
+    key.extend(path(depth, 0, location.0));
+    key
+}
+
+fn hash_key(depth: u32, height: u32, index: usize) -> Vec<u8> {
+    let mut key = vec![height as u8];
+    key.extend(path(depth, height, index));
+    key
+}
+
+fn generic_key(data: &[u8]) -> Vec<u8> {
+    let mut key = vec![GENERIC_PREFIX];
+    key.extend(data);
+    key
+}
+
+fn account_id_key(account_id: &AccountId) -> Vec<u8> {
+    generic_key(
+        format!(
+            "${}!0x{:064x}",
+            account_id.public_key.into_address(),
+            account_id.token_id.0
+        )
+        .as_bytes(),
+    )
+}
+
+/// Parses a location that is stored as the key of an account
+fn parse_location(depth: u32, value: &[u8]) -> Result<Location, RocksDbLedgerError> {
+    let num_bytes = (depth as usize + 7) / 8;
+    if value.len() != num_bytes + 1 || value[0] != ACCOUNT_PREFIX {
+        return Err(RocksDbLedgerError::InvalidLocation(value.to_vec()));
+    }
+    let mut bytes = [0; 16];
+    bytes[16 - num_bytes..].copy_from_slice(&value[1..]);
+    let padding = num_bytes * 8 - depth as usize;
+    Ok(Location((u128::from_be_bytes(bytes) >> padding) as usize))
+}
@@ -0,0 +1,925 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//! The scan state of a staged ledger is a queue of full binary trees, whose leaves
+//! are transactions to be proven (base jobs) and whose inner nodes merge the proofs
+//! of their children (merge jobs). Once the root of the oldest tree is proven,
+//! the tree is removed and its proof is emitted as the new snarked ledger
+//!
+//! This module is modeled after `Parallel_scan` in the OCaml implementation
+//! <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/parallel_scan/parallel_scan.ml>
+//! and the transaction scan state in
+//! <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/transaction_snark_scan_state/transaction_snark_scan_state.ml>
+//!
+//! A new tree is started as soon as the newest one is full. The older trees get work every
+//! `delay + 1` trees, one level at a time from the leaves to the root, so a tree is emitted after
+//! `(depth + 1) * (delay + 1)` newer trees have been started. Jobs are ordered from the oldest
+//! tree to the newest one, and from left to right within a level
+
+use mina_rs_base::snark_work::{SnarkWorkError, Statement, TransactionSnark, TransactionSnarkWork};
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    ser::SerializeTuple,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use sha2::{Digest, Sha256};
+use std::{fmt, marker::PhantomData};
+use thiserror::Error;
+
+/// Trees deeper than this cannot be allocated
+const MAX_TREE_DEPTH: u32 = 32;
+
+/// Errors that can be produced when updating a scan state
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum ScanStateError {
+    /// The number of base jobs of a tree must be a power of 2
+    #[error("Invalid max base jobs {0}, it must be a power of 2")]
+    InvalidMaxBaseJobs(u64),
+
+    /// More jobs are completed than the ones available
+    #[error("Too many completed jobs, available: {available}, completed: {completed}")]
+    TooManyCompletedJobs {
+        /// Number of jobs available for the update
+        available: usize,
+        /// Number of completed jobs
+        completed: usize,
+    },
+
+    /// More data is enqueued than the number of base jobs of a tree
+    #[error("Too much data enqueued, max base jobs: {max_base_jobs}, data: {data}")]
+    TooMuchData {
+        /// Number of base jobs of a tree
+        max_base_jobs: u64,
+        /// Number of data enqueued
+        data: usize,
+    },
+
+    /// A tree other than the oldest one is fully proven
+    #[error("A tree other than the oldest one is fully proven")]
+    OutOfOrderProof,
+
+    /// A job is not in the state expected by the scan state
+    #[error("Invalid job state: {0}")]
+    InvalidJobState(&'static str),
+
+    /// The statement of a completed proof does not match the job it completes
+    #[error("Statement of completed job {0} does not match the job")]
+    StatementMismatch(usize),
+
+    /// The statements of a completed work are malformed
+    #[error("Invalid snark work: {0}")]
+    SnarkWork(#[from] SnarkWorkError),
+
+    /// The scan state cannot be serialized or deserialized
+    #[error("Bin prot error: {0}")]
+    BinProt(String),
+}
+
+/// Weight of a subtree, which counts the base jobs that can still be enqueued
+/// and the jobs that are waiting to be done in the subtree
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Weight {
+    /// Number of empty base jobs
+    pub base: u64,
+    /// Number of jobs that are waiting to be done
+    pub merge: u64,
+}
+
+impl std::ops::Add for Weight {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            base: self.base + rhs.base,
+            merge: self.merge + rhs.merge,
+        }
+    }
+}
+
+/// Status of a job
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum JobStatus {
+    /// The job is waiting to be done
+    Todo,
+    /// The job is done
+    Done,
+}
+
+/// Merge job, which merges the proofs of its children
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum MergeJob<M> {
+    /// None of the children are proven yet
+    Empty,
+    /// Only the left child is proven
+    Part(M),
+    /// Both children are proven
+    Full {
+        /// Proof of the left child
+        left: M,
+        /// Proof of the right child
+        right: M,
+        /// Sequence number of the update that created the job
+        seq_no: u64,
+        /// Status of the job
+        status: JobStatus,
+    },
+}
+
+/// Base job, which proves a transaction
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum BaseJob<B> {
+    /// No data has been enqueued yet
+    Empty,
+    /// Data has been enqueued
+    Full {
+        /// The data to be proven
+        job: B,
+        /// Sequence number of the update that enqueued the data
+        seq_no: u64,
+        /// Status of the job
+        status: JobStatus,
+    },
+}
+
+/// A job that is available to be done
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AvailableJob<'a, M, B> {
+    /// Prove the data of a base job
+    Base(&'a B),
+    /// Merge two proofs
+    Merge(&'a M, &'a M),
+}
+
+/// A full binary tree of jobs, stored in level order with the merge jobs first,
+/// so the children of the node `i` are the nodes `2i + 1` and `2i + 2`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Tree<M, B> {
+    merges: Vec<MergeJob<M>>,
+    bases: Vec<BaseJob<B>>,
+}
+
+impl<M, B> Tree<M, B> {
+    fn new(depth: u32) -> Self {
+        Self {
+            merges: (0..(1usize << depth) - 1)
+                .map(|_| MergeJob::Empty)
+                .collect(),
+            bases: (0..1usize << depth).map(|_| BaseJob::Empty).collect(),
+        }
+    }
+
+    /// Merge jobs of the tree in level order
+    pub fn merge_jobs(&self) -> &[MergeJob<M>] {
+        &self.merges
+    }
+
+    /// Base jobs of the tree from left to right
+    pub fn base_jobs(&self) -> &[BaseJob<B>] {
+        &self.bases
+    }
+
+    fn depth(&self) -> u32 {
+        self.bases.len().trailing_zeros()
+    }
+
+    fn weight(&self, node: usize) -> Weight {
+        match node.checked_sub(self.merges.len()) {
+            Some(base) => match &self.bases[base] {
+                BaseJob::Empty => Weight { base: 1, merge: 0 },
+                BaseJob::Full {
+                    status: JobStatus::Todo,
+                    ..
+                } => Weight { base: 0, merge: 1 },
+                BaseJob::Full { .. } => Weight::default(),
+            },
+            None => {
+                let (left, right) = self.children_weights(node);
+                let merge = match &self.merges[node] {
+                    MergeJob::Full {
+                        status: JobStatus::Todo,
+                        ..
+                    } => 1,
+                    _ => 0,
+                };
+                left + right + Weight { base: 0, merge }
+            }
+        }
+    }
+
+    fn children_weights(&self, node: usize) -> (Weight, Weight) {
+        (self.weight(2 * node + 1), self.weight(2 * node + 2))
+    }
+
+    fn base_weight(&self, base: usize) -> Weight {
+        self.weight(self.merges.len() + base)
+    }
+
+    /// Nodes of the jobs on `level` that are waiting to be done, from left to right,
+    /// the root is on level 0 and the base jobs are on level `depth`
+    fn todo_nodes(&self, level: u32) -> impl Iterator<Item = usize> + '_ {
+        let first = (1usize << level) - 1;
+        (first..first + (1usize << level)).filter(move |node| {
+            match node.checked_sub(self.merges.len()) {
+                Some(base) => matches!(
+                    self.bases[base],
+                    BaseJob::Full {
+                        status: JobStatus::Todo,
+                        ..
+                    }
+                ),
+                None => matches!(
+                    self.merges[*node],
+                    MergeJob::Full {
+                        status: JobStatus::Todo,
+                        ..
+                    }
+                ),
+            }
+        })
+    }
+
+    fn available_job(&self, node: usize) -> Option<AvailableJob<M, B>> {
+        match node.checked_sub(self.merges.len()) {
+            Some(base) => match &self.bases[base] {
+                BaseJob::Full { job, .. } => Some(AvailableJob::Base(job)),
+                BaseJob::Empty => None,
+            },
+            None => match &self.merges[node] {
+                MergeJob::Full { left, right, .. } => Some(AvailableJob::Merge(left, right)),
+                _ => None,
+            },
+        }
+    }
+
+    /// Marks the job of the node as done and moves its proof to the parent,
+    /// returns the proof if the node is the root
+    fn complete(
+        &mut self,
+        node: usize,
+        proof: M,
+        seq_no: u64,
+    ) -> Result<Option<M>, ScanStateError> {
+        let status = match node.checked_sub(self.merges.len()) {
+            Some(base) => match &mut self.bases[base] {
+                BaseJob::Full { status, .. } => status,
+                BaseJob::Empty => return Err(ScanStateError::InvalidJobState("empty base job")),
+            },
+            None => match &mut self.merges[node] {
+                MergeJob::Full { status, .. } => status,
+                _ => return Err(ScanStateError::InvalidJobState("incomplete merge job")),
+            },
+        };
+        if *status == JobStatus::Done {
+            return Err(ScanStateError::InvalidJobState("job already done"));
+        }
+        *status = JobStatus::Done;
+
+        if node == 0 {
+            return Ok(Some(proof));
+        }
+        let parent = &mut self.merges[(node - 1) / 2];
+        *parent = match (std::mem::replace(parent, MergeJob::Empty), node % 2 == 1) {
+            (MergeJob::Empty, true) => MergeJob::Part(proof),
+            (MergeJob::Part(left), false) => MergeJob::Full {
+                left,
+                right: proof,
+                seq_no,
+                status: JobStatus::Todo,
+            },
+            _ => {
+                return Err(ScanStateError::InvalidJobState(
+                    "children completed out of order",
+                ))
+            }
+        };
+        Ok(None)
+    }
+
+    /// Number of empty base jobs
+    fn free_space(&self) -> usize {
+        self.bases
+            .iter()
+            .filter(|job| matches!(job, BaseJob::Empty))
+            .count()
+    }
+
+    /// Enqueues the data in the leftmost empty base job, returns the data back if the tree is full
+    fn enqueue(&mut self, data: B, seq_no: u64) -> Option<B> {
+        match self
+            .bases
+            .iter_mut()
+            .find(|job| matches!(job, BaseJob::Empty))
+        {
+            Some(job) => {
+                *job = BaseJob::Full {
+                    job: data,
+                    seq_no,
+                    status: JobStatus::Todo,
+                };
+                None
+            }
+            None => Some(data),
+        }
+    }
+
+    fn base_data(self) -> Vec<B> {
+        self.bases
+            .into_iter()
+            .filter_map(|job| match job {
+                BaseJob::Full { job, .. } => Some(job),
+                BaseJob::Empty => None,
+            })
+            .collect()
+    }
+
+    /// Done merge jobs are no longer needed, which is the `with_leaner_trees` in OCaml
+    fn leaner(&self) -> Self
+    where
+        M: Clone,
+        B: Clone,
+    {
+        let merges = self
+            .merges
+            .iter()
+            .map(|job| match job {
+                MergeJob::Full {
+                    status: JobStatus::Done,
+                    ..
+                } => MergeJob::Empty,
+                job => job.clone(),
+            })
+            .collect();
+        Self {
+            merges,
+            bases: self.bases.clone(),
+        }
+    }
+
+    fn map<M2, B2>(&self, fm: &impl Fn(&M) -> M2, fb: &impl Fn(&B) -> B2) -> Tree<M2, B2> {
+        let merges = self
+            .merges
+            .iter()
+            .map(|job| match job {
+                MergeJob::Empty => MergeJob::Empty,
+                MergeJob::Part(left) => MergeJob::Part(fm(left)),
+                MergeJob::Full {
+                    left,
+                    right,
+                    seq_no,
+                    status,
+                } => MergeJob::Full {
+                    left: fm(left),
+                    right: fm(right),
+                    seq_no: *seq_no,
+                    status: *status,
+                },
+            })
+            .collect();
+        let bases = self
+            .bases
+            .iter()
+            .map(|job| match job {
+                BaseJob::Empty => BaseJob::Empty,
+                BaseJob::Full {
+                    job,
+                    seq_no,
+                    status,
+                } => BaseJob::Full {
+                    job: fb(job),
+                    seq_no: *seq_no,
+                    status: *status,
+                },
+            })
+            .collect();
+        Tree { merges, bases }
+    }
+}
+
+// The OCaml tree is the nested type
+// `Leaf of 'base | Node of {depth; value: 'merge; sub_tree: ('merge * 'merge, 'base * 'base) t}`,
+// whose bin_prot layout is a `Node` tag, the depth and the merge jobs of each level,
+// followed by a `Leaf` tag and all the base jobs, where every job is paired with its weight
+const TREE_LEAF_TAG: u8 = 0;
+const TREE_NODE_TAG: u8 = 1;
+
+impl<M, B> Serialize for Tree<M, B>
+where
+    M: Serialize,
+    B: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let depth = self.depth();
+        let mut tuple = serializer.serialize_tuple(self.merges.len() + self.bases.len())?;
+        for level in 0..depth {
+            tuple.serialize_element(&TREE_NODE_TAG)?;
+            tuple.serialize_element(&(level as u64))?;
+            for node in (1usize << level) - 1..(1usize << (level + 1)) - 1 {
+                tuple.serialize_element(&(self.children_weights(node), &self.merges[node]))?;
+            }
+        }
+        tuple.serialize_element(&TREE_LEAF_TAG)?;
+        for (i, job) in self.bases.iter().enumerate() {
+            tuple.serialize_element(&(self.base_weight(i), job))?;
+        }
+        tuple.end()
+    }
+}
+
+impl<'de, M, B> Deserialize<'de> for Tree<M, B>
+where
+    M: Deserialize<'de>,
+    B: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TreeVisitor<M, B>(PhantomData<(M, B)>);
+
+        impl<'de, M, B> Visitor<'de> for TreeVisitor<M, B>
+        where
+            M: Deserialize<'de>,
+            B: Deserialize<'de>,
+        {
+            type Value = Tree<M, B>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a scan state tree")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                fn next<'de, A: SeqAccess<'de>, T: Deserialize<'de>>(
+                    seq: &mut A,
+                ) -> Result<T, A::Error> {
+                    seq.next_element()?
+                        .ok_or_else(|| de::Error::custom("truncated scan state tree"))
+                }
+
+                let mut merges = Vec::new();
+                for level in 0..=MAX_TREE_DEPTH {
+                    match next::<A, u8>(&mut seq)? {
+                        TREE_NODE_TAG => {
+                            let depth: u64 = next(&mut seq)?;
+                            if depth != level as u64 {
+                                return Err(de::Error::custom("unexpected tree depth"));
+                            }
+                            for _ in 0..1usize << level {
+                                let (_, job): ((Weight, Weight), MergeJob<M>) = next(&mut seq)?;
+                                merges.push(job);
+                            }
+                        }
+                        TREE_LEAF_TAG => {
+                            let mut bases = Vec::with_capacity(1usize << level);
+                            for _ in 0..1usize << level {
+                                let (_, job): (Weight, BaseJob<B>) = next(&mut seq)?;
+                                bases.push(job);
+                            }
+                            return Ok(Tree { merges, bases });
+                        }
+                        tag => return Err(de::Error::custom(format!("invalid tree tag {tag}"))),
+                    }
+                }
+                Err(de::Error::custom("scan state tree is too deep"))
+            }
+        }
+
+        deserializer.deserialize_tuple(usize::MAX, TreeVisitor(PhantomData))
+    }
+}
+
+/// Parallel scan state, where `M` is the proof of a merge job
+/// and `B` is the data of a base job
+///
+/// The trees are stored from the newest to the oldest one, which is the order of the OCaml
+/// implementation, and the layout of its bin_prot serialization
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ParallelScan<M, B> {
+    trees: (Tree<M, B>, Vec<Tree<M, B>>),
+    acc: Option<(M, Vec<B>)>,
+    curr_job_seq_no: u64,
+    max_base_jobs: u64,
+    delay: u64,
+}
+
+impl<M, B> ParallelScan<M, B> {
+    /// Creates an empty scan state whose trees have `max_base_jobs` base jobs
+    pub fn empty(max_base_jobs: u64, delay: u64) -> Result<Self, ScanStateError> {
+        if !max_base_jobs.is_power_of_two() || max_base_jobs.trailing_zeros() > MAX_TREE_DEPTH {
+            return Err(ScanStateError::InvalidMaxBaseJobs(max_base_jobs));
+        }
+        Ok(Self {
+            trees: (Tree::new(max_base_jobs.trailing_zeros()), vec![]),
+            acc: None,
+            curr_job_seq_no: 0,
+            max_base_jobs,
+            delay,
+        })
+    }
+
+    /// Number of base jobs of a tree
+    pub fn max_base_jobs(&self) -> u64 {
+        self.max_base_jobs
+    }
+
+    /// Work delay of the scan state
+    pub fn delay(&self) -> u64 {
+        self.delay
+    }
+
+    /// Sequence number of the latest update
+    pub fn curr_job_seq_no(&self) -> u64 {
+        self.curr_job_seq_no
+    }
+
+    /// The latest emitted proof along with the data it proves
+    pub fn last_emitted_value(&self) -> Option<&(M, Vec<B>)> {
+        self.acc.as_ref()
+    }
+
+    /// Trees of the scan state from the newest to the oldest one
+    pub fn trees(&self) -> impl DoubleEndedIterator<Item = &Tree<M, B>> {
+        std::iter::once(&self.trees.0).chain(self.trees.1.iter())
+    }
+
+    /// Positions of the jobs that are available for the next update, following `work_for_tree`
+    /// in the OCaml implementation: the `i`-th tree after the newest one gets the jobs of level
+    /// `depth - i / (delay + 1)` when `i % (delay + 1) == delay`, and no jobs otherwise.
+    /// Positions are ordered from the oldest tree to the newest one
+    fn todo_positions(&self) -> Vec<(usize, usize)> {
+        let delay = self.delay as usize + 1;
+        let depth = self.depth();
+        self.trees
+            .1
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(i, _)| i % delay == delay - 1 && i / delay <= depth as usize)
+            .flat_map(|(i, tree)| {
+                let level = depth - (i / delay) as u32;
+                tree.todo_nodes(level).map(move |node| (i + 1, node))
+            })
+            .collect()
+    }
+
+    fn tree(&self, index: usize) -> &Tree<M, B> {
+        match index {
+            0 => &self.trees.0,
+            i => &self.trees.1[i - 1],
+        }
+    }
+
+    fn tree_mut(&mut self, index: usize) -> &mut Tree<M, B> {
+        match index {
+            0 => &mut self.trees.0,
+            i => &mut self.trees.1[i - 1],
+        }
+    }
+
+    /// Jobs that are available for the next update, in the order they should be completed
+    /// in [Self::update], this doesn't include the jobs made available by starting a new tree
+    pub fn all_jobs(&self) -> Vec<AvailableJob<M, B>> {
+        self.todo_positions()
+            .into_iter()
+            .filter_map(|(tree, node)| self.tree(tree).available_job(node))
+            .collect()
+    }
+
+    /// Number of base jobs that can be enqueued before a new tree is started
+    pub fn free_space(&self) -> u64 {
+        self.trees.0.free_space() as u64
+    }
+
+    /// Completes the first available jobs with the proofs in `completed_jobs`, then enqueues `data`
+    /// as new base jobs, returns the proof of the oldest tree along with its data once it's proven
+    pub fn update(
+        &mut self,
+        data: Vec<B>,
+        completed_jobs: Vec<M>,
+    ) -> Result<Option<(M, Vec<B>)>, ScanStateError>
+    where
+        M: Clone,
+        B: Clone,
+    {
+        self.update_with_check(data, completed_jobs, |_, _, _| Ok(()))
+    }
+
+    /// Same as [Self::update], where `check` is called with the index of each completed job,
+    /// the job and its proof before the job is completed.
+    /// The scan state is left unchanged when an error is returned
+    pub fn update_with_check<E>(
+        &mut self,
+        mut data: Vec<B>,
+        mut completed_jobs: Vec<M>,
+        mut check: impl FnMut(usize, AvailableJob<M, B>, &M) -> Result<(), E>,
+    ) -> Result<Option<(M, Vec<B>)>, E>
+    where
+        M: Clone,
+        B: Clone,
+        E: From<ScanStateError>,
+    {
+        if data.len() as u64 > self.max_base_jobs {
+            return Err(ScanStateError::TooMuchData {
+                max_base_jobs: self.max_base_jobs,
+                data: data.len(),
+            }
+            .into());
+        }
+        let mut next = self.clone();
+        next.curr_job_seq_no += 1;
+
+        // Data that doesn't fit in the newest tree goes to a new tree, which moves the older trees
+        // one position further and makes more jobs available, as `update_helper` does in OCaml
+        let new_tree_data = data.split_off(next.trees.0.free_space().min(data.len()));
+        let available = next.todo_positions().len();
+        let new_tree_jobs = completed_jobs.split_off(available.min(completed_jobs.len()));
+
+        let mut emitted = next.complete_jobs(completed_jobs, 0, &mut check)?;
+        next.enqueue(data);
+        if !new_tree_jobs.is_empty() {
+            if let Some(proof) = next.complete_jobs(new_tree_jobs, available, &mut check)? {
+                if emitted.is_some() {
+                    return Err(ScanStateError::OutOfOrderProof.into());
+                }
+                emitted = Some(proof);
+            }
+        }
+        next.enqueue(new_tree_data);
+
+        if let Some(emitted) = &emitted {
+            next.acc = Some(emitted.clone());
+        }
+        *self = next;
+        Ok(emitted)
+    }
+
+    /// Completes the available jobs with the proofs in `jobs`, where `completed` is the number
+    /// of jobs completed earlier in the update, and removes the oldest tree once its root is proven
+    fn complete_jobs<E>(
+        &mut self,
+        jobs: Vec<M>,
+        completed: usize,
+        check: &mut impl FnMut(usize, AvailableJob<M, B>, &M) -> Result<(), E>,
+    ) -> Result<Option<(M, Vec<B>)>, E>
+    where
+        E: From<ScanStateError>,
+    {
+        let positions = self.todo_positions();
+        if jobs.len() > positions.len() {
+            return Err(ScanStateError::TooManyCompletedJobs {
+                available: completed + positions.len(),
+                completed: completed + jobs.len(),
+            }
+            .into());
+        }
+        let seq_no = self.curr_job_seq_no;
+        let oldest = self.trees.1.len();
+        let mut proof = None;
+        for (index, ((tree, node), job)) in positions.into_iter().zip(jobs).enumerate() {
+            if let Some(available) = self.tree(tree).available_job(node) {
+                check(completed + index, available, &job)?;
+            }
+            if let Some(p) = self.tree_mut(tree).complete(node, job, seq_no)? {
+                if tree != oldest || proof.is_some() {
+                    return Err(ScanStateError::OutOfOrderProof.into());
+                }
+                proof = Some(p);
+            }
+        }
+        match proof {
+            Some(proof) => {
+                let tree = self.trees.1.pop().ok_or(ScanStateError::OutOfOrderProof)?;
+                Ok(Some((proof, tree.base_data())))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Enqueues the data in the newest tree, which must have enough space for it,
+    /// and starts a new tree as soon as the newest one is full
+    fn enqueue(&mut self, data: Vec<B>) {
+        let seq_no = self.curr_job_seq_no;
+        for d in data {
+            self.trees.0.enqueue(d, seq_no);
+            if self.trees.0.free_space() == 0 {
+                let full = std::mem::replace(&mut self.trees.0, Tree::new(self.depth()));
+                self.trees.1.insert(0, full);
+            }
+        }
+    }
+
+    fn depth(&self) -> u32 {
+        self.max_base_jobs.trailing_zeros()
+    }
+
+    /// Converts the jobs of the scan state
+    pub fn map<M2, B2>(
+        &self,
+        fm: impl Fn(&M) -> M2,
+        fb: impl Fn(&B) -> B2,
+    ) -> ParallelScan<M2, B2> {
+        ParallelScan {
+            trees: (
+                self.trees.0.map(&fm, &fb),
+                self.trees.1.iter().map(|tree| tree.map(&fm, &fb)).collect(),
+            ),
+            acc: self
+                .acc
+                .as_ref()
+                .map(|(proof, data)| (fm(proof), data.iter().map(&fb).collect())),
+            curr_job_seq_no: self.curr_job_seq_no,
+            max_base_jobs: self.max_base_jobs,
+            delay: self.delay,
+        }
+    }
+
+    /// SHA256 hash of the scan state, where `f_merge` and `f_base` encode the jobs,
+    /// it follows `State.hash` of the OCaml implementation
+    pub fn hash(&self, f_merge: impl Fn(&M) -> Vec<u8>, f_base: impl Fn(&B) -> Vec<u8>) -> [u8; 32]
+    where
+        M: Clone,
+        B: Clone,
+    {
+        fn weight_string(w: &Weight) -> String {
+            format!("{}{}", w.base, w.merge)
+        }
+
+        let mut hasher = Sha256::new();
+        for tree in self.trees() {
+            let tree = tree.leaner();
+            for (node, job) in tree.merges.iter().enumerate() {
+                let (left, right) = tree.children_weights(node);
+                hasher.update(weight_string(&left) + &weight_string(&right));
+                match job {
+                    MergeJob::Empty => hasher.update("Empty"),
+                    MergeJob::Part(left) => {
+                        hasher.update("Part");
+                        hasher.update(f_merge(left));
+                    }
+                    MergeJob::Full {
+                        left,
+                        right,
+                        seq_no,
+                        status,
+                    } => {
+                        hasher.update(format!("Full{seq_no}{status:?}"));
+                        hasher.update(f_merge(left));
+                        hasher.update(f_merge(right));
+                    }
+                }
+            }
+            for (base, job) in tree.bases.iter().enumerate() {
+                hasher.update(weight_string(&tree.base_weight(base)));
+                match job {
+                    BaseJob::Empty => hasher.update("Empty"),
+                    BaseJob::Full {
+                        job,
+                        seq_no,
+                        status,
+                    } => {
+                        hasher.update(format!("Full{seq_no}{status:?}"));
+                        hasher.update(f_base(job));
+                    }
+                }
+            }
+        }
+        match &self.acc {
+            Some((proof, data)) => {
+                hasher.update(f_merge(proof));
+                for d in data {
+                    hasher.update(f_base(d));
+                }
+            }
+            None => hasher.update("None"),
+        }
+        hasher.update(self.curr_job_seq_no.to_string());
+        hasher.update(self.max_base_jobs.to_string());
+        hasher.update(self.delay.to_string());
+        hasher.finalize().into()
+    }
+}
+
+/// Scan state of a staged ledger, where the base jobs are the statements of the transactions
+/// to be proven and the merge jobs hold the ledger proofs
+///
+/// The transaction witnesses are not modeled yet so only the statements are kept
+pub type TransactionScanState = ParallelScan<TransactionSnark, Statement>;
+
+/// Serialization type of [TransactionScanState], see [TransactionScanState::to_bin_prot]
+pub type TransactionScanStateV1 = ParallelScan<
+    mina_serialization_types::snark_work::TransactionSnark,
+    mina_serialization_types::snark_work::Statement,
+>;
+
+impl TransactionScanState {
+    /// Completes the first available jobs with the proofs of the completed works
+    /// of a staged ledger diff, then enqueues the statements of its transactions
+    pub fn fill_work_and_enqueue_transactions(
+        &mut self,
+        completed_works: &[TransactionSnarkWork],
+        transactions: Vec<Statement>,
+    ) -> Result<Option<(TransactionSnark, Vec<Statement>)>, ScanStateError> {
+        let proofs = completed_proofs(completed_works)?;
+        self.fill_proofs_and_enqueue_transactions(proofs, transactions)
+    }
+
+    /// Completes the first available jobs with the proofs, whose statements have to match
+    /// the ones of the jobs, then enqueues the statements of the transactions
+    pub(crate) fn fill_proofs_and_enqueue_transactions(
+        &mut self,
+        proofs: Vec<TransactionSnark>,
+        transactions: Vec<Statement>,
+    ) -> Result<Option<(TransactionSnark, Vec<Statement>)>, ScanStateError> {
+        self.update_with_check(transactions, proofs, |index, job, proof| {
+            let matches = match job {
+                AvailableJob::Base(statement) => statement_matches(statement, &proof.statement),
+                AvailableJob::Merge(left, right) => {
+                    merged_statement_matches(&left.statement, &right.statement, &proof.statement)?
+                }
+            };
+            if !matches {
+                return Err(ScanStateError::StatementMismatch(index));
+            }
+            Ok(())
+        })
+    }
+
+    /// Serializes the scan state with bin_prot
+    pub fn to_bin_prot(&self) -> Result<Vec<u8>, ScanStateError> {
+        let state = self.map(
+            |proof| mina_serialization_types::snark_work::TransactionSnark::from(proof.clone()),
+            |statement| mina_serialization_types::snark_work::Statement::from(statement.clone()),
+        );
+        let mut output = Vec::new();
+        bin_prot::to_writer(&mut output, &state)
+            .map_err(|e| ScanStateError::BinProt(e.to_string()))?;
+        Ok(output)
+    }
+
+    /// Deserializes a scan state serialized with [Self::to_bin_prot]
+    pub fn from_bin_prot(bytes: &[u8]) -> Result<Self, ScanStateError> {
+        let state: TransactionScanStateV1 = bin_prot::from_reader_strict(bytes)
+            .map_err(|e| ScanStateError::BinProt(e.to_string()))?;
+        Ok(state.map(
+            |proof| proof.clone().into(),
+            |statement| statement.clone().into(),
+        ))
+    }
+}
+
+/// Proofs of the completed works in the order they complete the jobs of the scan state,
+/// the statements of each work are checked to be well formed
+pub(crate) fn completed_proofs(
+    completed_works: &[TransactionSnarkWork],
+) -> Result<Vec<TransactionSnark>, ScanStateError> {
+    Ok(completed_works
+        .iter()
+        .map(|work| {
+            work.check_statements()?;
+            Ok(work.snarks())
+        })
+        .collect::<Result<Vec<_>, SnarkWorkError>>()?
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect())
+}
+
+/// Compares the statements of a transaction and of its proof, the sok digest is
+/// specific to the prover so it's not compared
+fn statement_matches(expected: &Statement, actual: &Statement) -> bool {
+    expected.source == actual.source
+        && expected.target == actual.target
+        && expected.supply_increase == actual.supply_increase
+        && expected.pending_coinbase_stack_state == actual.pending_coinbase_stack_state
+        && expected.fee_excess == actual.fee_excess
+        && expected.next_available_token_before == actual.next_available_token_before
+        && expected.next_available_token_after == actual.next_available_token_after
+}
+
+/// Checks that the statement of a merge proof spans the statements of its children
+fn merged_statement_matches(
+    left: &Statement,
+    right: &Statement,
+    merged: &Statement,
+) -> Result<bool, ScanStateError> {
+    left.check_connected(right)?;
+    let supply_increase = left
+        .supply_increase
+        .0
+        .checked_add(right.supply_increase.0)
+        .ok_or(SnarkWorkError::SupplyIncreaseOverflow)?;
+    let fee_excess = left.fee_excess.combine(&right.fee_excess)?;
+    let merged_fee_excess: Vec<_> = [&merged.fee_excess.0, &merged.fee_excess.1]
+        .into_iter()
+        .filter(|excess| !excess.is_zero())
+        .map(|excess| (excess.token.clone(), excess.value()))
+        .collect();
+    Ok(merged.source == left.source
+        && merged.target == right.target
+        && merged.supply_increase.0 == supply_increase
+        && merged.pending_coinbase_stack_state.source == left.pending_coinbase_stack_state.source
+        && merged.pending_coinbase_stack_state.target == right.pending_coinbase_stack_state.target
+        && merged.next_available_token_before == left.next_available_token_before
+        && merged.next_available_token_after == right.next_available_token_after
+        && merged_fee_excess.len() == fee_excess.len()
+        && merged_fee_excess
+            .iter()
+            .all(|excess| fee_excess.contains(excess)))
+}
@@ -0,0 +1,100 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//! Monetary supply rules of blocks
+//!
+//! The coinbase of a block is supercharged when the block winner has no locked tokens
+//! in the staking ledger at the global slot of the block
+//! <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/staged_ledger/staged_ledger.ml>
+//!
+//! The total currency of the consensus state only increases when the staged ledger diff
+//! of a block emits a ledger proof, by the supply increase in the statement of that proof,
+//! so the coinbases of a block are accounted for a few blocks later
+//! <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/block_producer/block_producer.ml>
+
+use crate::{scan_state::*, transaction_logic::*};
+use mina_rs_base::{account::Account, consensus_state::ConsensusState, types::*};
+use thiserror::Error;
+
+/// Errors that can be produced when computing the total currency of a block
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum SupplyError {
+    /// Total currency overflows
+    #[error("Total currency overflow")]
+    TotalCurrencyOverflow,
+
+    /// The completed works of the staged ledger diff cannot be added to the scan state
+    #[error("{0}")]
+    ScanState(#[from] ScanStateError),
+}
+
+impl ConstraintConstants {
+    /// Coinbase amount of a block, which is scaled by the supercharged coinbase factor
+    /// when the coinbase is supercharged, [None] if the amount overflows
+    pub fn coinbase_amount(&self, supercharge_coinbase: bool) -> Option<Amount> {
+        if supercharge_coinbase {
+            self.coinbase_amount
+                .0
+                .checked_mul(self.supercharged_coinbase_factor)
+                .map(Amount)
+        } else {
+            Some(self.coinbase_amount)
+        }
+    }
+}
+
+/// Whether the minimum balance of the account is not zero at the given global slot
+pub fn has_locked_tokens(account: &Account, global_slot: GlobalSlotNumber) -> bool {
+    account.timing.min_balance_at_slot(global_slot).0 > 0
+}
+
+/// Whether the coinbase of a block is supercharged, given the account of the block winner
+/// in the staking ledger and the global slot since genesis of the block
+pub fn supercharge_coinbase(winner: &Account, global_slot: GlobalSlotNumber) -> bool {
+    !has_locked_tokens(winner, global_slot)
+}
+
+/// Supply increase of a block, given the ledger proof emitted by its staged ledger diff
+pub fn supply_increase(emitted_proof: Option<&TransactionSnark>) -> Amount {
+    emitted_proof
+        .map(|proof| proof.statement.supply_increase)
+        .unwrap_or_default()
+}
+
+/// Total currency of the block on top of the block whose consensus state is `parent`,
+/// given the ledger proof emitted by the staged ledger diff of the new block
+pub fn next_total_currency(
+    parent: &ConsensusState,
+    emitted_proof: Option<&TransactionSnark>,
+) -> Result<Amount, SupplyError> {
+    parent
+        .total_currency
+        .0
+        .checked_add(supply_increase(emitted_proof).0)
+        .map(Amount)
+        .ok_or(SupplyError::TotalCurrencyOverflow)
+}
+
+impl TransactionScanState {
+    /// Predicts the total currency of the block on top of the block whose consensus state is
+    /// `parent` and whose scan state is `self`, from the staged ledger diff of the new block.
+    /// Only the jobs that are available before the transactions of the diff are enqueued can
+    /// emit a ledger proof, so the other completed works are ignored and the scan state is
+    /// left untouched
+    pub fn predict_total_currency(
+        &self,
+        parent: &ConsensusState,
+        diff: &StagedLedgerDiff,
+    ) -> Result<Amount, SupplyError> {
+        let completed_works: Vec<TransactionSnarkWork> = std::iter::once(diff.diff.diff_two())
+            .chain(diff.diff.diff_one())
+            .flat_map(|pre_diff| pre_diff.completed_works.iter().cloned())
+            .collect();
+        let mut proofs = completed_proofs(&completed_works)?;
+        proofs.truncate(self.all_jobs().len());
+        let emitted = self
+            .clone()
+            .fill_proofs_and_enqueue_transactions(proofs, vec![])?;
+        next_total_currency(parent, emitted.as_ref().map(|(proof, _)| proof))
+    }
+}
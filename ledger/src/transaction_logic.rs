@@ -0,0 +1,1096 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//! Application of transactions to a [Ledger]
+//!
+//! This module implements the subset of `Transaction_logic` in the OCaml implementation
+//! that is required to replay blocks, i.e. signed commands (payments and stake delegations),
+//! fee transfers and coinbases
+//! <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/transaction_logic/mina_transaction_logic.ml>
+//!
+//! Failures are reported in two different ways, following the OCaml implementation.
+//! A user command that cannot be included in a block at all (e.g. its fee payer does not exist
+//! or its nonce is wrong) results in a [TransactionLogicError] and leaves the ledger untouched.
+//! A user command whose fee can be paid but whose body cannot be applied results in
+//! [TransactionStatus::Failed], in which case only the fee payer account is updated.
+//!
+//! Transactions are applied to any [Ledger] whose accounts implement [TransactionAccount],
+//! i.e. both the legacy accounts of mainnet and the accounts of berkeley.
+
+use crate::ledger::*;
+use mina_crypto::hash::{ChainHash, StateHash};
+use mina_rs_base::account::*;
+use mina_rs_base::types::*;
+use mina_rs_base::user_commands::signed_command::StakeDelegation;
+use proof_systems::mina_hasher::{create_kimchi, create_legacy, Fp, Hashable, Hasher, ROInput};
+use proof_systems::mina_signer::CompressedPubKey;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+pub use mina_rs_base::common::PoseidonVersion;
+
+/// Protocol constants that affect the application of transactions
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConstraintConstants {
+    /// Fee that is charged when a new account is created
+    pub account_creation_fee: Amount,
+    /// Amount of the coinbase of a block
+    pub coinbase_amount: Amount,
+    /// Multiplier of the coinbase amount when the coinbase is supercharged
+    pub supercharged_coinbase_factor: u64,
+    /// Version of the Poseidon hash of receipt chain hashes
+    pub poseidon_version: PoseidonVersion,
+    /// Log 2 of the number of transactions of a scan state tree
+    pub transaction_capacity_log_2: u32,
+    /// Number of blocks the snark work of a scan state tree is delayed by
+    pub work_delay: u32,
+}
+
+impl ConstraintConstants {
+    /// Constraint constants of mainnet
+    pub const fn mainnet() -> Self {
+        Self {
+            account_creation_fee: Amount(1_000_000_000),
+            coinbase_amount: Amount(720_000_000_000),
+            supercharged_coinbase_factor: 2,
+            poseidon_version: PoseidonVersion::Legacy,
+            transaction_capacity_log_2: 7,
+            work_delay: 2,
+        }
+    }
+
+    /// Constraint constants of berkeley
+    pub const fn berkeley() -> Self {
+        Self {
+            poseidon_version: PoseidonVersion::Kimchi,
+            ..Self::mainnet()
+        }
+    }
+}
+
+impl Default for ConstraintConstants {
+    fn default() -> Self {
+        Self::mainnet()
+    }
+}
+
+/// Errors that prevent a transaction from being applied,
+/// the ledger is left unchanged when any of them is returned
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum TransactionLogicError {
+    /// The command is no longer valid at the current global slot
+    #[error(
+        "Command is valid until slot {valid_until:?} but current global slot is {global_slot:?}"
+    )]
+    Expired {
+        /// The last slot the command is valid at
+        valid_until: GlobalSlotNumber,
+        /// The current global slot
+        global_slot: GlobalSlotNumber,
+    },
+
+    /// Only the default token is supported
+    #[error("Token {0:?} is not supported, only the default token is")]
+    UnsupportedToken(TokenId),
+
+    /// The fee payer account does not exist
+    #[error("Fee payer account does not exist")]
+    FeePayerNotPresent,
+
+    /// The nonce of the command does not match the one of the fee payer account
+    #[error("Incorrect nonce, expected: {expected:?}, actual: {actual:?}")]
+    IncorrectNonce {
+        /// Nonce of the fee payer account
+        expected: AccountNonce,
+        /// Nonce of the command
+        actual: AccountNonce,
+    },
+
+    /// The fee payer does not have permission to pay the fee
+    #[error("Fee payer is not permitted to send or increment its nonce")]
+    FeePayerUpdateNotPermitted,
+
+    /// The balance of the fee payer is not enough to pay the fee
+    #[error("Fee payer balance is insufficient to pay the fee")]
+    InsufficientFee,
+
+    /// Paying the fee would break the vesting schedule of the fee payer
+    #[error("Paying the fee would violate the minimum balance of the fee payer")]
+    FeePayerMinimumBalanceViolation,
+
+    /// The receiver of an internal command is not permitted to receive
+    #[error("Receiver is not permitted to receive")]
+    ReceiverUpdateNotPermitted,
+
+    /// The amount of an internal command cannot pay the account creation fee
+    #[error("Amount is insufficient to create the receiver account")]
+    AmountInsufficientToCreateAccount,
+
+    /// The fee transfer of a coinbase is larger than the coinbase amount
+    #[error("Coinbase fee transfer {fee:?} exceeds the coinbase amount {amount:?}")]
+    CoinbaseFeeTransferTooLarge {
+        /// Coinbase amount
+        amount: Amount,
+        /// Fee of the coinbase fee transfer
+        fee: Amount,
+    },
+
+    /// A balance overflows
+    #[error("Balance overflow")]
+    Overflow,
+
+    /// The receipt chain hash of an account is not a valid field element
+    #[error("Invalid receipt chain hash")]
+    InvalidReceiptChainHash,
+
+    /// The status of a user command does not match the one recorded in its block
+    #[error("Status mismatch, recorded: {recorded:?}, computed: {computed:?}")]
+    StatusMismatch {
+        /// Status recorded in the block
+        recorded: TransactionStatus,
+        /// Status computed by applying the user command
+        computed: TransactionStatus,
+    },
+
+    /// Error from the underlying ledger
+    #[error("{0}")]
+    Ledger(#[from] LedgerError),
+}
+
+/// A fee transfer to a single receiver
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SingleFeeTransfer {
+    /// Public key of the receiver
+    pub receiver_pk: CompressedPubKey,
+    /// Fee to transfer
+    pub fee: Amount,
+    /// Token of the fee
+    pub fee_token: TokenId,
+}
+
+impl SingleFeeTransfer {
+    /// Creates a fee transfer in the default token
+    pub fn new(receiver_pk: CompressedPubKey, fee: Amount) -> Self {
+        Self {
+            receiver_pk,
+            fee,
+            fee_token: DEFAULT_TOKEN_ID,
+        }
+    }
+
+    fn account_id(&self) -> AccountId {
+        AccountId::new(self.receiver_pk.clone(), self.fee_token.clone())
+    }
+}
+
+impl From<&CoinBaseFeeTransfer> for SingleFeeTransfer {
+    fn from(t: &CoinBaseFeeTransfer) -> Self {
+        Self::new(t.receiver_pk.clone(), t.fee)
+    }
+}
+
+/// A coinbase, which rewards the block producer,
+/// part of the reward may go to a snark worker as a fee transfer
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Coinbase {
+    /// Public key of the coinbase receiver
+    pub receiver: CompressedPubKey,
+    /// Total amount of the coinbase, including the fee transfer
+    pub amount: Amount,
+    /// Optional fee transfer that is paid out of the coinbase amount
+    pub fee_transfer: Option<SingleFeeTransfer>,
+}
+
+/// Applies a user command of a block to the ledger at the given global slot
+/// and checks the computed status against the one recorded in the block.
+/// The ledger is left unchanged when they do not match
+pub fn apply_user_command<L>(
+    ledger: &mut L,
+    constants: &ConstraintConstants,
+    txn_global_slot: GlobalSlotNumber,
+    user_command: &UserCommandWithStatus,
+) -> Result<TransactionStatus, TransactionLogicError>
+where
+    L: Ledger,
+    L::Account: TransactionAccount,
+{
+    let UserCommand::SignedCommand(signed_command) = &user_command.data;
+    let mut pending = PendingLedger::new(ledger);
+    let status = apply_signed_command_payload(
+        &mut pending,
+        constants,
+        txn_global_slot,
+        &signed_command.payload,
+    )?;
+    if status != user_command.status {
+        return Err(TransactionLogicError::StatusMismatch {
+            recorded: user_command.status.clone(),
+            computed: status,
+        });
+    }
+    let (updated, created) = pending.into_updates();
+    for (location, account) in updated {
+        ledger.set(location, account)?;
+    }
+    for (account_id, account) in created {
+        ledger.get_or_create_account(&account_id, account)?;
+    }
+    Ok(status)
+}
+
+/// Applies the payload of a signed command to the ledger at the given global slot,
+/// the signature is expected to be verified beforehand
+pub fn apply_signed_command_payload<L>(
+    ledger: &mut L,
+    constants: &ConstraintConstants,
+    txn_global_slot: GlobalSlotNumber,
+    payload: &SignedCommandPayload,
+) -> Result<TransactionStatus, TransactionLogicError>
+where
+    L: Ledger,
+    L::Account: TransactionAccount,
+{
+    if let SignedCommandPayloadBody::PaymentPayload(payment) = &payload.body {
+        if payment.token_id != DEFAULT_TOKEN_ID {
+            return Err(TransactionLogicError::UnsupportedToken(
+                payment.token_id.clone(),
+            ));
+        }
+        // A new receiver account must fit into the ledger
+        let receiver_id = AccountId::new(payment.receiver_pk.clone(), payment.token_id.clone());
+        if ledger.location_of_account(&receiver_id)?.is_none()
+            && ledger.num_accounts() >= ledger.capacity()
+        {
+            return Err(LedgerError::OutOfLeaves(ledger.capacity()).into());
+        }
+    }
+    let fee_payer_location = pay_fee(ledger, constants, txn_global_slot, payload)?;
+
+    // Apply the body, the fee is paid even if it fails
+    let body_result = match &payload.body {
+        SignedCommandPayloadBody::PaymentPayload(payment) => {
+            apply_payment(ledger, constants, txn_global_slot, payment)?
+        }
+        SignedCommandPayloadBody::StakeDelegation(delegation) => {
+            apply_stake_delegation(ledger, delegation)?
+        }
+    };
+    let fee_payer_balance = Some(get_account_at(ledger, fee_payer_location)?.balance());
+    Ok(match body_result {
+        Ok((auxiliary_data, balance_data)) => TransactionStatus::Applied(
+            auxiliary_data,
+            TransactionStatusBalanceData {
+                fee_payer_balance,
+                ..balance_data
+            },
+        ),
+        Err(failure) => TransactionStatus::Failed(
+            vec![failure],
+            TransactionStatusBalanceData {
+                fee_payer_balance,
+                source_balance: None,
+                receiver_balance: None,
+            },
+        ),
+    })
+}
+
+/// Charges the fee of a signed command to its fee payer, increments its nonce
+/// and extends its receipt chain, returns the location of the fee payer
+fn pay_fee<L>(
+    ledger: &mut L,
+    constants: &ConstraintConstants,
+    txn_global_slot: GlobalSlotNumber,
+    payload: &SignedCommandPayload,
+) -> Result<Location, TransactionLogicError>
+where
+    L: Ledger,
+    L::Account: TransactionAccount,
+{
+    let common = &payload.common;
+    if txn_global_slot.0 > common.valid_until.0 {
+        return Err(TransactionLogicError::Expired {
+            valid_until: common.valid_until,
+            global_slot: txn_global_slot,
+        });
+    }
+    if common.fee_token != DEFAULT_TOKEN_ID {
+        return Err(TransactionLogicError::UnsupportedToken(
+            common.fee_token.clone(),
+        ));
+    }
+    let fee_payer_id = AccountId::new(common.fee_payer_pk.clone(), common.fee_token.clone());
+    let fee_payer_location = ledger
+        .location_of_account(&fee_payer_id)?
+        .ok_or(TransactionLogicError::FeePayerNotPresent)?;
+    let mut fee_payer = get_account_at(ledger, fee_payer_location)?;
+    if fee_payer.nonce() != common.nonce {
+        return Err(TransactionLogicError::IncorrectNonce {
+            expected: fee_payer.nonce(),
+            actual: common.nonce,
+        });
+    }
+    if !fee_payer.send_permission().check(ControlTag::Signature)
+        || !fee_payer
+            .increment_nonce_permission()
+            .check(ControlTag::Signature)
+    {
+        return Err(TransactionLogicError::FeePayerUpdateNotPermitted);
+    }
+    let balance = fee_payer
+        .balance()
+        .0
+        .checked_sub(common.fee.0)
+        .map(Amount)
+        .ok_or(TransactionLogicError::InsufficientFee)?;
+    let timing = validate_timing(fee_payer.timing(), balance, txn_global_slot)
+        .ok_or(TransactionLogicError::FeePayerMinimumBalanceViolation)?;
+    let receipt_chain_hash = cons_signed_command_payload(payload, fee_payer.receipt_chain_hash())?;
+    fee_payer.set_balance(balance);
+    fee_payer.set_timing(timing);
+    fee_payer.set_nonce(AccountNonce(fee_payer.nonce().0 + 1));
+    fee_payer.set_receipt_chain_hash(receipt_chain_hash);
+    ledger.set(fee_payer_location, fee_payer)?;
+    Ok(fee_payer_location)
+}
+
+/// Result of applying the body of a signed command,
+/// the inner error indicates that the command failed but its fee has been paid
+type BodyResult = Result<
+    Result<
+        (TransactionStatusAuxiliaryData, TransactionStatusBalanceData),
+        TransactionStatusFailedType,
+    >,
+    TransactionLogicError,
+>;
+
+fn no_auxiliary_data() -> TransactionStatusAuxiliaryData {
+    TransactionStatusAuxiliaryData {
+        fee_payer_account_creation_fee_paid: None,
+        receiver_account_creation_fee_paid: None,
+        created_token: None,
+    }
+}
+
+fn apply_payment<L>(
+    ledger: &mut L,
+    constants: &ConstraintConstants,
+    txn_global_slot: GlobalSlotNumber,
+    payment: &PaymentPayload,
+) -> BodyResult
+where
+    L: Ledger,
+    L::Account: TransactionAccount,
+{
+    let source_id = AccountId::new(payment.source_pk.clone(), payment.token_id.clone());
+    let receiver_id = AccountId::new(payment.receiver_pk.clone(), payment.token_id.clone());
+
+    let source_location = match ledger.location_of_account(&source_id)? {
+        Some(location) => location,
+        None => return Ok(Err(TransactionStatusFailedType::SourceNotPresent)),
+    };
+    let mut source = get_account_at(ledger, source_location)?;
+    if !source.send_permission().check(ControlTag::Signature) {
+        return Ok(Err(TransactionStatusFailedType::UpdateNotPermitted));
+    }
+    let source_balance = match source.balance().0.checked_sub(payment.amount.0) {
+        Some(balance) => Amount(balance),
+        None => return Ok(Err(TransactionStatusFailedType::SourceInsufficientBalance)),
+    };
+    let source_timing = match validate_timing(source.timing(), source_balance, txn_global_slot) {
+        Some(timing) => timing,
+        None => {
+            return Ok(Err(
+                TransactionStatusFailedType::SourceMinimumBalanceViolation,
+            ))
+        }
+    };
+    source.set_balance(source_balance);
+    source.set_timing(source_timing);
+
+    // Sending to oneself only touches a single account
+    if receiver_id == source_id {
+        let balance = Amount(source_balance.0 + payment.amount.0);
+        source.set_balance(balance);
+        ledger.set(source_location, source)?;
+        return Ok(Ok((
+            no_auxiliary_data(),
+            TransactionStatusBalanceData {
+                fee_payer_balance: None,
+                source_balance: Some(balance),
+                receiver_balance: Some(balance),
+            },
+        )));
+    }
+
+    let receiver_location = ledger.location_of_account(&receiver_id)?;
+    let (mut receiver, creation_fee) = match receiver_location {
+        Some(location) => (get_account_at(ledger, location)?, None),
+        None => (
+            L::Account::create(&receiver_id, constants.poseidon_version),
+            Some(constants.account_creation_fee),
+        ),
+    };
+    if !receiver.receive_permission().check(ControlTag::NoneGiven) {
+        return Ok(Err(TransactionStatusFailedType::UpdateNotPermitted));
+    }
+    let credit = match creation_fee {
+        Some(fee) => match payment.amount.0.checked_sub(fee.0) {
+            Some(credit) => credit,
+            None => {
+                return Ok(Err(
+                    TransactionStatusFailedType::AmountInsufficientToCreateAccount,
+                ))
+            }
+        },
+        None => payment.amount.0,
+    };
+    let receiver_balance = match receiver.balance().0.checked_add(credit) {
+        Some(balance) => Amount(balance),
+        None => return Ok(Err(TransactionStatusFailedType::Overflow)),
+    };
+    receiver.set_balance(receiver_balance);
+
+    ledger.set(source_location, source)?;
+    match receiver_location {
+        Some(location) => ledger.set(location, receiver)?,
+        None => {
+            ledger.get_or_create_account(&receiver_id, receiver)?;
+        }
+    }
+    Ok(Ok((
+        TransactionStatusAuxiliaryData {
+            receiver_account_creation_fee_paid: creation_fee,
+            ..no_auxiliary_data()
+        },
+        TransactionStatusBalanceData {
+            fee_payer_balance: None,
+            source_balance: Some(source_balance),
+            receiver_balance: Some(receiver_balance),
+        },
+    )))
+}
+
+fn apply_stake_delegation<L>(ledger: &mut L, delegation: &StakeDelegation) -> BodyResult
+where
+    L: Ledger,
+    L::Account: TransactionAccount,
+{
+    let StakeDelegation::SetDelegate {
+        delegator,
+        new_delegate,
+    } = delegation;
+    let delegator_id = AccountId::new(delegator.clone(), DEFAULT_TOKEN_ID);
+    let delegate_id = AccountId::new(new_delegate.clone(), DEFAULT_TOKEN_ID);
+
+    let delegator_location = match ledger.location_of_account(&delegator_id)? {
+        Some(location) => location,
+        None => return Ok(Err(TransactionStatusFailedType::SourceNotPresent)),
+    };
+    if ledger.location_of_account(&delegate_id)?.is_none() {
+        return Ok(Err(TransactionStatusFailedType::ReceiverNotPresent));
+    }
+    let mut delegator = get_account_at(ledger, delegator_location)?;
+    if !delegator
+        .set_delegate_permission()
+        .check(ControlTag::Signature)
+    {
+        return Ok(Err(TransactionStatusFailedType::UpdateNotPermitted));
+    }
+    delegator.set_delegate(Some(new_delegate.clone()));
+    let source_balance = delegator.balance();
+    ledger.set(delegator_location, delegator)?;
+    Ok(Ok((
+        no_auxiliary_data(),
+        TransactionStatusBalanceData {
+            fee_payer_balance: None,
+            source_balance: Some(source_balance),
+            receiver_balance: None,
+        },
+    )))
+}
+
+/// Applies one or two fee transfers to the ledger,
+/// two transfers to the same receiver are combined into one
+pub fn apply_fee_transfer<L>(
+    ledger: &mut L,
+    constants: &ConstraintConstants,
+    txn_global_slot: GlobalSlotNumber,
+    first: &SingleFeeTransfer,
+    second: Option<&SingleFeeTransfer>,
+) -> Result<FeeTransferBalanceData, TransactionLogicError>
+where
+    L: Ledger,
+    L::Account: TransactionAccount,
+{
+    match second {
+        None => {
+            let receiver1_balance = credit_account(
+                ledger,
+                constants,
+                txn_global_slot,
+                &first.account_id(),
+                first.fee,
+            )?;
+            Ok(FeeTransferBalanceData {
+                receiver1_balance,
+                receiver2_balance: None,
+            })
+        }
+        Some(second) if second.account_id() == first.account_id() => {
+            let fee = first
+                .fee
+                .0
+                .checked_add(second.fee.0)
+                .ok_or(TransactionLogicError::Overflow)?;
+            let receiver1_balance = credit_account(
+                ledger,
+                constants,
+                txn_global_slot,
+                &first.account_id(),
+                Amount(fee),
+            )?;
+            Ok(FeeTransferBalanceData {
+                receiver1_balance,
+                receiver2_balance: None,
+            })
+        }
+        Some(second) => {
+            // Each receiver is checked before the ledger is mutated so that a failure
+            // of the second transfer does not leave the first one applied
+            check_credit(ledger, constants, &first.account_id(), first.fee)?;
+            check_credit(ledger, constants, &second.account_id(), second.fee)?;
+            let receiver1_balance = credit_account(
+                ledger,
+                constants,
+                txn_global_slot,
+                &first.account_id(),
+                first.fee,
+            )?;
+            let receiver2_balance = credit_account(
+                ledger,
+                constants,
+                txn_global_slot,
+                &second.account_id(),
+                second.fee,
+            )?;
+            Ok(FeeTransferBalanceData {
+                receiver1_balance,
+                receiver2_balance: Some(receiver2_balance),
+            })
+        }
+    }
+}
+
+/// Applies a coinbase to the ledger, the fee transfer, if any,
+/// is paid out of the coinbase amount
+pub fn apply_coinbase<L>(
+    ledger: &mut L,
+    constants: &ConstraintConstants,
+    txn_global_slot: GlobalSlotNumber,
+    coinbase: &Coinbase,
+) -> Result<CoinBaseBalanceData, TransactionLogicError>
+where
+    L: Ledger,
+    L::Account: TransactionAccount,
+{
+    let receiver_id = AccountId::new(coinbase.receiver.clone(), DEFAULT_TOKEN_ID);
+    match &coinbase.fee_transfer {
+        None => {
+            let coinbase_receiver_balance = credit_account(
+                ledger,
+                constants,
+                txn_global_slot,
+                &receiver_id,
+                coinbase.amount,
+            )?;
+            Ok(CoinBaseBalanceData {
+                coinbase_receiver_balance,
+                fee_transfer_receiver_balance: None,
+            })
+        }
+        Some(fee_transfer) => {
+            let receiver_reward = coinbase
+                .amount
+                .0
+                .checked_sub(fee_transfer.fee.0)
+                .map(Amount)
+                .ok_or(TransactionLogicError::CoinbaseFeeTransferTooLarge {
+                    amount: coinbase.amount,
+                    fee: fee_transfer.fee,
+                })?;
+            if fee_transfer.account_id() == receiver_id {
+                let coinbase_receiver_balance = credit_account(
+                    ledger,
+                    constants,
+                    txn_global_slot,
+                    &receiver_id,
+                    coinbase.amount,
+                )?;
+                return Ok(CoinBaseBalanceData {
+                    coinbase_receiver_balance,
+                    fee_transfer_receiver_balance: None,
+                });
+            }
+            check_credit(
+                ledger,
+                constants,
+                &fee_transfer.account_id(),
+                fee_transfer.fee,
+            )?;
+            check_credit(ledger, constants, &receiver_id, receiver_reward)?;
+            let fee_transfer_receiver_balance = credit_account(
+                ledger,
+                constants,
+                txn_global_slot,
+                &fee_transfer.account_id(),
+                fee_transfer.fee,
+            )?;
+            let coinbase_receiver_balance = credit_account(
+                ledger,
+                constants,
+                txn_global_slot,
+                &receiver_id,
+                receiver_reward,
+            )?;
+            Ok(CoinBaseBalanceData {
+                coinbase_receiver_balance,
+                fee_transfer_receiver_balance: Some(fee_transfer_receiver_balance),
+            })
+        }
+    }
+}
+
+/// Creates a new account with the given id and default fields,
+/// its receipt chain hash is the empty one of the given Poseidon version
+/// <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/mina_base/account.ml#L604>
+pub fn new_account(account_id: &AccountId, poseidon_version: PoseidonVersion) -> Account {
+    let delegate = if account_id.token_id == DEFAULT_TOKEN_ID {
+        Some(account_id.public_key.clone())
+    } else {
+        None
+    };
+    Account {
+        public_key: account_id.public_key.clone(),
+        token_id: account_id.token_id.clone(),
+        balance: Amount(0),
+        token_permissions: TokenPermissions::NotOwned {
+            account_disabled: false,
+        },
+        token_symbol: Default::default(),
+        nonce: AccountNonce(0),
+        receipt_chain_hash: empty_receipt_chain_hash(poseidon_version),
+        delegate,
+        voting_for: StateHash::default(),
+        timing: Timing::Untimed,
+        permissions: Permissions::user_default(),
+        zkapp: None,
+        zkapp_uri: None,
+    }
+}
+
+/// Accounts that transactions can be applied to, which are both the legacy accounts
+/// of mainnet and the accounts of berkeley
+pub trait TransactionAccount: LedgerAccount {
+    /// Creates a new account with the given id and default fields,
+    /// its receipt chain hash is the empty one of the given Poseidon version
+    fn create(account_id: &AccountId, poseidon_version: PoseidonVersion) -> Self;
+
+    /// Balance of the account
+    fn balance(&self) -> Amount;
+
+    /// Sets the balance of the account
+    fn set_balance(&mut self, balance: Amount);
+
+    /// Nonce of the account
+    fn nonce(&self) -> AccountNonce;
+
+    /// Sets the nonce of the account
+    fn set_nonce(&mut self, nonce: AccountNonce);
+
+    /// Receipt chain hash of the account
+    fn receipt_chain_hash(&self) -> &ChainHash;
+
+    /// Sets the receipt chain hash of the account
+    fn set_receipt_chain_hash(&mut self, receipt_chain_hash: ChainHash);
+
+    /// Timing of the account
+    fn timing(&self) -> &Timing;
+
+    /// Sets the timing of the account
+    fn set_timing(&mut self, timing: Timing);
+
+    /// Sets the delegate of the account
+    fn set_delegate(&mut self, delegate: Option<CompressedPubKey>);
+
+    /// Authorization required to send from the account
+    fn send_permission(&self) -> &AuthRequired;
+
+    /// Authorization required to send to the account
+    fn receive_permission(&self) -> &AuthRequired;
+
+    /// Authorization required to set the delegate of the account
+    fn set_delegate_permission(&self) -> &AuthRequired;
+
+    /// Authorization required to increment the nonce of the account
+    fn increment_nonce_permission(&self) -> &AuthRequired;
+}
+
+/// Implements the accessors of [TransactionAccount] for the fields
+/// that legacy and berkeley accounts have in common
+macro_rules! impl_transaction_account_fields {
+    () => {
+        fn balance(&self) -> Amount {
+            self.balance
+        }
+
+        fn set_balance(&mut self, balance: Amount) {
+            self.balance = balance;
+        }
+
+        fn nonce(&self) -> AccountNonce {
+            self.nonce
+        }
+
+        fn set_nonce(&mut self, nonce: AccountNonce) {
+            self.nonce = nonce;
+        }
+
+        fn receipt_chain_hash(&self) -> &ChainHash {
+            &self.receipt_chain_hash
+        }
+
+        fn set_receipt_chain_hash(&mut self, receipt_chain_hash: ChainHash) {
+            self.receipt_chain_hash = receipt_chain_hash;
+        }
+
+        fn timing(&self) -> &Timing {
+            &self.timing
+        }
+
+        fn set_timing(&mut self, timing: Timing) {
+            self.timing = timing;
+        }
+
+        fn set_delegate(&mut self, delegate: Option<CompressedPubKey>) {
+            self.delegate = delegate;
+        }
+
+        fn send_permission(&self) -> &AuthRequired {
+            &self.permissions.send
+        }
+
+        fn receive_permission(&self) -> &AuthRequired {
+            &self.permissions.receive
+        }
+
+        fn set_delegate_permission(&self) -> &AuthRequired {
+            &self.permissions.set_delegate
+        }
+    };
+}
+
+impl TransactionAccount for Account {
+    fn create(account_id: &AccountId, poseidon_version: PoseidonVersion) -> Self {
+        new_account(account_id, poseidon_version)
+    }
+
+    impl_transaction_account_fields!();
+
+    fn increment_nonce_permission(&self) -> &AuthRequired {
+        &self.permissions.increment_nonce
+    }
+}
+
+impl TransactionAccount for AccountLegacy {
+    fn create(account_id: &AccountId, poseidon_version: PoseidonVersion) -> Self {
+        let account = new_account(account_id, poseidon_version);
+        Self {
+            public_key: account.public_key,
+            token_id: account.token_id,
+            token_permissions: account.token_permissions,
+            balance: account.balance,
+            nonce: account.nonce,
+            receipt_chain_hash: account.receipt_chain_hash,
+            delegate: account.delegate,
+            voting_for: account.voting_for,
+            timing: account.timing,
+            permissions: PermissionsLegacy::user_default(),
+            snapp: None,
+        }
+    }
+
+    impl_transaction_account_fields!();
+
+    // Legacy permissions have no nonce permission, the nonce is incremented
+    // by whoever is permitted to send
+    fn increment_nonce_permission(&self) -> &AuthRequired {
+        &self.permissions.send
+    }
+}
+
+/// Checks the given balance against the minimum balance of the timing at the given slot,
+/// returns the updated timing if it is not violated. An account becomes untimed
+/// once its minimum balance reaches zero
+/// <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/transaction_logic/mina_transaction_logic.ml#L533>
+pub fn validate_timing(
+    timing: &Timing,
+    balance: Amount,
+    txn_global_slot: GlobalSlotNumber,
+) -> Option<Timing> {
+    let min_balance = timing.min_balance_at_slot(txn_global_slot);
+    if balance.0 < min_balance.0 {
+        None
+    } else if min_balance.0 == 0 {
+        Some(Timing::Untimed)
+    } else {
+        Some(timing.clone())
+    }
+}
+
+/// Receipt chain hash of a newly created account, which is salted
+/// with the Poseidon hash of the network
+/// <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/mina_base/receipt.ml>
+pub fn empty_receipt_chain_hash(poseidon_version: PoseidonVersion) -> ChainHash {
+    let hash = match poseidon_version {
+        PoseidonVersion::Legacy => {
+            create_legacy::<EmptyReceiptChainHashInput>(()).hash(&EmptyReceiptChainHashInput)
+        }
+        PoseidonVersion::Kimchi => {
+            create_kimchi::<EmptyReceiptChainHashInput>(()).hash(&EmptyReceiptChainHashInput)
+        }
+    };
+    (&hash).into()
+}
+
+/// Appends a signed command payload to the given receipt chain hash.
+/// Signed commands are hashed with the legacy Poseidon on every network,
+/// berkeley only switches to the kimchi Poseidon for zkapp commands
+/// <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/mina_base/receipt.ml#L33>
+pub fn cons_signed_command_payload(
+    payload: &SignedCommandPayload,
+    receipt_chain_hash: &ChainHash,
+) -> Result<ChainHash, TransactionLogicError> {
+    let previous: Fp = receipt_chain_hash
+        .try_into()
+        .map_err(|_| TransactionLogicError::InvalidReceiptChainHash)?;
+    let mut hasher = create_legacy::<ReceiptChainHashInput>(());
+    Ok((&hasher.hash(&ReceiptChainHashInput(payload.clone(), previous))).into())
+}
+
+#[derive(Clone)]
+struct EmptyReceiptChainHashInput;
+
+impl Hashable for EmptyReceiptChainHashInput {
+    type D = ();
+
+    fn to_roinput(&self) -> ROInput {
+        ROInput::new()
+    }
+
+    fn domain_string(_: Self::D) -> Option<String> {
+        Some("CodaReceiptEmpty".into())
+    }
+}
+
+#[derive(Clone)]
+struct ReceiptChainHashInput(SignedCommandPayload, Fp);
+
+impl Hashable for ReceiptChainHashInput {
+    type D = ();
+
+    fn to_roinput(&self) -> ROInput {
+        self.0.to_roinput().append_field(self.1)
+    }
+
+    fn domain_string(_: Self::D) -> Option<String> {
+        Some("CodaReceiptUC".into())
+    }
+}
+
+fn get_account_at<L>(ledger: &L, location: Location) -> Result<L::Account, TransactionLogicError>
+where
+    L: Ledger,
+    L::Account: TransactionAccount,
+{
+    ledger
+        .get(location)?
+        .ok_or(TransactionLogicError::Ledger(LedgerError::InvalidLocation(
+            location,
+        )))
+}
+
+/// Checks that the account with the given id can be credited with the given amount
+fn check_credit<L>(
+    ledger: &L,
+    constants: &ConstraintConstants,
+    account_id: &AccountId,
+    amount: Amount,
+) -> Result<(), TransactionLogicError>
+where
+    L: Ledger,
+    L::Account: TransactionAccount,
+{
+    match ledger.get_account(account_id)? {
+        Some(account) => {
+            if !account.receive_permission().check(ControlTag::NoneGiven) {
+                return Err(TransactionLogicError::ReceiverUpdateNotPermitted);
+            }
+            account
+                .balance()
+                .0
+                .checked_add(amount.0)
+                .ok_or(TransactionLogicError::Overflow)?;
+        }
+        None => {
+            if amount.0 < constants.account_creation_fee.0 {
+                return Err(TransactionLogicError::AmountInsufficientToCreateAccount);
+            }
+            if ledger.num_accounts() >= ledger.capacity() {
+                return Err(LedgerError::OutOfLeaves(ledger.capacity()).into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Credits the account with the given id, creating it when it does not exist,
+/// returns the new balance of the account
+fn credit_account<L>(
+    ledger: &mut L,
+    constants: &ConstraintConstants,
+    txn_global_slot: GlobalSlotNumber,
+    account_id: &AccountId,
+    amount: Amount,
+) -> Result<Amount, TransactionLogicError>
+where
+    L: Ledger,
+    L::Account: TransactionAccount,
+{
+    check_credit(ledger, constants, account_id, amount)?;
+    match ledger.location_of_account(account_id)? {
+        Some(location) => {
+            let mut account = get_account_at(ledger, location)?;
+            let balance = Amount(account.balance().0 + amount.0);
+            account.set_balance(balance);
+            // Crediting never violates the minimum balance,
+            // but a fully vested account becomes untimed
+            if account.timing().min_balance_at_slot(txn_global_slot).0 == 0 {
+                account.set_timing(Timing::Untimed);
+            }
+            ledger.set(location, account)?;
+            Ok(balance)
+        }
+        None => {
+            let mut account = L::Account::create(account_id, constants.poseidon_version);
+            let balance = Amount(amount.0 - constants.account_creation_fee.0);
+            account.set_balance(balance);
+            ledger.get_or_create_account(account_id, account)?;
+            Ok(balance)
+        }
+    }
+}
+
+/// A view of a ledger that keeps the updates of a transaction to itself, so that they are
+/// only written to the ledger once the transaction is known to match its block.
+/// Accounts are created at the locations following the ones of the ledger
+struct PendingLedger<'a, L: Ledger> {
+    ledger: &'a L,
+    updated: BTreeMap<Location, L::Account>,
+    created: Vec<(AccountId, L::Account)>,
+}
+
+impl<'a, L: Ledger> PendingLedger<'a, L> {
+    fn new(ledger: &'a L) -> Self {
+        Self {
+            ledger,
+            updated: BTreeMap::new(),
+            created: Vec::new(),
+        }
+    }
+
+    /// Index of the created account at the given location
+    fn created_index(&self, location: Location) -> Option<usize> {
+        location.0.checked_sub(self.ledger.num_accounts())
+    }
+
+    /// Consumes the view and returns the updated accounts of the ledger
+    /// and the created accounts in the order of creation
+    fn into_updates(self) -> (BTreeMap<Location, L::Account>, Vec<(AccountId, L::Account)>) {
+        (self.updated, self.created)
+    }
+}
+
+impl<'a, L: Ledger> Ledger for PendingLedger<'a, L> {
+    type Account = L::Account;
+    type Hash = L::Hash;
+
+    fn depth(&self) -> u32 {
+        self.ledger.depth()
+    }
+
+    fn num_accounts(&self) -> usize {
+        self.ledger.num_accounts() + self.created.len()
+    }
+
+    fn location_of_account(&self, account_id: &AccountId) -> Result<Option<Location>, LedgerError> {
+        if let Some(location) = self.ledger.location_of_account(account_id)? {
+            return Ok(Some(location));
+        }
+        Ok(self
+            .created
+            .iter()
+            .position(|(created_id, _)| created_id == account_id)
+            .map(|index| Location(self.ledger.num_accounts() + index)))
+    }
+
+    fn get(&self, location: Location) -> Result<Option<Self::Account>, LedgerError> {
+        if let Some(account) = self.updated.get(&location) {
+            return Ok(Some(account.clone()));
+        }
+        match self.created_index(location) {
+            Some(index) => Ok(self.created.get(index).map(|(_, account)| account.clone())),
+            None => self.ledger.get(location),
+        }
+    }
+
+    fn set(&mut self, location: Location, account: Self::Account) -> Result<(), LedgerError> {
+        let existing = self
+            .get(location)?
+            .ok_or(LedgerError::InvalidLocation(location))?;
+        if existing.account_id() != account.account_id() {
+            return Err(LedgerError::AccountIdMismatch(location));
+        }
+        match self.created_index(location) {
+            Some(index) => self.created[index].1 = account,
+            None => {
+                self.updated.insert(location, account);
+            }
+        }
+        Ok(())
+    }
+
+    fn get_or_create_account(
+        &mut self,
+        account_id: &AccountId,
+        account: Self::Account,
+    ) -> Result<(GetOrCreated, Location), LedgerError> {
+        if let Some(location) = self.location_of_account(account_id)? {
+            return Ok((GetOrCreated::Existed, location));
+        }
+        let location = Location(self.num_accounts());
+        if &account.account_id() != account_id {
+            return Err(LedgerError::AccountIdMismatch(location));
+        }
+        if location.0 >= self.capacity() {
+            return Err(LedgerError::OutOfLeaves(self.capacity()));
+        }
+        self.created.push((account_id.clone(), account));
+        Ok((GetOrCreated::Added, location))
+    }
+
+    /// Pending updates are not hashed
+    fn merkle_root(&mut self) -> Option<Self::Hash> {
+        None
+    }
+}
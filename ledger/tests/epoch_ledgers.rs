@@ -0,0 +1,202 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use anyhow::ensure;
+    use mina_crypto::hash::LedgerHash;
+    use mina_ledger::*;
+    use mina_rs_base::{account::*, types::*};
+    use rocksdb::*;
+
+    const DB_PATH_BERKELEY: &str =  "test-data/genesis_ledger_a99a1ff63d4ba4a07cc6bedbff3e23bd6c1f482f9ecef33abdf7fb817564cc89/";
+
+    fn genesis_ledger() -> anyhow::Result<InMemoryLedger<Account>> {
+        let db = rocksdb::DB::open_for_read_only(&Options::default(), DB_PATH_BERKELEY, true)?;
+        let genesis_ledger: RocksDbGenesisLedger<20, Account> = RocksDbGenesisLedger::new(&db);
+        Ok(InMemoryLedger::from_genesis_ledger(&genesis_ledger)?)
+    }
+
+    fn ledger_hash(ledger: &mut InMemoryLedger<Account>) -> LedgerHash {
+        (&ledger.merkle_root().unwrap()).into()
+    }
+
+    fn epoch_data(hash: &LedgerHash) -> EpochData {
+        let mut epoch_data = EpochData::default();
+        epoch_data.ledger.hash = hash.clone();
+        epoch_data
+    }
+
+    fn bump_balance(
+        ledger: &mut InMemoryLedger<Account>,
+        location: Location,
+    ) -> anyhow::Result<()> {
+        let mut account = ledger.get(location)?.unwrap();
+        account.balance = Amount(account.balance.0 + 1);
+        ledger.set(location, account)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_epoch_ledgers_rotate() -> anyhow::Result<()> {
+        let mut ledger = genesis_ledger()?;
+        let genesis_hash = ledger_hash(&mut ledger);
+        let mut epoch_ledgers = EpochLedgers::new(&ledger)?;
+        ensure!(epoch_ledgers.epoch() == 0);
+        ensure!(epoch_ledgers.staking_ledger_hash() == &genesis_hash);
+        ensure!(epoch_ledgers.next_epoch_ledger_hash() == &genesis_hash);
+        ensure!(epoch_ledgers.staking_ledger().num_accounts() == 6404);
+
+        // Epoch 1, the genesis ledger becomes the staking ledger
+        bump_balance(&mut ledger, Location(0))?;
+        let hash_1 = ledger_hash(&mut ledger);
+        epoch_ledgers.rotate(1, &ledger, &epoch_data(&hash_1))?;
+        ensure!(epoch_ledgers.epoch() == 1);
+        ensure!(epoch_ledgers.staking_ledger_hash() == &genesis_hash);
+        ensure!(epoch_ledgers.next_epoch_ledger_hash() == &hash_1);
+
+        // Snapshots are frozen, later updates to the ledger are not reflected
+        bump_balance(&mut ledger, Location(1))?;
+        let frozen = epoch_ledgers.get(&hash_1).unwrap();
+        ensure!(
+            frozen.get(Location(1))?.unwrap().balance != ledger.get(Location(1))?.unwrap().balance
+        );
+
+        // Epoch 3, an epoch without blocks is skipped
+        let hash_3 = ledger_hash(&mut ledger);
+        epoch_ledgers.rotate(3, &ledger, &epoch_data(&hash_3))?;
+        ensure!(epoch_ledgers.epoch() == 3);
+        ensure!(epoch_ledgers.staking_ledger_hash() == &hash_1);
+        ensure!(epoch_ledgers.next_epoch_ledger_hash() == &hash_3);
+        let staking = epoch_ledgers.staking_ledger();
+        ensure!(
+            staking.get(Location(0))?.unwrap().balance == ledger.get(Location(0))?.unwrap().balance
+        );
+
+        // Snapshots that are no longer referred are dropped
+        ensure!(epoch_ledgers.get(&genesis_hash).is_none());
+        ensure!(epoch_ledgers.get(&hash_1).is_some());
+        ensure!(epoch_ledgers.get(&hash_3).is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_epoch_ledgers_rotate_errors() -> anyhow::Result<()> {
+        let mut ledger = genesis_ledger()?;
+        let genesis_hash = ledger_hash(&mut ledger);
+        let mut epoch_ledgers = EpochLedgers::new(&ledger)?;
+
+        bump_balance(&mut ledger, Location(0))?;
+        let hash = ledger_hash(&mut ledger);
+        ensure!(
+            epoch_ledgers.rotate(1, &ledger, &epoch_data(&genesis_hash))
+                == Err(EpochLedgerError::LedgerHashMismatch {
+                    expected: genesis_hash.clone(),
+                    actual: hash.clone(),
+                })
+        );
+        ensure!(
+            epoch_ledgers.rotate(0, &ledger, &epoch_data(&hash))
+                == Err(EpochLedgerError::StaleEpoch { current: 0, new: 0 })
+        );
+        // Failed rotations leave the snapshots untouched
+        ensure!(epoch_ledgers.epoch() == 0);
+        ensure!(epoch_ledgers.next_epoch_ledger_hash() == &genesis_hash);
+        ensure!(epoch_ledgers.get(&hash).is_none());
+        Ok(())
+    }
+
+    /// A ledger that claims one more account than it stores
+    struct SparseLedger(InMemoryLedger<Account>);
+
+    impl Ledger for SparseLedger {
+        type Account = Account;
+        type Hash = <InMemoryLedger<Account> as Ledger>::Hash;
+
+        fn depth(&self) -> u32 {
+            self.0.depth()
+        }
+
+        fn num_accounts(&self) -> usize {
+            self.0.num_accounts() + 1
+        }
+
+        fn location_of_account(
+            &self,
+            account_id: &AccountId,
+        ) -> Result<Option<Location>, LedgerError> {
+            self.0.location_of_account(account_id)
+        }
+
+        fn get(&self, location: Location) -> Result<Option<Account>, LedgerError> {
+            self.0.get(location)
+        }
+
+        fn set(&mut self, location: Location, account: Account) -> Result<(), LedgerError> {
+            self.0.set(location, account)
+        }
+
+        fn get_or_create_account(
+            &mut self,
+            account_id: &AccountId,
+            account: Account,
+        ) -> Result<(GetOrCreated, Location), LedgerError> {
+            self.0.get_or_create_account(account_id, account)
+        }
+
+        fn merkle_root(&mut self) -> Option<Self::Hash> {
+            self.0.merkle_root()
+        }
+    }
+
+    #[test]
+    fn test_epoch_ledgers_missing_account() -> anyhow::Result<()> {
+        let ledger = genesis_ledger()?;
+        let missing = Location(ledger.num_accounts());
+        ensure!(
+            EpochLedgers::new(&SparseLedger(ledger)).err()
+                == Some(EpochLedgerError::Ledger(LedgerError::InvalidLocation(
+                    missing
+                )))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_epoch_ledgers_update() -> anyhow::Result<()> {
+        let mut ledger = genesis_ledger()?;
+        let genesis_hash = ledger_hash(&mut ledger);
+        let mut epoch_ledgers = EpochLedgers::new(&ledger)?;
+
+        let mut consensus_state = ConsensusState {
+            staking_epoch_data: epoch_data(&genesis_hash),
+            next_epoch_data: epoch_data(&genesis_hash),
+            ..Default::default()
+        };
+        epoch_ledgers.update(&consensus_state, &ledger)?;
+        ensure!(epoch_ledgers.epoch() == 0);
+
+        bump_balance(&mut ledger, Location(0))?;
+        let hash = ledger_hash(&mut ledger);
+        consensus_state.epoch_count = Length(1);
+        consensus_state.next_epoch_data = epoch_data(&hash);
+        epoch_ledgers.update(&consensus_state, &ledger)?;
+        ensure!(epoch_ledgers.epoch() == 1);
+        ensure!(epoch_ledgers.next_epoch_ledger_hash() == &hash);
+
+        // Blocks in the same epoch do not rotate the snapshots
+        bump_balance(&mut ledger, Location(1))?;
+        epoch_ledgers.update(&consensus_state, &ledger)?;
+        ensure!(epoch_ledgers.next_epoch_ledger_hash() == &hash);
+
+        consensus_state.staking_epoch_data = epoch_data(&hash);
+        ensure!(
+            epoch_ledgers.check(&consensus_state)
+                == Err(EpochLedgerError::LedgerHashMismatch {
+                    expected: hash.clone(),
+                    actual: genesis_hash,
+                })
+        );
+        Ok(())
+    }
+}
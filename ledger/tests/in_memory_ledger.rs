@@ -0,0 +1,113 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use anyhow::ensure;
+    use mina_ledger::*;
+    use mina_merkle::*;
+    use mina_rs_base::{account::*, types::*};
+    use rocksdb::*;
+
+    const DB_PATH_BERKELEY: &str =  "test-data/genesis_ledger_a99a1ff63d4ba4a07cc6bedbff3e23bd6c1f482f9ecef33abdf7fb817564cc89/";
+
+    #[test]
+    fn test_in_memory_ledger_from_genesis_ledger() -> anyhow::Result<()> {
+        let db = rocksdb::DB::open_for_read_only(&Options::default(), DB_PATH_BERKELEY, true)?;
+        let genesis_ledger: RocksDbGenesisLedger<20, Account> = RocksDbGenesisLedger::new(&db);
+        let mut ledger = InMemoryLedger::from_genesis_ledger(&genesis_ledger)?;
+        let mut merkle_ledger = genesis_ledger.to_mina_merkle_ledger();
+        ensure!(ledger.depth() == 20);
+        ensure!(ledger.num_accounts() == 6404);
+        ensure!(ledger.merkle_root() == merkle_ledger.root());
+
+        for (i, account) in genesis_ledger.accounts().enumerate().step_by(500) {
+            let account = account?;
+            let location = ledger.location_of_account(&account.account_id())?;
+            ensure!(location == Some(Location(i)));
+            let proof = ledger.merkle_proof(Location(i));
+            ensure!(proof.is_some());
+            ensure!(proof.unwrap().verify(&ledger.merkle_root().unwrap()));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_in_memory_ledger_set() -> anyhow::Result<()> {
+        let db = rocksdb::DB::open_for_read_only(&Options::default(), DB_PATH_BERKELEY, true)?;
+        let genesis_ledger: RocksDbGenesisLedger<20, Account> = RocksDbGenesisLedger::new(&db);
+        let mut accounts: Vec<Account> = genesis_ledger.accounts().flatten().collect();
+        let mut ledger = InMemoryLedger::from_accounts(20, accounts.clone())?;
+        let root_before = ledger.merkle_root();
+
+        let location = Location(42);
+        let mut account = ledger.get(location)?.unwrap();
+        account.balance = Amount(account.balance.0 + 1);
+        account.nonce = AccountNonce(account.nonce.0 + 1);
+        ledger.set(location, account.clone())?;
+        accounts[location.0] = account.clone();
+
+        let root_after = ledger.merkle_root();
+        ensure!(root_before != root_after);
+        // The incrementally updated root should match the root of a freshly built ledger
+        let mut expected = MinaLedgerMerkleTree::new(20);
+        expected.add_batch(accounts);
+        ensure!(root_after == expected.root());
+
+        // Account id must match the one stored at the location
+        let other = ledger.get(Location(43))?.unwrap();
+        ensure!(ledger.set(location, other) == Err(LedgerError::AccountIdMismatch(location)));
+        ensure!(
+            ledger.set(Location(6404), account)
+                == Err(LedgerError::InvalidLocation(Location(6404)))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_in_memory_ledger_get_or_create_account() -> anyhow::Result<()> {
+        let db = rocksdb::DB::open_for_read_only(&Options::default(), DB_PATH_BERKELEY, true)?;
+        let genesis_ledger: RocksDbGenesisLedger<20, Account> = RocksDbGenesisLedger::new(&db);
+        let accounts: Vec<Account> = genesis_ledger.accounts().flatten().collect();
+        let (head, tail) = accounts.split_at(100);
+        let mut ledger = InMemoryLedger::from_accounts(20, head.to_vec())?;
+
+        let existing = head[10].clone();
+        ensure!(
+            ledger.get_or_create_account(&existing.account_id(), existing)?
+                == (GetOrCreated::Existed, Location(10))
+        );
+
+        let new_account = tail[0].clone();
+        let account_id = new_account.account_id();
+        ensure!(ledger.get_account(&account_id)?.is_none());
+        ensure!(
+            ledger.get_or_create_account(&account_id, new_account)?
+                == (GetOrCreated::Added, Location(100))
+        );
+        ensure!(ledger.num_accounts() == 101);
+        ensure!(ledger.get_account(&account_id)?.is_some());
+
+        let mut expected = MinaLedgerMerkleTree::new(20);
+        expected.add_batch(accounts.into_iter().take(101));
+        ensure!(ledger.merkle_root() == expected.root());
+        Ok(())
+    }
+
+    #[test]
+    fn test_in_memory_ledger_out_of_leaves() -> anyhow::Result<()> {
+        let db = rocksdb::DB::open_for_read_only(&Options::default(), DB_PATH_BERKELEY, true)?;
+        let genesis_ledger: RocksDbGenesisLedger<20, Account> = RocksDbGenesisLedger::new(&db);
+        let accounts: Vec<Account> = genesis_ledger.accounts().flatten().take(5).collect();
+        ensure!(
+            InMemoryLedger::from_accounts(2, accounts.clone()).err()
+                == Some(LedgerError::OutOfLeaves(4))
+        );
+        let mut ledger = InMemoryLedger::from_accounts(2, accounts[..4].to_vec())?;
+        ensure!(
+            ledger.get_or_create_account(&accounts[4].account_id(), accounts[4].clone())
+                == Err(LedgerError::OutOfLeaves(4))
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use anyhow::ensure;
+    use mina_crypto::hash::{CoinBaseHash, StateHash};
+    use mina_ledger::*;
+    use mina_rs_base::types::*;
+    use mina_serialization_types::json::ExternalTransitionJson;
+    use proof_systems::{mina_hasher::Fp, mina_signer::CompressedPubKey};
+    use std::str::FromStr;
+
+    const MAINNET_GENESIS_PENDING_COINBASE_HASH: &str =
+        "2n1tLdP2gkifmyVmrmzYXTS4ohPbZPJn6Qq4x55ywrbRWB4543cC";
+
+    #[test]
+    fn test_pending_coinbase_genesis_hash() -> anyhow::Result<()> {
+        let depth = PendingCoinbase::depth(7, 2);
+        ensure!(depth == 5);
+
+        let mut pending_coinbase = PendingCoinbase::new(depth);
+        ensure!(pending_coinbase.capacity() == 32);
+        ensure!(pending_coinbase.num_stacks() == 0);
+        ensure!(
+            pending_coinbase.merkle_root()
+                == CoinBaseHash::from_str(MAINNET_GENESIS_PENDING_COINBASE_HASH)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_pending_coinbase_stacks() -> anyhow::Result<()> {
+        let receiver = CompressedPubKey::from_address(
+            "B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg",
+        )?;
+        let mut pending_coinbase = PendingCoinbase::new(2);
+        let empty_root = pending_coinbase.merkle_root();
+
+        ensure!(pending_coinbase.add_state(Fp::from(1u64)) == Err(PendingCoinbaseError::NoStack));
+        ensure!(pending_coinbase.pop_oldest() == Err(PendingCoinbaseError::NoStack));
+
+        pending_coinbase.new_stack()?;
+        ensure!(pending_coinbase.latest_stack() == Some(Stack::empty()));
+        pending_coinbase.add_coinbase(&receiver, Amount(720_000_000_000))?;
+        pending_coinbase.add_state(Fp::from(1u64))?;
+        let first = pending_coinbase.latest_stack().unwrap();
+        ensure!(first.data == CoinbaseStack::empty().push(&receiver, Amount(720_000_000_000)));
+        ensure!(first.state == StateStack::empty().push(Fp::from(1u64)));
+        ensure!(pending_coinbase.merkle_root() != empty_root);
+
+        // A new stack continues from the state stack of the latest one
+        pending_coinbase.new_stack()?;
+        let second = pending_coinbase.latest_stack().unwrap();
+        ensure!(second.data == CoinbaseStack::empty());
+        ensure!(second.state == StateStack::create(first.state.curr));
+        ensure!(pending_coinbase.oldest_stack() == Some(first));
+
+        pending_coinbase.new_stack()?;
+        pending_coinbase.new_stack()?;
+        ensure!(pending_coinbase.new_stack() == Err(PendingCoinbaseError::Full(4)));
+
+        ensure!(pending_coinbase.pop_oldest()? == first);
+        ensure!(pending_coinbase.num_stacks() == 3);
+        // The leaf of the popped stack is reused
+        pending_coinbase.new_stack()?;
+        while pending_coinbase.num_stacks() > 0 {
+            pending_coinbase.pop_oldest()?;
+        }
+        ensure!(pending_coinbase.merkle_root() == empty_root);
+        Ok(())
+    }
+
+    fn coinbase_stack(hash: &StateHash) -> CoinbaseStack {
+        CoinbaseStack(hash.try_into().unwrap())
+    }
+
+    #[test]
+    fn test_coinbase_stack_mainnet_statements() -> anyhow::Result<()> {
+        let blocks: Vec<ExternalTransition> = test_fixtures::JSON_TEST_BLOCKS
+            .values()
+            .map(|json| {
+                serde_json::from_value::<ExternalTransitionJson>(json.clone())
+                    .unwrap()
+                    .into()
+            })
+            .collect();
+        let pre_diffs = || {
+            blocks.iter().flat_map(|block| {
+                std::iter::once(block.staged_ledger_diff.diff.diff_two())
+                    .chain(block.staged_ledger_diff.diff.diff_one())
+            })
+        };
+        let statements: Vec<&Statement> = pre_diffs()
+            .flat_map(|pre_diff| pre_diff.completed_works.iter())
+            .flat_map(|work| work.statements())
+            .collect();
+
+        // A new stack starts without coinbases, so the statements that start
+        // at the first transaction of a tree start from the empty coinbase stack
+        ensure!(statements.iter().any(|statement| {
+            coinbase_stack(&statement.pending_coinbase_stack_state.source.data_stack)
+                == CoinbaseStack::empty()
+        }));
+
+        // The receivers of the coinbases are not part of the statements,
+        // the candidates are the public keys that appear in the fixtures
+        let mut candidates: Vec<CompressedPubKey> = vec![];
+        for block in blocks.iter() {
+            let consensus_state = &block.protocol_state.body.consensus_state;
+            candidates.push(consensus_state.coinbase_receiver.clone());
+            candidates.push(consensus_state.block_creator.clone());
+        }
+        for pre_diff in pre_diffs() {
+            candidates.extend(pre_diff.completed_works.iter().map(|w| w.prover.clone()));
+            candidates.extend(
+                pre_diff
+                    .coinbase
+                    .fee_transfers()
+                    .into_iter()
+                    .map(|ft| ft.receiver_pk.clone()),
+            );
+            for command in pre_diff.commands.iter() {
+                let UserCommand::SignedCommand(signed_command) = &command.data;
+                candidates.push(signed_command.payload.common.fee_payer_pk.clone());
+                if let SignedCommandPayloadBody::PaymentPayload(payment) =
+                    &signed_command.payload.body
+                {
+                    candidates.push(payment.receiver_pk.clone());
+                }
+            }
+        }
+        candidates.sort_by_key(|pk| pk.into_address());
+        candidates.dedup();
+
+        // Statements that span a single coinbase push it to the coinbase stack
+        let coinbase_amounts = [Amount(720_000_000_000), Amount(1_440_000_000_000)];
+        let pushed = statements.iter().filter(|statement| {
+            let stack_state = &statement.pending_coinbase_stack_state;
+            let source = coinbase_stack(&stack_state.source.data_stack);
+            let target = coinbase_stack(&stack_state.target.data_stack);
+            source != target
+                && coinbase_amounts.contains(&statement.supply_increase)
+                && candidates
+                    .iter()
+                    .any(|pk| source.push(pk, statement.supply_increase) == target)
+        });
+        ensure!(pushed.count() > 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,449 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use anyhow::ensure;
+    use mina_consensus::genesis::*;
+    use mina_crypto::hash::*;
+    use mina_ledger::*;
+    use mina_rs_base::user_commands::signed_command::builder::*;
+    use mina_rs_base::{account::*, types::*, BinProtSerializationType};
+    use mina_serialization_types::{json::ExternalTransitionJson, v1::HashV1};
+    use proof_systems::mina_signer::Signature;
+    use rocksdb::*;
+
+    const DB_PATH_LEGACY: &str =  "test-data/genesis_ledger_6a887ea130e53b06380a9ab27b327468d28d4ce47515a0cc59759d4a3912f0ef/";
+    const DB_PATH_BERKELEY: &str =  "test-data/genesis_ledger_a99a1ff63d4ba4a07cc6bedbff3e23bd6c1f482f9ecef33abdf7fb817564cc89/";
+
+    const MINA: u64 = 1_000_000_000;
+
+    fn genesis_accounts() -> anyhow::Result<Vec<Account>> {
+        let db = rocksdb::DB::open_for_read_only(&Options::default(), DB_PATH_BERKELEY, true)?;
+        let genesis_ledger: RocksDbGenesisLedger<20, Account> = RocksDbGenesisLedger::new(&db);
+        Ok(genesis_ledger.accounts().flatten().collect())
+    }
+
+    // Apart from the mainnet genesis block, the mainnet blocks in test-fixtures are far away
+    // from genesis (77748 onwards) and the ledgers they are built upon are not available
+    // in test-data. The blocks below are built on top of the berkeley genesis ledger instead,
+    // with the expected statuses and ledger hashes computed by applying the same transactions
+    // directly.
+    #[test]
+    fn test_replay_block() -> anyhow::Result<()> {
+        let accounts = genesis_accounts()?;
+        let constants = ConstraintConstants::berkeley();
+        let mut candidates = accounts[..100]
+            .iter()
+            .filter(|a| a.timing == Timing::Untimed && a.balance.0 > 100 * MINA);
+        let sender = candidates.next().unwrap().clone();
+        let receiver = candidates.next().unwrap().clone();
+        let producer = candidates.next().unwrap().clone();
+
+        let mut block = ExternalTransition::from_genesis_config(&MAINNET_CONFIG);
+        let consensus_state = &mut block.protocol_state.body.consensus_state;
+        consensus_state.coinbase_receiver = producer.public_key.clone();
+        consensus_state.supercharge_coinbase = false;
+        let txn_global_slot = consensus_state.global_slot_since_genesis;
+
+        // Compute the expected outcome of the block
+        let mut expected_ledger = InMemoryLedger::from_accounts(20, accounts[..100].to_vec())?;
+        // No proof is emitted, so the snarked ledger stays the genesis one
+        block
+            .protocol_state
+            .body
+            .blockchain_state
+            .snarked_ledger_hash = (&expected_ledger.merkle_root().unwrap()).into();
+        let payload = SignedTransferCommandBuilder::new(
+            sender.public_key.clone(),
+            receiver.public_key.clone(),
+            10 * MINA,
+            3 * MINA,
+            sender.nonce,
+        )
+        .build();
+        let status = apply_signed_command_payload(
+            &mut expected_ledger,
+            &constants,
+            txn_global_slot,
+            &payload,
+        )?;
+        let coinbase_balances = apply_coinbase(
+            &mut expected_ledger,
+            &constants,
+            txn_global_slot,
+            &Coinbase {
+                receiver: producer.public_key.clone(),
+                amount: constants.coinbase_amount,
+                fee_transfer: None,
+            },
+        )?;
+        // Without snark work, all user command fees go to the producer
+        let fee_transfer_balances = apply_fee_transfer(
+            &mut expected_ledger,
+            &constants,
+            txn_global_slot,
+            &SingleFeeTransfer::new(producer.public_key.clone(), Amount(3 * MINA)),
+            None,
+        )?;
+        let expected_hash: LedgerHash = (&expected_ledger.merkle_root().unwrap()).into();
+
+        block.staged_ledger_diff.diff = StagedLedgerDiffTuple::new(
+            StagedLedgerPreDiff {
+                completed_works: vec![],
+                commands: vec![UserCommandWithStatus {
+                    data: UserCommand::SignedCommand(SignedCommand {
+                        payload,
+                        signer: sender.public_key.clone(),
+                        signature: Signature::new(Default::default(), Default::default()),
+                    }),
+                    status,
+                }],
+                coinbase: CoinBase::One(None),
+                internal_command_balances: vec![
+                    InternalCommandBalanceData::CoinBase(coinbase_balances),
+                    InternalCommandBalanceData::FeeTransfer(fee_transfer_balances),
+                ],
+            },
+            None,
+        );
+        block
+            .protocol_state
+            .body
+            .blockchain_state
+            .staged_ledger_hash
+            .non_snark
+            .ledger_hash = expected_hash.clone();
+
+        let ledger = InMemoryLedger::from_accounts(20, accounts[..100].to_vec())?;
+        let mut replayer = BlockReplayer::new(ledger, constants.clone())?;
+        ensure!(replayer.apply_block(&block)? == expected_hash);
+        let producer_after = replayer
+            .ledger()
+            .get_account(&producer.account_id())?
+            .unwrap();
+        ensure!(producer_after.balance.0 == producer.balance.0 + 720 * MINA + 3 * MINA);
+
+        // A block with an unexpected user command status is rejected
+        let mut tampered = block.clone();
+        let mut pre_diff = tampered.staged_ledger_diff.diff.diff_two().clone();
+        let expected_status = TransactionStatus::Failed(
+            vec![TransactionStatusFailedType::SourceInsufficientBalance],
+            TransactionStatusBalanceData {
+                fee_payer_balance: None,
+                source_balance: None,
+                receiver_balance: None,
+            },
+        );
+        pre_diff.commands[0].status = expected_status.clone();
+        tampered.staged_ledger_diff.diff = StagedLedgerDiffTuple::new(pre_diff, None);
+        let ledger = InMemoryLedger::from_accounts(20, accounts[..100].to_vec())?;
+        let mut replayer = BlockReplayer::new(ledger, constants.clone())?;
+        match replayer.apply_block(&tampered) {
+            Err(ReplayError::UserCommandStatusMismatch {
+                index: 0, expected, ..
+            }) => ensure!(expected == expected_status),
+            r => anyhow::bail!("Unexpected replay result: {r:?}"),
+        }
+
+        // A block with an unexpected staged ledger hash is rejected
+        let mut tampered = block;
+        tampered
+            .protocol_state
+            .body
+            .blockchain_state
+            .staged_ledger_hash
+            .non_snark
+            .ledger_hash = LedgerHash::default();
+        let ledger = InMemoryLedger::from_accounts(20, accounts[..100].to_vec())?;
+        let mut replayer = BlockReplayer::new(ledger, constants)?;
+        ensure!(
+            replayer.apply_block(&tampered)
+                == Err(ReplayError::StagedLedgerHashMismatch {
+                    expected: LedgerHash::default(),
+                    actual: expected_hash,
+                })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_two_pre_diffs() -> anyhow::Result<()> {
+        let accounts = genesis_accounts()?;
+        let constants = ConstraintConstants::berkeley();
+        let producer = accounts[0].clone();
+
+        let mut block = ExternalTransition::from_genesis_config(&MAINNET_CONFIG);
+        let consensus_state = &mut block.protocol_state.body.consensus_state;
+        consensus_state.coinbase_receiver = producer.public_key.clone();
+        consensus_state.supercharge_coinbase = true;
+        let txn_global_slot = consensus_state.global_slot_since_genesis;
+
+        // The coinbase is split into two parts in the second pre-diff,
+        // the first part is the account creation fee when it has no fee transfer
+        let supercharged = Amount(constants.coinbase_amount.0 * 2);
+        let mut expected_ledger = InMemoryLedger::from_accounts(20, accounts[..100].to_vec())?;
+        // No proof is emitted, so the snarked ledger stays the genesis one
+        block
+            .protocol_state
+            .body
+            .blockchain_state
+            .snarked_ledger_hash = (&expected_ledger.merkle_root().unwrap()).into();
+        let mut expected_balances = vec![];
+        for amount in [
+            constants.account_creation_fee,
+            Amount(supercharged.0 - constants.account_creation_fee.0),
+        ] {
+            let balances = apply_coinbase(
+                &mut expected_ledger,
+                &constants,
+                txn_global_slot,
+                &Coinbase {
+                    receiver: producer.public_key.clone(),
+                    amount,
+                    fee_transfer: None,
+                },
+            )?;
+            expected_balances.push(InternalCommandBalanceData::CoinBase(balances));
+        }
+        let expected_hash: LedgerHash = (&expected_ledger.merkle_root().unwrap()).into();
+
+        block.staged_ledger_diff.diff = StagedLedgerDiffTuple::new(
+            StagedLedgerPreDiff::default(),
+            Some(StagedLedgerPreDiff {
+                completed_works: vec![],
+                commands: vec![],
+                coinbase: CoinBase::Two(None, None),
+                internal_command_balances: expected_balances,
+            }),
+        );
+        block
+            .protocol_state
+            .body
+            .blockchain_state
+            .staged_ledger_hash
+            .non_snark
+            .ledger_hash = expected_hash.clone();
+
+        let ledger = InMemoryLedger::from_accounts(20, accounts[..100].to_vec())?;
+        let mut replayer = BlockReplayer::new(ledger, constants)?;
+        ensure!(replayer.apply_block(&block)? == expected_hash);
+        let producer_after = replayer
+            .ledger()
+            .get_account(&producer.account_id())?
+            .unwrap();
+        ensure!(producer_after.balance.0 == producer.balance.0 + supercharged.0);
+        Ok(())
+    }
+
+    fn ledger_proof(
+        source: &LedgerHash,
+        target: &LedgerHash,
+        template: &TransactionSnark,
+    ) -> Box<TransactionSnark> {
+        let mut proof = template.clone();
+        proof.statement.source = HashV1::from(source.clone()).into();
+        proof.statement.target = HashV1::from(target.clone()).into();
+        Box::new(proof)
+    }
+
+    #[test]
+    fn test_replay_tracks_snarked_ledger() -> anyhow::Result<()> {
+        let accounts = genesis_accounts()?;
+        // Trees of two transactions whose work is not delayed, so that the
+        // proof of the first tree is emitted by the third block
+        let constants = ConstraintConstants {
+            transaction_capacity_log_2: 1,
+            work_delay: 0,
+            ..ConstraintConstants::berkeley()
+        };
+        let producer = accounts[0].clone();
+        let json_block = test_fixtures::JSON_TEST_BLOCKS
+            .get("mainnet-116121-3NK6myZRzc3GvS5iydv88on2XTEU2btYrjMVkgtbuoeXASRipSa6.json")
+            .unwrap();
+        let fixture: ExternalTransition =
+            serde_json::from_value::<ExternalTransitionJson>(json_block.clone())?.into();
+        let template =
+            fixture.staged_ledger_diff.diff.diff_two().completed_works[0].snarks()[0].clone();
+        let work = |proofs: OneORTwo| TransactionSnarkWork {
+            fee: Amount(0),
+            proofs,
+            prover: producer.public_key.clone(),
+        };
+
+        // Each block applies the two parts of a coinbase, which are two transactions,
+        // the hashes of the ledgers between them are returned along with the block
+        let mut expected_ledger = InMemoryLedger::from_accounts(20, accounts[..100].to_vec())?;
+        let genesis_hash: LedgerHash = (&expected_ledger.merkle_root().unwrap()).into();
+        let mut next_block = |completed_works: Vec<TransactionSnarkWork>|
+         -> anyhow::Result<(ExternalTransition, Vec<LedgerHash>)> {
+            let mut block = ExternalTransition::from_genesis_config(&MAINNET_CONFIG);
+            let consensus_state = &mut block.protocol_state.body.consensus_state;
+            consensus_state.coinbase_receiver = producer.public_key.clone();
+            consensus_state.supercharge_coinbase = false;
+            let txn_global_slot = consensus_state.global_slot_since_genesis;
+            let mut hashes = vec![(&expected_ledger.merkle_root().unwrap()).into()];
+            let mut balances = vec![];
+            for amount in [
+                constants.account_creation_fee,
+                Amount(constants.coinbase_amount.0 - constants.account_creation_fee.0),
+            ] {
+                let coinbase_balances = apply_coinbase(
+                    &mut expected_ledger,
+                    &constants,
+                    txn_global_slot,
+                    &Coinbase {
+                        receiver: producer.public_key.clone(),
+                        amount,
+                        fee_transfer: None,
+                    },
+                )?;
+                balances.push(InternalCommandBalanceData::CoinBase(coinbase_balances));
+                hashes.push((&expected_ledger.merkle_root().unwrap()).into());
+            }
+            block.staged_ledger_diff.diff = StagedLedgerDiffTuple::new(
+                StagedLedgerPreDiff {
+                    completed_works,
+                    commands: vec![],
+                    coinbase: CoinBase::Two(None, None),
+                    internal_command_balances: balances,
+                },
+                None,
+            );
+            block
+                .protocol_state
+                .body
+                .blockchain_state
+                .staged_ledger_hash
+                .non_snark
+                .ledger_hash = hashes[2].clone();
+            Ok((block, hashes))
+        };
+
+        let (mut first_block, first) = next_block(vec![])?;
+        // The second block proves the transactions of the first one
+        let (mut second_block, _) = next_block(vec![work(OneORTwo::Two(
+            ledger_proof(&first[0], &first[1], &template),
+            ledger_proof(&first[1], &first[2], &template),
+        ))])?;
+        // The third block merges them, which emits the proof of the first tree
+        let (mut third_block, _) = next_block(vec![work(OneORTwo::One(ledger_proof(
+            &first[0], &first[2], &template,
+        )))])?;
+        for (block, snarked_ledger_hash) in [
+            (&mut first_block, &genesis_hash),
+            (&mut second_block, &genesis_hash),
+            (&mut third_block, &first[2]),
+        ] {
+            block
+                .protocol_state
+                .body
+                .blockchain_state
+                .snarked_ledger_hash = snarked_ledger_hash.clone();
+        }
+
+        let new_replayer = || -> anyhow::Result<_> {
+            let ledger = InMemoryLedger::from_accounts(20, accounts[..100].to_vec())?;
+            Ok(BlockReplayer::new(ledger, constants.clone())?)
+        };
+        let mut replayer = new_replayer()?;
+        ensure!(replayer.snarked_ledger_hash() == &genesis_hash);
+        for (block, snarked_ledger_hash) in [
+            (&first_block, &genesis_hash),
+            (&second_block, &genesis_hash),
+            (&third_block, &first[2]),
+        ] {
+            replayer.apply_block(block)?;
+            ensure!(replayer.snarked_ledger_hash() == snarked_ledger_hash);
+        }
+
+        // A block whose snarked ledger hash is not the target of the emitted proof is rejected
+        let mut replayer = new_replayer()?;
+        replayer.apply_block(&first_block)?;
+        replayer.apply_block(&second_block)?;
+        let mut tampered = third_block;
+        tampered
+            .protocol_state
+            .body
+            .blockchain_state
+            .snarked_ledger_hash = genesis_hash.clone();
+        ensure!(
+            replayer.apply_block(&tampered)
+                == Err(ReplayError::SnarkedLedgerHashMismatch {
+                    expected: genesis_hash,
+                    actual: first[2].clone(),
+                })
+        );
+
+        // A proof whose ledger hashes do not match the job it completes is rejected
+        let mut replayer = new_replayer()?;
+        replayer.apply_block(&first_block)?;
+        let mut tampered = second_block;
+        let mut pre_diff = tampered.staged_ledger_diff.diff.diff_two().clone();
+        pre_diff.completed_works = vec![work(OneORTwo::Two(
+            ledger_proof(&first[0], &first[2], &template),
+            ledger_proof(&first[2], &first[2], &template),
+        ))];
+        tampered.staged_ledger_diff.diff = StagedLedgerDiffTuple::new(pre_diff, None);
+        ensure!(
+            replayer.apply_block(&tampered)
+                == Err(ReplayError::ProofMismatch {
+                    index: 0,
+                    expected: LedgerTransition {
+                        source: first[0].clone(),
+                        target: first[1].clone(),
+                    },
+                    actual: LedgerTransition {
+                        source: first[0].clone(),
+                        target: first[2].clone(),
+                    },
+                })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_genesis_block_on_other_ledger() -> anyhow::Result<()> {
+        // The mainnet genesis block does not describe the berkeley genesis ledger,
+        // replaying its empty diff must be rejected by the staged ledger hash check
+        let accounts = genesis_accounts()?;
+        let mut ledger = InMemoryLedger::from_accounts(20, accounts)?;
+        let actual: LedgerHash = (&ledger.merkle_root().unwrap()).into();
+        let block = ExternalTransition::from_genesis_config(&MAINNET_CONFIG);
+        let expected = block
+            .protocol_state
+            .body
+            .blockchain_state
+            .staged_ledger_hash
+            .non_snark
+            .ledger_hash
+            .clone();
+        let mut replayer = BlockReplayer::new(ledger, ConstraintConstants::berkeley())?;
+        ensure!(
+            replayer.apply_block(&block)
+                == Err(ReplayError::StagedLedgerHashMismatch { expected, actual })
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "the legacy account hash is incomplete, see test_iterate_database"]
+    fn test_replay_mainnet_genesis_block() -> anyhow::Result<()> {
+        // The genesis block of mainnet has an empty diff, replaying it on the mainnet
+        // genesis ledger checks its staged and snarked ledger hashes
+        let db = rocksdb::DB::open_for_read_only(&Options::default(), DB_PATH_LEGACY, true)?;
+        let genesis_ledger: RocksDbGenesisLedger<20, AccountLegacy> =
+            RocksDbGenesisLedger::new(&db);
+        let ledger = InMemoryLedger::from_genesis_ledger(&genesis_ledger)?;
+        let block = ExternalTransition::try_from_binprot(
+            test_fixtures::GENESIS_BLOCK_MAINNET.bytes.as_slice(),
+        )?;
+        let blockchain_state = &block.protocol_state.body.blockchain_state;
+
+        let mut replayer = BlockReplayer::new(ledger, ConstraintConstants::mainnet())?;
+        ensure!(replayer.snarked_ledger_hash() == &blockchain_state.snarked_ledger_hash);
+        ensure!(replayer.apply_block(&block)? == blockchain_state.genesis_ledger_hash);
+        ensure!(replayer.snarked_ledger_hash() == &blockchain_state.snarked_ledger_hash);
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use anyhow::ensure;
+    use mina_ledger::*;
+    use mina_merkle::*;
+    use mina_rs_base::{account::*, types::*};
+    use rocksdb::*;
+    use std::path::{Path, PathBuf};
+
+    const DB_PATH_BERKELEY: &str =  "test-data/genesis_ledger_a99a1ff63d4ba4a07cc6bedbff3e23bd6c1f482f9ecef33abdf7fb817564cc89/";
+
+    fn genesis_accounts() -> anyhow::Result<Vec<Account>> {
+        let db = rocksdb::DB::open_for_read_only(&Options::default(), DB_PATH_BERKELEY, true)?;
+        let genesis_ledger: RocksDbGenesisLedger<20, Account> = RocksDbGenesisLedger::new(&db);
+        Ok(genesis_ledger.accounts().flatten().collect())
+    }
+
+    /// Path to a fresh directory for a database that is written by the test
+    fn temp_db_path(name: &str) -> anyhow::Result<PathBuf> {
+        let path =
+            std::env::temp_dir().join(format!("mina-rs-ledger-{name}-{}", std::process::id()));
+        if path.exists() {
+            std::fs::remove_dir_all(&path)?;
+        }
+        Ok(path)
+    }
+
+    /// Copies the genesis ledger database so that it can be opened for writing
+    fn copy_genesis_db(name: &str) -> anyhow::Result<PathBuf> {
+        let path = temp_db_path(name)?;
+        std::fs::create_dir_all(&path)?;
+        for entry in std::fs::read_dir(Path::new(DB_PATH_BERKELEY))? {
+            let entry = entry?;
+            std::fs::copy(entry.path(), path.join(entry.file_name()))?;
+        }
+        Ok(path)
+    }
+
+    #[test]
+    fn test_rocksdb_ledger_open_genesis_ledger() -> anyhow::Result<()> {
+        let accounts = genesis_accounts()?;
+        let path = copy_genesis_db("open")?;
+        let mut ledger = RocksDbLedger::<Account>::open(&path, 20)?;
+        ensure!(ledger.depth() == 20);
+        ensure!(ledger.num_accounts() == 6404);
+
+        // The stored root hash matches the one of the accounts
+        let mut expected = InMemoryLedger::from_accounts(20, accounts.clone())?;
+        let root = ledger.merkle_root();
+        ensure!(root.is_some());
+        ensure!(root == expected.merkle_root());
+
+        for (i, account) in accounts.iter().enumerate().step_by(500) {
+            let location = ledger.location_of_account(&account.account_id())?;
+            ensure!(location == Some(Location(i)));
+            ensure!(ledger.get(Location(i))?.map(|a| a.account_id()) == Some(account.account_id()));
+            let proof = ledger.merkle_proof(Location(i))?;
+            ensure!(proof.is_some());
+            ensure!(proof.unwrap().verify(&root.unwrap()));
+        }
+        ensure!(ledger.get(Location(6404))?.is_none());
+        ensure!(ledger.merkle_proof(Location(6404))?.is_none());
+        std::fs::remove_dir_all(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_rocksdb_ledger_set() -> anyhow::Result<()> {
+        let accounts = genesis_accounts()?;
+        let path = copy_genesis_db("set")?;
+        let mut expected = InMemoryLedger::from_accounts(20, accounts)?;
+        {
+            let mut ledger = RocksDbLedger::<Account>::open(&path, 20)?;
+            let root_before = ledger.merkle_root();
+            for location in [Location(0), Location(42), Location(6403)] {
+                let mut account = ledger.get(location)?.unwrap();
+                account.balance = Amount(account.balance.0 + 1);
+                account.nonce = AccountNonce(account.nonce.0 + 1);
+                ledger.set(location, account.clone())?;
+                expected.set(location, account.clone())?;
+                let stored = ledger.get(location)?.unwrap();
+                ensure!(stored.balance == account.balance && stored.nonce == account.nonce);
+                ensure!(ledger.merkle_root() == expected.merkle_root());
+            }
+            ensure!(ledger.merkle_root() != root_before);
+
+            // Account id must match the one stored at the location
+            let other = ledger.get(Location(43))?.unwrap();
+            ensure!(
+                ledger.set(Location(42), other.clone())
+                    == Err(LedgerError::AccountIdMismatch(Location(42)))
+            );
+            ensure!(
+                ledger.set(Location(6404), other)
+                    == Err(LedgerError::InvalidLocation(Location(6404)))
+            );
+        }
+
+        // Updates are persisted
+        let mut ledger = RocksDbLedger::<Account>::open(&path, 20)?;
+        ensure!(ledger.merkle_root() == expected.merkle_root());
+        ensure!(
+            ledger.get(Location(42))?.unwrap().balance
+                == expected.get(Location(42))?.unwrap().balance
+        );
+        std::fs::remove_dir_all(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_rocksdb_ledger_get_or_create_account() -> anyhow::Result<()> {
+        let accounts = genesis_accounts()?;
+        let (head, tail) = accounts.split_at(100);
+        let path = temp_db_path("create")?;
+        let mut expected = InMemoryLedger::new(20);
+        {
+            let mut ledger = RocksDbLedger::<Account>::open(&path, 20)?;
+            ensure!(ledger.num_accounts() == 0);
+            ensure!(ledger.merkle_root() == expected.merkle_root());
+
+            for (i, account) in head.iter().enumerate() {
+                ensure!(
+                    ledger.get_or_create_account(&account.account_id(), account.clone())?
+                        == (GetOrCreated::Added, Location(i))
+                );
+                expected.get_or_create_account(&account.account_id(), account.clone())?;
+            }
+            ensure!(ledger.num_accounts() == 100);
+            ensure!(ledger.merkle_root() == expected.merkle_root());
+
+            let existing = head[10].clone();
+            ensure!(
+                ledger.get_or_create_account(&existing.account_id(), existing)?
+                    == (GetOrCreated::Existed, Location(10))
+            );
+            ensure!(
+                ledger.get_or_create_account(&tail[0].account_id(), tail[1].clone())
+                    == Err(LedgerError::AccountIdMismatch(Location(100)))
+            );
+        }
+
+        // Accounts and their locations are persisted
+        let mut ledger = RocksDbLedger::<Account>::open(&path, 20)?;
+        ensure!(ledger.num_accounts() == 100);
+        ensure!(ledger.merkle_root() == expected.merkle_root());
+        let new_account = tail[0].clone();
+        let account_id = new_account.account_id();
+        ensure!(ledger.get_account(&account_id)?.is_none());
+        ensure!(
+            ledger.get_or_create_account(&account_id, new_account)?
+                == (GetOrCreated::Added, Location(100))
+        );
+        ensure!(ledger.get_account(&account_id)?.is_some());
+
+        let mut tree = MinaLedgerMerkleTree::new(20);
+        tree.add_batch(accounts.into_iter().take(101));
+        ensure!(ledger.merkle_root() == tree.root());
+        std::fs::remove_dir_all(&path)?;
+        Ok(())
+    }
+}
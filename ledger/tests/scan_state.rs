@@ -0,0 +1,237 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use anyhow::ensure;
+    use mina_consensus::genesis::*;
+    use mina_ledger::*;
+    use mina_rs_base::types::*;
+    use mina_serialization_types::json::ExternalTransitionJson;
+
+    // A scan state where the proof of a merge job is the sum of its children
+    type SumScan = ParallelScan<u64, u64>;
+
+    fn completed_works(block_path: &str) -> Vec<TransactionSnarkWork> {
+        let json_block = test_fixtures::JSON_TEST_BLOCKS.get(block_path).unwrap();
+        let block: ExternalTransition =
+            serde_json::from_value::<ExternalTransitionJson>(json_block.clone())
+                .unwrap()
+                .into();
+        block
+            .staged_ledger_diff
+            .diff
+            .diff_two()
+            .completed_works
+            .clone()
+    }
+
+    #[test]
+    fn test_parallel_scan_update() -> anyhow::Result<()> {
+        ensure!(SumScan::empty(3, 2) == Err(ScanStateError::InvalidMaxBaseJobs(3)));
+
+        // Without delay, every level of a tree is worked on by the next update
+        let mut scan = SumScan::empty(4, 0)?;
+        ensure!(scan.free_space() == 4);
+        ensure!(scan.all_jobs().is_empty());
+        ensure!(
+            scan.update(vec![0; 5], vec![])
+                == Err(ScanStateError::TooMuchData {
+                    max_base_jobs: 4,
+                    data: 5,
+                })
+        );
+
+        // A new tree is started once the newest one is full
+        ensure!(scan.update(vec![1, 2, 3, 4], vec![])?.is_none());
+        ensure!(scan.trees().count() == 2);
+        ensure!(scan.free_space() == 4);
+        ensure!(
+            scan.all_jobs()
+                == vec![
+                    AvailableJob::Base(&1),
+                    AvailableJob::Base(&2),
+                    AvailableJob::Base(&3),
+                    AvailableJob::Base(&4),
+                ]
+        );
+
+        // The merge jobs of a tree are available after the next tree is started
+        ensure!(scan.update(vec![5, 6], vec![1, 2, 3, 4])?.is_none());
+        ensure!(scan.all_jobs().is_empty());
+        ensure!(scan.update(vec![7, 8], vec![])?.is_none());
+        ensure!(scan.trees().count() == 3);
+        ensure!(
+            scan.all_jobs()
+                == vec![
+                    AvailableJob::Merge(&1, &2),
+                    AvailableJob::Merge(&3, &4),
+                    AvailableJob::Base(&5),
+                    AvailableJob::Base(&6),
+                    AvailableJob::Base(&7),
+                    AvailableJob::Base(&8),
+                ]
+        );
+
+        // A failed update leaves the scan state unchanged
+        let before = scan.clone();
+        ensure!(
+            scan.update(vec![], vec![0; 7])
+                == Err(ScanStateError::TooManyCompletedJobs {
+                    available: 6,
+                    completed: 7,
+                })
+        );
+        ensure!(scan == before);
+
+        // Filling the newest tree makes the root of the oldest tree available in the same update,
+        // the oldest tree is emitted once its root is proven
+        ensure!(
+            scan.update(vec![9, 10, 11, 12], vec![3, 7, 5, 6, 7, 8, 10])?
+                == Some((10, vec![1, 2, 3, 4]))
+        );
+        ensure!(scan.last_emitted_value() == Some(&(10, vec![1, 2, 3, 4])));
+        ensure!(scan.trees().count() == 3);
+        ensure!(scan.curr_job_seq_no() == 4);
+        ensure!(
+            scan.all_jobs()
+                == vec![
+                    AvailableJob::Merge(&5, &6),
+                    AvailableJob::Merge(&7, &8),
+                    AvailableJob::Base(&9),
+                    AvailableJob::Base(&10),
+                    AvailableJob::Base(&11),
+                    AvailableJob::Base(&12),
+                ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_scan_delay() -> anyhow::Result<()> {
+        // With a delay of 1, a tree is worked on every other tree
+        let mut scan = SumScan::empty(2, 1)?;
+        scan.update(vec![1, 2], vec![])?;
+        ensure!(scan.all_jobs().is_empty());
+        scan.update(vec![3, 4], vec![])?;
+        ensure!(scan.all_jobs() == vec![AvailableJob::Base(&1), AvailableJob::Base(&2)]);
+        scan.update(vec![5, 6], vec![1, 2])?;
+        ensure!(scan.all_jobs() == vec![AvailableJob::Base(&3), AvailableJob::Base(&4)]);
+        scan.update(vec![7, 8], vec![3, 4])?;
+        ensure!(
+            scan.all_jobs()
+                == vec![
+                    AvailableJob::Merge(&1, &2),
+                    AvailableJob::Base(&5),
+                    AvailableJob::Base(&6),
+                ]
+        );
+        ensure!(scan.update(vec![], vec![3, 5, 6])? == Some((3, vec![1, 2])));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_scan_bin_prot_and_hash() -> anyhow::Result<()> {
+        let hash = |scan: &SumScan| {
+            scan.hash(
+                |proof| proof.to_be_bytes().to_vec(),
+                |data| data.to_be_bytes().to_vec(),
+            )
+        };
+
+        let mut scan = SumScan::empty(2, 0)?;
+        let mut hashes = vec![hash(&scan)];
+        for (data, completed) in [
+            (vec![1, 2], vec![]),
+            (vec![3], vec![1, 2]),
+            (vec![4], vec![]),
+            (vec![], vec![3]),
+        ] {
+            scan.update(data, completed)?;
+            hashes.push(hash(&scan));
+
+            let mut bytes = vec![];
+            bin_prot::to_writer(&mut bytes, &scan)?;
+            let deserialized: SumScan = bin_prot::from_reader_strict(bytes.as_slice())?;
+            ensure!(deserialized == scan);
+            ensure!(hash(&deserialized) == hash(&scan));
+        }
+        hashes.dedup();
+        ensure!(hashes.len() == 5);
+
+        // Layout of an empty tree of depth 1:
+        // Node tag, depth 0, weights of the children and the Empty merge job,
+        // then Leaf tag, 2 bases with their weight and the Empty base job
+        let mut bytes = vec![];
+        bin_prot::to_writer(&mut bytes, &SumScan::empty(2, 0)?)?;
+        ensure!(
+            bytes
+                == vec![
+                    0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, // root merge node
+                    0x00, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, // base nodes
+                    0x00, // no other trees
+                    0x00, // no emitted value
+                    0x00, 0x02, 0x00, // seq no, max base jobs and delay
+                ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_state_mainnet_genesis_aux_hash() -> anyhow::Result<()> {
+        // The aux hash of the genesis staged ledger hash is the hash of an empty scan state
+        // whose trees have 2^7 base jobs, with a work delay of 2
+        let genesis = ExternalTransition::from_genesis_config(&MAINNET_CONFIG);
+        let aux_hash = &genesis
+            .protocol_state
+            .body
+            .blockchain_state
+            .staged_ledger_hash
+            .non_snark
+            .aux_hash;
+        let scan = TransactionScanState::empty(128, 2)?;
+        let hash = scan.hash(|_| unreachable!(), |_| unreachable!());
+        ensure!(hash[..] == aux_hash.0[..]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_transaction_scan_state_fill_work() -> anyhow::Result<()> {
+        let works = completed_works(
+            "mainnet-116121-3NK6myZRzc3GvS5iydv88on2XTEU2btYrjMVkgtbuoeXASRipSa6.json",
+        );
+        let (work, other_work) = {
+            let mut pairs = works
+                .iter()
+                .filter(|work| matches!(work.proofs, OneORTwo::Two(..)));
+            (pairs.next().unwrap(), pairs.next().unwrap())
+        };
+        let statements: Vec<Statement> = work.statements().into_iter().cloned().collect();
+
+        let mut scan = TransactionScanState::empty(2, 0)?;
+        scan.fill_work_and_enqueue_transactions(&[], statements.clone())?;
+
+        let mut invalid = scan.clone();
+        ensure!(
+            invalid.fill_work_and_enqueue_transactions(&[other_work.clone()], vec![])
+                == Err(ScanStateError::StatementMismatch(0))
+        );
+        ensure!(invalid == scan);
+
+        // The merge job is available once the next tree is started
+        scan.fill_work_and_enqueue_transactions(&[work.clone()], statements.clone())?;
+        let snarks: Vec<TransactionSnark> = work.snarks().into_iter().cloned().collect();
+        ensure!(
+            scan.all_jobs()
+                == vec![
+                    AvailableJob::Merge(&snarks[0], &snarks[1]),
+                    AvailableJob::Base(&statements[0]),
+                    AvailableJob::Base(&statements[1]),
+                ]
+        );
+
+        let bytes = scan.to_bin_prot()?;
+        ensure!(TransactionScanState::from_bin_prot(&bytes)? == scan);
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use anyhow::ensure;
+    use mina_ledger::*;
+    use mina_rs_base::{account::*, types::*};
+    use mina_serialization_types::json::ExternalTransitionJson;
+    use proof_systems::mina_signer::CompressedPubKey;
+
+    const MINA: u64 = 1_000_000_000;
+
+    fn read_block_json(block_path: &str) -> ExternalTransition {
+        let json_block = test_fixtures::JSON_TEST_BLOCKS.get(block_path).unwrap();
+        serde_json::from_value::<ExternalTransitionJson>(json_block.clone())
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn test_supercharged_coinbase() -> anyhow::Result<()> {
+        let constants = ConstraintConstants::mainnet();
+        ensure!(constants.coinbase_amount(false) == Some(Amount(720 * MINA)));
+        ensure!(constants.coinbase_amount(true) == Some(Amount(1440 * MINA)));
+        let overflowing = ConstraintConstants {
+            coinbase_amount: Amount(u64::MAX),
+            ..constants
+        };
+        ensure!(overflowing.coinbase_amount(true).is_none());
+
+        let public_key = CompressedPubKey::from_address(
+            "B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg",
+        )?;
+        let mut winner = new_account(
+            &AccountId::new(public_key, DEFAULT_TOKEN_ID),
+            PoseidonVersion::Legacy,
+        );
+        ensure!(supercharge_coinbase(&winner, GlobalSlotNumber(0)));
+
+        // A timed account is supercharged once fully vested
+        winner.timing = Timing::Timed(TimedData {
+            initial_minimum_balance: Amount(100),
+            cliff_time: BlockTime(10),
+            cliff_amount: Amount(10),
+            vesting_period: BlockTime(5),
+            vesting_increment: Amount(30),
+        });
+        ensure!(has_locked_tokens(&winner, GlobalSlotNumber(20)));
+        ensure!(!supercharge_coinbase(&winner, GlobalSlotNumber(20)));
+        ensure!(supercharge_coinbase(&winner, GlobalSlotNumber(25)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_total_currency() -> anyhow::Result<()> {
+        // No ledger proof is emitted between these blocks
+        let parent = read_block_json(
+            "mainnet-77748-3NKaBJsN1SehD6iJwRwJSFmVzJg5DXSUQVgnMxtH4eer4aF5BrDK.json",
+        );
+        let block = read_block_json(
+            "mainnet-77749-3NK3P5bJHhqR7xkZBquGGfq3sERUeXNYNma5YXRMjgCNsTJRZpgL.json",
+        );
+        let parent_state = &parent.protocol_state.body.consensus_state;
+        ensure!(
+            next_total_currency(parent_state, None)?
+                == block.protocol_state.body.consensus_state.total_currency
+        );
+
+        let works = read_block_json(
+            "mainnet-116121-3NK6myZRzc3GvS5iydv88on2XTEU2btYrjMVkgtbuoeXASRipSa6.json",
+        )
+        .staged_ledger_diff
+        .diff
+        .diff_two()
+        .completed_works
+        .clone();
+        let proof = works[0].snarks()[0];
+        ensure!(
+            next_total_currency(parent_state, Some(proof))?.0
+                == parent_state.total_currency.0 + proof.statement.supply_increase.0
+        );
+
+        let mut overflowing = parent_state.clone();
+        overflowing.total_currency = Amount(u64::MAX);
+        ensure!(proof.statement.supply_increase.0 > 0);
+        ensure!(
+            next_total_currency(&overflowing, Some(proof))
+                == Err(SupplyError::TotalCurrencyOverflow)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_predict_total_currency() -> anyhow::Result<()> {
+        let block = read_block_json(
+            "mainnet-116121-3NK6myZRzc3GvS5iydv88on2XTEU2btYrjMVkgtbuoeXASRipSa6.json",
+        );
+        let parent_state = &block.protocol_state.body.consensus_state;
+        let mut pairs = block
+            .staged_ledger_diff
+            .diff
+            .diff_two()
+            .completed_works
+            .iter()
+            .filter(|work| matches!(work.proofs, OneORTwo::Two(..)));
+        let (work, other_work) = (pairs.next().unwrap().clone(), pairs.next().unwrap().clone());
+
+        // Proving the base jobs does not emit a ledger proof
+        let mut scan = TransactionScanState::empty(2, 0)?;
+        scan.fill_work_and_enqueue_transactions(
+            &[],
+            work.statements().into_iter().cloned().collect(),
+        )?;
+        let diff_with_works = |completed_works: Vec<TransactionSnarkWork>| {
+            let mut pre_diff = block.staged_ledger_diff.diff.diff_two().clone();
+            pre_diff.completed_works = completed_works;
+            StagedLedgerDiff {
+                diff: StagedLedgerDiffTuple::new(pre_diff, None),
+            }
+        };
+        ensure!(
+            scan.predict_total_currency(parent_state, &diff_with_works(vec![work]))?
+                == parent_state.total_currency
+        );
+
+        // The completed works must match the jobs of the scan state
+        ensure!(
+            scan.predict_total_currency(parent_state, &diff_with_works(vec![other_work]))
+                == Err(SupplyError::ScanState(ScanStateError::StatementMismatch(0)))
+        );
+        ensure!(scan.all_jobs().len() == 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_predict_total_currency_emitted_proof() -> anyhow::Result<()> {
+        // No ledger proof is emitted between these blocks, the child block has no completed works
+        let parent = read_block_json(
+            "mainnet-77748-3NKaBJsN1SehD6iJwRwJSFmVzJg5DXSUQVgnMxtH4eer4aF5BrDK.json",
+        );
+        let child = read_block_json(
+            "mainnet-77749-3NK3P5bJHhqR7xkZBquGGfq3sERUeXNYNma5YXRMjgCNsTJRZpgL.json",
+        );
+        let parent_state = &parent.protocol_state.body.consensus_state;
+        let child_state = &child.protocol_state.body.consensus_state;
+        ensure!(
+            TransactionScanState::empty(1, 0)?
+                .predict_total_currency(parent_state, &child.staged_ledger_diff)?
+                == child_state.total_currency
+        );
+
+        // With a single base job, the root is proven by the first proof of the completed works,
+        // which emits a ledger proof and increases the total currency by its supply increase
+        let block = read_block_json(
+            "mainnet-116121-3NK6myZRzc3GvS5iydv88on2XTEU2btYrjMVkgtbuoeXASRipSa6.json",
+        );
+        let works = &block.staged_ledger_diff.diff.diff_two().completed_works;
+        let statement = works[0].statements()[0].clone();
+        ensure!(statement.supply_increase.0 > 0);
+        let mut scan = TransactionScanState::empty(1, 0)?;
+        scan.fill_work_and_enqueue_transactions(&[], vec![statement.clone()])?;
+        ensure!(scan.all_jobs().len() == 1);
+        let predicted = scan.predict_total_currency(child_state, &block.staged_ledger_diff)?;
+        ensure!(predicted.0 == child_state.total_currency.0 + statement.supply_increase.0);
+        ensure!(predicted == next_total_currency(child_state, Some(works[0].snarks()[0]))?);
+
+        // The scan state is left untouched
+        ensure!(scan.all_jobs().len() == 1);
+        ensure!(scan.last_emitted_value().is_none());
+        Ok(())
+    }
+}
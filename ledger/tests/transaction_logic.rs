@@ -0,0 +1,547 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use anyhow::ensure;
+    use mina_ledger::*;
+    use mina_rs_base::user_commands::signed_command::{builder::*, StakeDelegation};
+    use mina_rs_base::{account::timing::TimedData, account::*, types::*};
+    use proof_systems::mina_signer::Signature;
+    use rocksdb::*;
+
+    const DB_PATH_LEGACY: &str =  "test-data/genesis_ledger_6a887ea130e53b06380a9ab27b327468d28d4ce47515a0cc59759d4a3912f0ef/";
+    const DB_PATH_BERKELEY: &str =  "test-data/genesis_ledger_a99a1ff63d4ba4a07cc6bedbff3e23bd6c1f482f9ecef33abdf7fb817564cc89/";
+
+    const MINA: u64 = 1_000_000_000;
+
+    /// Loads the first 100 genesis accounts into a ledger,
+    /// the remaining accounts can be used as receivers that do not exist yet
+    fn load_ledger() -> anyhow::Result<(InMemoryLedger<Account>, Vec<Account>)> {
+        let db = rocksdb::DB::open_for_read_only(&Options::default(), DB_PATH_BERKELEY, true)?;
+        let genesis_ledger: RocksDbGenesisLedger<20, Account> = RocksDbGenesisLedger::new(&db);
+        let accounts: Vec<Account> = genesis_ledger.accounts().flatten().collect();
+        let ledger = InMemoryLedger::from_accounts(20, accounts[..100].iter().cloned())?;
+        Ok((ledger, accounts))
+    }
+
+    /// Finds two distinct untimed accounts with enough balance to make payments
+    fn sender_and_receiver(accounts: &[Account]) -> (Account, Account) {
+        let mut candidates = accounts[..100]
+            .iter()
+            .filter(|a| a.timing == Timing::Untimed && a.balance.0 > 100 * MINA);
+        let sender = candidates.next().unwrap().clone();
+        let receiver = candidates.next().unwrap().clone();
+        (sender, receiver)
+    }
+
+    /// Wraps the payload into a user command of a block with the given status,
+    /// the signature is not checked when the command is applied
+    fn signed_command(
+        payload: SignedCommandPayload,
+        signer: &Account,
+        status: TransactionStatus,
+    ) -> UserCommandWithStatus {
+        UserCommandWithStatus {
+            data: UserCommand::SignedCommand(SignedCommand {
+                payload,
+                signer: signer.public_key.clone(),
+                signature: Signature::new(Default::default(), Default::default()),
+            }),
+            status,
+        }
+    }
+
+    #[test]
+    fn test_apply_payment() -> anyhow::Result<()> {
+        let (mut ledger, accounts) = load_ledger()?;
+        let (sender, receiver) = sender_and_receiver(&accounts);
+        let root_before = ledger.merkle_root();
+
+        let payload = SignedTransferCommandBuilder::new(
+            sender.public_key.clone(),
+            receiver.public_key.clone(),
+            10 * MINA,
+            MINA / 10,
+            sender.nonce,
+        )
+        .build();
+        let status = apply_signed_command_payload(
+            &mut ledger,
+            &ConstraintConstants::berkeley(),
+            GlobalSlotNumber(0),
+            &payload,
+        )?;
+
+        let sender_after = ledger.get_account(&sender.account_id())?.unwrap();
+        let receiver_after = ledger.get_account(&receiver.account_id())?.unwrap();
+        ensure!(sender_after.balance.0 == sender.balance.0 - 10 * MINA - MINA / 10);
+        ensure!(sender_after.nonce.0 == sender.nonce.0 + 1);
+        ensure!(sender_after.receipt_chain_hash != sender.receipt_chain_hash);
+        ensure!(receiver_after.balance.0 == receiver.balance.0 + 10 * MINA);
+        ensure!(receiver_after.nonce == receiver.nonce);
+        ensure!(
+            status
+                == TransactionStatus::Applied(
+                    TransactionStatusAuxiliaryData {
+                        fee_payer_account_creation_fee_paid: None,
+                        receiver_account_creation_fee_paid: None,
+                        created_token: None,
+                    },
+                    TransactionStatusBalanceData {
+                        fee_payer_balance: Some(sender_after.balance),
+                        source_balance: Some(sender_after.balance),
+                        receiver_balance: Some(receiver_after.balance),
+                    }
+                )
+        );
+        ensure!(ledger.merkle_root() != root_before);
+        ensure!(ledger.num_accounts() == 100);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_payment_creates_receiver() -> anyhow::Result<()> {
+        let (mut ledger, accounts) = load_ledger()?;
+        let (sender, _) = sender_and_receiver(&accounts);
+        let receiver_id = accounts[100].account_id();
+        ensure!(ledger.get_account(&receiver_id)?.is_none());
+
+        let payload = SignedTransferCommandBuilder::new(
+            sender.public_key.clone(),
+            receiver_id.public_key.clone(),
+            10 * MINA,
+            MINA / 10,
+            sender.nonce,
+        )
+        .build();
+        let status = apply_signed_command_payload(
+            &mut ledger,
+            &ConstraintConstants::berkeley(),
+            GlobalSlotNumber(0),
+            &payload,
+        )?;
+
+        ensure!(ledger.num_accounts() == 101);
+        ensure!(ledger.location_of_account(&receiver_id)? == Some(Location(100)));
+        let receiver = ledger.get_account(&receiver_id)?.unwrap();
+        ensure!(receiver.balance.0 == 9 * MINA);
+        ensure!(receiver.nonce.0 == 0);
+        ensure!(receiver.delegate == Some(receiver_id.public_key.clone()));
+        ensure!(receiver.receipt_chain_hash == empty_receipt_chain_hash(PoseidonVersion::Kimchi));
+        match status {
+            TransactionStatus::Applied(aux, _) => {
+                ensure!(aux.receiver_account_creation_fee_paid == Some(Amount(MINA)))
+            }
+            _ => anyhow::bail!("Payment is expected to be applied, status: {status:?}"),
+        }
+
+        // Sending less than the account creation fee fails, but the fee is still paid
+        let receiver_id = accounts[101].account_id();
+        let sender = ledger.get_account(&sender.account_id())?.unwrap();
+        let payload = SignedTransferCommandBuilder::new(
+            sender.public_key.clone(),
+            receiver_id.public_key.clone(),
+            MINA / 2,
+            MINA / 10,
+            sender.nonce,
+        )
+        .build();
+        let status = apply_signed_command_payload(
+            &mut ledger,
+            &ConstraintConstants::berkeley(),
+            GlobalSlotNumber(0),
+            &payload,
+        )?;
+        let sender_after = ledger.get_account(&sender.account_id())?.unwrap();
+        ensure!(
+            status
+                == TransactionStatus::Failed(
+                    vec![TransactionStatusFailedType::AmountInsufficientToCreateAccount],
+                    TransactionStatusBalanceData {
+                        fee_payer_balance: Some(sender_after.balance),
+                        source_balance: None,
+                        receiver_balance: None,
+                    }
+                )
+        );
+        ensure!(sender_after.balance.0 == sender.balance.0 - MINA / 10);
+        ensure!(sender_after.nonce.0 == sender.nonce.0 + 1);
+        ensure!(ledger.get_account(&receiver_id)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_receipt_chain_hash() -> anyhow::Result<()> {
+        // Genesis accounts have not sent any command yet
+        let db = rocksdb::DB::open_for_read_only(&Options::default(), DB_PATH_LEGACY, true)?;
+        let genesis_ledger: RocksDbGenesisLedger<20, AccountLegacy> =
+            RocksDbGenesisLedger::new(&db);
+        let account = genesis_ledger.accounts().next().unwrap()?;
+        ensure!(account.nonce.0 == 0);
+        ensure!(account.receipt_chain_hash == empty_receipt_chain_hash(PoseidonVersion::Legacy));
+
+        let (_, accounts) = load_ledger()?;
+        ensure!(accounts[0].nonce.0 == 0);
+        ensure!(
+            accounts[0].receipt_chain_hash == empty_receipt_chain_hash(PoseidonVersion::Kimchi)
+        );
+        ensure!(
+            empty_receipt_chain_hash(PoseidonVersion::Legacy)
+                != empty_receipt_chain_hash(PoseidonVersion::Kimchi)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_user_command_recorded_as_failed() -> anyhow::Result<()> {
+        let (mut ledger, accounts) = load_ledger()?;
+        let (sender, receiver) = sender_and_receiver(&accounts);
+        let root_before = ledger.merkle_root();
+
+        // The payment can be applied, but the block records it as failed
+        let payload = SignedTransferCommandBuilder::new(
+            sender.public_key.clone(),
+            receiver.public_key.clone(),
+            10 * MINA,
+            MINA / 10,
+            sender.nonce,
+        )
+        .build();
+        let recorded = TransactionStatus::Failed(
+            vec![TransactionStatusFailedType::SourceMinimumBalanceViolation],
+            TransactionStatusBalanceData {
+                fee_payer_balance: Some(Amount(sender.balance.0 - MINA / 10)),
+                source_balance: None,
+                receiver_balance: None,
+            },
+        );
+        let result = apply_user_command(
+            &mut ledger,
+            &ConstraintConstants::berkeley(),
+            GlobalSlotNumber(0),
+            &signed_command(payload, &sender, recorded.clone()),
+        );
+        match result {
+            Err(TransactionLogicError::StatusMismatch {
+                recorded: actual_recorded,
+                computed: TransactionStatus::Applied(..),
+            }) => ensure!(actual_recorded == recorded),
+            r => anyhow::bail!("Unexpected result: {r:?}"),
+        }
+        ensure!(ledger.merkle_root() == root_before);
+        let sender_after = ledger.get_account(&sender.account_id())?.unwrap();
+        ensure!(sender_after.balance == sender.balance);
+        ensure!(sender_after.nonce == sender.nonce);
+
+        // A failure that is recorded correctly only charges the fee
+        let payload = SignedTransferCommandBuilder::new(
+            sender.public_key.clone(),
+            receiver.public_key.clone(),
+            sender.balance.0,
+            MINA / 10,
+            sender.nonce,
+        )
+        .build();
+        let recorded = TransactionStatus::Failed(
+            vec![TransactionStatusFailedType::SourceInsufficientBalance],
+            TransactionStatusBalanceData {
+                fee_payer_balance: Some(Amount(sender.balance.0 - MINA / 10)),
+                source_balance: None,
+                receiver_balance: None,
+            },
+        );
+        let status = apply_user_command(
+            &mut ledger,
+            &ConstraintConstants::berkeley(),
+            GlobalSlotNumber(0),
+            &signed_command(payload, &sender, recorded.clone()),
+        )?;
+        ensure!(status == recorded);
+        let sender_after = ledger.get_account(&sender.account_id())?.unwrap();
+        ensure!(sender_after.balance.0 == sender.balance.0 - MINA / 10);
+        ensure!(sender_after.nonce.0 == sender.nonce.0 + 1);
+        ensure!(sender_after.receipt_chain_hash != sender.receipt_chain_hash);
+        let receiver_after = ledger.get_account(&receiver.account_id())?.unwrap();
+        ensure!(receiver_after.balance == receiver.balance);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_payment_legacy() -> anyhow::Result<()> {
+        let db = rocksdb::DB::open_for_read_only(&Options::default(), DB_PATH_LEGACY, true)?;
+        let genesis_ledger: RocksDbGenesisLedger<20, AccountLegacy> =
+            RocksDbGenesisLedger::new(&db);
+        let accounts: Vec<AccountLegacy> = genesis_ledger.accounts().flatten().collect();
+        let mut ledger = InMemoryLedger::from_accounts(20, accounts[..100].iter().cloned())?;
+        let sender = accounts[..100]
+            .iter()
+            .find(|a| a.timing == Timing::Untimed && a.balance.0 > 100 * MINA)
+            .unwrap()
+            .clone();
+        let receiver_id = accounts[100].account_id();
+
+        let payload = SignedTransferCommandBuilder::new(
+            sender.public_key.clone(),
+            receiver_id.public_key.clone(),
+            10 * MINA,
+            MINA / 10,
+            sender.nonce,
+        )
+        .build();
+        let status = apply_signed_command_payload(
+            &mut ledger,
+            &ConstraintConstants::mainnet(),
+            GlobalSlotNumber(0),
+            &payload,
+        )?;
+
+        let sender_after = ledger.get_account(&sender.account_id())?.unwrap();
+        let receiver = ledger.get_account(&receiver_id)?.unwrap();
+        ensure!(sender_after.balance.0 == sender.balance.0 - 10 * MINA - MINA / 10);
+        ensure!(sender_after.nonce.0 == sender.nonce.0 + 1);
+        ensure!(sender_after.receipt_chain_hash != sender.receipt_chain_hash);
+        ensure!(receiver.balance.0 == 9 * MINA);
+        ensure!(receiver.delegate == Some(receiver_id.public_key.clone()));
+        ensure!(receiver.receipt_chain_hash == empty_receipt_chain_hash(PoseidonVersion::Legacy));
+        match status {
+            TransactionStatus::Applied(aux, _) => {
+                ensure!(aux.receiver_account_creation_fee_paid == Some(Amount(MINA)))
+            }
+            _ => anyhow::bail!("Payment is expected to be applied, status: {status:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_payment_insufficient_balance() -> anyhow::Result<()> {
+        let (mut ledger, accounts) = load_ledger()?;
+        let (sender, receiver) = sender_and_receiver(&accounts);
+
+        let payload = SignedTransferCommandBuilder::new(
+            sender.public_key.clone(),
+            receiver.public_key.clone(),
+            sender.balance.0,
+            MINA / 10,
+            sender.nonce,
+        )
+        .build();
+        let status = apply_signed_command_payload(
+            &mut ledger,
+            &ConstraintConstants::berkeley(),
+            GlobalSlotNumber(0),
+            &payload,
+        )?;
+        match status {
+            TransactionStatus::Failed(failures, _) => {
+                ensure!(failures == vec![TransactionStatusFailedType::SourceInsufficientBalance])
+            }
+            _ => anyhow::bail!("Payment is expected to fail, status: {status:?}"),
+        }
+        let receiver_after = ledger.get_account(&receiver.account_id())?.unwrap();
+        ensure!(receiver_after.balance == receiver.balance);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_payment_rejected() -> anyhow::Result<()> {
+        let (mut ledger, accounts) = load_ledger()?;
+        let (sender, receiver) = sender_and_receiver(&accounts);
+        let root_before = ledger.merkle_root();
+        let constants = ConstraintConstants::berkeley();
+
+        let builder = || {
+            SignedTransferCommandBuilder::new(
+                sender.public_key.clone(),
+                receiver.public_key.clone(),
+                MINA,
+                MINA / 10,
+                sender.nonce,
+            )
+        };
+
+        let payload = SignedTransferCommandBuilder::new(
+            sender.public_key.clone(),
+            receiver.public_key.clone(),
+            MINA,
+            MINA / 10,
+            AccountNonce(sender.nonce.0 + 1),
+        )
+        .build();
+        ensure!(
+            apply_signed_command_payload(&mut ledger, &constants, GlobalSlotNumber(0), &payload)
+                == Err(TransactionLogicError::IncorrectNonce {
+                    expected: sender.nonce,
+                    actual: AccountNonce(sender.nonce.0 + 1),
+                })
+        );
+
+        let payload = builder().valid_until(GlobalSlotNumber(10)).build();
+        ensure!(
+            apply_signed_command_payload(&mut ledger, &constants, GlobalSlotNumber(11), &payload)
+                == Err(TransactionLogicError::Expired {
+                    valid_until: GlobalSlotNumber(10),
+                    global_slot: GlobalSlotNumber(11),
+                })
+        );
+
+        let payload = builder().fee_token(TokenId(2)).build();
+        ensure!(
+            apply_signed_command_payload(&mut ledger, &constants, GlobalSlotNumber(0), &payload)
+                == Err(TransactionLogicError::UnsupportedToken(TokenId(2)))
+        );
+
+        let payload = builder()
+            .fee_payer(accounts[100].public_key.clone())
+            .build();
+        ensure!(
+            apply_signed_command_payload(&mut ledger, &constants, GlobalSlotNumber(0), &payload)
+                == Err(TransactionLogicError::FeePayerNotPresent)
+        );
+
+        // Rejected commands leave the ledger untouched
+        ensure!(ledger.merkle_root() == root_before);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_stake_delegation() -> anyhow::Result<()> {
+        let (mut ledger, accounts) = load_ledger()?;
+        let (delegator, delegate) = sender_and_receiver(&accounts);
+
+        let mut payload = SignedTransferCommandBuilder::new(
+            delegator.public_key.clone(),
+            delegate.public_key.clone(),
+            0,
+            MINA / 10,
+            delegator.nonce,
+        )
+        .build();
+        payload.body = SignedCommandPayloadBody::StakeDelegation(StakeDelegation::SetDelegate {
+            delegator: delegator.public_key.clone(),
+            new_delegate: delegate.public_key.clone(),
+        });
+        let status = apply_signed_command_payload(
+            &mut ledger,
+            &ConstraintConstants::berkeley(),
+            GlobalSlotNumber(0),
+            &payload,
+        )?;
+        let delegator_after = ledger.get_account(&delegator.account_id())?.unwrap();
+        ensure!(delegator_after.delegate == Some(delegate.public_key.clone()));
+        ensure!(delegator_after.balance.0 == delegator.balance.0 - MINA / 10);
+        ensure!(matches!(status, TransactionStatus::Applied(_, _)));
+
+        // Delegating to an account that does not exist fails
+        payload.common.nonce = delegator_after.nonce;
+        payload.body = SignedCommandPayloadBody::StakeDelegation(StakeDelegation::SetDelegate {
+            delegator: delegator.public_key.clone(),
+            new_delegate: accounts[100].public_key.clone(),
+        });
+        let status = apply_signed_command_payload(
+            &mut ledger,
+            &ConstraintConstants::berkeley(),
+            GlobalSlotNumber(0),
+            &payload,
+        )?;
+        match status {
+            TransactionStatus::Failed(failures, _) => {
+                ensure!(failures == vec![TransactionStatusFailedType::ReceiverNotPresent])
+            }
+            _ => anyhow::bail!("Delegation is expected to fail, status: {status:?}"),
+        }
+        let delegator_after = ledger.get_account(&delegator.account_id())?.unwrap();
+        ensure!(delegator_after.delegate == Some(delegate.public_key));
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_coinbase_and_fee_transfer() -> anyhow::Result<()> {
+        let (mut ledger, accounts) = load_ledger()?;
+        let constants = ConstraintConstants::berkeley();
+        let producer = accounts[0].clone();
+        let snark_worker = accounts[100].public_key.clone();
+
+        let coinbase = Coinbase {
+            receiver: producer.public_key.clone(),
+            amount: Amount(720 * MINA),
+            fee_transfer: Some(SingleFeeTransfer::new(
+                snark_worker.clone(),
+                Amount(2 * MINA),
+            )),
+        };
+        let balances = apply_coinbase(&mut ledger, &constants, GlobalSlotNumber(0), &coinbase)?;
+        ensure!(balances.coinbase_receiver_balance.0 == producer.balance.0 + 718 * MINA);
+        ensure!(balances.fee_transfer_receiver_balance == Some(Amount(MINA)));
+        ensure!(ledger.num_accounts() == 101);
+
+        let too_large = Coinbase {
+            fee_transfer: Some(SingleFeeTransfer::new(
+                snark_worker.clone(),
+                Amount(721 * MINA),
+            )),
+            ..coinbase
+        };
+        let root_before = ledger.merkle_root();
+        ensure!(
+            apply_coinbase(&mut ledger, &constants, GlobalSlotNumber(0), &too_large)
+                == Err(TransactionLogicError::CoinbaseFeeTransferTooLarge {
+                    amount: Amount(720 * MINA),
+                    fee: Amount(721 * MINA),
+                })
+        );
+        ensure!(ledger.merkle_root() == root_before);
+
+        // Transfers to the same receiver are combined
+        let transfer = SingleFeeTransfer::new(snark_worker.clone(), Amount(MINA));
+        let balances = apply_fee_transfer(
+            &mut ledger,
+            &constants,
+            GlobalSlotNumber(0),
+            &transfer,
+            Some(&transfer),
+        )?;
+        ensure!(
+            balances
+                == FeeTransferBalanceData {
+                    receiver1_balance: Amount(3 * MINA),
+                    receiver2_balance: None,
+                }
+        );
+
+        // A transfer that cannot pay the account creation fee is rejected as a whole
+        let new_receiver = SingleFeeTransfer::new(accounts[101].public_key.clone(), Amount(1));
+        let root_before = ledger.merkle_root();
+        ensure!(
+            apply_fee_transfer(
+                &mut ledger,
+                &constants,
+                GlobalSlotNumber(0),
+                &transfer,
+                Some(&new_receiver),
+            ) == Err(TransactionLogicError::AmountInsufficientToCreateAccount)
+        );
+        ensure!(ledger.merkle_root() == root_before);
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_timing() -> anyhow::Result<()> {
+        let timing = Timing::Timed(TimedData {
+            initial_minimum_balance: Amount(100),
+            cliff_time: BlockTime(10),
+            cliff_amount: Amount(10),
+            vesting_period: BlockTime(5),
+            vesting_increment: Amount(30),
+        });
+        ensure!(timing.min_balance_at_slot(GlobalSlotNumber(9)) == Amount(100));
+        ensure!(timing.min_balance_at_slot(GlobalSlotNumber(10)) == Amount(90));
+        ensure!(timing.min_balance_at_slot(GlobalSlotNumber(15)) == Amount(60));
+        ensure!(timing.min_balance_at_slot(GlobalSlotNumber(25)) == Amount(0));
+
+        ensure!(validate_timing(&timing, Amount(99), GlobalSlotNumber(9)).is_none());
+        ensure!(validate_timing(&timing, Amount(60), GlobalSlotNumber(15)) == Some(timing.clone()));
+        ensure!(validate_timing(&timing, Amount(0), GlobalSlotNumber(25)) == Some(Timing::Untimed));
+        Ok(())
+    }
+}
@@ -9,14 +9,20 @@
 
 mod proof;
 pub use proof::*;
+mod multiproof;
+pub use multiproof::*;
 mod tree;
 pub use tree::*;
 mod tree_impl;
 pub use tree_impl::*;
+mod sparse_tree_impl;
+pub use sparse_tree_impl::*;
 mod maskable;
 pub use maskable::*;
 mod masking;
 pub use masking::*;
+mod mask_impl;
+pub use mask_impl::*;
 mod merger;
 pub use merger::*;
 mod merger_poseidon;
@@ -0,0 +1,231 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+};
+
+/// A mask that layers over a [MaskableMerkleTree], compatible with
+/// <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/merkle_mask/masking_merkle_tree.ml>
+///
+/// Changed leaves are recorded in the mask while the parent stays untouched,
+/// only hashes of the nodes on the paths from the changed leaves to the root
+/// are recalculated, lazily the next time they are requested.
+/// Changes are written to the parent by [MaskingMerkleTree::commit],
+/// or thrown away by [MaskingMerkleTree::discard].
+///
+/// The parent is shared through a [MerkleTreeHandle], so any number of masks can be
+/// registered in it. Changes of the parent are notified to its masks, which drop the
+/// cached hashes on the path of the changed leaf, and the leaf itself when the mask
+/// holds the same value. Masks can be stacked as a mask is also a [MaskableMerkleTree]
+pub struct MerkleMask<Parent>
+where
+    Parent: MaskableMerkleTree,
+{
+    parent: MerkleTreeHandle<Parent>,
+    // The parent is borrowed while it notifies its masks, so its depth is kept here
+    depth: u32,
+    num_leaves: usize,
+    leafs: BTreeMap<usize, Parent::Item>,
+    // Nodes on the paths of changed leaves, `None` means the hash needs to be recalculated
+    nodes: HashMap<(u32, usize), Option<Parent::Hash>>,
+    masks: RegisteredMasks<Parent::Item>,
+}
+
+impl<Parent> MerkleMask<Parent>
+where
+    Parent: MaskableMerkleTree + 'static,
+{
+    /// Creates a new mask on top of the given parent and registers it in the parent
+    pub fn new(parent: &MerkleTreeHandle<Parent>) -> MerkleTreeHandle<Self> {
+        let (depth, num_leaves) = {
+            let parent = parent.borrow();
+            (parent.depth(), parent.num_leaves())
+        };
+        let mask = Rc::new(RefCell::new(Self {
+            parent: parent.clone(),
+            depth,
+            num_leaves,
+            leafs: BTreeMap::new(),
+            nodes: HashMap::new(),
+            masks: Default::default(),
+        }));
+        parent.borrow_mut().register(&mask);
+        mask
+    }
+}
+
+impl<Parent> MerkleMask<Parent>
+where
+    Parent: MaskableMerkleTree,
+{
+    /// Marks the hashes of the nodes on the path of the leaf to be recalculated,
+    /// nodes that are not masked yet are masked when `mask` is set
+    fn invalidate_path(&mut self, index: usize, mask: bool) {
+        for height in 1..=self.depth {
+            match self.nodes.get_mut(&(height, index >> height)) {
+                Some(hash) => *hash = None,
+                None if mask => {
+                    self.nodes.insert((height, index >> height), None);
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+impl<Parent> MaskableMerkleTree for MerkleMask<Parent>
+where
+    Parent: MaskableMerkleTree,
+{
+    type Item = Parent::Item;
+    type Hash = Parent::Hash;
+    type Hasher = Parent::Hasher;
+    type Merger = Parent::Merger;
+
+    fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    fn num_leaves(&self) -> usize {
+        self.num_leaves
+    }
+
+    fn get_leaf(&self, index: usize) -> Option<Parent::Item> {
+        match self.leafs.get(&index) {
+            Some(item) => Some(item.clone()),
+            None => self.parent.borrow().get_leaf(index),
+        }
+    }
+
+    fn get_node_hash(&mut self, height: u32, index: usize) -> Option<Parent::Hash> {
+        match self.nodes.get(&(height, index)).cloned() {
+            // Not masked, the parent has the same hash
+            None => self.parent.borrow_mut().get_node_hash(height, index),
+            Some(Some(hash)) => Some(hash),
+            Some(None) => {
+                let hash = if height == 0 {
+                    self.leafs
+                        .get(&index)
+                        .map(|item| Parent::Hasher::hash(item, MerkleTreeNodeMetadata::new(0, 0)))
+                } else {
+                    let left = self.get_node_hash(height - 1, index * 2);
+                    let right = self.get_node_hash(height - 1, index * 2 + 1);
+                    Parent::Merger::merge([left, right], MerkleTreeNodeMetadata::new(0, height))
+                };
+                self.nodes.insert((height, index), hash.clone());
+                hash
+            }
+        }
+    }
+
+    fn set_leaf(&mut self, index: usize, item: Parent::Item) {
+        assert!(
+            index <= self.num_leaves && index < 2_usize.pow(self.depth),
+            "index {index} is out of range"
+        );
+        if index == self.num_leaves {
+            self.num_leaves += 1;
+        }
+        self.leafs.insert(index, item.clone());
+        self.nodes.insert((0, index), None);
+        self.invalidate_path(index, true);
+        self.masks.update(index, &item);
+    }
+
+    fn register<Mask>(&mut self, mask: &MerkleTreeHandle<Mask>) -> bool
+    where
+        Mask: MaskingMerkleTree<Item = Self::Item> + 'static,
+    {
+        self.masks.register(mask)
+    }
+
+    fn unregister<Mask>(&mut self, mask: &MerkleTreeHandle<Mask>) -> bool
+    where
+        Mask: MaskingMerkleTree<Item = Self::Item> + 'static,
+    {
+        self.masks.unregister(mask)
+    }
+}
+
+impl<Parent> MaskingMerkleTree for MerkleMask<Parent>
+where
+    Parent: MaskableMerkleTree,
+{
+    type Parent = Parent;
+
+    fn parent(&self) -> &MerkleTreeHandle<Parent> {
+        &self.parent
+    }
+
+    fn masked_indices(&self) -> Vec<usize> {
+        self.leafs.keys().copied().collect()
+    }
+
+    fn update(&mut self, index: usize, item: &Parent::Item) {
+        // Leaves appended to the parent are visible through the mask
+        self.num_leaves = self.num_leaves.max(index + 1);
+        if self.leafs.get(&index) == Some(item) {
+            self.leafs.remove(&index);
+            self.nodes.remove(&(0, index));
+        }
+        self.invalidate_path(index, false);
+        // Stacked masks see the change through this mask unless it's masked here
+        if !self.leafs.contains_key(&index) {
+            self.masks.update(index, item);
+        }
+    }
+
+    fn commit(&mut self) {
+        // Leaves are committed in ascending order so that appended ones stay contiguous
+        let leafs = std::mem::take(&mut self.leafs);
+        self.nodes.clear();
+        let mut parent = self.parent.borrow_mut();
+        for (index, item) in leafs {
+            parent.set_leaf(index, item);
+        }
+    }
+
+    fn discard(&mut self) {
+        let indices = self.masked_indices();
+        self.leafs.clear();
+        self.nodes.clear();
+        self.num_leaves = self.parent.borrow().num_leaves();
+        // Stacked masks see the leaves of the parent again
+        let parent = self.parent.borrow();
+        for index in indices {
+            if let Some(item) = parent.get_leaf(index) {
+                self.masks.update(index, &item);
+            }
+        }
+    }
+}
+
+impl<Parent> MerkleTree for MerkleMask<Parent>
+where
+    Parent: MaskableMerkleTree,
+{
+    type Item = Parent::Item;
+    type Hash = Parent::Hash;
+
+    fn height(&self) -> u32 {
+        self.depth()
+    }
+
+    fn count(&self) -> usize {
+        self.num_leaves
+    }
+
+    fn root(&mut self) -> Option<Parent::Hash> {
+        self.root_hash()
+    }
+
+    fn add_batch(&mut self, items: impl IntoIterator<Item = Parent::Item>) {
+        for item in items {
+            self.set_leaf(self.num_leaves, item);
+        }
+    }
+}
@@ -2,11 +2,145 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    rc::{Rc, Weak},
+};
 
-/// A merkle tree that can be masked by [super::MaskingMerkleTree]
+/// Shared handle of a [MaskableMerkleTree], so that several masks can be registered
+/// in the same parent, and the parent can notify them of its changes
+pub type MerkleTreeHandle<T> = Rc<RefCell<T>>;
+
+/// A merkle tree with a fixed depth that can be masked by [super::MaskingMerkleTree],
+/// nodes are addressed by their height (0 for leaf nodes) and their 0-based index
+/// among the nodes of the same height
 pub trait MaskableMerkleTree {
-    /// Register a [super::MaskingMerkleTree]
-    fn register(&self, mask: impl MaskingMerkleTree) -> bool;
-    /// Unregister a [super::MaskingMerkleTree]
-    fn unregister(&self, mask: impl MaskingMerkleTree) -> bool;
+    /// Type of the leaf data
+    type Item: Clone + PartialEq;
+    /// Type of the hash values
+    type Hash: Clone;
+    /// Hasher that calculates hashes of leaf nodes
+    type Hasher: MerkleHasher<Item = Self::Item, Hash = Self::Hash>;
+    /// Merger that calculates hashes of non-leaf nodes
+    type Merger: MerkleMerger<Hash = Self::Hash>;
+
+    /// Depth of the tree, leaf nodes are not counted
+    fn depth(&self) -> u32;
+    /// Number of leaves
+    fn num_leaves(&self) -> usize;
+    /// Gets the leaf with the given index
+    fn get_leaf(&self, index: usize) -> Option<Self::Item>;
+    /// Gets the hash of the node with the given height and index,
+    /// [None] indicates an empty sub-tree that is left to the merger to fill
+    fn get_node_hash(&mut self, height: u32, index: usize) -> Option<Self::Hash>;
+    /// Replaces the leaf with the given index, or appends a leaf when the index
+    /// equals the number of leaves, the registered masks are updated with the change.
+    /// This function panics when the index is out of range.
+    fn set_leaf(&mut self, index: usize, item: Self::Item);
+    /// Registers a [super::MaskingMerkleTree] so that it is updated with the changes of this tree,
+    /// returns false when it's already registered. Masks are unregistered once they are dropped
+    fn register<Mask>(&mut self, mask: &MerkleTreeHandle<Mask>) -> bool
+    where
+        Mask: MaskingMerkleTree<Item = Self::Item> + 'static;
+    /// Unregisters a [super::MaskingMerkleTree], returns false when it's not registered
+    fn unregister<Mask>(&mut self, mask: &MerkleTreeHandle<Mask>) -> bool
+    where
+        Mask: MaskingMerkleTree<Item = Self::Item> + 'static;
+
+    /// Root hash of the tree
+    fn root_hash(&mut self) -> Option<Self::Hash> {
+        self.get_node_hash(self.depth(), 0)
+    }
+
+    /// Gets the merkle multiproof of the leaves with the given indices,
+    /// [None] when no index is given or any of the leaves does not exist
+    fn get_multi_proof(
+        &mut self,
+        indices: impl IntoIterator<Item = usize>,
+    ) -> Option<DefaultMerkleMultiProof<Self::Item, Self::Hash, Self::Hasher, Self::Merger>>
+    where
+        Self::Hash: PartialEq,
+    {
+        let mut leaves = BTreeMap::new();
+        for index in indices {
+            leaves.insert(index, self.get_leaf(index)?);
+        }
+        if leaves.is_empty() {
+            return None;
+        }
+        // Only hashes of the siblings that are not on the paths are included,
+        // in the order they are consumed in [DefaultMerkleMultiProof] verification
+        let mut indices: BTreeSet<usize> = leaves.keys().copied().collect();
+        let mut hashes = Vec::new();
+        for height in 0..self.depth() {
+            for &index in indices.iter() {
+                if !indices.contains(&(index ^ 1)) {
+                    hashes.push(self.get_node_hash(height, index ^ 1));
+                }
+            }
+            indices = indices.iter().map(|index| index / 2).collect();
+        }
+        Some(DefaultMerkleMultiProof::new(
+            self.depth(),
+            leaves.into_iter().collect(),
+            hashes,
+        ))
+    }
+}
+
+type MaskUpdater<Item> = Box<dyn FnMut(usize, &Item) -> bool>;
+
+/// Masks registered in a [MaskableMerkleTree], see `Maskable_merkle_tree` in
+/// <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/merkle_mask/maskable_merkle_tree.ml>
+pub(crate) struct RegisteredMasks<Item> {
+    // Masks are identified by the address of their handle
+    masks: Vec<(usize, MaskUpdater<Item>)>,
+}
+
+impl<Item> Default for RegisteredMasks<Item> {
+    fn default() -> Self {
+        Self { masks: Vec::new() }
+    }
+}
+
+impl<Item> RegisteredMasks<Item> {
+    fn id<Mask>(mask: &MerkleTreeHandle<Mask>) -> usize {
+        Rc::as_ptr(mask) as *const () as usize
+    }
+
+    pub(crate) fn register<Mask>(&mut self, mask: &MerkleTreeHandle<Mask>) -> bool
+    where
+        Mask: MaskingMerkleTree<Item = Item> + 'static,
+    {
+        let id = Self::id(mask);
+        if self.masks.iter().any(|(registered, _)| *registered == id) {
+            return false;
+        }
+        let mask: Weak<RefCell<Mask>> = Rc::downgrade(mask);
+        let update = move |index: usize, item: &Item| match mask.upgrade() {
+            Some(mask) => {
+                // A mask that is borrowed is the one committing the change
+                if let Ok(mut mask) = mask.try_borrow_mut() {
+                    mask.update(index, item);
+                }
+                true
+            }
+            None => false,
+        };
+        self.masks.push((id, Box::new(update)));
+        true
+    }
+
+    pub(crate) fn unregister<Mask>(&mut self, mask: &MerkleTreeHandle<Mask>) -> bool {
+        let id = Self::id(mask);
+        let len = self.masks.len();
+        self.masks.retain(|(registered, _)| *registered != id);
+        self.masks.len() != len
+    }
+
+    /// Updates the registered masks with a changed leaf, dropped masks are unregistered
+    pub(crate) fn update(&mut self, index: usize, item: &Item) {
+        self.masks.retain_mut(|(_, update)| update(index, item));
+    }
 }
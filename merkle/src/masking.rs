@@ -3,10 +3,21 @@
 
 use super::*;
 
-/// A merkle tree that can be used to mask [super::MaskableMerkleTree]
-pub trait MaskingMerkleTree {
-    /// Update a [super::MaskingMerkleTree] with changed notified from the [super::MaskableMerkleTree] it's registered in
-    fn update(&self);
-    /// Commits changes from a [super::MaskingMerkleTree] to the [super::MaskableMerkleTree] it's registered in
-    fn commit(self, parent: impl MaskableMerkleTree);
+/// A merkle tree that can be used to mask [super::MaskableMerkleTree],
+/// a mask is also maskable so that masks can be stacked
+pub trait MaskingMerkleTree: MaskableMerkleTree {
+    /// Type of the [super::MaskableMerkleTree] that is masked
+    type Parent: MaskableMerkleTree<Item = Self::Item, Hash = Self::Hash>;
+
+    /// Gets the [super::MaskableMerkleTree] that is masked
+    fn parent(&self) -> &MerkleTreeHandle<Self::Parent>;
+    /// Indices of the leaves that are changed in the mask, in ascending order
+    fn masked_indices(&self) -> Vec<usize>;
+    /// Update a [super::MaskingMerkleTree] with the leaf changed in the [super::MaskableMerkleTree] it's registered in
+    fn update(&mut self, index: usize, item: &Self::Item);
+    /// Commits changes from a [super::MaskingMerkleTree] to the [super::MaskableMerkleTree] it's registered in,
+    /// the mask stays registered without changes of its own
+    fn commit(&mut self);
+    /// Discards changes of a [super::MaskingMerkleTree], leaving the [super::MaskableMerkleTree] it's registered in untouched
+    fn discard(&mut self);
 }
@@ -36,6 +36,45 @@ impl MerkleMerger for MinaPoseidonMerkleMerger {
     }
 }
 
+/// Merger for the pending coinbase merkle tree that uses legacy poseidon hash
+/// with coinbase specific domain string calculated from node height,
+/// all leaves of the tree are expected to be present
+pub struct MinaPoseidonCoinbaseMerkleMergerLegacy;
+
+impl MerkleMerger for MinaPoseidonCoinbaseMerkleMergerLegacy {
+    type Hash = Fp;
+    fn merge(
+        hashes: [Option<Self::Hash>; 2],
+        metadata: MerkleTreeNodeMetadata,
+    ) -> Option<Self::Hash> {
+        match hashes {
+            [Some(left), Some(right)] => {
+                let mut hasher = create_legacy(metadata.height());
+                Some(hasher.hash(&MinaPoseidonCoinbaseMerkleTreeNonLeafNode([left, right])))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct MinaPoseidonCoinbaseMerkleTreeNonLeafNode([Fp; 2]);
+
+impl Hashable for MinaPoseidonCoinbaseMerkleTreeNonLeafNode {
+    type D = u32;
+
+    fn to_roinput(&self) -> mina_hasher::ROInput {
+        ROInput::new()
+            .append_field(self.0[0])
+            .append_field(self.0[1])
+    }
+
+    fn domain_string(height: Self::D) -> Option<String> {
+        // use height - 1 here because in mina leaf nodes are not counted
+        Some(make_prefix_coinbase_merkle_tree(height - 1))
+    }
+}
+
 #[derive(Clone)]
 struct MinaPoseidonMerkleTreeNonLeafNode([Option<Fp>; 2], u32);
 
@@ -0,0 +1,186 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains structs and utilities of merkle multiproof
+
+use crate::*;
+use proof_systems::{
+    mina_hasher::Fp,
+    o1_utils::{field_helpers::FieldHelpersError, FieldHelpers},
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, marker::PhantomData};
+
+/// Merkle proof of multiple leaf nodes of a tree with a fixed height,
+/// the hashes shared by the paths of the leaves are only included once,
+/// hashes that can be calculated from the leaves are not included at all.
+///
+/// Leaves are ordered by their 0-based index, hashes are ordered from the bottom
+/// of the tree up, and from left to right among the nodes of the same height,
+/// [None] is an empty sub-tree that is left to the merger to fill
+pub struct DefaultMerkleMultiProof<Item, Hash, Hasher, Merger>
+where
+    Hash: PartialEq + Clone,
+    Hasher: MerkleHasher<Item = Item, Hash = Hash>,
+    Merger: MerkleMerger<Hash = Hash>,
+{
+    /// Height of the tree, leaf nodes are not counted
+    pub height: u32,
+    /// 0-based leaf indices and items
+    pub leaves: Vec<(usize, Item)>,
+    /// Hashes of the sibling nodes that are not on the paths of the leaves
+    pub hashes: Vec<Option<Hash>>,
+    ///
+    pub _hasher: PhantomData<Hasher>,
+    ///
+    pub _merger: PhantomData<Merger>,
+}
+
+impl<Item, Hash, Hasher, Merger> DefaultMerkleMultiProof<Item, Hash, Hasher, Merger>
+where
+    Hash: PartialEq + Clone,
+    Hasher: MerkleHasher<Item = Item, Hash = Hash>,
+    Merger: MerkleMerger<Hash = Hash>,
+{
+    /// Creates merkle multiproof instance
+    pub fn new(height: u32, leaves: Vec<(usize, Item)>, hashes: Vec<Option<Hash>>) -> Self {
+        Self {
+            height,
+            leaves,
+            hashes,
+            _hasher: Default::default(),
+            _merger: Default::default(),
+        }
+    }
+}
+
+impl<Item, Hash, Hasher, Merger> MerkleProof for DefaultMerkleMultiProof<Item, Hash, Hasher, Merger>
+where
+    Hash: PartialEq + Clone + std::fmt::Debug,
+    Hasher: MerkleHasher<Item = Item, Hash = Hash>,
+    Merger: MerkleMerger<Hash = Hash>,
+{
+    type Hash = Hash;
+    type Error = MerkleProofError;
+
+    fn root_hash(&self) -> Result<Hash, Self::Error> {
+        // 1. Calculates hashes of the leaves, keyed by their index among the nodes of the same height
+        let mut level = BTreeMap::new();
+        for (index, item) in self.leaves.iter() {
+            if *index >= 2_usize.pow(self.height) {
+                return Err(MerkleProofError::InvalidIndex);
+            }
+            let hash = Hasher::hash(item, node_metadata(0, *index, self.height));
+            if level.insert(*index, Some(hash)).is_some() {
+                return Err(MerkleProofError::InvalidProof);
+            }
+        }
+        if level.is_empty() {
+            return Err(MerkleProofError::InvalidProof);
+        }
+        let mut hashes = self.hashes.iter();
+        for height in 0..self.height {
+            // 2. Pairs each node with its sibling, either calculated or from the proof,
+            // and calculates the hash of their parent by invoking the associated merkle merger
+            let mut parents = BTreeMap::new();
+            let mut nodes = level.into_iter().peekable();
+            while let Some((index, hash)) = nodes.next() {
+                let siblings = if index % 2 == 0 {
+                    let right = match nodes.next_if(|(i, _)| *i == index + 1) {
+                        Some((_, right)) => right,
+                        None => hashes.next().ok_or(MerkleProofError::InvalidProof)?.clone(),
+                    };
+                    [hash, right]
+                } else {
+                    let left = hashes.next().ok_or(MerkleProofError::InvalidProof)?.clone();
+                    [left, hash]
+                };
+                let parent_hash =
+                    Merger::merge(siblings, node_metadata(height + 1, index / 2, self.height));
+                parents.insert(index / 2, parent_hash);
+            }
+            // 3. Go back to step 2 and apply the same flow to the parents
+            // until the root hash has been calculated.
+            level = parents;
+        }
+        if hashes.next().is_some() {
+            return Err(MerkleProofError::InvalidProof);
+        }
+        match level.remove(&0) {
+            Some(Some(hash)) => Ok(hash),
+            _ => Err(MerkleProofError::MergerFailure),
+        }
+    }
+}
+
+/// Encoding of [DefaultMerkleMultiProof] with [Fp] hashes,
+/// which can be serded with bin-prot or json
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleMultiProofEncoding<Item> {
+    /// Height of the tree, leaf nodes are not counted
+    pub height: u32,
+    /// 0-based leaf indices and items
+    pub leaves: Vec<(u64, Item)>,
+    /// Hashes of the sibling nodes, in bytes
+    pub hashes: Vec<Option<[u8; 32]>>,
+}
+
+impl<Item, Hasher, Merger> From<DefaultMerkleMultiProof<Item, Fp, Hasher, Merger>>
+    for MerkleMultiProofEncoding<Item>
+where
+    Hasher: MerkleHasher<Item = Item, Hash = Fp>,
+    Merger: MerkleMerger<Hash = Fp>,
+{
+    fn from(proof: DefaultMerkleMultiProof<Item, Fp, Hasher, Merger>) -> Self {
+        let mut hashes = Vec::with_capacity(proof.hashes.len());
+        for hash in proof.hashes {
+            hashes.push(hash.map(|h| {
+                let mut bytes = [0; 32];
+                bytes.copy_from_slice(h.to_bytes().as_slice());
+                bytes
+            }));
+        }
+        Self {
+            height: proof.height,
+            leaves: proof
+                .leaves
+                .into_iter()
+                .map(|(index, item)| (index as u64, item))
+                .collect(),
+            hashes,
+        }
+    }
+}
+
+impl<Item, Hasher, Merger> TryFrom<MerkleMultiProofEncoding<Item>>
+    for DefaultMerkleMultiProof<Item, Fp, Hasher, Merger>
+where
+    Hasher: MerkleHasher<Item = Item, Hash = Fp>,
+    Merger: MerkleMerger<Hash = Fp>,
+{
+    type Error = FieldHelpersError;
+
+    fn try_from(encoding: MerkleMultiProofEncoding<Item>) -> Result<Self, Self::Error> {
+        let mut hashes = Vec::with_capacity(encoding.hashes.len());
+        for hash in encoding.hashes {
+            hashes.push(match hash {
+                Some(bytes) => Some(Fp::from_bytes(bytes.as_slice())?),
+                None => None,
+            });
+        }
+        Ok(Self::new(
+            encoding.height,
+            encoding
+                .leaves
+                .into_iter()
+                .map(|(index, item)| (index as usize, item))
+                .collect(),
+            hashes,
+        ))
+    }
+}
+
+/// Metadata of the node with the given height and index among the nodes of the same height
+fn node_metadata(height: u32, index: usize, tree_height: u32) -> MerkleTreeNodeMetadata {
+    MerkleTreeNodeMetadata::new(2_usize.pow(tree_height - height) - 1 + index, tree_height)
+}
@@ -0,0 +1,241 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use proof_systems::mina_hasher::Fp;
+use std::{
+    collections::{BTreeMap, HashMap},
+    marker::PhantomData,
+};
+
+/// Sparse binary merkle tree with a fixed height that is compatible with
+/// <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/sparse_ledger_lib/sparse_ledger.ml>
+///
+/// Only the leaves of the merkle proofs being added are tracked, together with
+/// the hashes of the nodes on their paths and the siblings of those nodes,
+/// which are enough to update a tracked leaf and recalculate the root hash.
+pub struct MinaSparseMerkleTree<Item, Hash, Hasher, Merger>
+where
+    Hasher: MerkleHasher<Item = Item, Hash = Hash>,
+    Merger: MerkleMerger<Hash = Hash>,
+    Hash: Clone + PartialEq + std::fmt::Debug,
+    Item: Clone,
+{
+    height: u32,
+    root: Option<Hash>,
+    // Tracked leaves, keyed by the 0-based leaf index
+    leafs: BTreeMap<usize, Item>,
+    // Known node hashes, keyed by the node index counted from root,
+    // `None` is an empty sub-tree that is left to the merger to fill
+    nodes: HashMap<usize, Option<Hash>>,
+
+    _pd_hasher: PhantomData<Hasher>,
+    _pd_merger: PhantomData<Merger>,
+}
+
+impl<Item, Hash, Hasher, Merger> MinaSparseMerkleTree<Item, Hash, Hasher, Merger>
+where
+    Hasher: MerkleHasher<Item = Item, Hash = Hash>,
+    Merger: MerkleMerger<Hash = Hash>,
+    Hash: Clone + PartialEq + std::fmt::Debug,
+    Item: Clone,
+{
+    /// Creates a new instance of an empty [MinaSparseMerkleTree],
+    /// the height does not count leaf nodes
+    pub fn new(height: u32) -> Self {
+        Self {
+            height,
+            root: None,
+            leafs: BTreeMap::new(),
+            nodes: HashMap::new(),
+            _pd_hasher: Default::default(),
+            _pd_merger: Default::default(),
+        }
+    }
+
+    /// Height of the tree, leaf nodes are not counted
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Root hash shared by all the merkle proofs added,
+    /// [None] when no merkle proof has been added
+    pub fn root(&self) -> Option<Hash> {
+        self.root.clone()
+    }
+
+    /// Number of tracked leaves
+    pub fn count(&self) -> usize {
+        self.leafs.len()
+    }
+
+    /// Indices of the tracked leaves in ascending order
+    pub fn indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.leafs.keys().copied()
+    }
+
+    /// Gets the tracked item with the 0-based leaf index
+    pub fn get(&self, index: usize) -> Option<&Item> {
+        self.leafs.get(&index)
+    }
+
+    /// Replaces the tracked item with the 0-based leaf index,
+    /// hashes of the nodes on its path are recalculated as well as the root hash
+    pub fn set(&mut self, index: usize, item: Item) -> Result<(), SparseMerkleTreeError> {
+        if !self.leafs.contains_key(&index) {
+            return Err(SparseMerkleTreeError::UnknownLeaf(index));
+        }
+        let mut node_index = self.first_leaf_index() + index;
+        let mut hash = Some(Hasher::hash(
+            &item,
+            MerkleTreeNodeMetadata::new(node_index, self.height),
+        ));
+        while node_index > 0 {
+            // Siblings on the path of a tracked leaf are always known
+            let sibling_hash = self.nodes[&sibling_index(node_index)].clone();
+            let hashes = order_siblings(node_index, hash.clone(), sibling_hash);
+            self.nodes.insert(node_index, hash);
+            node_index = parent_index(node_index);
+            hash = Merger::merge(hashes, MerkleTreeNodeMetadata::new(node_index, self.height));
+        }
+        self.nodes.insert(0, hash.clone());
+        self.root = hash;
+        self.leafs.insert(index, item);
+        Ok(())
+    }
+
+    /// Gets the merkle proof of a tracked item with the 0-based leaf index
+    pub fn get_proof(
+        &self,
+        index: usize,
+    ) -> Option<DefaultMerkleProof<Item, Hash, Hasher, Merger>> {
+        let item = self.leafs.get(&index)?;
+        let mut node_index = self.first_leaf_index() + index;
+        let mut peer_indices = Vec::with_capacity(self.height as usize);
+        let mut peer_hashes = Vec::with_capacity(self.height as usize);
+        while node_index > 0 {
+            let peer_index = sibling_index(node_index);
+            peer_indices.push(peer_index);
+            peer_hashes.push(self.nodes[&peer_index].clone());
+            node_index = parent_index(node_index);
+        }
+        Some(DefaultMerkleProof::new(
+            self.first_leaf_index() + index,
+            item.clone(),
+            peer_indices,
+            peer_hashes,
+        ))
+    }
+
+    fn first_leaf_index(&self) -> usize {
+        2_usize.pow(self.height) - 1
+    }
+
+    fn add_proof(
+        &mut self,
+        proof: DefaultMerkleProof<Item, Hash, Hasher, Merger>,
+    ) -> Result<(), SparseMerkleTreeError> {
+        let proof_height = proof.peer_indices.len() as u32;
+        if proof_height != self.height {
+            return Err(SparseMerkleTreeError::HeightMismatch {
+                expected: self.height,
+                actual: proof_height,
+            });
+        }
+        let first_leaf_index = self.first_leaf_index();
+        if proof.index < first_leaf_index || proof.index > first_leaf_index * 2 {
+            return Err(MerkleProofError::InvalidIndex.into());
+        }
+        // Calculates hashes of the nodes on the path, the same way as [MerkleProof::root_hash]
+        let mut path = Vec::with_capacity(self.height as usize * 2 + 1);
+        let mut node_index = proof.index;
+        let mut hash = Some(Hasher::hash(
+            &proof.item,
+            MerkleTreeNodeMetadata::new(node_index, self.height),
+        ));
+        for (&peer_index, peer_hash) in proof.peer_indices.iter().zip(proof.peer_hashes) {
+            if peer_index != sibling_index(node_index) {
+                return Err(MerkleProofError::InvalidProof.into());
+            }
+            let hashes = order_siblings(node_index, hash.clone(), peer_hash.clone());
+            path.push((node_index, hash));
+            path.push((peer_index, peer_hash));
+            node_index = parent_index(node_index);
+            hash = Merger::merge(hashes, MerkleTreeNodeMetadata::new(node_index, self.height));
+        }
+        let root = hash.ok_or(MerkleProofError::MergerFailure)?;
+        if let Some(expected) = &self.root {
+            if expected != &root {
+                return Err(SparseMerkleTreeError::RootMismatch);
+            }
+        }
+        path.push((0, Some(root.clone())));
+        self.root = Some(root);
+        self.nodes.extend(path);
+        let leaf_index = proof.index - first_leaf_index;
+        self.leafs.insert(leaf_index, proof.item);
+        Ok(())
+    }
+}
+
+impl<Item, Hasher, Merger> MinaSparseMerkleTree<Item, Fp, Hasher, Merger>
+where
+    Hasher: MerkleHasher<Item = Item, Hash = Fp>,
+    Merger: MerkleMerger<Hash = Fp>,
+    Item: Clone,
+{
+    /// Adds a [MerklePath] of the given item, as returned by mina graphql api
+    pub fn add_path(&mut self, path: &MerklePath, item: Item) -> anyhow::Result<()> {
+        let proof = path.to_proof(item)?;
+        self.add(proof)?;
+        Ok(())
+    }
+}
+
+impl<Item, Hash, Hasher, Merger> SparseMerkleTree
+    for MinaSparseMerkleTree<Item, Hash, Hasher, Merger>
+where
+    Hasher: MerkleHasher<Item = Item, Hash = Hash>,
+    Merger: MerkleMerger<Hash = Hash>,
+    Hash: Clone + PartialEq + std::fmt::Debug,
+    Item: Clone,
+{
+    type MerkleProof = DefaultMerkleProof<Item, Hash, Hasher, Merger>;
+
+    fn add_batch(
+        &mut self,
+        proofs: impl IntoIterator<Item = Self::MerkleProof>,
+    ) -> Result<(), SparseMerkleTreeError> {
+        for proof in proofs {
+            self.add_proof(proof)?;
+        }
+        Ok(())
+    }
+}
+
+fn parent_index(index: usize) -> usize {
+    debug_assert!(index > 0);
+    (index - 1) / 2
+}
+
+fn sibling_index(index: usize) -> usize {
+    debug_assert!(index > 0);
+    if index % 2 == 1 {
+        index + 1
+    } else {
+        index - 1
+    }
+}
+
+fn order_siblings<Hash>(
+    index: usize,
+    hash: Option<Hash>,
+    sibling_hash: Option<Hash>,
+) -> [Option<Hash>; 2] {
+    // Left children have odd indices
+    if index % 2 == 1 {
+        [hash, sibling_hash]
+    } else {
+        [sibling_hash, hash]
+    }
+}
@@ -25,16 +25,42 @@ pub trait MerkleTree {
 }
 
 /// Trait for implementing sparse binary merkle tree.
-/// It is essentially a collection of [MerkleProof]
+/// It is essentially a collection of [MerkleProof] that share the same root hash
 pub trait SparseMerkleTree {
     /// Type of the merkle proof
     type MerkleProof: MerkleProof;
 
     /// Adds a single [MerkleProof]
-    fn add(&mut self, proof: Self::MerkleProof) {
+    fn add(&mut self, proof: Self::MerkleProof) -> Result<(), SparseMerkleTreeError> {
         self.add_batch(vec![proof])
     }
 
-    /// Adds a collection of [MerkleProof]
-    fn add_batch(&mut self, proofs: impl IntoIterator<Item = Self::MerkleProof>);
+    /// Adds a collection of [MerkleProof], proofs are added in the given order
+    /// until the first invalid one
+    fn add_batch(
+        &mut self,
+        proofs: impl IntoIterator<Item = Self::MerkleProof>,
+    ) -> Result<(), SparseMerkleTreeError>;
+}
+
+/// Type that represents errors in adding merkle proofs to or updating a [SparseMerkleTree]
+#[derive(thiserror::Error, Debug, Eq, PartialEq)]
+pub enum SparseMerkleTreeError {
+    /// The merkle proof has a different root hash from the tree
+    #[error("The merkle proof has a different root hash from the tree")]
+    RootMismatch,
+    /// The merkle proof has a different height from the tree
+    #[error("Expected a merkle proof of height {expected}, got {actual}")]
+    HeightMismatch {
+        /// height of the tree
+        expected: u32,
+        /// height of the merkle proof
+        actual: u32,
+    },
+    /// The leaf is not tracked by the tree
+    #[error("Leaf {0} is not tracked by the tree")]
+    UnknownLeaf(usize),
+    /// The merkle proof is invalid
+    #[error(transparent)]
+    InvalidProof(#[from] MerkleProofError),
 }
@@ -45,8 +45,10 @@ where
 {
     mode: Mode,
     variable_height: u32,
-    leafs: Vec<(Item, Option<Hash>)>,
+    // Removed leaves are kept as `None` so that the indices of other leaves do not change
+    leafs: Vec<(Option<Item>, Option<Hash>)>,
     nodes: Vec<Option<Hash>>,
+    masks: RegisteredMasks<Item>,
 
     _pd_hasher: PhantomData<Hasher>,
     _pd_merger: PhantomData<Merger>,
@@ -101,7 +103,7 @@ where
     }
 
     /// Gets the merkle proof of an item with the 0-based index of the item
-    /// being added, e.g. the first item is index 0.
+    /// being added, e.g. the first item is index 0, [None] when the item is removed.
     /// This function panics when the index is out of range.
     pub fn get_proof(
         &mut self,
@@ -119,7 +121,7 @@ where
             let capacity = self.mode.fixed_height().unwrap_or(self.variable_height) as usize;
             let mut peer_indices = Vec::with_capacity(capacity);
             let mut peer_hashes = Vec::with_capacity(capacity);
-            let (item, _) = &self.leafs[index];
+            let item = self.leafs[index].0.clone()?;
             let index_with_offset = index_offset + index;
             // 2. Gets the index and hash of its sibling in the tree, and push to the proof vec
             let peer_index = if index % 2 == 0 { index + 1 } else { index - 1 };
@@ -163,7 +165,7 @@ where
             }
             Some(DefaultMerkleProof::new(
                 index_with_offset,
-                item.clone(),
+                item,
                 peer_indices,
                 peer_hashes,
             ))
@@ -172,17 +174,47 @@ where
         }
     }
 
+    /// Gets the item with the 0-based index of the item
+    /// being added, e.g. the first item is index 0.
+    /// Returns [None] when the index is out of range or the item is removed.
+    pub fn get(&self, index: usize) -> Option<&Item> {
+        self.leafs.get(index).and_then(|(item, _)| item.as_ref())
+    }
+
+    /// Replaces the item with the 0-based index of the item
+    /// being added, e.g. the first item is index 0.
+    /// Only cached hashes of its ancester nodes are cleared, they are
+    /// recalculated lazily the next time the root hash is requested.
+    /// This function panics when the index is out of range.
+    pub fn set(&mut self, index: usize, item: Item) {
+        self.leafs[index] = (Some(item), None);
+        self.clear_dirty_hashes(self.nodes.len() + index);
+    }
+
+    /// Removes the item with the 0-based index of the item
+    /// being added, e.g. the first item is index 0, and returns it.
+    /// The leaf is cleared back to an empty leaf whose hash is left to
+    /// the merger to fill, the number of leaves and indices of other items
+    /// are not changed. Only cached hashes of its ancester nodes are cleared.
+    /// This function panics when the index is out of range.
+    pub fn remove(&mut self, index: usize) -> Option<Item> {
+        let (item, hash) = &mut self.leafs[index];
+        *hash = None;
+        let removed = item.take();
+        self.clear_dirty_hashes(self.nodes.len() + index);
+        removed
+    }
+
     /// Clears cached hashes of all ancester nodes of the give leaf
-    /// because the values become invaid once the leaf is updated
+    /// because the values become invaid once the leaf is updated.
+    /// The whole path to the root is cleared, an ancestor without a cached hash
+    /// does not imply the ones above it are cleared, e.g. the merger may produce
+    /// no hash for a sub-tree whose leaves are all removed
     fn clear_dirty_hashes(&mut self, leaf_index: usize) {
         let mut parent = leaf_index;
         while parent > 0 {
             parent = calculate_parent_index(parent);
-            if self.nodes[parent].is_some() {
-                self.nodes[parent] = None;
-            } else {
-                break;
-            }
+            self.nodes[parent] = None;
         }
     }
 
@@ -210,16 +242,18 @@ where
             let leaf_index = index - self.nodes.len();
             if leaf_index < self.leafs.len() {
                 let (data, hash) = &mut self.leafs[leaf_index];
-                match hash {
-                    None => {
-                        let node_hash = Some(Hasher::hash(
-                            data,
-                            MerkleTreeNodeMetadata::new(index, self.variable_height),
-                        ));
-                        *hash = node_hash.clone();
-                        node_hash
+                match data {
+                    // Removed leaf, the merger fills the empty hash
+                    None => None,
+                    Some(data) => {
+                        if hash.is_none() {
+                            *hash = Some(Hasher::hash(
+                                data,
+                                MerkleTreeNodeMetadata::new(index, self.variable_height),
+                            ));
+                        }
+                        hash.clone()
                     }
-                    _ => hash.clone(),
                 }
             } else {
                 None
@@ -298,6 +332,83 @@ where
     }
 }
 
+impl<Item, Hash, Hasher, Merger> MaskableMerkleTree
+    for MinaMerkleTree<Item, Hash, Hasher, Merger, FixedHeightMode>
+where
+    Hasher: MerkleHasher<Item = Item, Hash = Hash>,
+    Merger: MerkleMerger<Hash = Hash>,
+    Hash: Clone + PartialEq + std::fmt::Debug,
+    Item: Clone + PartialEq,
+{
+    type Item = Item;
+    type Hash = Hash;
+    type Hasher = Hasher;
+    type Merger = Merger;
+
+    fn depth(&self) -> u32 {
+        self.mode.0
+    }
+
+    fn num_leaves(&self) -> usize {
+        self.leafs.len()
+    }
+
+    fn get_leaf(&self, index: usize) -> Option<Item> {
+        self.get(index).cloned()
+    }
+
+    fn get_node_hash(&mut self, height: u32, index: usize) -> Option<Hash> {
+        if height == 0 {
+            if index < self.leafs.len() {
+                self.calculate_hash_if_needed(self.nodes.len() + index)
+            } else {
+                None
+            }
+        } else if height <= self.variable_height {
+            // Nodes of the same height are stored contiguously,
+            // there are `first + 1` of them
+            let first = calculate_node_count(self.variable_height - height);
+            if index <= first {
+                self.calculate_hash_if_needed(first + index)
+            } else {
+                None
+            }
+        } else if index == 0 {
+            // Virtual nodes above the stored nodes, see [MerkleTree::root]
+            let mut hash = self.calculate_hash_if_needed(0);
+            for h in (self.variable_height + 1)..=height {
+                hash = Merger::merge([hash, None], MerkleTreeNodeMetadata::new(0, h));
+            }
+            hash
+        } else {
+            None
+        }
+    }
+
+    fn set_leaf(&mut self, index: usize, item: Item) {
+        match index.cmp(&self.leafs.len()) {
+            Ordering::Less => self.set(index, item.clone()),
+            Ordering::Equal => self.add(item.clone()),
+            Ordering::Greater => panic!("index {index} is out of range"),
+        }
+        self.masks.update(index, &item);
+    }
+
+    fn register<Mask>(&mut self, mask: &MerkleTreeHandle<Mask>) -> bool
+    where
+        Mask: MaskingMerkleTree<Item = Self::Item> + 'static,
+    {
+        self.masks.register(mask)
+    }
+
+    fn unregister<Mask>(&mut self, mask: &MerkleTreeHandle<Mask>) -> bool
+    where
+        Mask: MaskingMerkleTree<Item = Self::Item> + 'static,
+    {
+        self.masks.unregister(mask)
+    }
+}
+
 impl<Item, Hash, Hasher, Merger, Mode> Default for MinaMerkleTree<Item, Hash, Hasher, Merger, Mode>
 where
     Hasher: MerkleHasher<Item = Item, Hash = Hash>,
@@ -312,6 +423,7 @@ where
             variable_height: 0,
             leafs: Vec::new(),
             nodes: Vec::new(),
+            masks: Default::default(),
             _pd_hasher: Default::default(),
             _pd_merger: Default::default(),
         }
@@ -333,7 +445,8 @@ fn add_batch<Item, Hash, Hasher, Merger, Mode>(
         .map(|item| {
             (
                 // Tree height might be changed, do not calculate hash here.
-                item, None,
+                Some(item),
+                None,
             )
         })
         .collect();
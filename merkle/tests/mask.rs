@@ -0,0 +1,256 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use mina_hasher::{Fp, Hashable, ROInput};
+    use mina_merkle::*;
+    use proof_systems::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct TestLeafNode(Fp);
+
+    impl Hashable for TestLeafNode {
+        type D = ();
+
+        fn to_roinput(&self) -> mina_hasher::ROInput {
+            ROInput::new().append_field(self.0)
+        }
+
+        fn domain_string(_: Self::D) -> Option<String> {
+            None
+        }
+    }
+
+    struct TestHasher;
+
+    impl MerkleHasher for TestHasher {
+        type Item = TestLeafNode;
+        type Hash = Fp;
+        fn hash(item: &Self::Item, _: MerkleTreeNodeMetadata) -> Self::Hash {
+            item.0
+        }
+    }
+
+    type TestFixedHeightMerkleTree = MinaMerkleTree<
+        <TestHasher as MerkleHasher>::Item,
+        <TestHasher as MerkleHasher>::Hash,
+        TestHasher,
+        MinaPoseidonMerkleMerger,
+        FixedHeightMode,
+    >;
+
+    const HEIGHT: u32 = 6;
+
+    fn leaf(i: u64) -> TestLeafNode {
+        TestLeafNode(Fp::from(i))
+    }
+
+    fn build_tree(leaves: impl IntoIterator<Item = TestLeafNode>) -> TestFixedHeightMerkleTree {
+        let mut tree = TestFixedHeightMerkleTree::new(HEIGHT);
+        tree.add_batch(leaves);
+        tree
+    }
+
+    #[test]
+    fn test_fixed_height_tree_node_hashes() {
+        for n in [0, 1, 2, 5, 13, 32, 64] {
+            let mut tree = build_tree((0..n).map(leaf));
+            let root = tree.root();
+            assert_eq!(tree.root_hash(), root, "n: {n}");
+            assert_eq!(tree.num_leaves(), n as usize);
+            for i in 0..n as usize {
+                assert_eq!(tree.get_leaf(i), Some(leaf(i as u64)));
+                assert_eq!(tree.get_node_hash(0, i), Some(Fp::from(i as u64)));
+            }
+            assert_eq!(tree.get_leaf(n as usize), None);
+            assert_eq!(tree.get_node_hash(0, n as usize), None);
+        }
+    }
+
+    fn shared(tree: TestFixedHeightMerkleTree) -> MerkleTreeHandle<TestFixedHeightMerkleTree> {
+        Rc::new(RefCell::new(tree))
+    }
+
+    #[test]
+    fn test_mask_without_changes() {
+        let tree = shared(build_tree((0..13).map(leaf)));
+        let root = tree.borrow_mut().root();
+        let mask = MerkleMask::new(&tree);
+        let mut mask = mask.borrow_mut();
+        assert_eq!(mask.num_leaves(), 13);
+        assert_eq!(mask.depth(), HEIGHT);
+        assert_eq!(mask.get_leaf(7), Some(leaf(7)));
+        assert_eq!(mask.root(), root);
+        assert!(mask.masked_indices().is_empty());
+    }
+
+    #[test]
+    fn test_mask_set_and_append() {
+        let tree = shared(build_tree((0..13).map(leaf)));
+        let root = tree.borrow_mut().root();
+
+        let mask = MerkleMask::new(&tree);
+        let mut mask = mask.borrow_mut();
+        mask.set_leaf(3, leaf(100));
+        mask.set_leaf(12, leaf(101));
+        mask.add_batch([leaf(102), leaf(103)]);
+        assert_eq!(mask.num_leaves(), 15);
+        assert_eq!(mask.get_leaf(3), Some(leaf(100)));
+        assert_eq!(mask.get_leaf(4), Some(leaf(4)));
+        assert_eq!(mask.get_leaf(14), Some(leaf(103)));
+        assert_eq!(mask.masked_indices(), vec![3, 12, 13, 14]);
+
+        let mut expected = build_tree((0..13).map(leaf));
+        expected.set(3, leaf(100));
+        expected.set(12, leaf(101));
+        expected.add_batch([leaf(102), leaf(103)]);
+        let expected_root = expected.root();
+        assert_ne!(expected_root, root);
+        assert_eq!(mask.root(), expected_root);
+
+        // Hashes are recalculated after further changes
+        mask.set_leaf(3, leaf(3));
+        expected.set(3, leaf(3));
+        assert_eq!(mask.root(), expected.root());
+
+        // The parent is untouched
+        mask.discard();
+        assert_eq!(mask.num_leaves(), 13);
+        assert_eq!(mask.root(), root);
+        let mut tree = tree.borrow_mut();
+        assert_eq!(tree.num_leaves(), 13);
+        assert_eq!(tree.get_leaf(3), Some(leaf(3)));
+        assert_eq!(tree.root(), root);
+    }
+
+    #[test]
+    fn test_mask_commit() {
+        let tree = shared(build_tree((0..5).map(leaf)));
+        let mask = MerkleMask::new(&tree);
+        let mut mask = mask.borrow_mut();
+        mask.set_leaf(0, leaf(100));
+        mask.add_batch((5..20).map(leaf));
+        let mask_root = mask.root();
+        mask.commit();
+        assert!(mask.masked_indices().is_empty());
+        assert_eq!(mask.root(), mask_root);
+
+        let mut expected = build_tree([leaf(100)].into_iter().chain((1..20).map(leaf)));
+        let mut tree = tree.borrow_mut();
+        assert_eq!(tree.num_leaves(), 20);
+        assert_eq!(tree.root(), mask_root);
+        assert_eq!(tree.root(), expected.root());
+    }
+
+    #[test]
+    fn test_masks_sharing_parent() {
+        let tree = shared(build_tree((0..10).map(leaf)));
+        let mask1 = MerkleMask::new(&tree);
+        let mask2 = MerkleMask::new(&tree);
+        let mut expected1 = build_tree((0..10).map(leaf));
+        let mut expected2 = build_tree((0..10).map(leaf));
+
+        mask1.borrow_mut().set_leaf(1, leaf(101));
+        mask1.borrow_mut().set_leaf(4, leaf(104));
+        expected1.set(1, leaf(101));
+        expected1.set(4, leaf(104));
+        mask2.borrow_mut().set_leaf(1, leaf(101));
+        mask2.borrow_mut().set_leaf(5, leaf(105));
+        expected2.set(1, leaf(101));
+        expected2.set(5, leaf(105));
+        assert_eq!(mask1.borrow_mut().root(), expected1.root());
+        assert_eq!(mask2.borrow_mut().root(), expected2.root());
+
+        // Committing the first mask updates the second one, which drops
+        // the leaf that is now the same in the parent
+        mask1.borrow_mut().commit();
+        expected2.set(4, leaf(104));
+        assert_eq!(mask2.borrow().masked_indices(), vec![5]);
+        assert_eq!(mask2.borrow().get_leaf(4), Some(leaf(104)));
+        assert_eq!(mask2.borrow_mut().root(), expected2.root());
+        assert_eq!(tree.borrow_mut().root(), expected1.root());
+
+        // Leaves appended to the parent are visible through the masks
+        tree.borrow_mut().set_leaf(10, leaf(110));
+        expected1.add(leaf(110));
+        expected2.add(leaf(110));
+        assert_eq!(mask1.borrow().num_leaves(), 11);
+        assert_eq!(mask1.borrow_mut().root(), expected1.root());
+        assert_eq!(mask2.borrow().num_leaves(), 11);
+        assert_eq!(mask2.borrow_mut().root(), expected2.root());
+    }
+
+    #[test]
+    fn test_mask_registration() {
+        let tree = shared(build_tree((0..10).map(leaf)));
+        let mask = MerkleMask::new(&tree);
+        assert!(!tree.borrow_mut().register(&mask));
+        assert!(tree.borrow_mut().unregister(&mask));
+        assert!(!tree.borrow_mut().unregister(&mask));
+
+        // An unregistered mask is not updated by its parent
+        mask.borrow_mut().set_leaf(2, leaf(102));
+        tree.borrow_mut().set_leaf(2, leaf(102));
+        assert_eq!(mask.borrow().masked_indices(), vec![2]);
+        assert!(tree.borrow_mut().register(&mask));
+        tree.borrow_mut().set_leaf(2, leaf(102));
+        assert!(mask.borrow().masked_indices().is_empty());
+
+        // Dropped masks are unregistered by the parent
+        drop(mask);
+        tree.borrow_mut().set_leaf(3, leaf(103));
+        assert_eq!(tree.borrow().get_leaf(3), Some(leaf(103)));
+    }
+
+    #[test]
+    fn test_stacked_masks() {
+        let tree = shared(build_tree((0..10).map(leaf)));
+        let root = tree.borrow_mut().root();
+        let mut expected = build_tree((0..10).map(leaf));
+
+        let mask1 = MerkleMask::new(&tree);
+        mask1.borrow_mut().set_leaf(1, leaf(101));
+        expected.set(1, leaf(101));
+        let mask1_root = expected.root();
+        assert_eq!(mask1.borrow_mut().root(), mask1_root);
+
+        {
+            // Changes of a discarded mask are not visible to its parent
+            let mask2 = MerkleMask::new(&mask1);
+            let mut mask2 = mask2.borrow_mut();
+            mask2.set_leaf(2, leaf(102));
+            assert_eq!(mask2.get_leaf(1), Some(leaf(101)));
+            assert_ne!(mask2.root(), mask1_root);
+            mask2.discard();
+        }
+        assert_eq!(mask1.borrow_mut().root(), mask1_root);
+        assert_eq!(mask1.borrow().get_leaf(2), Some(leaf(2)));
+
+        {
+            let mask2 = MerkleMask::new(&mask1);
+            mask2.borrow_mut().set_leaf(2, leaf(102));
+            mask2.borrow_mut().add(leaf(110));
+            let mask3 = MerkleMask::new(&mask2);
+            mask3.borrow_mut().set_leaf(1, leaf(201));
+            expected.set(1, leaf(201));
+            expected.set(2, leaf(102));
+            expected.add(leaf(110));
+            assert_eq!(mask3.borrow_mut().root(), expected.root());
+            mask3.borrow_mut().commit();
+            assert_eq!(mask2.borrow_mut().root(), expected.root());
+            mask2.borrow_mut().commit();
+        }
+        assert_eq!(mask1.borrow().num_leaves(), 11);
+        assert_eq!(mask1.borrow_mut().root(), expected.root());
+        mask1.borrow_mut().commit();
+
+        let mut tree = tree.borrow_mut();
+        assert_eq!(tree.get_leaf(1), Some(leaf(201)));
+        assert_eq!(tree.get_leaf(2), Some(leaf(102)));
+        assert_eq!(tree.get_leaf(10), Some(leaf(110)));
+        assert_ne!(tree.root(), root);
+        assert_eq!(tree.root(), expected.root());
+    }
+}
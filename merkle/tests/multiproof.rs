@@ -0,0 +1,124 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use mina_hasher::Fp;
+    use mina_merkle::*;
+
+    struct TestHasher;
+
+    impl MerkleHasher for TestHasher {
+        type Item = u64;
+        type Hash = Fp;
+        fn hash(item: &Self::Item, _: MerkleTreeNodeMetadata) -> Self::Hash {
+            Fp::from(*item)
+        }
+    }
+
+    type TestMerkleTree =
+        MinaMerkleTree<u64, Fp, TestHasher, MinaPoseidonMerkleMerger, FixedHeightMode>;
+
+    type TestMerkleMultiProof =
+        DefaultMerkleMultiProof<u64, Fp, TestHasher, MinaPoseidonMerkleMerger>;
+
+    const HEIGHT: u32 = 10;
+
+    fn build_tree(n: u64) -> TestMerkleTree {
+        let mut tree = TestMerkleTree::new(HEIGHT);
+        tree.add_batch(0..n);
+        tree
+    }
+
+    #[test]
+    fn test_multi_proof() {
+        let mut tree = build_tree(600);
+        let root = tree.root().unwrap();
+        for indices in [
+            vec![0],
+            vec![599],
+            vec![0, 1],
+            vec![1, 2],
+            vec![3, 2, 100, 101, 102, 511, 512, 599],
+            (0..600).step_by(7).collect(),
+            (0..600).collect(),
+        ] {
+            let proof = tree.get_multi_proof(indices.iter().copied()).unwrap();
+            assert!(proof.verify(&root), "indices: {indices:?}");
+
+            // Shared sibling hashes are deduplicated
+            let mut sorted = indices.clone();
+            sorted.sort_unstable();
+            assert_eq!(
+                proof.leaves,
+                sorted.iter().map(|&i| (i, i as u64)).collect::<Vec<_>>()
+            );
+            assert!(proof.hashes.len() <= HEIGHT as usize * indices.len());
+        }
+
+        // Two adjacent leaves share all hashes but one
+        let proof = tree.get_multi_proof([4, 5]).unwrap();
+        assert_eq!(proof.hashes.len(), HEIGHT as usize - 1);
+        // All leaves need no hash except for the empty sub-trees
+        let proof = tree.get_multi_proof(0..600).unwrap();
+        assert!(proof.hashes.len() < HEIGHT as usize);
+
+        assert!(tree.get_multi_proof([]).is_none());
+        assert!(tree.get_multi_proof([1, 600]).is_none());
+    }
+
+    #[test]
+    fn test_multi_proof_from_mask() {
+        let mut tree = build_tree(100);
+        let mut mask = MerkleMask::new(&mut tree);
+        mask.set_leaf(3, 1000);
+        mask.add(100);
+        let root = mask.root().unwrap();
+        let proof = mask.get_multi_proof([3, 50, 100]).unwrap();
+        assert!(proof.verify(&root));
+    }
+
+    #[test]
+    fn test_invalid_multi_proof() {
+        let mut tree = build_tree(600);
+        let root = tree.root().unwrap();
+        let indices = [3, 20, 21, 300];
+
+        let mut proof = tree.get_multi_proof(indices).unwrap();
+        proof.leaves[1].1 = 1000;
+        assert!(!proof.verify(&root));
+
+        let mut proof = tree.get_multi_proof(indices).unwrap();
+        proof.hashes.pop();
+        assert_eq!(proof.root_hash(), Err(MerkleProofError::InvalidProof));
+
+        let mut proof = tree.get_multi_proof(indices).unwrap();
+        proof.hashes.push(None);
+        assert_eq!(proof.root_hash(), Err(MerkleProofError::InvalidProof));
+
+        let mut proof = tree.get_multi_proof(indices).unwrap();
+        proof.leaves.push((2_usize.pow(HEIGHT), 0));
+        assert_eq!(proof.root_hash(), Err(MerkleProofError::InvalidIndex));
+    }
+
+    #[test]
+    fn test_multi_proof_encoding_roundtrip() -> anyhow::Result<()> {
+        let mut tree = build_tree(600);
+        let root = tree.root().unwrap();
+        let proof = tree.get_multi_proof([3, 20, 21, 300]).unwrap();
+        let encoding: MerkleMultiProofEncoding<u64> = proof.into();
+
+        let mut bytes = vec![];
+        bin_prot::to_writer(&mut bytes, &encoding)?;
+        let decoded: MerkleMultiProofEncoding<u64> = bin_prot::from_reader(bytes.as_slice())?;
+        assert_eq!(decoded, encoding);
+
+        let json = serde_json::to_string(&encoding)?;
+        let decoded: MerkleMultiProofEncoding<u64> = serde_json::from_str(&json)?;
+        assert_eq!(decoded, encoding);
+
+        let proof: TestMerkleMultiProof = decoded.try_into()?;
+        assert!(proof.verify(&root));
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use mina_hasher::Fp;
+    use mina_merkle::*;
+
+    struct TestHasher;
+
+    impl MerkleHasher for TestHasher {
+        type Item = Fp;
+        type Hash = Fp;
+        fn hash(item: &Self::Item, _: MerkleTreeNodeMetadata) -> Self::Hash {
+            *item
+        }
+    }
+
+    type TestMerkleTree =
+        MinaMerkleTree<Fp, Fp, TestHasher, MinaPoseidonMerkleMerger, FixedHeightMode>;
+
+    type TestSparseMerkleTree = MinaSparseMerkleTree<Fp, Fp, TestHasher, MinaPoseidonMerkleMerger>;
+
+    const HEIGHT: u32 = 8;
+
+    fn build_tree(n: u64) -> TestMerkleTree {
+        let mut tree = TestMerkleTree::new(HEIGHT);
+        tree.add_batch((0..n).map(Fp::from));
+        tree
+    }
+
+    #[test]
+    fn test_sparse_tree_from_proofs() {
+        let mut tree = build_tree(100);
+        let root = tree.root();
+        let indices = [0, 1, 37, 99];
+
+        let mut sparse = TestSparseMerkleTree::new(HEIGHT);
+        assert_eq!(sparse.root(), None);
+        sparse
+            .add_batch(indices.iter().map(|&i| tree.get_proof(i).unwrap()))
+            .unwrap();
+        assert_eq!(sparse.root(), root);
+        assert_eq!(sparse.count(), indices.len());
+        assert_eq!(sparse.indices().collect::<Vec<_>>(), indices);
+        assert_eq!(sparse.get(37), Some(&Fp::from(37_u64)));
+        assert_eq!(sparse.get(38), None);
+
+        // Updates are reflected in the root hash and the merkle proofs of all tracked leaves
+        for (i, value) in [(37, 1000_u64), (0, 1001), (99, 1002), (37, 1003)] {
+            tree.set(i, Fp::from(value));
+            sparse.set(i, Fp::from(value)).unwrap();
+            let root = tree.root();
+            assert_eq!(sparse.root(), root);
+            for &j in indices.iter() {
+                let proof = sparse.get_proof(j).unwrap();
+                assert_eq!(Some(&proof.item), tree.get(j));
+                assert!(proof.verify(&root.unwrap()));
+            }
+        }
+
+        assert_eq!(
+            sparse.set(2, Fp::from(2_u64)),
+            Err(SparseMerkleTreeError::UnknownLeaf(2))
+        );
+        assert!(sparse.get_proof(2).is_none());
+    }
+
+    #[test]
+    fn test_sparse_tree_rejects_invalid_proofs() {
+        let mut tree = build_tree(100);
+        let mut sparse = TestSparseMerkleTree::new(HEIGHT);
+        sparse.add(tree.get_proof(3).unwrap()).unwrap();
+        let root = sparse.root();
+
+        // Proof of a different ledger
+        let mut other = build_tree(101);
+        assert_eq!(
+            sparse.add(other.get_proof(4).unwrap()),
+            Err(SparseMerkleTreeError::RootMismatch)
+        );
+
+        // Proof of a ledger with a different height
+        let mut other = TestMerkleTree::new(HEIGHT + 1);
+        other.add_batch((0..100).map(Fp::from));
+        assert_eq!(
+            sparse.add(other.get_proof(4).unwrap()),
+            Err(SparseMerkleTreeError::HeightMismatch {
+                expected: HEIGHT,
+                actual: HEIGHT + 1
+            })
+        );
+
+        // Tampered proof
+        let mut proof = tree.get_proof(4).unwrap();
+        proof.item = Fp::from(5_u64);
+        assert_eq!(sparse.add(proof), Err(SparseMerkleTreeError::RootMismatch));
+
+        // Invalid proofs are not tracked
+        assert_eq!(sparse.root(), root);
+        assert_eq!(sparse.indices().collect::<Vec<_>>(), vec![3]);
+    }
+}
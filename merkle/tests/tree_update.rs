@@ -0,0 +1,152 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use mina_hasher::Fp;
+    use mina_merkle::*;
+    use std::cell::Cell;
+
+    thread_local! {
+        static HASH_COUNT: Cell<usize> = Cell::new(0);
+        static MERGE_COUNT: Cell<usize> = Cell::new(0);
+    }
+
+    fn take_counts() -> (usize, usize) {
+        (
+            HASH_COUNT.with(|c| c.take()),
+            MERGE_COUNT.with(|c| c.take()),
+        )
+    }
+
+    struct CountingHasher;
+
+    impl MerkleHasher for CountingHasher {
+        type Item = i64;
+        type Hash = i64;
+        fn hash(item: &Self::Item, _: MerkleTreeNodeMetadata) -> Self::Hash {
+            HASH_COUNT.with(|c| c.set(c.get() + 1));
+            *item
+        }
+    }
+
+    struct CountingMerger;
+
+    impl MerkleMerger for CountingMerger {
+        type Hash = i64;
+        fn merge(hashes: [Option<Self::Hash>; 2], _: MerkleTreeNodeMetadata) -> Option<Self::Hash> {
+            MERGE_COUNT.with(|c| c.set(c.get() + 1));
+            match hashes {
+                [None, None] => None,
+                [left, right] => Some(left.unwrap_or_default() + right.unwrap_or_default()),
+            }
+        }
+    }
+
+    type TestMerkleTree =
+        MinaMerkleTree<i64, i64, CountingHasher, CountingMerger, VariableHeightMode>;
+
+    type TestFixedHeightMerkleTree =
+        MinaMerkleTree<i64, i64, CountingHasher, CountingMerger, FixedHeightMode>;
+
+    struct FpHasher;
+
+    impl MerkleHasher for FpHasher {
+        type Item = Fp;
+        type Hash = Fp;
+        fn hash(item: &Self::Item, _: MerkleTreeNodeMetadata) -> Self::Hash {
+            *item
+        }
+    }
+
+    type TestPoseidonMerkleTree =
+        MinaMerkleTree<Fp, Fp, FpHasher, MinaPoseidonMerkleMerger, FixedHeightMode>;
+
+    #[test]
+    fn test_set_get_remove() {
+        let mut tree = TestMerkleTree::new();
+        tree.add_batch(1..=100);
+        assert_eq!(tree.root(), Some((1..=100).sum()));
+        assert_eq!(tree.get(9), Some(&10));
+
+        tree.set(9, 1000);
+        assert_eq!(tree.get(9), Some(&1000));
+        assert_eq!(tree.root(), Some((1..=100).sum::<i64>() - 10 + 1000));
+
+        assert_eq!(tree.remove(9), Some(1000));
+        assert_eq!(tree.remove(9), None);
+        assert_eq!(tree.get(9), None);
+        assert_eq!(tree.count(), 100);
+        assert_eq!(tree.root(), Some((1..=100).sum::<i64>() - 10));
+        assert!(tree.get_proof(9).is_none());
+        assert!(tree.get_proof(10).is_some());
+
+        // A removed leaf can be set again
+        tree.set(9, 10);
+        assert_eq!(tree.root(), Some((1..=100).sum()));
+        assert!(tree.get_proof(9).unwrap().verify(&(1..=100).sum()));
+
+        for i in 0..100 {
+            tree.remove(i);
+        }
+        assert_eq!(tree.root(), None);
+    }
+
+    #[test]
+    fn test_set_after_removing_sibling_leaves() {
+        let mut tree = TestMerkleTree::new();
+        tree.add_batch(1..=8);
+        assert_eq!(tree.root(), Some(36));
+
+        // The parent of the removed siblings has no hash, but its ancestors do
+        assert_eq!(tree.remove(2), Some(3));
+        assert_eq!(tree.remove(3), Some(4));
+        assert_eq!(tree.root(), Some(29));
+
+        // Setting one of them has to invalidate the whole path to the root
+        tree.set(2, 100);
+        assert_eq!(tree.root(), Some(129));
+        assert!(tree.get_proof(2).unwrap().verify(&129));
+    }
+
+    #[test]
+    fn test_incremental_recomputation() {
+        const HEIGHT: u32 = 10;
+        let mut tree = TestFixedHeightMerkleTree::new(HEIGHT);
+        tree.add_batch(0..1000);
+        let root = tree.root();
+        assert_eq!(take_counts().0, 1000);
+
+        // Only the nodes on the path to the root are recalculated
+        tree.set(500, 10_000);
+        assert_eq!(tree.root(), root.map(|r| r - 500 + 10_000));
+        assert_eq!(take_counts(), (1, HEIGHT as usize));
+
+        assert_eq!(tree.remove(123), Some(123));
+        assert_eq!(tree.root(), root.map(|r| r - 500 + 10_000 - 123));
+        assert_eq!(take_counts(), (0, HEIGHT as usize));
+
+        // Nothing is recalculated when there's no change
+        tree.root();
+        assert_eq!(take_counts(), (0, 0));
+    }
+
+    #[test]
+    fn test_removed_leaf_has_empty_hash() {
+        let mut tree = TestPoseidonMerkleTree::new(8);
+        tree.add_batch((0..20_u64).map(Fp::from));
+        let root = tree.root();
+
+        let mut expected = TestPoseidonMerkleTree::new(8);
+        expected.add_batch((0..19_u64).map(Fp::from));
+        let expected_root = expected.root();
+        assert_ne!(root, expected_root);
+
+        // The last leaf is replaced by an empty leaf, same as it's never added
+        assert_eq!(tree.remove(19), Some(Fp::from(19_u64)));
+        assert_eq!(tree.root(), expected_root);
+
+        tree.set(19, Fp::from(19_u64));
+        assert_eq!(tree.root(), root);
+    }
+}
@@ -4,6 +4,7 @@
 use super::*;
 use libp2p::{
     core::{muxing::StreamMuxerBox, transport, upgrade},
+    futures::{AsyncRead, AsyncWrite},
     identity,
     mplex::MplexConfig,
     noise::{self, AuthenticKeypair, X25519Spec},
@@ -104,7 +105,21 @@ impl TransportBuilder {
             }
         };
 
-        Ok((
+        Ok(self.build_with_transport(transport))
+    }
+
+    /// Builds libp2p transport on top of the given base transport instead of the
+    /// default TCP and websocket one, e.g. an in-process memory transport
+    pub fn build_with_transport<T>(self, transport: T) -> (BoxedP2PTransport, PeerId)
+    where
+        T: Transport + Send + 'static,
+        T::Output: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        T::Error: Send + Sync + 'static,
+        T::Listener: Send + 'static,
+        T::ListenerUpgrade: Send + 'static,
+        T::Dial: Send + 'static,
+    {
+        (
             transport
                 .and_then(move |socket, _| self.pnet_config.handshake(socket))
                 .upgrade(upgrade::Version::V1)
@@ -113,7 +128,7 @@ impl TransportBuilder {
                 .timeout(self.timeout)
                 .boxed(),
             self.peer_id,
-        ))
+        )
     }
 }
 
@@ -0,0 +1,321 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//! Gossip of blocks, snark work and user commands over the pubsub topic of mina
+
+use libp2p::{
+    futures::{Stream, StreamExt},
+    gossipsub::{
+        error::{PublishError, SubscriptionError},
+        Gossipsub, GossipsubConfigBuilder, GossipsubEvent, IdentTopic, MessageAcceptance,
+        MessageAuthenticity, MessageId, ValidationMode,
+    },
+    identity,
+    swarm::SwarmEvent,
+    NetworkBehaviour, PeerId, Swarm,
+};
+use log::error;
+use mina_rs_base::types::{ExternalTransition, TransactionSnarkWork, UserCommand};
+use mina_serialization_types::{
+    gossip, snark_work,
+    v1::{FeeWithProverV1, PricedProofV1, TransactionPoolDiffV1, UserCommandV1},
+};
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use thiserror::Error;
+
+/// Pubsub topic that mina nodes gossip their messages on
+pub const CONSENSUS_MESSAGES_TOPIC: &str = "coda/consensus-messages/0.0.1";
+
+/// Maximum size of a gossip message, blocks with a full staged ledger diff
+/// are far larger than the default limit of gossipsub
+pub const MAX_GOSSIP_MESSAGE_SIZE: usize = 32 * 1024 * 1024;
+
+/// Errors of the gossip behaviour
+#[derive(Error, Debug)]
+pub enum GossipError {
+    /// Invalid gossipsub configuration
+    #[error("Invalid gossipsub config: {0}")]
+    Config(&'static str),
+
+    /// Failed to subscribe to the topic
+    #[error("Failed to subscribe: {0:?}")]
+    Subscription(SubscriptionError),
+
+    /// Failed to publish a message
+    #[error("Failed to publish: {0:?}")]
+    Publish(PublishError),
+
+    /// The payload of a message is not a valid bin_prot encoded gossip message
+    #[error("Invalid gossip message: {0}")]
+    BinProt(String),
+}
+
+/// Message that is gossiped over [CONSENSUS_MESSAGES_TOPIC], its bin_prot encoding has no
+/// version tag of its own, see [gossip::GossipMessage]
+#[derive(Clone, Debug, PartialEq)]
+pub enum GossipMessage {
+    /// A new block
+    NewState(Box<ExternalTransition>),
+    /// Proofs of a completed snark work along with the fee asked by the prover
+    SnarkPoolDiff(Box<TransactionSnarkWork>),
+    /// New user commands
+    TransactionPoolDiff(Vec<UserCommand>),
+}
+
+impl GossipMessage {
+    /// Decodes a bin_prot encoded gossip message
+    pub fn try_from_binprot(bytes: &[u8]) -> Result<Self, GossipError> {
+        let message: gossip::GossipMessage =
+            bin_prot::from_reader_strict(bytes).map_err(|e| GossipError::BinProt(e.to_string()))?;
+        Ok(message.into())
+    }
+
+    /// Encodes the gossip message with bin_prot
+    pub fn try_into_binprot(self) -> Result<Vec<u8>, GossipError> {
+        let message: gossip::GossipMessage = self.into();
+        let mut bytes = Vec::new();
+        bin_prot::to_writer(&mut bytes, &message)
+            .map_err(|e| GossipError::BinProt(e.to_string()))?;
+        Ok(bytes)
+    }
+}
+
+impl From<gossip::GossipMessage> for GossipMessage {
+    fn from(message: gossip::GossipMessage) -> Self {
+        match message {
+            gossip::GossipMessage::NewState(block) => Self::NewState(Box::new((*block).into())),
+            gossip::GossipMessage::SnarkPoolDiff(diff) => {
+                // The statements are the ones of the proofs, so only the priced proof is kept
+                let gossip::SnarkPoolDiff::AddSolvedWork(_, priced_proof) = (*diff).inner();
+                let priced_proof = (*priced_proof).inner();
+                let fee = priced_proof.fee.inner();
+                let work = snark_work::TransactionSnarkWork {
+                    fee: fee.fee,
+                    proofs: priced_proof.proof,
+                    prover: fee.prover,
+                };
+                Self::SnarkPoolDiff(Box::new(work.into()))
+            }
+            gossip::GossipMessage::TransactionPoolDiff(commands) => {
+                Self::TransactionPoolDiff(commands.inner().into_iter().map(Into::into).collect())
+            }
+        }
+    }
+}
+
+impl From<GossipMessage> for gossip::GossipMessage {
+    fn from(message: GossipMessage) -> Self {
+        match message {
+            GossipMessage::NewState(block) => Self::NewState(Box::new((*block).into())),
+            GossipMessage::SnarkPoolDiff(work) => {
+                let statements = match work.statements().as_slice() {
+                    [one] => gossip::TransactionSnarkWorkStatement::One((*one).clone().into()),
+                    [first, second] => gossip::TransactionSnarkWorkStatement::Two(
+                        (*first).clone().into(),
+                        (*second).clone().into(),
+                    ),
+                    _ => unreachable!("a snark work has one or two proofs"),
+                };
+                let work: snark_work::TransactionSnarkWork = (*work).into();
+                let priced_proof: PricedProofV1 = gossip::PricedProof {
+                    proof: work.proofs,
+                    fee: FeeWithProverV1::new(gossip::FeeWithProver {
+                        fee: work.fee,
+                        prover: work.prover,
+                    }),
+                }
+                .into();
+                Self::SnarkPoolDiff(Box::new(
+                    gossip::SnarkPoolDiff::AddSolvedWork(
+                        Box::new(statements.into()),
+                        Box::new(priced_proof),
+                    )
+                    .into(),
+                ))
+            }
+            GossipMessage::TransactionPoolDiff(commands) => {
+                let commands: Vec<UserCommandV1> = commands.into_iter().map(Into::into).collect();
+                Self::TransactionPoolDiff(TransactionPoolDiffV1::new(commands))
+            }
+        }
+    }
+}
+
+/// Events emitted by [GossipBehaviour]
+#[derive(Debug)]
+pub enum GossipEvent {
+    /// A gossip message is received
+    Message {
+        /// Peer that propagated the message
+        propagation_source: PeerId,
+        /// Id of the message
+        message_id: MessageId,
+        /// The decoded message
+        message: GossipMessage,
+    },
+    /// A message that cannot be decoded is received
+    InvalidMessage {
+        /// Peer that propagated the message
+        propagation_source: PeerId,
+        /// Id of the message
+        message_id: MessageId,
+        /// Reason of the decoding failure
+        error: GossipError,
+    },
+    /// A peer subscribed to the topic
+    Subscribed(PeerId),
+    /// A peer unsubscribed from the topic
+    Unsubscribed(PeerId),
+    /// A peer does not support gossipsub
+    GossipsubNotSupported(PeerId),
+}
+
+impl From<GossipsubEvent> for GossipEvent {
+    fn from(event: GossipsubEvent) -> Self {
+        match event {
+            GossipsubEvent::Message {
+                propagation_source,
+                message_id,
+                message,
+            } => match GossipMessage::try_from_binprot(&message.data) {
+                Ok(message) => Self::Message {
+                    propagation_source,
+                    message_id,
+                    message,
+                },
+                Err(error) => Self::InvalidMessage {
+                    propagation_source,
+                    message_id,
+                    error,
+                },
+            },
+            GossipsubEvent::Subscribed { peer_id, .. } => Self::Subscribed(peer_id),
+            GossipsubEvent::Unsubscribed { peer_id, .. } => Self::Unsubscribed(peer_id),
+            GossipsubEvent::GossipsubNotSupported { peer_id } => {
+                Self::GossipsubNotSupported(peer_id)
+            }
+        }
+    }
+}
+
+/// Network behaviour that joins [CONSENSUS_MESSAGES_TOPIC], decodes the gossip messages
+/// that are received and publishes our own messages
+#[derive(NetworkBehaviour)]
+#[behaviour(out_event = "GossipEvent", event_process = false)]
+pub struct GossipBehaviour {
+    gossipsub: Gossipsub,
+}
+
+impl GossipBehaviour {
+    /// Creates a behaviour that signs its messages with the given keypair
+    /// and subscribes to [CONSENSUS_MESSAGES_TOPIC]. Received messages are only forwarded
+    /// once they are reported valid with [GossipBehaviour::report_validation_result]
+    pub fn new(keypair: identity::Keypair) -> Result<Self, GossipError> {
+        let config = GossipsubConfigBuilder::default()
+            .validation_mode(ValidationMode::Strict)
+            .validate_messages()
+            .max_transmit_size(MAX_GOSSIP_MESSAGE_SIZE)
+            .build()
+            .map_err(GossipError::Config)?;
+        let mut gossipsub = Gossipsub::new(MessageAuthenticity::Signed(keypair), config)
+            .map_err(GossipError::Config)?;
+        gossipsub
+            .subscribe(&Self::topic())
+            .map_err(GossipError::Subscription)?;
+        Ok(Self { gossipsub })
+    }
+
+    /// The topic of the gossip messages
+    pub fn topic() -> IdentTopic {
+        IdentTopic::new(CONSENSUS_MESSAGES_TOPIC)
+    }
+
+    /// Reports the result of the validation of a received message, accepted messages are
+    /// forwarded to the other peers and rejected ones penalize the peer that propagated them.
+    /// Returns whether the message was still in the cache
+    pub fn report_validation_result(
+        &mut self,
+        message_id: &MessageId,
+        propagation_source: &PeerId,
+        acceptance: MessageAcceptance,
+    ) -> Result<bool, GossipError> {
+        self.gossipsub
+            .report_message_validation_result(message_id, propagation_source, acceptance)
+            .map_err(GossipError::Publish)
+    }
+
+    /// Publishes a message to the peers subscribed to the topic
+    pub fn publish(&mut self, message: GossipMessage) -> Result<MessageId, GossipError> {
+        let data = message.try_into_binprot()?;
+        self.gossipsub
+            .publish(Self::topic(), data)
+            .map_err(GossipError::Publish)
+    }
+}
+
+/// Drives the swarm and yields the gossip messages that are received along with the peers
+/// that propagated them and their ids, see [gossip_messages]
+pub struct GossipMessages<'a> {
+    swarm: &'a mut Swarm<GossipBehaviour>,
+}
+
+impl<'a> GossipMessages<'a> {
+    /// Reports the result of the validation of a message yielded by the stream,
+    /// see [GossipBehaviour::report_validation_result]
+    pub fn report_validation_result(
+        &mut self,
+        message_id: &MessageId,
+        propagation_source: &PeerId,
+        acceptance: MessageAcceptance,
+    ) -> Result<bool, GossipError> {
+        self.swarm.behaviour_mut().report_validation_result(
+            message_id,
+            propagation_source,
+            acceptance,
+        )
+    }
+}
+
+impl<'a> Stream for GossipMessages<'a> {
+    type Item = (PeerId, MessageId, GossipMessage);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match self.swarm.poll_next_unpin(cx) {
+                Poll::Ready(Some(SwarmEvent::Behaviour(GossipEvent::Message {
+                    propagation_source,
+                    message_id,
+                    message,
+                }))) => return Poll::Ready(Some((propagation_source, message_id, message))),
+                // Messages that cannot be decoded are never forwarded
+                Poll::Ready(Some(SwarmEvent::Behaviour(GossipEvent::InvalidMessage {
+                    propagation_source,
+                    message_id,
+                    ..
+                }))) => {
+                    if let Err(err) = self.report_validation_result(
+                        &message_id,
+                        &propagation_source,
+                        MessageAcceptance::Reject,
+                    ) {
+                        error!("{err}");
+                    }
+                }
+                Poll::Ready(Some(_)) => {}
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Drives the swarm and yields the gossip messages that are received along with the peers
+/// that propagated them and their ids, other events are dropped. The yielded messages
+/// are only forwarded once they are reported valid with
+/// [GossipMessages::report_validation_result], messages that cannot be decoded are rejected
+pub fn gossip_messages(swarm: &mut Swarm<GossipBehaviour>) -> GossipMessages<'_> {
+    GossipMessages { swarm }
+}
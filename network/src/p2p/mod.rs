@@ -7,3 +7,13 @@ mod config;
 pub use config::*;
 mod builder;
 pub use builder::*;
+mod gossip;
+pub use gossip::*;
+mod rpc;
+pub use rpc::*;
+mod node;
+pub use node::*;
+mod peer_manager;
+pub use peer_manager::*;
+mod trust_system;
+pub use trust_system::*;
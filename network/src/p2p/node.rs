@@ -0,0 +1,87 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//! Network behaviour of a mina node, which combines the protocols that mina peers speak
+
+use super::*;
+use libp2p::{
+    identify::{Identify, IdentifyConfig, IdentifyEvent},
+    identity,
+    kad::{store::MemoryStore, Kademlia, KademliaConfig, KademliaEvent},
+    NetworkBehaviour,
+};
+
+/// Protocol version that is reported to peers by the identify protocol
+pub const IDENTIFY_PROTOCOL_VERSION: &str = "ipfs/0.1.0";
+
+/// Events emitted by [NodeBehaviour]
+#[derive(Debug)]
+pub enum NodeEvent {
+    /// Event of the gossip protocol
+    Gossip(GossipEvent),
+    /// Event of the rpc protocol
+    Rpc(RpcEvent),
+    /// Event of the Kademlia DHT
+    Kademlia(KademliaEvent),
+    /// Event of the identify protocol
+    Identify(IdentifyEvent),
+}
+
+impl From<GossipEvent> for NodeEvent {
+    fn from(event: GossipEvent) -> Self {
+        Self::Gossip(event)
+    }
+}
+
+impl From<RpcEvent> for NodeEvent {
+    fn from(event: RpcEvent) -> Self {
+        Self::Rpc(event)
+    }
+}
+
+impl From<KademliaEvent> for NodeEvent {
+    fn from(event: KademliaEvent) -> Self {
+        Self::Kademlia(event)
+    }
+}
+
+impl From<IdentifyEvent> for NodeEvent {
+    fn from(event: IdentifyEvent) -> Self {
+        Self::Identify(event)
+    }
+}
+
+/// Network behaviour that gossips with peers, queries them with rpcs
+/// and discovers new peers, see [PeerManager]
+#[derive(NetworkBehaviour)]
+#[behaviour(out_event = "NodeEvent", event_process = false)]
+pub struct NodeBehaviour {
+    /// Gossip of blocks, snark work and user commands
+    pub gossip: GossipBehaviour,
+    /// Rpcs between peers
+    pub rpc: RpcBehaviour,
+    /// Kademlia DHT that peers are discovered from
+    pub kademlia: Kademlia<MemoryStore>,
+    /// Exchange of the listen addresses of peers
+    pub identify: Identify,
+}
+
+impl NodeBehaviour {
+    /// Creates a behaviour that signs its gossip messages with the given keypair
+    pub fn new(keypair: identity::Keypair) -> Result<Self, GossipError> {
+        let peer_id = keypair.public().to_peer_id();
+        let mut kademlia_config = KademliaConfig::default();
+        kademlia_config.set_protocol_name(KADEMLIA_PROTOCOL_NAME);
+        let kademlia = Kademlia::with_config(peer_id, MemoryStore::new(peer_id), kademlia_config);
+        let identify = Identify::new(IdentifyConfig::new(
+            IDENTIFY_PROTOCOL_VERSION.into(),
+            keypair.public(),
+        ));
+        Ok(Self {
+            gossip: GossipBehaviour::new(keypair)?,
+            rpc: RpcBehaviour::new(),
+            kademlia,
+            identify,
+        })
+    }
+}
@@ -0,0 +1,278 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//! Discovery of peers over the Kademlia DHT of mina, bootstrapped from seed peers,
+//! and banning of the peers that the [TrustSystem] distrusts
+
+use super::*;
+use libp2p::{
+    identify::IdentifyEvent,
+    kad::{GetClosestPeersOk, KademliaEvent, QueryResult},
+    multiaddr::Protocol,
+    swarm::{DialError, SwarmEvent},
+    Multiaddr, PeerId, Swarm,
+};
+use log::warn;
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+/// Protocol name of the Kademlia DHT of mina
+pub const KADEMLIA_PROTOCOL_NAME: &[u8] = b"/coda/kad/1.0.0";
+
+/// Errors of [PeerManager]
+#[derive(Error, Debug)]
+pub enum PeerManagerError {
+    /// The address of a seed peer does not end with its peer id
+    #[error("Seed address without peer id: {0}")]
+    SeedWithoutPeerId(Multiaddr),
+
+    /// Failed to dial a seed peer
+    #[error("Failed to dial seed peer: {0}")]
+    Dial(#[from] DialError),
+}
+
+/// Configuration of [PeerManager]
+#[derive(Clone, Debug)]
+pub struct PeerManagerConfig {
+    /// Addresses of the seed peers, which end with `/p2p/<peer id>`
+    pub seeds: Vec<Multiaddr>,
+    /// Number of connected peers below which new peers are discovered and dialed
+    pub target_peers: usize,
+    /// Number of connected peers above which new connections are closed, seed peers are always kept
+    pub max_peers: usize,
+    /// Interval at which [PeerManager::discover] is expected to be called
+    pub discovery_interval: Duration,
+    /// Configuration of the trust system that bans misbehaving peers
+    pub trust: TrustConfig,
+}
+
+impl Default for PeerManagerConfig {
+    fn default() -> Self {
+        Self {
+            seeds: vec![],
+            target_peers: 20,
+            max_peers: 50,
+            discovery_interval: Duration::from_secs(60),
+            trust: TrustConfig::default(),
+        }
+    }
+}
+
+/// Churn of the peers, the ban of a peer is reported by [PeerManager::record]
+/// and the other events by [PeerManager::on_swarm_event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PeerEvent {
+    /// A peer is discovered over the DHT
+    Discovered(PeerId),
+    /// The first connection to a peer is established
+    Connected(PeerId),
+    /// The last connection to a peer is closed
+    Disconnected(PeerId),
+    /// A peer is banned until the ban expires. The ban is enforced by the swarm, which closes
+    /// the connections to the peer and drops the new ones once they are established,
+    /// the transport itself does not refuse them
+    Banned(PeerId),
+}
+
+/// Manager of the peers of a [NodeBehaviour] swarm, which bootstraps from the seed peers
+/// and keeps the number of connected peers between the target and the maximum.
+/// The actions of peers are recorded in its [TrustSystem], which gets the swarm
+/// to ban the misbehaving ones
+#[derive(Clone, Debug, Default)]
+pub struct PeerManager {
+    config: PeerManagerConfig,
+    seeds: Vec<(PeerId, Multiaddr)>,
+    connected: HashSet<PeerId>,
+    trust_system: TrustSystem,
+}
+
+impl PeerManager {
+    /// Creates a peer manager, the seed addresses have to end with the peer ids of the seeds
+    pub fn new(config: PeerManagerConfig) -> Result<Self, PeerManagerError> {
+        let seeds = config
+            .seeds
+            .iter()
+            .map(|address| match address.iter().last() {
+                Some(Protocol::P2p(hash)) => PeerId::from_multihash(hash)
+                    .map(|peer_id| (peer_id, address.clone()))
+                    .map_err(|_| PeerManagerError::SeedWithoutPeerId(address.clone())),
+                _ => Err(PeerManagerError::SeedWithoutPeerId(address.clone())),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            trust_system: TrustSystem::new(config.trust.clone()),
+            config,
+            seeds,
+            connected: HashSet::new(),
+        })
+    }
+
+    /// Gets the configuration
+    pub fn config(&self) -> &PeerManagerConfig {
+        &self.config
+    }
+
+    /// Gets the connected peers
+    pub fn connected_peers(&self) -> impl Iterator<Item = &PeerId> {
+        self.connected.iter()
+    }
+
+    /// Number of connected peers
+    pub fn num_connected(&self) -> usize {
+        self.connected.len()
+    }
+
+    /// Gets the trust system
+    pub fn trust_system(&self) -> &TrustSystem {
+        &self.trust_system
+    }
+
+    /// Records an action of a peer, the peer is banned from the swarm
+    /// when its trust falls to the ban threshold. This is the only way peers get banned,
+    /// so every ban is both enforced and reported as [PeerEvent::Banned]
+    pub fn record(
+        &mut self,
+        swarm: &mut Swarm<NodeBehaviour>,
+        peer_id: PeerId,
+        action: PeerAction,
+    ) -> Option<PeerEvent> {
+        self.trust_system
+            .record(peer_id, action, Instant::now())
+            .map(|_| Self::ban(swarm, peer_id))
+    }
+
+    fn ban(swarm: &mut Swarm<NodeBehaviour>, peer_id: PeerId) -> PeerEvent {
+        swarm.ban_peer_id(peer_id);
+        swarm.behaviour_mut().kademlia.remove_peer(&peer_id);
+        PeerEvent::Banned(peer_id)
+    }
+
+    /// Adds the seed peers to the DHT, dials them and starts bootstrapping the DHT.
+    /// A seed that cannot be dialed is logged and skipped, the error of the last one
+    /// is returned only when none of the seeds can be dialed
+    pub fn bootstrap(&mut self, swarm: &mut Swarm<NodeBehaviour>) -> Result<(), PeerManagerError> {
+        if self.seeds.is_empty() {
+            return Ok(());
+        }
+        let mut dialed = 0;
+        let mut last_error = None;
+        for (peer_id, address) in &self.seeds {
+            swarm
+                .behaviour_mut()
+                .kademlia
+                .add_address(peer_id, address.clone());
+            match swarm.dial(address.clone()) {
+                Ok(()) => dialed += 1,
+                Err(err) => {
+                    warn!("Failed to dial seed peer {address}: {err}");
+                    last_error = Some(err);
+                }
+            }
+        }
+        // There are known peers now, so bootstrapping cannot fail
+        let _ = swarm.behaviour_mut().kademlia.bootstrap();
+        match last_error {
+            Some(err) if dialed == 0 => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Starts a random walk over the DHT to discover new peers, when there are fewer connected
+    /// peers than the target, and lifts the expired bans.
+    /// It is expected to be called every [PeerManagerConfig::discovery_interval]
+    pub fn discover(&mut self, swarm: &mut Swarm<NodeBehaviour>) {
+        for peer_id in self.trust_system.lift_expired_bans(Instant::now()) {
+            swarm.unban_peer_id(peer_id);
+        }
+        if self.connected.len() < self.config.target_peers {
+            swarm
+                .behaviour_mut()
+                .kademlia
+                .get_closest_peers(PeerId::random());
+        }
+    }
+
+    /// Handles an event of the swarm and reports the churn of the peers
+    pub fn on_swarm_event<E>(
+        &mut self,
+        swarm: &mut Swarm<NodeBehaviour>,
+        event: &SwarmEvent<NodeEvent, E>,
+    ) -> Option<PeerEvent> {
+        match event {
+            SwarmEvent::ConnectionEstablished {
+                peer_id,
+                num_established,
+                ..
+            } if num_established.get() == 1 => {
+                let is_seed = self.seeds.iter().any(|(seed, _)| seed == peer_id);
+                if self.connected.len() >= self.config.max_peers && !is_seed {
+                    let _ = swarm.disconnect_peer_id(*peer_id);
+                    return None;
+                }
+                self.connected.insert(*peer_id);
+                Some(PeerEvent::Connected(*peer_id))
+            }
+            SwarmEvent::ConnectionClosed {
+                peer_id,
+                num_established: 0,
+                ..
+            } => {
+                if !self.connected.remove(peer_id) {
+                    return None;
+                }
+                self.discover(swarm);
+                Some(PeerEvent::Disconnected(*peer_id))
+            }
+            // Peers report the addresses they listen on, which makes them routable
+            // even when they are the ones that have dialed us
+            SwarmEvent::Behaviour(NodeEvent::Identify(IdentifyEvent::Received {
+                peer_id,
+                info,
+            })) => {
+                for address in &info.listen_addrs {
+                    swarm
+                        .behaviour_mut()
+                        .kademlia
+                        .add_address(peer_id, address.clone());
+                }
+                None
+            }
+            SwarmEvent::Behaviour(NodeEvent::Kademlia(KademliaEvent::RoutingUpdated {
+                peer,
+                is_new_peer,
+                ..
+            })) => {
+                self.dial_if_needed(swarm, peer);
+                if *is_new_peer {
+                    Some(PeerEvent::Discovered(*peer))
+                } else {
+                    None
+                }
+            }
+            SwarmEvent::Behaviour(NodeEvent::Kademlia(KademliaEvent::OutboundQueryCompleted {
+                result: QueryResult::GetClosestPeers(Ok(GetClosestPeersOk { peers, .. })),
+                ..
+            })) => {
+                for peer in peers {
+                    self.dial_if_needed(swarm, peer);
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn dial_if_needed(&self, swarm: &mut Swarm<NodeBehaviour>, peer: &PeerId) {
+        if self.connected.len() < self.config.target_peers
+            && !self.connected.contains(peer)
+            && peer != swarm.local_peer_id()
+            && !self.trust_system.is_banned(peer, Instant::now())
+        {
+            // Dialing fails when the peer is being dialed already
+            let _ = swarm.dial(*peer);
+        }
+    }
+}
@@ -0,0 +1,560 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//! Rpcs that mina peers query each other with over the Async_rpc protocol
+//!
+//! Every query is sent over its own stream of [RPC_PROTOCOL_NAME], both sides of which
+//! start with the Async_rpc handshake, followed by bin_prot encoded messages that are
+//! prefixed by their length as a 64 bit little endian integer
+//!
+//! The request_response behaviour closes the writing half of the stream once the query is
+//! written and then reads the response. Async_rpc peers of the OCaml implementation keep
+//! a connection open for many queries, that they answer on a half closed stream is checked
+//! by the ignored test `rpc_with_mainnet_peer`, which needs a reachable mainnet peer
+
+use async_trait::async_trait;
+use libp2p::{
+    core::ProtocolName,
+    futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    request_response::{
+        ProtocolSupport, RequestId, RequestResponse, RequestResponseCodec, RequestResponseConfig,
+        RequestResponseEvent, ResponseChannel,
+    },
+    Multiaddr, NetworkBehaviour, PeerId,
+};
+use mina_ledger::TransactionScanStateV1;
+use mina_serialization_types::{
+    rpc::{self, RpcHeader, RpcMessage, RpcQuery, RpcResponse, RpcVersion, Sexp},
+    v1::*,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{io, iter};
+use thiserror::Error;
+
+/// Protocol name of the rpc streams
+pub const RPC_PROTOCOL_NAME: &[u8] = b"coda/rpcs/0.0.1";
+
+/// Magic number of the Async_rpc handshake, which is "RPC" in ascii
+pub const RPC_MAGIC_NUMBER: i64 = 4_411_474;
+
+/// Version of the Async_rpc protocol
+pub const RPC_PROTOCOL_VERSION: i64 = 1;
+
+/// Maximum size of an rpc message, responses can carry a number of full blocks
+pub const MAX_RPC_MESSAGE_SIZE: u64 = 128 * 1024 * 1024;
+
+/// Errors of the rpcs
+#[derive(Error, Debug)]
+pub enum RpcError {
+    /// IO error of the stream
+    #[error("{0}")]
+    Io(#[from] io::Error),
+
+    /// A message or a payload is not valid bin_prot
+    #[error("Invalid rpc message: {0}")]
+    BinProt(String),
+
+    /// The handshake of the peer does not support our protocol version
+    #[error("Unsupported rpc handshake: {0:?}")]
+    Handshake(RpcHeader),
+
+    /// The length prefix of a message exceeds [MAX_RPC_MESSAGE_SIZE]
+    #[error("Rpc message of {0} bytes exceeds the size limit")]
+    MessageTooLarge(u64),
+
+    /// A message of the wrong kind is received, e.g. a response on the side that answers queries
+    #[error("Unexpected rpc message: {0}")]
+    UnexpectedMessage(&'static str),
+
+    /// The query is not a query of the expected rpc
+    #[error("Unexpected query of rpc {tag} version {version}")]
+    UnexpectedRpc {
+        /// Tag of the query
+        tag: String,
+        /// Version of the query
+        version: i64,
+    },
+
+    /// The peer answered the query with an error
+    #[error("Rpc failed on the peer: {0:?}")]
+    Remote(rpc::RpcError),
+
+    /// The id of the response is not the one of the query
+    #[error("Response id {actual} does not match query id {expected}")]
+    ResponseIdMismatch {
+        /// Id of the query
+        expected: i64,
+        /// Id of the response
+        actual: i64,
+    },
+
+    /// The query can no longer be answered, e.g. the connection is closed
+    #[error("The response channel is closed")]
+    ResponseChannelClosed,
+}
+
+impl From<RpcError> for io::Error {
+    fn from(error: RpcError) -> Self {
+        match error {
+            RpcError::Io(error) => error,
+            error => io::Error::new(io::ErrorKind::InvalidData, error),
+        }
+    }
+}
+
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, RpcError> {
+    let mut bytes = Vec::new();
+    bin_prot::to_writer(&mut bytes, value).map_err(|e| RpcError::BinProt(e.to_string()))?;
+    Ok(bytes)
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, RpcError> {
+    bin_prot::from_reader_strict(bytes).map_err(|e| RpcError::BinProt(e.to_string()))
+}
+
+/// Writes a frame, which is the payload prefixed by its length
+pub async fn write_frame<W>(io: &mut W, payload: &[u8]) -> Result<(), RpcError>
+where
+    W: AsyncWrite + Unpin,
+{
+    io.write_all(&(payload.len() as u64).to_le_bytes()).await?;
+    io.write_all(payload).await?;
+    io.flush().await?;
+    Ok(())
+}
+
+/// Reads a frame and returns its payload
+pub async fn read_frame<R>(io: &mut R) -> Result<Vec<u8>, RpcError>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut len = [0; 8];
+    io.read_exact(&mut len).await?;
+    let len = u64::from_le_bytes(len);
+    if len > MAX_RPC_MESSAGE_SIZE {
+        return Err(RpcError::MessageTooLarge(len));
+    }
+    let mut payload = vec![0; len as usize];
+    io.read_exact(&mut payload).await?;
+    Ok(payload)
+}
+
+/// Writes a bin_prot encoded message in a frame
+pub async fn write_message<W, T>(io: &mut W, message: &T) -> Result<(), RpcError>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let payload = encode(message)?;
+    write_frame(io, &payload).await
+}
+
+/// Reads a frame and decodes its bin_prot encoded message
+pub async fn read_message<R, T>(io: &mut R) -> Result<T, RpcError>
+where
+    R: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    decode(&read_frame(io).await?)
+}
+
+/// Writes the handshake, which is the magic number followed by our protocol version
+pub async fn write_handshake<W>(io: &mut W) -> Result<(), RpcError>
+where
+    W: AsyncWrite + Unpin,
+{
+    let header: RpcHeader = vec![RPC_MAGIC_NUMBER, RPC_PROTOCOL_VERSION];
+    write_message(io, &header).await
+}
+
+/// Reads the handshake of the peer and checks that it is the magic number
+/// followed by our protocol version
+pub async fn read_handshake<R>(io: &mut R) -> Result<(), RpcError>
+where
+    R: AsyncRead + Unpin,
+{
+    let header: RpcHeader = read_message(io).await?;
+    if header == [RPC_MAGIC_NUMBER, RPC_PROTOCOL_VERSION] {
+        Ok(())
+    } else {
+        Err(RpcError::Handshake(header))
+    }
+}
+
+/// Writes the handshake followed by the query
+pub async fn write_query<W>(io: &mut W, query: RpcQuery) -> Result<(), RpcError>
+where
+    W: AsyncWrite + Unpin,
+{
+    write_handshake(io).await?;
+    write_message(io, &RpcMessage::Query(query)).await
+}
+
+/// Reads the handshake of the peer followed by its query, heartbeats are skipped
+pub async fn read_query<R>(io: &mut R) -> Result<RpcQuery, RpcError>
+where
+    R: AsyncRead + Unpin,
+{
+    read_handshake(io).await?;
+    loop {
+        match read_message(io).await? {
+            RpcMessage::Heartbeat => continue,
+            RpcMessage::Query(query) => return Ok(query),
+            RpcMessage::Response(_) => return Err(RpcError::UnexpectedMessage("response")),
+        }
+    }
+}
+
+/// Writes the handshake followed by the response
+pub async fn write_response<W>(io: &mut W, response: RpcResponse) -> Result<(), RpcError>
+where
+    W: AsyncWrite + Unpin,
+{
+    write_handshake(io).await?;
+    write_message(io, &RpcMessage::Response(response)).await
+}
+
+/// Reads the handshake of the peer followed by its response to the query with the given id,
+/// heartbeats are skipped
+pub async fn read_response<R>(io: &mut R, query_id: i64) -> Result<RpcResponse, RpcError>
+where
+    R: AsyncRead + Unpin,
+{
+    read_handshake(io).await?;
+    loop {
+        match read_message(io).await? {
+            RpcMessage::Heartbeat => continue,
+            RpcMessage::Query(_) => return Err(RpcError::UnexpectedMessage("query")),
+            RpcMessage::Response(response) if response.id != query_id => {
+                return Err(RpcError::ResponseIdMismatch {
+                    expected: query_id,
+                    actual: response.id,
+                })
+            }
+            RpcMessage::Response(response) => return Ok(response),
+        }
+    }
+}
+
+/// Query or response of an rpc, which is carried bin_prot encoded by the rpc messages
+pub trait RpcPayload: Sized {
+    /// Encodes the payload with bin_prot
+    fn to_binprot(&self) -> Result<Vec<u8>, RpcError>;
+
+    /// Decodes a bin_prot encoded payload
+    fn from_binprot(bytes: &[u8]) -> Result<Self, RpcError>;
+}
+
+impl<T: Serialize + DeserializeOwned> RpcPayload for T {
+    fn to_binprot(&self) -> Result<Vec<u8>, RpcError> {
+        encode(self)
+    }
+
+    fn from_binprot(bytes: &[u8]) -> Result<Self, RpcError> {
+        decode(bytes)
+    }
+}
+
+/// Typed rpc, which is a query and response pair with a name and a version
+pub trait Rpc {
+    /// Name of the rpc, which is the tag of its queries
+    const NAME: &'static str;
+
+    /// Version of the rpc
+    const VERSION: i64;
+
+    /// Query of the rpc
+    type Query: RpcPayload;
+
+    /// Response of the rpc
+    type Response: RpcPayload;
+
+    /// Whether the query is a query of this rpc
+    fn is_query(query: &RpcQuery) -> bool {
+        query.tag == Self::NAME && query.version == Self::VERSION
+    }
+
+    /// Encodes a query of this rpc with the given query id
+    fn encode_query(id: i64, query: &Self::Query) -> Result<RpcQuery, RpcError> {
+        Ok(RpcQuery {
+            tag: Self::NAME.into(),
+            version: Self::VERSION,
+            id,
+            data: query.to_binprot()?,
+        })
+    }
+
+    /// Decodes a query of this rpc
+    fn decode_query(query: &RpcQuery) -> Result<Self::Query, RpcError> {
+        if !Self::is_query(query) {
+            return Err(RpcError::UnexpectedRpc {
+                tag: query.tag.clone(),
+                version: query.version,
+            });
+        }
+        <Self::Query as RpcPayload>::from_binprot(&query.data)
+    }
+
+    /// Encodes the response to the given query
+    fn encode_response(
+        query: &RpcQuery,
+        response: &Self::Response,
+    ) -> Result<RpcResponse, RpcError> {
+        Ok(RpcResponse {
+            id: query.id,
+            data: Ok(response.to_binprot()?),
+        })
+    }
+
+    /// Decodes a response of this rpc, errors sent back by the peer are returned as [RpcError::Remote]
+    fn decode_response(response: &RpcResponse) -> Result<Self::Response, RpcError> {
+        match &response.data {
+            Ok(data) => <Self::Response as RpcPayload>::from_binprot(data),
+            Err(error) => Err(RpcError::Remote(error.clone())),
+        }
+    }
+}
+
+/// Gets the blocks with the given state hashes, [None] if the peer does not have all of them
+pub struct GetTransitionChain;
+
+impl Rpc for GetTransitionChain {
+    const NAME: &'static str = "get_transition_chain";
+    const VERSION: i64 = 1;
+    type Query = Vec<HashV1>;
+    type Response = Option<Vec<ExternalTransitionV1>>;
+}
+
+/// Gets the state hash of the root of the transition frontier of the peer along with the
+/// state body hashes of the blocks from that root to the block with the given state hash
+pub struct GetTransitionChainProof;
+
+impl Rpc for GetTransitionChainProof {
+    const NAME: &'static str = "get_transition_chain_proof";
+    const VERSION: i64 = 1;
+    type Query = HashV1;
+    type Response = Option<(HashV1, Vec<HashV1>)>;
+}
+
+/// Gets the state hashes of the blocks in the transition frontier of the peer
+pub struct GetTransitionKnowledge;
+
+impl Rpc for GetTransitionKnowledge {
+    const NAME: &'static str = "Get_transition_knowledge";
+    const VERSION: i64 = 1;
+    type Query = ();
+    type Response = Vec<HashV1>;
+}
+
+/// Gets the root of the transition frontier of the peer along with the proof that it is
+/// an ancestor of the block with the given consensus state and state hash
+pub struct GetAncestry;
+
+impl Rpc for GetAncestry {
+    const NAME: &'static str = "get_ancestry";
+    const VERSION: i64 = 1;
+    type Query = ConsensusStateWithHashV1;
+    type Response = Option<ProofCarryingTransitionV1>;
+}
+
+/// Gets the best tip of the peer along with the proof that it extends
+/// the root of the transition frontier of the peer
+pub struct GetBestTip;
+
+impl Rpc for GetBestTip {
+    const NAME: &'static str = "get_best_tip";
+    const VERSION: i64 = 1;
+    type Query = ();
+    type Response = Option<ProofCarryingTransitionV1>;
+}
+
+/// Scan state, ledger hash, pending coinbase and the protocol states that the scan state
+/// refers to of a staged ledger, see [GetStagedLedgerAuxAndPendingCoinbasesAtHash]
+pub type StagedLedgerAuxAndPendingCoinbases = (
+    TransactionScanStateV1,
+    HashV1,
+    PendingCoinbaseCollectionV1,
+    Vec<ProtocolStateV1>,
+);
+
+/// Gets the staged ledger of the block with the given state hash, [None] if the peer
+/// does not have the block. The base jobs of [TransactionScanStateV1] only carry the
+/// statements of the transactions, so the scan states of OCaml peers, whose base jobs
+/// also carry the transaction witnesses, cannot be decoded yet
+pub struct GetStagedLedgerAuxAndPendingCoinbasesAtHash;
+
+impl Rpc for GetStagedLedgerAuxAndPendingCoinbasesAtHash {
+    const NAME: &'static str = "get_staged_ledger_aux_and_pending_coinbases_at_hash";
+    const VERSION: i64 = 1;
+    type Query = HashV1;
+    type Response = Option<StagedLedgerAuxAndPendingCoinbases>;
+}
+
+/// Answers a query of the ledger sync protocol about the ledger with the given hash
+pub struct AnswerSyncLedgerQuery;
+
+impl Rpc for AnswerSyncLedgerQuery {
+    const NAME: &'static str = "answer_sync_ledger_query";
+    const VERSION: i64 = 1;
+    type Query = (HashV1, SyncLedgerQueryV1);
+    type Response = Result<SyncLedgerAnswerV1, Sexp>;
+}
+
+/// Protocol of the rpc streams
+#[derive(Clone, Debug, Default)]
+pub struct RpcProtocol;
+
+impl ProtocolName for RpcProtocol {
+    fn protocol_name(&self) -> &[u8] {
+        RPC_PROTOCOL_NAME
+    }
+}
+
+/// Codec of the rpc streams, every stream carries a single query and its response
+#[derive(Clone, Debug, Default)]
+pub struct RpcCodec {
+    /// Id of the query written on the stream, which the response has to carry
+    query_id: i64,
+}
+
+#[async_trait]
+impl RequestResponseCodec for RpcCodec {
+    type Protocol = RpcProtocol;
+    type Request = RpcQuery;
+    type Response = RpcResponse;
+
+    async fn read_request<T>(&mut self, _: &RpcProtocol, io: &mut T) -> io::Result<RpcQuery>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        Ok(read_query(io).await?)
+    }
+
+    async fn read_response<T>(&mut self, _: &RpcProtocol, io: &mut T) -> io::Result<RpcResponse>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        Ok(read_response(io, self.query_id).await?)
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        _: &RpcProtocol,
+        io: &mut T,
+        query: RpcQuery,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        self.query_id = query.id;
+        Ok(write_query(io, query).await?)
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        _: &RpcProtocol,
+        io: &mut T,
+        response: RpcResponse,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        Ok(write_response(io, response).await?)
+    }
+}
+
+/// Events emitted by [RpcBehaviour], queries of peers are received as requests
+/// and are answered with [RpcBehaviour::respond]
+pub type RpcEvent = RequestResponseEvent<RpcQuery, RpcResponse>;
+
+/// Network behaviour that queries peers and receives their queries over the rpc streams
+#[derive(NetworkBehaviour)]
+#[behaviour(out_event = "RpcEvent", event_process = false)]
+pub struct RpcBehaviour {
+    request_response: RequestResponse<RpcCodec>,
+    #[behaviour(ignore)]
+    next_query_id: i64,
+}
+
+impl Default for RpcBehaviour {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RpcBehaviour {
+    /// Creates a behaviour that both sends and answers queries
+    pub fn new() -> Self {
+        let request_response = RequestResponse::new(
+            RpcCodec::default(),
+            iter::once((RpcProtocol, ProtocolSupport::Full)),
+            RequestResponseConfig::default(),
+        );
+        Self {
+            request_response,
+            next_query_id: 0,
+        }
+    }
+
+    /// Adds a known address of the peer, which is dialed when it is queried while not connected
+    pub fn add_address(&mut self, peer: &PeerId, address: Multiaddr) {
+        self.request_response.add_address(peer, address)
+    }
+
+    /// Queries the peer, its response is emitted as [RpcEvent] along with the returned request id
+    pub fn query<R: Rpc>(
+        &mut self,
+        peer: &PeerId,
+        query: &R::Query,
+    ) -> Result<RequestId, RpcError> {
+        let query = R::encode_query(self.next_query_id, query)?;
+        self.next_query_id += 1;
+        Ok(self.request_response.send_request(peer, query))
+    }
+
+    /// Answers the query of a peer
+    pub fn respond<R: Rpc>(
+        &mut self,
+        channel: ResponseChannel<RpcResponse>,
+        query: &RpcQuery,
+        response: &R::Response,
+    ) -> Result<(), RpcError> {
+        let response = R::encode_response(query, response)?;
+        self.send_response(channel, response)
+    }
+
+    /// Answers the query of a peer with an error
+    pub fn respond_error(
+        &mut self,
+        channel: ResponseChannel<RpcResponse>,
+        query: &RpcQuery,
+        error: rpc::RpcError,
+    ) -> Result<(), RpcError> {
+        let response = RpcResponse {
+            id: query.id,
+            data: Err(error),
+        };
+        self.send_response(channel, response)
+    }
+
+    /// Answers the query of a peer for an rpc that we do not implement
+    pub fn respond_unimplemented(
+        &mut self,
+        channel: ResponseChannel<RpcResponse>,
+        query: &RpcQuery,
+    ) -> Result<(), RpcError> {
+        let error =
+            rpc::RpcError::UnimplementedRpc(query.tag.clone(), RpcVersion::Version(query.version));
+        self.respond_error(channel, query, error)
+    }
+
+    fn send_response(
+        &mut self,
+        channel: ResponseChannel<RpcResponse>,
+        response: RpcResponse,
+    ) -> Result<(), RpcError> {
+        self.request_response
+            .send_response(channel, response)
+            .map_err(|_| RpcError::ResponseChannelClosed)
+    }
+}
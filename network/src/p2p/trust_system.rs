@@ -0,0 +1,221 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//!
+//! Trust of peers, which is built up by the actions they take and gets them banned
+//! once it falls to the ban threshold, this mirrors the trust system of mina
+//! <https://github.com/MinaProtocol/mina/tree/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/trust_system>
+//!
+//! The trust of a peer is in `[-1, 1]` and decays exponentially towards 0, so
+//! the trust changes of actions are expressed as the maximum rate at which a peer
+//! can take them without ever reaching the bounds
+//!
+
+use libp2p::PeerId;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Trust below which a record is forgotten once it is not banned anymore
+const FORGOTTEN_TRUST: f64 = 1e-3;
+
+/// Actions of peers that change their trust
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PeerAction {
+    /// Sent a block with invalid signatures
+    SentInvalidBlock,
+    /// Sent a block whose delta transition chain proof does not verify
+    SentInvalidProof,
+    /// Sent a message that cannot be decoded
+    ViolatedProtocol,
+    /// Answered a query with a block that was not queried
+    SentUnrequestedBlock,
+    /// Did not answer a query in time
+    RequestTimeout,
+    /// Made a request, which costs resources to answer
+    MadeRequest,
+    /// Answered a query with valid data
+    SentUsefulResponse,
+    /// Gossiped a valid block
+    SentUsefulGossip,
+}
+
+/// Change of trust caused by a [PeerAction]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrustChange {
+    /// Increases the trust by the amount
+    Increment(f64),
+    /// Decreases the trust by the amount
+    Decrement(f64),
+    /// Bans the peer right away
+    InstaBan,
+}
+
+impl PeerAction {
+    /// Change of trust caused by the action under the given configuration
+    pub fn trust_change(&self, config: &TrustConfig) -> TrustChange {
+        match self {
+            Self::SentInvalidBlock
+            | Self::SentInvalidProof
+            | Self::ViolatedProtocol
+            | Self::SentUnrequestedBlock => TrustChange::InstaBan,
+            Self::RequestTimeout => TrustChange::Decrement(config.max_rate(1.)),
+            Self::MadeRequest => TrustChange::Decrement(config.max_rate(10.)),
+            Self::SentUsefulResponse | Self::SentUsefulGossip => {
+                TrustChange::Increment(config.max_rate(10.))
+            }
+        }
+    }
+}
+
+/// Configuration of [TrustSystem]
+#[derive(Clone, Debug)]
+pub struct TrustConfig {
+    /// Time it takes for trust to decay by a factor of `e`
+    pub decay_time: Duration,
+    /// Trust at or below which peers get banned
+    pub ban_threshold: f64,
+    /// Duration of bans
+    pub ban_duration: Duration,
+}
+
+impl Default for TrustConfig {
+    fn default() -> Self {
+        const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+        Self {
+            decay_time: DAY,
+            ban_threshold: -1.,
+            ban_duration: DAY,
+        }
+    }
+}
+
+impl TrustConfig {
+    /// Trust change of an action that can be taken `per_second` times a second indefinitely,
+    /// i.e. the trust that decays from the maximum trust in `1 / per_second` seconds
+    pub fn max_rate(&self, per_second: f64) -> f64 {
+        1. - (-1. / (per_second * self.decay_time.as_secs_f64())).exp()
+    }
+}
+
+/// Trust record of a peer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PeerTrust {
+    /// Trust at the time of the last update
+    pub trust: f64,
+    /// Time of the last update
+    pub updated_at: Instant,
+    /// Time until which the peer is banned
+    pub banned_until: Option<Instant>,
+}
+
+impl PeerTrust {
+    fn new(now: Instant) -> Self {
+        Self {
+            trust: 0.,
+            updated_at: now,
+            banned_until: None,
+        }
+    }
+
+    /// Trust decayed until the given time
+    pub fn trust_at(&self, now: Instant, config: &TrustConfig) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated_at);
+        self.trust * (-elapsed.as_secs_f64() / config.decay_time.as_secs_f64()).exp()
+    }
+
+    /// If the peer is banned at the given time
+    pub fn is_banned_at(&self, now: Instant) -> bool {
+        matches!(self.banned_until, Some(until) if now < until)
+    }
+}
+
+/// Records the actions of peers and decides which ones are banned,
+/// the time is passed explicitly so that the caller controls the clock
+#[derive(Clone, Debug, Default)]
+pub struct TrustSystem {
+    config: TrustConfig,
+    peers: HashMap<PeerId, PeerTrust>,
+}
+
+impl TrustSystem {
+    /// Creates a trust system where all peers start with no trust
+    pub fn new(config: TrustConfig) -> Self {
+        Self {
+            config,
+            peers: HashMap::new(),
+        }
+    }
+
+    /// Gets the configuration
+    pub fn config(&self) -> &TrustConfig {
+        &self.config
+    }
+
+    /// Gets the trust record of a peer
+    pub fn get(&self, peer_id: &PeerId) -> Option<&PeerTrust> {
+        self.peers.get(peer_id)
+    }
+
+    /// Trust of a peer at the given time, unknown peers have no trust
+    pub fn trust(&self, peer_id: &PeerId, now: Instant) -> f64 {
+        self.peers
+            .get(peer_id)
+            .map(|record| record.trust_at(now, &self.config))
+            .unwrap_or_default()
+    }
+
+    /// If a peer is banned at the given time
+    pub fn is_banned(&self, peer_id: &PeerId, now: Instant) -> bool {
+        self.peers
+            .get(peer_id)
+            .map(|record| record.is_banned_at(now))
+            .unwrap_or_default()
+    }
+
+    /// Records an action of a peer, returns the time until which the peer is banned
+    /// when the action gets it banned. The actions of banned peers are ignored
+    pub fn record(&mut self, peer_id: PeerId, action: PeerAction, now: Instant) -> Option<Instant> {
+        let config = &self.config;
+        let record = self
+            .peers
+            .entry(peer_id)
+            .or_insert_with(|| PeerTrust::new(now));
+        if record.is_banned_at(now) {
+            return None;
+        }
+        let trust = record.trust_at(now, config);
+        let change = action.trust_change(config);
+        record.trust = match change {
+            TrustChange::Increment(amount) => (trust + amount).min(1.),
+            TrustChange::Decrement(amount) => (trust - amount).max(-1.),
+            TrustChange::InstaBan => -1.,
+        };
+        record.updated_at = now;
+        if change == TrustChange::InstaBan || record.trust <= config.ban_threshold {
+            let banned_until = now + config.ban_duration;
+            record.banned_until = Some(banned_until);
+            Some(banned_until)
+        } else {
+            None
+        }
+    }
+
+    /// Lifts the bans that have expired at the given time and returns the peers
+    /// they are lifted from, records of peers whose trust has decayed to nothing are forgotten
+    pub fn lift_expired_bans(&mut self, now: Instant) -> Vec<PeerId> {
+        let config = &self.config;
+        let mut unbanned = Vec::new();
+        self.peers.retain(|peer_id, record| {
+            if record.is_banned_at(now) {
+                return true;
+            }
+            if record.banned_until.take().is_some() {
+                unbanned.push(*peer_id);
+            }
+            record.trust_at(now, config).abs() >= FORGOTTEN_TRUST
+        });
+        unbanned
+    }
+}
@@ -20,6 +20,9 @@ mod processor_impl;
 #[cfg(target_arch = "wasm32")]
 pub mod js;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub mod native;
+
 use async_trait::async_trait;
 use tokio::sync::{mpsc, RwLock};
 
@@ -0,0 +1,414 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//!
+//! Native implementation of the networking operations over libp2p,
+//! which queries blocks from peers with rpcs and receives new blocks from gossip.
+//! Blocks are checked for what can be verified without their parents before they
+//! are passed on, and the peers that send invalid ones get banned
+//!
+
+use crate::{p2p::*, processor::*};
+use libp2p::{
+    futures::StreamExt,
+    gossipsub::{MessageAcceptance, MessageId},
+    request_response::{OutboundFailure, RequestId, RequestResponseEvent, RequestResponseMessage},
+    swarm::SwarmEvent,
+    PeerId, Swarm,
+};
+use log::{debug, error, info, warn};
+use mina_rs_base::{
+    types::{ExternalTransition, SignedCommandPayload},
+    verifiable::Verifiable,
+};
+use mina_serialization_types::{json::StateHashV1Json, v1::HashV1};
+use proof_systems::mina_signer::{self, NetworkId};
+use std::collections::{HashMap, VecDeque};
+
+/// Commands that [NonConsensusLibp2pBackend] sends to its [Libp2pEventLoop]
+#[derive(Debug)]
+enum BackendCommand {
+    SetBlockResponder(mpsc::Sender<ExternalTransition>),
+    QueryBlock(BlockQuery),
+}
+
+/// Query of a block along with the peers that are left to query
+#[derive(Debug)]
+struct BlockQuery {
+    state_hash: String,
+    hash: HashV1,
+    peers: Vec<PeerId>,
+}
+
+/// [NonConsensusNetworkingOps] backend that talks to peers over libp2p,
+/// the swarm is driven by the [Libp2pEventLoop] that is created along with it
+#[derive(Debug, Clone)]
+pub struct NonConsensusLibp2pBackend {
+    commands: mpsc::UnboundedSender<BackendCommand>,
+}
+
+impl NonConsensusLibp2pBackend {
+    /// Creates a backend on top of the swarm, the signatures of blocks are checked against
+    /// the given network and the peers that send invalid ones are banned.
+    /// The returned event loop has to be run for the backend to make any progress
+    pub fn new(swarm: Swarm<NodeBehaviour>, network_id: NetworkId) -> (Self, Libp2pEventLoop) {
+        let (commands, command_receiver) = mpsc::unbounded_channel();
+        let event_loop = Libp2pEventLoop {
+            swarm,
+            commands: command_receiver,
+            block_responder: None,
+            peer_manager: PeerManager::default(),
+            network_id,
+            pending_queries: HashMap::new(),
+            unsent_queries: Vec::new(),
+            undelivered_blocks: VecDeque::new(),
+        };
+        (Self { commands }, event_loop)
+    }
+
+    fn send(&self, command: BackendCommand) -> anyhow::Result<()> {
+        self.commands
+            .send(command)
+            .map_err(|_| anyhow::Error::msg("libp2p event loop is stopped"))
+    }
+}
+
+#[async_trait(?Send)]
+impl NonConsensusNetworkingOps for NonConsensusLibp2pBackend {
+    type Block = ExternalTransition;
+
+    fn set_block_responder(&mut self, sender: mpsc::Sender<Self::Block>) {
+        if let Err(err) = self.send(BackendCommand::SetBlockResponder(sender)) {
+            error!("{err}");
+        }
+    }
+
+    async fn query_block(&mut self, request: &QueryBlockRequest) -> anyhow::Result<()> {
+        let hash = StateHashV1Json::from_base58(&request.state_hash)?.into();
+        self.send(BackendCommand::QueryBlock(BlockQuery {
+            state_hash: request.state_hash.clone(),
+            hash,
+            peers: Vec::new(),
+        }))
+    }
+}
+
+/// Event loop that drives the swarm of a [NonConsensusLibp2pBackend]. It sends the blocks
+/// that are gossiped or queried to the block responder, queries the connected peers one after
+/// another for the requested blocks, answers the rpcs of peers and manages the peers with
+/// its [PeerManager], which bans the peers that misbehave
+pub struct Libp2pEventLoop {
+    swarm: Swarm<NodeBehaviour>,
+    commands: mpsc::UnboundedReceiver<BackendCommand>,
+    block_responder: Option<mpsc::Sender<ExternalTransition>>,
+    peer_manager: PeerManager,
+    network_id: NetworkId,
+    pending_queries: HashMap<RequestId, BlockQuery>,
+    // Queries that wait for a peer to connect, either because no peer was connected when they
+    // were requested or because none of the connected peers has answered them
+    unsent_queries: Vec<BlockQuery>,
+    // Blocks that wait for room in the block responder or for the responder to be set
+    undelivered_blocks: VecDeque<ExternalTransition>,
+}
+
+impl Libp2pEventLoop {
+    /// Gets the swarm, e.g. to listen on or to dial addresses before running the event loop
+    pub fn swarm_mut(&mut self) -> &mut Swarm<NodeBehaviour> {
+        &mut self.swarm
+    }
+
+    /// Sets the peer manager, which by default has no seed peers to bootstrap from
+    pub fn with_peer_manager(mut self, peer_manager: PeerManager) -> Self {
+        self.peer_manager = peer_manager;
+        self
+    }
+
+    /// Runs the event loop until the backend is dropped
+    pub async fn run(mut self) {
+        if let Err(err) = self.peer_manager.bootstrap(&mut self.swarm) {
+            error!("{err}");
+        }
+        let mut discovery = tokio::time::interval(self.peer_manager.config().discovery_interval);
+        loop {
+            tokio::select! {
+                biased;
+                command = self.commands.recv() => match command {
+                    Some(command) => self.handle_command(command),
+                    None => break,
+                },
+                _ = discovery.tick() => self.peer_manager.discover(&mut self.swarm),
+                permit = reserve(self.block_responder.as_ref()), if !self.undelivered_blocks.is_empty() => {
+                    match permit {
+                        Ok(permit) => {
+                            if let Some(block) = self.undelivered_blocks.pop_front() {
+                                permit.send(block);
+                            }
+                        }
+                        Err(err) => {
+                            error!("{err}, dropping {} blocks", self.undelivered_blocks.len());
+                            self.undelivered_blocks.clear();
+                            self.block_responder = None;
+                        }
+                    }
+                }
+                event = self.swarm.select_next_some() => self.handle_swarm_event(event),
+            }
+        }
+    }
+
+    fn handle_command(&mut self, command: BackendCommand) {
+        match command {
+            BackendCommand::SetBlockResponder(sender) => self.block_responder = Some(sender),
+            BackendCommand::QueryBlock(mut query) => {
+                if self.peer_manager.num_connected() == 0 {
+                    self.unsent_queries.push(query);
+                } else {
+                    query.peers = self.peer_manager.connected_peers().cloned().collect();
+                    self.query_next_peer(query);
+                }
+            }
+        }
+    }
+
+    /// Queries the block from the next peer that is left, the query waits for
+    /// the next peer to connect once all the peers have failed to answer it
+    fn query_next_peer(&mut self, mut query: BlockQuery) {
+        let peer = match query.peers.pop() {
+            Some(peer) => peer,
+            None => {
+                warn!(
+                    "No peer has answered the query of block {}, waiting for new peers",
+                    query.state_hash
+                );
+                self.unsent_queries.push(query);
+                return;
+            }
+        };
+        match self
+            .swarm
+            .behaviour_mut()
+            .rpc
+            .query::<GetTransitionChain>(&peer, &vec![query.hash.clone()])
+        {
+            Ok(request_id) => {
+                self.pending_queries.insert(request_id, query);
+            }
+            Err(err) => {
+                error!("{err}");
+                self.query_next_peer(query);
+            }
+        }
+    }
+
+    fn handle_swarm_event<E>(&mut self, event: SwarmEvent<NodeEvent, E>) {
+        if let Some(peer_event) = self.peer_manager.on_swarm_event(&mut self.swarm, &event) {
+            self.handle_peer_event(peer_event);
+        }
+        match event {
+            SwarmEvent::Behaviour(NodeEvent::Gossip(GossipEvent::Message {
+                propagation_source,
+                message_id,
+                message: GossipMessage::NewState(block),
+            })) => {
+                if self.check_block(propagation_source, &block) {
+                    self.report_gossip(&message_id, &propagation_source, MessageAcceptance::Accept);
+                    self.record(propagation_source, PeerAction::SentUsefulGossip);
+                    self.respond_block(*block);
+                } else {
+                    self.report_gossip(&message_id, &propagation_source, MessageAcceptance::Reject);
+                }
+            }
+            // Snark work and user commands are not checked, so they are not forwarded
+            SwarmEvent::Behaviour(NodeEvent::Gossip(GossipEvent::Message {
+                propagation_source,
+                message_id,
+                ..
+            })) => {
+                self.report_gossip(&message_id, &propagation_source, MessageAcceptance::Ignore);
+            }
+            SwarmEvent::Behaviour(NodeEvent::Gossip(GossipEvent::InvalidMessage {
+                propagation_source,
+                message_id,
+                error,
+            })) => {
+                warn!("{error}");
+                self.report_gossip(&message_id, &propagation_source, MessageAcceptance::Reject);
+                self.record(propagation_source, PeerAction::ViolatedProtocol);
+            }
+            SwarmEvent::Behaviour(NodeEvent::Rpc(event)) => self.handle_rpc_event(event),
+            _ => {}
+        }
+    }
+
+    fn report_gossip(
+        &mut self,
+        message_id: &MessageId,
+        propagation_source: &PeerId,
+        acceptance: MessageAcceptance,
+    ) {
+        if let Err(err) = self.swarm.behaviour_mut().gossip.report_validation_result(
+            message_id,
+            propagation_source,
+            acceptance,
+        ) {
+            error!("{err}");
+        }
+    }
+
+    /// Handles the churn of the peers, which is reported by the peer manager
+    /// both for the events of the swarm and for the recorded actions of peers
+    fn handle_peer_event(&mut self, event: PeerEvent) {
+        match event {
+            PeerEvent::Connected(peer_id) => {
+                info!("Connected to peer {peer_id}");
+                for mut query in std::mem::take(&mut self.unsent_queries) {
+                    query.peers = self.peer_manager.connected_peers().cloned().collect();
+                    self.query_next_peer(query);
+                }
+            }
+            PeerEvent::Disconnected(peer_id) => info!("Disconnected from peer {peer_id}"),
+            PeerEvent::Discovered(peer_id) => debug!("Discovered peer {peer_id}"),
+            PeerEvent::Banned(peer_id) => warn!("Banned peer {peer_id}"),
+        }
+    }
+
+    fn record(&mut self, peer_id: PeerId, action: PeerAction) {
+        debug!("Recording {action:?} of peer {peer_id}");
+        if let Some(peer_event) = self.peer_manager.record(&mut self.swarm, peer_id, action) {
+            self.handle_peer_event(peer_event);
+        }
+    }
+
+    /// Checks the signatures and the delta transition chain proof of a block,
+    /// which do not depend on its parent, the peer that sent an invalid block is distrusted
+    fn check_block(&mut self, peer_id: PeerId, block: &ExternalTransition) -> bool {
+        let mut ctx = mina_signer::create_legacy::<SignedCommandPayload>(self.network_id.clone());
+        let action = if block.verify_delta_transition_chain().is_none() {
+            PeerAction::SentInvalidProof
+        } else if !block.verify(&mut ctx) {
+            PeerAction::SentInvalidBlock
+        } else {
+            return true;
+        };
+        self.record(peer_id, action);
+        false
+    }
+
+    fn handle_rpc_event(&mut self, event: RpcEvent) {
+        match event {
+            RequestResponseEvent::Message {
+                message:
+                    RequestResponseMessage::Request {
+                        request, channel, ..
+                    },
+                peer,
+            } => {
+                self.record(peer, PeerAction::MadeRequest);
+                // Serving the blocks and ledgers of the transition frontier is left to consensus nodes
+                if let Err(err) = self
+                    .swarm
+                    .behaviour_mut()
+                    .rpc
+                    .respond_unimplemented(channel, &request)
+                {
+                    error!("{err}");
+                }
+            }
+            RequestResponseEvent::Message {
+                message:
+                    RequestResponseMessage::Response {
+                        request_id,
+                        response,
+                    },
+                peer,
+            } => {
+                if let Some(query) = self.pending_queries.remove(&request_id) {
+                    match GetTransitionChain::decode_response(&response) {
+                        // A single block is queried, so the chain has to be that block
+                        Ok(Some(mut blocks)) if blocks.len() == 1 => {
+                            let block: ExternalTransition = blocks.remove(0).into();
+                            if HashV1::from(block.protocol_state.state_hash()) != query.hash {
+                                warn!(
+                                    "Peer {peer} answered the query of block {} with another block",
+                                    query.state_hash
+                                );
+                                self.record(peer, PeerAction::SentUnrequestedBlock);
+                                self.query_next_peer(query);
+                            } else if self.check_block(peer, &block) {
+                                self.record(peer, PeerAction::SentUsefulResponse);
+                                self.respond_block(block);
+                            } else {
+                                self.query_next_peer(query);
+                            }
+                        }
+                        Ok(Some(blocks)) => {
+                            warn!(
+                                "Peer {peer} answered the query of block {} with {} blocks",
+                                query.state_hash,
+                                blocks.len()
+                            );
+                            self.record(peer, PeerAction::ViolatedProtocol);
+                            self.query_next_peer(query);
+                        }
+                        Ok(None) => self.query_next_peer(query),
+                        Err(err) => {
+                            warn!("{err}");
+                            if let RpcError::BinProt(_) = err {
+                                self.record(peer, PeerAction::ViolatedProtocol);
+                            }
+                            self.query_next_peer(query);
+                        }
+                    }
+                }
+            }
+            RequestResponseEvent::OutboundFailure {
+                peer,
+                request_id,
+                error,
+            } => {
+                if let Some(query) = self.pending_queries.remove(&request_id) {
+                    warn!("{error:?}");
+                    if let OutboundFailure::Timeout = error {
+                        self.record(peer, PeerAction::RequestTimeout);
+                    }
+                    self.query_next_peer(query);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Sends the block to the block responder without waiting, so that the swarm keeps
+    /// being driven. While the responder is full or not set yet, the block is queued
+    /// and the event loop sends the queued blocks in order once there is room
+    fn respond_block(&mut self, block: ExternalTransition) {
+        let block = match &self.block_responder {
+            Some(block_responder) if self.undelivered_blocks.is_empty() => {
+                match block_responder.try_send(block) {
+                    Ok(()) => return,
+                    Err(mpsc::error::TrySendError::Full(block)) => block,
+                    Err(err @ mpsc::error::TrySendError::Closed(_)) => {
+                        error!("{err}");
+                        return;
+                    }
+                }
+            }
+            _ => block,
+        };
+        debug!(
+            "Block responder is not ready, queueing block {}",
+            block.protocol_state.state_hash()
+        );
+        self.undelivered_blocks.push_back(block);
+    }
+}
+
+/// Reserves room in the block responder, which never completes while the responder is not set
+async fn reserve(
+    block_responder: Option<&mpsc::Sender<ExternalTransition>>,
+) -> Result<mpsc::Permit<'_, ExternalTransition>, mpsc::error::SendError<()>> {
+    match block_responder {
+        Some(block_responder) => block_responder.reserve().await,
+        None => std::future::pending().await,
+    }
+}
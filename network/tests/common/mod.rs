@@ -0,0 +1,50 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//! Helpers that are shared by the network tests, every test crate
+//! uses a part of them only
+#![allow(dead_code)]
+
+use libp2p::{
+    core::transport::MemoryTransport,
+    identity,
+    swarm::{NetworkBehaviour, SwarmBuilder},
+    Swarm,
+};
+use mina_network::p2p::*;
+use mina_rs_base::types::*;
+use mina_serialization_types::json::ExternalTransitionJson;
+use std::time::Duration;
+
+/// Time after which a test that drives swarms fails
+pub const TEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub fn read_block_json(block_path: &str) -> ExternalTransition {
+    let json_block = test_fixtures::JSON_TEST_BLOCKS.get(block_path).unwrap();
+    serde_json::from_value::<ExternalTransitionJson>(json_block.clone())
+        .unwrap()
+        .into()
+}
+
+/// Creates a swarm over the memory transport with the mainnet config,
+/// whose behaviour is created from the keypair of the swarm
+pub fn memory_swarm<B: NetworkBehaviour>(
+    new_behaviour: impl FnOnce(identity::Keypair) -> anyhow::Result<B>,
+) -> anyhow::Result<Swarm<B>> {
+    let keypair = identity::Keypair::generate_ed25519();
+    let (transport, peer_id) = TransportBuilder::new_with_key(keypair.clone())
+        .with_mainnet_config()
+        .build_with_transport(MemoryTransport::default());
+    let swarm = SwarmBuilder::new(transport, new_behaviour(keypair)?, peer_id)
+        // executor has to be explicitly set due to https://github.com/libp2p/rust-libp2p/issues/2173
+        .executor(Box::new(|fut| {
+            tokio::spawn(fut);
+        }))
+        .build();
+    Ok(swarm)
+}
+
+/// Creates a swarm of [NodeBehaviour] over the memory transport
+pub fn node_swarm() -> anyhow::Result<Swarm<NodeBehaviour>> {
+    memory_swarm(|keypair| Ok(NodeBehaviour::new(keypair)?))
+}
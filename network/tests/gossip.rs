@@ -0,0 +1,167 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+mod common;
+
+#[cfg(test)]
+pub mod tests {
+    use crate::common::*;
+    use libp2p::{
+        futures::StreamExt, gossipsub::MessageAcceptance, swarm::SwarmEvent, Multiaddr, PeerId,
+        Swarm,
+    };
+    use mina_network::p2p::*;
+    use mina_rs_base::types::*;
+
+    fn test_block() -> ExternalTransition {
+        read_block_json("mainnet-116121-3NK6myZRzc3GvS5iydv88on2XTEU2btYrjMVkgtbuoeXASRipSa6.json")
+    }
+
+    fn gossip_swarm() -> anyhow::Result<(Swarm<GossipBehaviour>, PeerId)> {
+        let swarm = memory_swarm(|keypair| Ok(GossipBehaviour::new(keypair)?))?;
+        let peer_id = *swarm.local_peer_id();
+        Ok((swarm, peer_id))
+    }
+
+    #[test]
+    fn gossip_message_binprot_roundtrip() -> anyhow::Result<()> {
+        let block = test_block();
+        let pre_diff = block.staged_ledger_diff.diff.diff_two().clone();
+        let messages = vec![
+            GossipMessage::NewState(Box::new(block.clone())),
+            GossipMessage::SnarkPoolDiff(Box::new(pre_diff.completed_works[0].clone())),
+            GossipMessage::TransactionPoolDiff(
+                pre_diff.commands.iter().map(|c| c.data.clone()).collect(),
+            ),
+        ];
+        for (tag, message) in messages.into_iter().enumerate() {
+            let bytes = message.clone().try_into_binprot()?;
+            assert_eq!(bytes[0] as usize, tag);
+            assert_eq!(GossipMessage::try_from_binprot(&bytes)?, message);
+        }
+
+        let bytes = GossipMessage::NewState(Box::new(block)).try_into_binprot()?;
+        assert!(matches!(
+            GossipMessage::try_from_binprot(&bytes[..bytes.len() - 1]),
+            Err(GossipError::BinProt(_))
+        ));
+        assert!(matches!(
+            GossipMessage::try_from_binprot(&[3]),
+            Err(GossipError::BinProt(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn gossip_message_from_ocaml_bytes() -> anyhow::Result<()> {
+        // A new state message is the variant tag followed by the block, which is encoded
+        // as the OCaml implementation does
+        let block_bytes = &test_fixtures::TEST_BLOCKS
+            .get("3NKjZ5fjms6BMaH4aq7DopPGyMY7PbG6vhRsX5XnYRxih8i9G7dj.hex")
+            .unwrap()
+            .bytes;
+        let mut bytes = vec![0];
+        bytes.extend_from_slice(block_bytes);
+
+        let message = GossipMessage::try_from_binprot(&bytes)?;
+        let block = read_block_json(
+            "mainnet-117896-3NKjZ5fjms6BMaH4aq7DopPGyMY7PbG6vhRsX5XnYRxih8i9G7dj.json",
+        );
+        assert_eq!(message, GossipMessage::NewState(Box::new(block)));
+        assert_eq!(message.try_into_binprot()?, bytes);
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn gossip_over_memory_transport() -> anyhow::Result<()> {
+        let (mut publisher, publisher_id) = gossip_swarm()?;
+        let (mut subscriber, subscriber_id) = gossip_swarm()?;
+
+        let address: Multiaddr = "/memory/8302".parse()?;
+        publisher.listen_on(address.clone())?;
+        subscriber.dial(address)?;
+
+        let message = GossipMessage::NewState(Box::new(test_block()));
+        let mut messages = gossip_messages(&mut subscriber);
+        tokio::time::timeout(TEST_TIMEOUT, async {
+            loop {
+                tokio::select! {
+                    event = publisher.select_next_some() => {
+                        // Publish once the subscriber has joined the topic
+                        if let SwarmEvent::Behaviour(GossipEvent::Subscribed(peer_id)) = event {
+                            assert_eq!(peer_id, subscriber_id);
+                            publisher.behaviour_mut().publish(message.clone())?;
+                        }
+                    }
+                    Some((source, _, received)) = messages.next() => {
+                        assert_eq!(source, publisher_id);
+                        assert_eq!(received, message);
+                        return Ok::<(), anyhow::Error>(());
+                    }
+                }
+            }
+        })
+        .await?
+    }
+
+    #[tokio::test]
+    pub async fn gossip_forwarded_once_accepted() -> anyhow::Result<()> {
+        let (mut publisher, publisher_id) = gossip_swarm()?;
+        let (mut forwarder, forwarder_id) = gossip_swarm()?;
+        let (mut subscriber, _) = gossip_swarm()?;
+
+        // The publisher and the subscriber are only connected to the forwarder
+        let address: Multiaddr = "/memory/8303".parse()?;
+        forwarder.listen_on(address.clone())?;
+        publisher.dial(address.clone())?;
+        subscriber.dial(address)?;
+
+        let message = GossipMessage::NewState(Box::new(test_block()));
+        let mut forwarded = gossip_messages(&mut forwarder);
+        let (mut publisher_joined, mut subscriber_joined, mut published) = (false, false, false);
+        tokio::time::timeout(TEST_TIMEOUT, async {
+            loop {
+                tokio::select! {
+                    event = publisher.select_next_some() => {
+                        if let SwarmEvent::Behaviour(GossipEvent::Subscribed(peer_id)) = event {
+                            assert_eq!(peer_id, forwarder_id);
+                            publisher_joined = true;
+                        }
+                    }
+                    event = subscriber.select_next_some() => match event {
+                        SwarmEvent::Behaviour(GossipEvent::Subscribed(peer_id)) => {
+                            assert_eq!(peer_id, forwarder_id);
+                            subscriber_joined = true;
+                        }
+                        SwarmEvent::Behaviour(GossipEvent::Message {
+                            propagation_source,
+                            message: received,
+                            ..
+                        }) => {
+                            assert!(published);
+                            assert_eq!(propagation_source, forwarder_id);
+                            assert_eq!(received, message);
+                            return Ok::<(), anyhow::Error>(());
+                        }
+                        _ => {}
+                    },
+                    Some((source, message_id, received)) = forwarded.next() => {
+                        assert_eq!(source, publisher_id);
+                        assert_eq!(received, message);
+                        forwarded.report_validation_result(
+                            &message_id,
+                            &source,
+                            MessageAcceptance::Accept,
+                        )?;
+                    }
+                }
+                // Publish once both peers of the forwarder have joined the topic
+                if publisher_joined && subscriber_joined && !published {
+                    publisher.behaviour_mut().publish(message.clone())?;
+                    published = true;
+                }
+            }
+        })
+        .await?
+    }
+}
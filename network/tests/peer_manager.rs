@@ -0,0 +1,127 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+mod common;
+
+#[cfg(test)]
+pub mod tests {
+    use std::{collections::HashSet, time::Duration};
+
+    use crate::common::*;
+    use libp2p::{futures::StreamExt, multiaddr::Protocol, Multiaddr, PeerId, Swarm};
+    use mina_network::p2p::*;
+    use tokio::{sync::mpsc, task::JoinHandle, time::timeout};
+
+    // The first node is the seed of the others
+    const NODES: usize = 4;
+
+    fn spawn_node(
+        index: usize,
+        mut swarm: Swarm<NodeBehaviour>,
+        mut peer_manager: PeerManager,
+        events: mpsc::UnboundedSender<(usize, PeerEvent)>,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            peer_manager.bootstrap(&mut swarm).unwrap();
+            let mut discovery = tokio::time::interval(peer_manager.config().discovery_interval);
+            loop {
+                tokio::select! {
+                    _ = discovery.tick() => peer_manager.discover(&mut swarm),
+                    event = swarm.select_next_some() => {
+                        if let Some(event) = peer_manager.on_swarm_event(&mut swarm, &event) {
+                            if events.send((index, event)).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn seed_addresses_end_with_peer_ids() -> anyhow::Result<()> {
+        let config = PeerManagerConfig {
+            seeds: vec!["/memory/8305".parse()?],
+            ..Default::default()
+        };
+        assert!(matches!(
+            PeerManager::new(config),
+            Err(PeerManagerError::SeedWithoutPeerId(_))
+        ));
+
+        let seed: Multiaddr = format!("/memory/8305/p2p/{}", PeerId::random()).parse()?;
+        let config = PeerManagerConfig {
+            seeds: vec![seed],
+            ..Default::default()
+        };
+        assert!(PeerManager::new(config).is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn discover_peers_from_seed() -> anyhow::Result<()> {
+        let mut swarms = Vec::new();
+        for index in 0..NODES {
+            let mut swarm = node_swarm()?;
+            swarm.listen_on(format!("/memory/{}", 8310 + index).parse()?)?;
+            swarms.push(swarm);
+        }
+        let peer_ids: Vec<PeerId> = swarms.iter().map(|swarm| *swarm.local_peer_id()).collect();
+        let seed = "/memory/8310"
+            .parse::<Multiaddr>()?
+            .with(Protocol::P2p(peer_ids[0].into()));
+        let config = PeerManagerConfig {
+            seeds: vec![seed],
+            target_peers: NODES - 1,
+            discovery_interval: Duration::from_millis(200),
+            ..Default::default()
+        };
+
+        let (sender, mut events) = mpsc::unbounded_channel();
+        let mut nodes = Vec::new();
+        for (index, swarm) in swarms.into_iter().enumerate() {
+            let peer_manager = if index == 0 {
+                PeerManager::default()
+            } else {
+                PeerManager::new(config.clone())?
+            };
+            nodes.push(spawn_node(index, swarm, peer_manager, sender.clone()));
+        }
+
+        // Every node gets connected to all the others through the seed
+        let mut connected = vec![HashSet::new(); NODES];
+        timeout(TEST_TIMEOUT, async {
+            while connected.iter().any(|peers| peers.len() < NODES - 1) {
+                match events.recv().await {
+                    Some((index, PeerEvent::Connected(peer_id))) => {
+                        connected[index].insert(peer_id);
+                    }
+                    Some((index, PeerEvent::Disconnected(peer_id))) => {
+                        connected[index].remove(&peer_id);
+                    }
+                    _ => {}
+                }
+            }
+        })
+        .await?;
+        for (index, peers) in connected.iter().enumerate() {
+            assert!(!peers.contains(&peer_ids[index]));
+        }
+
+        // The others report the churn when the last node stops
+        let last = peer_ids[NODES - 1];
+        nodes[NODES - 1].abort();
+        let mut disconnected = HashSet::new();
+        timeout(TEST_TIMEOUT, async {
+            while disconnected.len() < NODES - 1 {
+                if let Some((index, PeerEvent::Disconnected(peer_id))) = events.recv().await {
+                    assert_eq!(peer_id, last);
+                    disconnected.insert(index);
+                }
+            }
+        })
+        .await?;
+        Ok(())
+    }
+}
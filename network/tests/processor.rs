@@ -0,0 +1,176 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+mod common;
+
+#[cfg(test)]
+pub mod tests {
+    use crate::common::*;
+    use async_trait::async_trait;
+    use libp2p::{
+        futures::StreamExt,
+        request_response::{RequestResponseEvent, RequestResponseMessage},
+        swarm::SwarmEvent,
+        Multiaddr,
+    };
+    use mina_network::{
+        p2p::*,
+        processor::{native::*, *},
+    };
+    use mina_rs_base::types::*;
+    use mina_serialization_types::{json::StateHashV1Json, v1::ExternalTransitionV1};
+    use proof_systems::mina_signer::NetworkId;
+    use tokio::sync::mpsc;
+
+    const QUERIED_STATE_HASH: &str = "3NK6myZRzc3GvS5iydv88on2XTEU2btYrjMVkgtbuoeXASRipSa6";
+
+    /// Transition frontier that records the blocks it receives
+    struct RecordingTransitionFrontier {
+        blocks: mpsc::UnboundedSender<ExternalTransition>,
+    }
+
+    #[async_trait(?Send)]
+    impl TransitionFrontier for RecordingTransitionFrontier {
+        type Block = ExternalTransition;
+
+        async fn add_block(&mut self, block: Self::Block) -> anyhow::Result<()> {
+            self.blocks.send(block)?;
+            Ok(())
+        }
+
+        fn set_block_requester(&mut self, _: mpsc::Sender<QueryBlockRequest>) {}
+    }
+
+    #[tokio::test]
+    pub async fn libp2p_backend_queries_and_receives_blocks() -> anyhow::Result<()> {
+        let queried = read_block_json(&format!("mainnet-116121-{QUERIED_STATE_HASH}.json"));
+        let gossiped = read_block_json(
+            "mainnet-77749-3NK3P5bJHhqR7xkZBquGGfq3sERUeXNYNma5YXRMjgCNsTJRZpgL.json",
+        );
+
+        let mut peer = node_swarm()?;
+        let address: Multiaddr = "/memory/8304".parse()?;
+        peer.listen_on(address.clone())?;
+
+        let (backend, mut event_loop) =
+            NonConsensusLibp2pBackend::new(node_swarm()?, NetworkId::MAINNET);
+        event_loop.swarm_mut().dial(address)?;
+
+        let (sender, mut blocks) = mpsc::unbounded_channel();
+        let processor =
+            NetworkMessageProcessor::new(RecordingTransitionFrontier { blocks: sender }, backend);
+        // The query is sent once the peer is connected
+        processor
+            .nonconsensus_ops_mut()
+            .await
+            .query_block(&QueryBlockRequest {
+                height: 116121,
+                state_hash: QUERIED_STATE_HASH.into(),
+            })
+            .await?;
+
+        let run = async { tokio::join!(processor.run(), event_loop.run()) };
+        tokio::pin!(run);
+        let received = tokio::time::timeout(TEST_TIMEOUT, async {
+            let mut received = Vec::new();
+            while received.len() < 2 {
+                tokio::select! {
+                    _ = &mut run => anyhow::bail!("The processor has stopped"),
+                    event = peer.select_next_some() => match event {
+                        SwarmEvent::Behaviour(NodeEvent::Gossip(GossipEvent::Subscribed(_))) => {
+                            peer.behaviour_mut()
+                                .gossip
+                                .publish(GossipMessage::NewState(Box::new(gossiped.clone())))?;
+                        }
+                        SwarmEvent::Behaviour(NodeEvent::Rpc(RequestResponseEvent::Message {
+                            message: RequestResponseMessage::Request { request, channel, .. },
+                            ..
+                        })) => {
+                            let hash = StateHashV1Json::from_base58(QUERIED_STATE_HASH)?.into();
+                            assert_eq!(GetTransitionChain::decode_query(&request)?, vec![hash]);
+                            let response: Option<Vec<ExternalTransitionV1>> =
+                                Some(vec![queried.clone().into()]);
+                            peer.behaviour_mut()
+                                .rpc
+                                .respond::<GetTransitionChain>(channel, &request, &response)?;
+                        }
+                        _ => {}
+                    },
+                    Some(block) = blocks.recv() => received.push(block),
+                }
+            }
+            Ok::<_, anyhow::Error>(received)
+        })
+        .await??;
+        assert!(received.contains(&queried));
+        assert!(received.contains(&gossiped));
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn libp2p_backend_queries_block_from_next_peer() -> anyhow::Result<()> {
+        let queried = read_block_json(&format!("mainnet-116121-{QUERIED_STATE_HASH}.json"));
+
+        // The first peer answers with an empty chain, so the query waits for the second one
+        let mut first = node_swarm()?;
+        let first_address: Multiaddr = "/memory/8305".parse()?;
+        first.listen_on(first_address.clone())?;
+        let mut second = node_swarm()?;
+
+        let (backend, mut event_loop) =
+            NonConsensusLibp2pBackend::new(node_swarm()?, NetworkId::MAINNET);
+        let address: Multiaddr = "/memory/8306".parse()?;
+        event_loop.swarm_mut().listen_on(address.clone())?;
+        event_loop.swarm_mut().dial(first_address)?;
+
+        let (sender, mut blocks) = mpsc::unbounded_channel();
+        let processor =
+            NetworkMessageProcessor::new(RecordingTransitionFrontier { blocks: sender }, backend);
+        processor
+            .nonconsensus_ops_mut()
+            .await
+            .query_block(&QueryBlockRequest {
+                height: 116121,
+                state_hash: QUERIED_STATE_HASH.into(),
+            })
+            .await?;
+
+        let run = async { tokio::join!(processor.run(), event_loop.run()) };
+        tokio::pin!(run);
+        let received = tokio::time::timeout(TEST_TIMEOUT, async {
+            loop {
+                tokio::select! {
+                    _ = &mut run => anyhow::bail!("The processor has stopped"),
+                    event = first.select_next_some() => {
+                        if let SwarmEvent::Behaviour(NodeEvent::Rpc(RequestResponseEvent::Message {
+                            message: RequestResponseMessage::Request { request, channel, .. },
+                            ..
+                        })) = event {
+                            let response: Option<Vec<ExternalTransitionV1>> = Some(vec![]);
+                            first.behaviour_mut()
+                                .rpc
+                                .respond::<GetTransitionChain>(channel, &request, &response)?;
+                            second.dial(address.clone())?;
+                        }
+                    }
+                    event = second.select_next_some() => {
+                        if let SwarmEvent::Behaviour(NodeEvent::Rpc(RequestResponseEvent::Message {
+                            message: RequestResponseMessage::Request { request, channel, .. },
+                            ..
+                        })) = event {
+                            let response: Option<Vec<ExternalTransitionV1>> =
+                                Some(vec![queried.clone().into()]);
+                            second.behaviour_mut()
+                                .rpc
+                                .respond::<GetTransitionChain>(channel, &request, &response)?;
+                        }
+                    }
+                    Some(block) = blocks.recv() => return Ok::<_, anyhow::Error>(block),
+                }
+            }
+        })
+        .await??;
+        assert_eq!(received, queried);
+        Ok(())
+    }
+}
@@ -0,0 +1,287 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+mod common;
+
+#[cfg(test)]
+pub mod tests {
+    use crate::common::*;
+    use libp2p::{
+        futures::{io::Cursor, StreamExt},
+        request_response::{RequestResponseEvent, RequestResponseMessage},
+        swarm::{SwarmBuilder, SwarmEvent},
+        Multiaddr, PeerId,
+    };
+    use mina_ledger::TransactionScanStateV1;
+    use mina_network::p2p::*;
+    use mina_serialization_types::{
+        rpc::{
+            self, PendingCoinbaseCollection, RpcMessage, RpcVersion, SparseLedger, SparseLedgerTree,
+        },
+        snark_work::{PendingCoinbase, StateStack},
+        v1::{
+            ExternalTransitionV1, HashV1, PendingCoinbaseCollectionV1, PendingCoinbaseV1,
+            ProtocolStateV1, StackIdV1,
+        },
+    };
+    use std::str::FromStr;
+
+    // Handshake that the OCaml implementation sends, the length prefix followed by
+    // the bin_prot encoded list of the magic number and the protocol version
+    const HANDSHAKE: &[u8] = b"\x07\x00\x00\x00\x00\x00\x00\x00\x02\xfd\x52\x50\x43\x00\x01";
+
+    fn test_block() -> ExternalTransitionV1 {
+        read_block_json("mainnet-116121-3NK6myZRzc3GvS5iydv88on2XTEU2btYrjMVkgtbuoeXASRipSa6.json")
+            .into()
+    }
+
+    #[tokio::test]
+    pub async fn rpc_framing() -> anyhow::Result<()> {
+        let mut io = Cursor::new(Vec::new());
+        write_handshake(&mut io).await?;
+        assert_eq!(io.get_ref().as_slice(), HANDSHAKE);
+
+        // Heartbeats in front of the query are skipped
+        write_message(&mut io, &RpcMessage::Heartbeat).await?;
+        let hashes = vec![HashV1::new([1; 32]), HashV1::new([2; 32])];
+        let query = GetTransitionChain::encode_query(7, &hashes)?;
+        write_message(&mut io, &RpcMessage::Query(query.clone())).await?;
+        io.set_position(0);
+        assert_eq!(read_query(&mut io).await?, query);
+        assert_eq!(GetTransitionChain::decode_query(&query)?, hashes);
+        assert!(matches!(
+            GetBestTip::decode_query(&query),
+            Err(RpcError::UnexpectedRpc { .. })
+        ));
+
+        let response = GetTransitionChain::encode_response(&query, &None)?;
+        assert_eq!(response.id, 7);
+        let mut io = Cursor::new(Vec::new());
+        write_response(&mut io, response.clone()).await?;
+        io.set_position(0);
+        let received = read_response(&mut io, 7).await?;
+        assert_eq!(received, response);
+        assert_eq!(GetTransitionChain::decode_response(&received)?, None);
+
+        // The response has to answer the query
+        io.set_position(0);
+        assert!(matches!(
+            read_response(&mut io, 8).await,
+            Err(RpcError::ResponseIdMismatch {
+                expected: 8,
+                actual: 7
+            })
+        ));
+
+        // A response is not a query
+        io.set_position(0);
+        assert!(matches!(
+            read_query(&mut io).await,
+            Err(RpcError::UnexpectedMessage(_))
+        ));
+
+        // The handshake has to be exactly the magic number followed by our protocol version
+        for header in [
+            vec![RPC_PROTOCOL_VERSION],
+            vec![RPC_PROTOCOL_VERSION, RPC_MAGIC_NUMBER],
+            vec![RPC_MAGIC_NUMBER, RPC_PROTOCOL_VERSION, 2],
+        ] {
+            let mut io = Cursor::new(Vec::new());
+            write_message(&mut io, &header).await?;
+            io.set_position(0);
+            assert!(matches!(
+                read_handshake(&mut io).await,
+                Err(RpcError::Handshake(_))
+            ));
+        }
+
+        let mut io = Cursor::new((MAX_RPC_MESSAGE_SIZE + 1).to_le_bytes().to_vec());
+        assert!(matches!(
+            read_frame(&mut io).await,
+            Err(RpcError::MessageTooLarge(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn staged_ledger_aux_response_roundtrip() -> anyhow::Result<()> {
+        let hash = HashV1::new([1; 32]);
+        let stack: PendingCoinbaseV1 = PendingCoinbase {
+            data_stack: hash.clone(),
+            state_stack: StateStack {
+                init: hash.clone(),
+                curr: hash.clone(),
+            }
+            .into(),
+        }
+        .into();
+        let pending_coinbase: PendingCoinbaseCollectionV1 = PendingCoinbaseCollection {
+            tree: SparseLedger {
+                indexes: vec![(StackIdV1::new(0), 0)],
+                depth: 1,
+                tree: SparseLedgerTree::Node(
+                    hash.clone(),
+                    Box::new(SparseLedgerTree::Account(stack)),
+                    Box::new(SparseLedgerTree::Hash(hash.clone())),
+                )
+                .into(),
+            }
+            .into(),
+            pos_list: vec![StackIdV1::new(0)],
+            new_pos: StackIdV1::new(1),
+        }
+        .into();
+        let protocol_state: ProtocolStateV1 = read_block_json(
+            "mainnet-116121-3NK6myZRzc3GvS5iydv88on2XTEU2btYrjMVkgtbuoeXASRipSa6.json",
+        )
+        .protocol_state
+        .into();
+        let response = Some((
+            TransactionScanStateV1::empty(128, 2)?,
+            hash.clone(),
+            pending_coinbase,
+            vec![protocol_state],
+        ));
+
+        let query = GetStagedLedgerAuxAndPendingCoinbasesAtHash::encode_query(0, &hash)?;
+        let encoded =
+            GetStagedLedgerAuxAndPendingCoinbasesAtHash::encode_response(&query, &response)?;
+        assert_eq!(
+            GetStagedLedgerAuxAndPendingCoinbasesAtHash::decode_response(&encoded)?,
+            response
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn rpc_over_memory_transport() -> anyhow::Result<()> {
+        let mut server = memory_swarm(|_| Ok(RpcBehaviour::new()))?;
+        let server_id = *server.local_peer_id();
+        let mut client = memory_swarm(|_| Ok(RpcBehaviour::new()))?;
+
+        let address: Multiaddr = "/memory/8303".parse()?;
+        server.listen_on(address.clone())?;
+        client.behaviour_mut().add_address(&server_id, address);
+
+        let block = test_block();
+        let hashes = vec![HashV1::new([1; 32])];
+        let chain_query = client
+            .behaviour_mut()
+            .query::<GetTransitionChain>(&server_id, &hashes)?;
+        let best_tip_query = client
+            .behaviour_mut()
+            .query::<GetBestTip>(&server_id, &())?;
+
+        tokio::time::timeout(TEST_TIMEOUT, async {
+            let mut responses = 0;
+            while responses < 2 {
+                tokio::select! {
+                    event = server.select_next_some() => {
+                        if let SwarmEvent::Behaviour(RequestResponseEvent::Message {
+                            message: RequestResponseMessage::Request { request, channel, .. },
+                            ..
+                        }) = event {
+                            // Only get_transition_chain is implemented by the server
+                            if GetTransitionChain::is_query(&request) {
+                                assert_eq!(GetTransitionChain::decode_query(&request)?, hashes);
+                                server.behaviour_mut().respond::<GetTransitionChain>(
+                                    channel,
+                                    &request,
+                                    &Some(vec![block.clone()]),
+                                )?;
+                            } else {
+                                server.behaviour_mut().respond_unimplemented(channel, &request)?;
+                            }
+                        }
+                    }
+                    event = client.select_next_some() => {
+                        match event {
+                            SwarmEvent::Behaviour(RequestResponseEvent::Message {
+                                message: RequestResponseMessage::Response { request_id, response },
+                                ..
+                            }) => {
+                                if request_id == chain_query {
+                                    assert_eq!(
+                                        GetTransitionChain::decode_response(&response)?,
+                                        Some(vec![block.clone()])
+                                    );
+                                } else {
+                                    assert_eq!(request_id, best_tip_query);
+                                    match GetBestTip::decode_response(&response) {
+                                        Err(RpcError::Remote(rpc::RpcError::UnimplementedRpc(
+                                            tag,
+                                            RpcVersion::Version(version),
+                                        ))) => {
+                                            assert_eq!(tag, GetBestTip::NAME);
+                                            assert_eq!(version, GetBestTip::VERSION);
+                                        }
+                                        other => anyhow::bail!("Unexpected response: {:?}", other),
+                                    }
+                                }
+                                responses += 1;
+                            }
+                            SwarmEvent::Behaviour(RequestResponseEvent::OutboundFailure { error, .. }) => {
+                                anyhow::bail!("Query failed: {:?}", error)
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            Ok::<(), anyhow::Error>(())
+        })
+        .await?
+    }
+
+    // Peer id and address of a mainnet seed, the same one as in the p2p tests
+    const MAINNET_PEER_ID: &str = "12D3KooWSxxCtzRLfUzoxgRYW9fTKWPUujdvStuwCPSPUN3629mb";
+    const MAINNET_PEER_ADDRESS: &str = "/ip4/95.217.106.189/tcp/8302";
+
+    // The query is written on a stream whose writing half is then closed, which the OCaml
+    // implementation has to answer. Any decoded response, including an error, is an answer
+    #[ignore = "needs a reachable mainnet peer"]
+    #[tokio::test]
+    pub async fn rpc_with_mainnet_peer() -> anyhow::Result<()> {
+        let (transport, local_peer_id) =
+            TransportBuilder::default().with_mainnet_config().build()?;
+        let mut swarm = SwarmBuilder::new(transport, RpcBehaviour::new(), local_peer_id)
+            // executor has to be explicitly set due to https://github.com/libp2p/rust-libp2p/issues/2173
+            .executor(Box::new(|fut| {
+                tokio::spawn(fut);
+            }))
+            .build();
+        let peer_id = PeerId::from_str(MAINNET_PEER_ID)?;
+        swarm
+            .behaviour_mut()
+            .add_address(&peer_id, MAINNET_PEER_ADDRESS.parse()?);
+        let query = swarm
+            .behaviour_mut()
+            .query::<GetTransitionKnowledge>(&peer_id, &())?;
+
+        tokio::time::timeout(TEST_TIMEOUT, async {
+            loop {
+                match swarm.select_next_some().await {
+                    SwarmEvent::Behaviour(RequestResponseEvent::Message {
+                        message:
+                            RequestResponseMessage::Response {
+                                request_id,
+                                response,
+                            },
+                        ..
+                    }) => {
+                        assert_eq!(request_id, query);
+                        return match GetTransitionKnowledge::decode_response(&response) {
+                            Ok(_) | Err(RpcError::Remote(_)) => Ok(()),
+                            Err(err) => Err(err.into()),
+                        };
+                    }
+                    SwarmEvent::Behaviour(RequestResponseEvent::OutboundFailure {
+                        error, ..
+                    }) => anyhow::bail!("Query failed: {:?}", error),
+                    _ => {}
+                }
+            }
+        })
+        .await?
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+mod common;
+
+#[cfg(test)]
+pub mod tests {
+    use crate::common::*;
+    use libp2p::{futures::StreamExt, swarm::SwarmEvent, Multiaddr, PeerId};
+    use mina_network::{p2p::*, processor::native::*};
+    use proof_systems::mina_signer::NetworkId;
+    use std::time::{Duration, Instant};
+    use tokio::time::timeout;
+
+    #[test]
+    fn trust_decays_and_repeated_timeouts_ban() -> anyhow::Result<()> {
+        let config = TrustConfig {
+            decay_time: Duration::from_secs(10),
+            ..Default::default()
+        };
+        let mut trust_system = TrustSystem::new(config.clone());
+        let peer_id = PeerId::random();
+        let now = Instant::now();
+        assert_eq!(trust_system.trust(&peer_id, now), 0.);
+
+        assert_eq!(
+            trust_system.record(peer_id, PeerAction::SentUsefulGossip, now),
+            None
+        );
+        let trust = trust_system.trust(&peer_id, now);
+        assert_eq!(trust, config.max_rate(10.));
+        let decayed = trust_system.trust(&peer_id, now + config.decay_time);
+        assert!((decayed - trust / std::f64::consts::E).abs() < 1e-12);
+
+        // A timeout every second for a whole decay time is tolerated, a burst of them is not
+        let timeouts = (1. / config.max_rate(1.)).ceil() as usize;
+        for _ in 1..timeouts {
+            assert_eq!(
+                trust_system.record(peer_id, PeerAction::RequestTimeout, now),
+                None
+            );
+        }
+        assert!(!trust_system.is_banned(&peer_id, now));
+        assert_eq!(
+            trust_system.record(peer_id, PeerAction::RequestTimeout, now),
+            Some(now + config.ban_duration)
+        );
+        assert!(trust_system.is_banned(&peer_id, now));
+        assert_eq!(trust_system.trust(&peer_id, now), -1.);
+        Ok(())
+    }
+
+    #[test]
+    fn insta_ban_expires() -> anyhow::Result<()> {
+        let config = TrustConfig::default();
+        let mut trust_system = TrustSystem::new(config.clone());
+        let peer_id = PeerId::random();
+        let other_peer_id = PeerId::random();
+        let now = Instant::now();
+
+        trust_system.record(other_peer_id, PeerAction::SentUsefulResponse, now);
+        assert_eq!(
+            trust_system.record(peer_id, PeerAction::SentInvalidProof, now),
+            Some(now + config.ban_duration)
+        );
+        assert!(trust_system.is_banned(&peer_id, now));
+        assert!(!trust_system.is_banned(&other_peer_id, now));
+
+        // Actions of banned peers are ignored
+        assert_eq!(
+            trust_system.record(peer_id, PeerAction::SentUsefulGossip, now),
+            None
+        );
+        assert_eq!(trust_system.trust(&peer_id, now), -1.);
+        assert!(trust_system.lift_expired_bans(now).is_empty());
+
+        let expiry = now + config.ban_duration;
+        assert!(!trust_system.is_banned(&peer_id, expiry));
+        assert_eq!(trust_system.lift_expired_bans(expiry), vec![peer_id]);
+        assert_eq!(trust_system.get(&peer_id).unwrap().banned_until, None);
+        // The distrust lingers after the ban, while the small trust
+        // of the other peer has been forgotten
+        assert!(trust_system.trust(&peer_id, expiry) < -0.3);
+        assert!(trust_system.get(&other_peer_id).is_none());
+
+        // The peer can be banned again
+        assert!(trust_system
+            .record(peer_id, PeerAction::ViolatedProtocol, expiry)
+            .is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn peer_gossiping_invalid_block_is_banned() -> anyhow::Result<()> {
+        let mut block = read_block_json(
+            "mainnet-77749-3NK3P5bJHhqR7xkZBquGGfq3sERUeXNYNma5YXRMjgCNsTJRZpgL.json",
+        );
+        block.delta_transition_chain_proof.0 = block.protocol_state.state_hash();
+
+        let (_backend, mut event_loop) =
+            NonConsensusLibp2pBackend::new(node_swarm()?, NetworkId::MAINNET);
+        let address: Multiaddr = "/memory/8320".parse()?;
+        event_loop.swarm_mut().listen_on(address.clone())?;
+        let node_peer_id = *event_loop.swarm_mut().local_peer_id();
+
+        let mut peer = node_swarm()?;
+        peer.dial(address.clone())?;
+
+        let run = event_loop.run();
+        tokio::pin!(run);
+        let mut redialed = false;
+        timeout(TEST_TIMEOUT, async {
+            loop {
+                tokio::select! {
+                    _ = &mut run => anyhow::bail!("The event loop has stopped"),
+                    event = peer.select_next_some() => match event {
+                        SwarmEvent::Behaviour(NodeEvent::Gossip(GossipEvent::Subscribed(_)))
+                            if !redialed =>
+                        {
+                            peer.behaviour_mut()
+                                .gossip
+                                .publish(GossipMessage::NewState(Box::new(block.clone())))?;
+                        }
+                        // The node closes the connection once the peer is banned
+                        // and keeps refusing it afterwards
+                        SwarmEvent::ConnectionClosed { peer_id, num_established: 0, .. }
+                        | SwarmEvent::OutgoingConnectionError { peer_id: Some(peer_id), .. }
+                            if peer_id == node_peer_id =>
+                        {
+                            if redialed {
+                                break;
+                            }
+                            redialed = true;
+                            peer.dial(address.clone())?;
+                        }
+                        _ => {}
+                    },
+                }
+            }
+            Ok::<_, anyhow::Error>(())
+        })
+        .await??;
+        Ok(())
+    }
+}
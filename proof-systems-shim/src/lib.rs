@@ -14,6 +14,7 @@
 //! ```
 //!
 
+pub use groupmap;
 pub use mina_curves;
 pub use mina_hasher;
 pub use mina_signer;
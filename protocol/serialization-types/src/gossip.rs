@@ -0,0 +1,62 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//! Messages that are gossiped between nodes over the consensus messages pubsub topic
+#![allow(missing_docs)]
+
+use crate::v1::*;
+use serde::{Deserialize, Serialize};
+use versioned::Versioned;
+
+/// Gossip message, which is `Gossip_net.Message.V1.T.msg` in the OCaml implementation
+/// <https://github.com/MinaProtocol/mina/blob/65b59f56b6e98e1d9648280c2153d809abb42ba3/src/lib/gossip_net/message.ml>
+///
+/// The message derives `version {rpc}`, which doesn't add a version tag, so it's encoded as
+/// the variant tag followed by the payload, which carries its own version tags
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum GossipMessage {
+    NewState(Box<ExternalTransitionV1>),
+    SnarkPoolDiff(Box<SnarkPoolDiffV1>),
+    TransactionPoolDiff(TransactionPoolDiffV1),
+}
+
+/// Diff of the snark pool, announces the proofs of a completed snark work
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum SnarkPoolDiff {
+    AddSolvedWork(Box<TransactionSnarkWorkStatementV1>, Box<PricedProofV1>),
+}
+
+pub type SnarkPoolDiffV1 = Versioned<SnarkPoolDiff, 1>;
+
+/// Diff of the transaction pool, announces new user commands
+pub type TransactionPoolDiffV1 = Versioned<Vec<UserCommandV1>, 1>;
+
+/// Statements of a snark work
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename = "Polyvar")]
+pub enum TransactionSnarkWorkStatement {
+    #[serde(rename = "One")]
+    One(StatementV1),
+    #[serde(rename = "Two")]
+    Two(StatementV1, StatementV1),
+}
+
+pub type TransactionSnarkWorkStatementV1 =
+    Versioned<Versioned<TransactionSnarkWorkStatement, 1>, 1>;
+
+/// Proofs of a snark work with the fee asked by the prover
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PricedProof {
+    pub proof: OneORTwoV1,
+    pub fee: FeeWithProverV1,
+}
+
+pub type PricedProofV1 = Versioned<PricedProof, 1>;
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct FeeWithProver {
+    pub fee: AmountV1,
+    pub prover: PublicKeyV1,
+}
+
+pub type FeeWithProverV1 = Versioned<FeeWithProver, 1>;
@@ -26,6 +26,7 @@ pub mod errors;
 pub mod external_transition;
 pub mod field_and_curve_elements;
 pub mod global_slot;
+pub mod gossip;
 pub mod macros;
 pub mod opening_proof;
 pub mod proof_evaluations;
@@ -35,6 +36,7 @@ pub mod protocol_state;
 pub mod protocol_state_body;
 pub mod protocol_state_proof;
 pub mod protocol_version;
+pub mod rpc;
 pub mod signatures;
 pub mod snark_work;
 pub mod staged_ledger_diff;
@@ -75,6 +77,10 @@ pub mod v1 {
         FiniteECPointPairVecV1, FiniteECPointVecV1, InnerCurveScalar,
     };
     pub use super::global_slot::GlobalSlotV1;
+    pub use super::gossip::{
+        FeeWithProverV1, PricedProofV1, SnarkPoolDiffV1, TransactionPoolDiffV1,
+        TransactionSnarkWorkStatementV1,
+    };
     pub use super::opening_proof::OpeningProofV1;
     pub use super::proof_evaluations::ProofEvaluationsV1;
     pub use super::proof_messages::{
@@ -89,6 +95,11 @@ pub mod v1 {
         ProofV1, ProtocolStateProofV1, ShiftedValueV1, SpongeDigestBeforeEvaluationsV1,
     };
     pub use super::protocol_version::ProtocolVersionV1;
+    pub use super::rpc::{
+        ConsensusStateWithHashV1, MerkleAddressV1, PendingCoinbaseCollectionV1,
+        ProofCarryingDataV1, ProofCarryingTransitionV1, SparseLedgerTreeV1, SparseLedgerV1,
+        StackIdV1, SyncLedgerAnswerV1, SyncLedgerQueryV1, WithHashV1,
+    };
     pub use super::signatures::{PublicKey2V1, PublicKeyV1, SignatureV1};
     pub use super::snark_work::{
         FeeExcessPairV1, LedgerProofV1, OneORTwoV1, PendingCoinbaseStackStateV1, PendingCoinbaseV1,
@@ -0,0 +1,154 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0
+
+//! Messages of the Async_rpc protocol that peers use to query each other,
+//! along with the queries and responses of the mina rpcs
+#![allow(missing_docs)]
+
+use crate::v1::*;
+use serde::{Deserialize, Serialize};
+use versioned::Versioned;
+
+/// Handshake that both sides send when a connection is opened,
+/// which is `Protocol_version_header.t` in the OCaml implementation,
+/// i.e. a magic number followed by the supported protocol versions
+pub type RpcHeader = Vec<i64>;
+
+/// Message of the Async_rpc protocol, which is `Protocol.Message.t` in the OCaml implementation
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum RpcMessage {
+    Heartbeat,
+    Query(RpcQuery),
+    Response(RpcResponse),
+}
+
+/// Query of an rpc, whose data is the bin_prot encoded query of that rpc
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct RpcQuery {
+    pub tag: String,
+    pub version: i64,
+    pub id: i64,
+    pub data: Vec<u8>,
+}
+
+/// Response to the query with the same id, whose data is the bin_prot encoded response of the rpc
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct RpcResponse {
+    pub id: i64,
+    pub data: Result<Vec<u8>, RpcError>,
+}
+
+/// Error that is sent back instead of a response, which is `Rpc_error.t` in the OCaml implementation
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum RpcError {
+    BinIoExn(Sexp),
+    ConnectionClosed,
+    WriteError(Sexp),
+    UncaughtExn(Sexp),
+    UnimplementedRpc(String, RpcVersion),
+    UnknownQueryId(i64),
+}
+
+/// Version of an unimplemented rpc
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename = "Polyvar")]
+pub enum RpcVersion {
+    #[serde(rename = "Version")]
+    Version(i64),
+}
+
+/// S-expression, which is how the OCaml implementation serializes errors
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum Sexp {
+    Atom(String),
+    List(Vec<Sexp>),
+}
+
+/// Value along with its hash
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct WithHash<T, H> {
+    pub data: T,
+    pub hash: H,
+}
+
+pub type WithHashV1<T, H> = Versioned<WithHash<T, H>, 1>;
+
+/// Value along with the proof of its validity
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ProofCarryingData<T, P> {
+    pub data: T,
+    pub proof: P,
+}
+
+pub type ProofCarryingDataV1<T, P> = Versioned<ProofCarryingData<T, P>, 1>;
+
+/// Best tip or ancestor of a peer, along with the state body hashes of the blocks that lead to it
+/// from the root of the transition frontier of that peer and the root itself
+pub type ProofCarryingTransitionV1 =
+    ProofCarryingDataV1<ExternalTransitionV1, (Vec<HashV1>, ExternalTransitionV1)>;
+
+/// Consensus state of a block along with the state hash of the block
+pub type ConsensusStateWithHashV1 = WithHashV1<ConsensusStateV1, HashV1>;
+
+/// Address of a node in the merkle tree of a ledger, which is encoded as
+/// the depth of the node and the bits of the path to it, packed in bytes
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct MerkleAddress(pub i64, pub Vec<u8>);
+
+pub type MerkleAddressV1 = Versioned<MerkleAddress, 1>;
+
+/// Query of the ledger sync protocol
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum SyncLedgerQuery {
+    WhatChildHashes(MerkleAddressV1),
+    WhatContents(MerkleAddressV1),
+    NumAccounts,
+}
+
+pub type SyncLedgerQueryV1 = Versioned<SyncLedgerQuery, 1>;
+
+/// Answer of the ledger sync protocol
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum SyncLedgerAnswer {
+    ChildHashesAre(HashV1, HashV1),
+    ContentsAre(Vec<AccountV1>),
+    NumAccounts(i64, HashV1),
+}
+
+pub type SyncLedgerAnswerV1 = Versioned<SyncLedgerAnswer, 1>;
+
+/// Id of a pending coinbase stack, which is its leaf index in the merkle tree
+pub type StackIdV1 = Versioned<i64, 1>;
+
+/// Merkle tree of a sparse ledger, where only the paths to the accounts in use are kept
+/// and the other subtrees are pruned to their hashes
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum SparseLedgerTree<H, A> {
+    Account(A),
+    Hash(H),
+    Node(H, Box<SparseLedgerTree<H, A>>, Box<SparseLedgerTree<H, A>>),
+}
+
+pub type SparseLedgerTreeV1<H, A> = Versioned<SparseLedgerTree<H, A>, 1>;
+
+/// Sparse ledger along with the leaf index of every key in use
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SparseLedger<H, K, A> {
+    pub indexes: Vec<(K, i64)>,
+    pub depth: i64,
+    pub tree: SparseLedgerTreeV1<H, A>,
+}
+
+pub type SparseLedgerV1<H, K, A> = Versioned<SparseLedger<H, K, A>, 1>;
+
+/// Pending coinbase of a staged ledger, which is `Pending_coinbase.t` in the OCaml implementation,
+/// i.e. the merkle tree of the stacks, the ids of the stacks in use from the oldest to the newest
+/// one and the id of the next new stack
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PendingCoinbaseCollection {
+    pub tree: SparseLedgerV1<HashV1, StackIdV1, PendingCoinbaseV1>,
+    pub pos_list: Vec<StackIdV1>,
+    pub new_pos: StackIdV1,
+}
+
+pub type PendingCoinbaseCollectionV1 = Versioned<Versioned<PendingCoinbaseCollection, 1>, 1>;